@@ -9,4 +9,8 @@ fn main() {
         .file(src_dir.join("parser.c"))
         .file(src_dir.join("scanner.c")) // Include external scanner
         .compile("tree-sitter-nix");
+
+    // cc only registers env-based rerun triggers, so track the sources explicitly
+    println!("cargo:rerun-if-changed={}", src_dir.join("parser.c").display());
+    println!("cargo:rerun-if-changed={}", src_dir.join("scanner.c").display());
 }
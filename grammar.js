@@ -154,6 +154,10 @@ module.exports = grammar({
     ),
 
     // Paths
+    //
+    // Paths with interpolations, such as `./${name}.nix`, are found by the
+    // external scanner, which lexes them as a `string` without quotes: its
+    // start token is the text before the first `${`, and its end is empty.
     path: $ => token(choice(
       // Absolute path
       /\/[a-zA-Z0-9._\-+][a-zA-Z0-9._\-+\/]*/,
@@ -23,6 +23,9 @@
 
 ; Paths and URIs
 (path) @string.special.path
+; The scanner lexes paths with interpolations as unquoted strings
+((string) @string.special.path
+  (#not-match? @string.special.path "^\""))
 (uri) @string.special.uri
 
 ; Identifiers
@@ -0,0 +1,144 @@
+//! Memoization of analysis results
+
+use std::collections::HashMap;
+
+use crate::analysis::AnalysisResult;
+use crate::ast::{Expression, SourceLocation};
+
+/// Analysis results of previously analyzed expressions
+///
+/// A result is reused for an expression equal to one analyzed before,
+/// source locations included, since the results report locations: an
+/// unchanged expression that moved within the file is analyzed again.
+/// Only the expressions passed to [`Analyzer::analyze`] are cached, not
+/// their subtrees, whose analysis depends on the enclosing scopes.
+///
+/// The cache is unbounded; call [`AnalysisCache::clear`] to release its
+/// entries.
+///
+/// [`Analyzer::analyze`]: crate::analysis::Analyzer::analyze
+#[derive(Debug, Default)]
+pub struct AnalysisCache {
+    /// Analyzed expressions and their results, by structural hash
+    entries: HashMap<u64, Vec<(Expression, AnalysisResult)>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl AnalysisCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the cached result for `expression`, counting a hit or a miss
+    pub fn get(&mut self, expression: &Expression) -> Option<AnalysisResult> {
+        let result = self.entries.get(&expression.structural_hash()).and_then(|entries| {
+            let wanted = locations(expression);
+            entries
+                .iter()
+                .find(|(cached, _)| cached == expression && locations(cached) == wanted)
+                .map(|(_, result)| result.clone())
+        });
+        if result.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        result
+    }
+
+    /// Cache the result of analyzing `expression`
+    pub fn insert(&mut self, expression: &Expression, result: AnalysisResult) {
+        let entries = self.entries.entry(expression.structural_hash()).or_default();
+        let wanted = locations(expression);
+        entries.retain(|(cached, _)| !(cached == expression && locations(cached) == wanted));
+        entries.push((expression.clone(), result));
+    }
+
+    /// Get the number of cached results
+    pub fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    /// Check if the cache holds no results
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the number of lookups that found a result
+    pub const fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Get the number of lookups that found nothing
+    pub const fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Remove every cached result, keeping the hit and miss counts
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Get the source locations of an expression and everything nested in it,
+/// which equality of expressions ignores
+fn locations(expression: &Expression) -> Vec<Option<SourceLocation>> {
+    let mut locations = Vec::new();
+    // Trees can be deep, so walk them with an explicit stack
+    let mut stack = vec![expression];
+    while let Some(expr) = stack.pop() {
+        locations.push(expr.location());
+        if let Expression::AttributeSet { attributes, .. } = expr {
+            locations.extend(attributes.iter().map(|attr| attr.location));
+        }
+        stack.extend(expr.subexpressions());
+    }
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+    use crate::analysis::Analyzer;
+
+    #[test]
+    fn test_cached_analysis() {
+        let expr = parse_expr("let a = import ./a.nix; b = 1; in { x = a; x = 2; }");
+        let mut analyzer = Analyzer::new().with_cache();
+
+        let first = analyzer.analyze(&expr).unwrap();
+        let second = analyzer.analyze(&expr).unwrap();
+        assert_eq!(format!("{first:?}"), format!("{second:?}"));
+        assert_eq!(second.lint_results.len(), 1);
+        assert_eq!(second.errors.len(), 1);
+
+        let cache = analyzer.cache().unwrap();
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_cache_keys() {
+        let mut cache = AnalysisCache::new();
+        cache.insert(&parse_expr("x: x"), Analyzer::new().analyze(&parse_expr("x: x")).unwrap());
+
+        assert!(cache.get(&parse_expr("x: x")).is_some());
+        assert!(cache.get(&parse_expr("y: y")).is_none());
+        // The same expression elsewhere in the source reports other locations
+        assert!(cache.get(&parse_expr(" x: x")).is_none());
+        assert!(cache.get(&parse_expr("x:  x")).is_none());
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+
+        // Entries sharing a hash are told apart by comparing expressions
+        let (x, y) = (parse_expr("x: x"), parse_expr("y: y"));
+        let result = Analyzer::new().analyze(&x).unwrap();
+        cache.entries.entry(y.structural_hash()).or_default().push((x, result));
+        assert!(cache.get(&y).is_none());
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
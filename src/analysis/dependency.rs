@@ -1,12 +1,16 @@
 //! Dependency analysis for Nix expressions
 
-use crate::ast::Expression;
-use crate::error::Result;
+use std::collections::VecDeque;
+
+use crate::ast::{AttrKey, Expression, PathType, SourceLocation};
+use crate::error::{ParseError, Result};
+use crate::visitor::{LocationTracker, VisitControl, Visitor};
 
 /// Analyzer for tracking dependencies between Nix expressions
-/// 
+///
 /// Identifies imports, variable references, and other dependencies
 /// to build a dependency graph for the analyzed code.
+#[derive(Default)]
 pub struct DependencyAnalyzer {}
 
 impl DependencyAnalyzer {
@@ -14,36 +18,444 @@ impl DependencyAnalyzer {
     pub fn new() -> Self {
         Self {}
     }
-    
+
     /// Analyze an expression to build its dependency graph
-    /// 
+    ///
+    /// Both `import <path>` and `builtins.import <path>` are recognised.
+    /// Imports of computed expressions, such as `import (./. + "/a.nix")`,
+    /// cannot be resolved statically and are not recorded.
+    ///
     /// # Arguments
-    /// 
-    /// * `_expression` - The expression to analyze for dependencies
-    /// 
+    ///
+    /// * `expression` - The expression to analyze for dependencies
+    ///
     /// # Returns
-    /// 
+    ///
     /// A dependency graph representing all found dependencies
-    pub fn analyze(&mut self, _expression: &Expression) -> Result<DependencyGraph> {
-        Ok(DependencyGraph::new())
+    ///
+    /// # Errors
+    ///
+    /// Never fails: unresolvable imports are skipped rather than reported.
+    pub fn analyze(&mut self, expression: &Expression) -> Result<DependencyGraph> {
+        let mut collector = ImportCollector {
+            graph: DependencyGraph::new(),
+            locations: LocationTracker::default(),
+        };
+        collector.visit_expression(expression);
+        Ok(collector.graph)
     }
 }
 
 /// Represents a single dependency relationship
-#[derive(Debug, Clone)]
-pub struct Dependency {}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    /// Imported path, classified as relative, absolute, home or `<search>`
+    pub path: PathType,
+    /// Location of the import expression, if the AST carries locations
+    pub location: Option<SourceLocation>,
+}
+
+impl Dependency {
+    /// Check if the dependency is resolved through `NIX_PATH`, like `<nixpkgs>`
+    pub fn is_search_path(&self) -> bool {
+        matches!(self.path, PathType::Search(_))
+    }
+}
+
+/// Identifier of a node in a [`DependencyGraph`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Get the position of the node in [`DependencyGraph::nodes`]
+    pub const fn index(self) -> usize {
+        self.0
+    }
+}
 
 /// A graph representing all dependencies in analyzed code
-#[derive(Debug, Clone)]
-pub struct DependencyGraph {}
+///
+/// Besides the dependencies found in a single expression, the graph holds
+/// named nodes (typically files) connected by directed edges pointing from
+/// a node to the nodes it depends on.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    dependencies: Vec<Dependency>,
+    nodes: Vec<String>,
+    edges: Vec<(NodeId, NodeId)>,
+    /// Outgoing edges of each node, indexed by `NodeId`
+    adjacency: Vec<Vec<NodeId>>,
+}
 
 impl DependencyGraph {
     /// Create a new empty dependency graph
     pub fn new() -> Self {
-        Self {}
+        Self {
+            dependencies: Vec::new(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            adjacency: Vec::new(),
+        }
+    }
+
+    /// Add a node, returning the existing id if a node with this name exists
+    pub fn add_node(&mut self, name: impl Into<String>) -> NodeId {
+        let name = name.into();
+        if let Some(id) = self.node_id(&name) {
+            return id;
+        }
+        self.nodes.push(name);
+        self.adjacency.push(Vec::new());
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Add an edge from `from` to a node it depends on
+    ///
+    /// Duplicate edges are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either node does not belong to this graph.
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        assert!(to.0 < self.nodes.len(), "unknown node {to:?}");
+        if !self.adjacency[from.0].contains(&to) {
+            self.adjacency[from.0].push(to);
+            self.edges.push((from, to));
+        }
+    }
+
+    /// Get the node names, indexed by `NodeId`
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    /// Get the edges in insertion order
+    pub fn edges(&self) -> &[(NodeId, NodeId)] {
+        &self.edges
+    }
+
+    /// Look up a node by name
+    pub fn node_id(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node == name).map(NodeId)
+    }
+
+    /// Get the name of a node
+    pub fn node_name(&self, id: NodeId) -> Option<&str> {
+        self.nodes.get(id.0).map(String::as_str)
+    }
+
+    /// Order the nodes so that every node comes before its dependencies
+    ///
+    /// Ties are broken by insertion order, so the result is deterministic.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::SemanticError` naming the nodes of a cycle if
+    /// the graph is not acyclic.
+    pub fn topological_order(&self) -> Result<Vec<NodeId>> {
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for &(_, to) in &self.edges {
+            in_degree[to.0] += 1;
+        }
+
+        let mut ready: VecDeque<NodeId> = (0..self.nodes.len())
+            .filter(|&index| in_degree[index] == 0)
+            .map(NodeId)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for &next in &self.adjacency[node.0] {
+                in_degree[next.0] -= 1;
+                if in_degree[next.0] == 0 {
+                    ready.push_back(next);
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            let cycle = self.find_cycles().into_iter().next().unwrap_or_default();
+            let names: Vec<&str> = cycle
+                .iter()
+                .chain(cycle.first())
+                .filter_map(|&id| self.node_name(id))
+                .collect();
+            return Err(ParseError::semantic_error(format!(
+                "Dependency cycle: {}",
+                names.join(" -> ")
+            )));
+        }
+        Ok(order)
+    }
+
+    /// Find cycles in the graph
+    ///
+    /// Runs a depth-first search and reports one cycle per back edge, each
+    /// listed from the node the cycle is entered at, without repeating it at
+    /// the end. A node depending on itself forms a cycle of length one.
+    pub fn find_cycles(&self) -> Vec<Vec<NodeId>> {
+        let mut state = vec![DfsState::Unvisited; self.nodes.len()];
+        let mut path = Vec::new();
+        let mut cycles = Vec::new();
+
+        for index in 0..self.nodes.len() {
+            if state[index] == DfsState::Unvisited {
+                self.collect_cycles(NodeId(index), &mut state, &mut path, &mut cycles);
+            }
+        }
+        cycles
+    }
+
+    fn collect_cycles(
+        &self,
+        node: NodeId,
+        state: &mut [DfsState],
+        path: &mut Vec<NodeId>,
+        cycles: &mut Vec<Vec<NodeId>>,
+    ) {
+        state[node.0] = DfsState::OnPath;
+        path.push(node);
+
+        for &next in &self.adjacency[node.0] {
+            match state[next.0] {
+                DfsState::Unvisited => self.collect_cycles(next, state, path, cycles),
+                DfsState::OnPath => {
+                    let start = path.iter().position(|&id| id == next).unwrap_or(0);
+                    cycles.push(path[start..].to_vec());
+                }
+                DfsState::Done => {}
+            }
+        }
+
+        path.pop();
+        state[node.0] = DfsState::Done;
+    }
+
+    /// Render the graph in Graphviz DOT format
+    ///
+    /// Each node is declared as `nN`, with its name (usually a file path)
+    /// as the label. Edges that lie on a cycle are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let nodes = self.nodes.iter().enumerate().map(|(index, name)| {
+            format!("    n{} [label=\"{}\"];", index, escape_dot(name))
+        });
+        let edges = self.edges.iter().map(|&(from, to)| {
+            let style = if self.reaches(to, from) { " [color=red]" } else { "" };
+            format!("    n{} -> n{}{};", from.0, to.0, style)
+        });
+
+        std::iter::once("digraph dependencies {".to_string())
+            .chain(nodes)
+            .chain(edges)
+            .chain(std::iter::once("}".to_string()))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Check if there is a path of zero or more edges from `from` to `to`
+    fn reaches(&self, from: NodeId, to: NodeId) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![from];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if !std::mem::replace(&mut visited[node.0], true) {
+                stack.extend(&self.adjacency[node.0]);
+            }
+        }
+        false
+    }
+
+    /// Record a dependency
+    pub fn add_dependency(&mut self, dependency: Dependency) {
+        self.dependencies.push(dependency);
+    }
+
+    /// Get the recorded dependencies in source order
+    pub fn dependencies(&self) -> &[Dependency] {
+        &self.dependencies
     }
 }
 
+/// Escape a string for use inside a quoted DOT identifier
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 /// Configuration options for dependency analysis
 #[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+pub struct Config {}
+
+/// Progress of a node during the cycle search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DfsState {
+    Unvisited,
+    OnPath,
+    Done,
+}
+
+/// Visitor that records every import of a literal path
+struct ImportCollector {
+    graph: DependencyGraph,
+    locations: LocationTracker,
+}
+
+impl ImportCollector {
+    /// Get the path argument of an import expression
+    fn imported_path(expr: &Expression) -> Option<&PathType> {
+        let argument = match expr {
+            Expression::Import { path } => path,
+            Expression::Application { function, argument } if Self::is_import(function) => argument,
+            _ => return None,
+        };
+        match argument.unlocated() {
+            Expression::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Check if `function` is `import` or `builtins.import`
+    fn is_import(function: &Expression) -> bool {
+        match function.unlocated() {
+            Expression::Identifier(name) => name == "import",
+            Expression::Select { expr, path, default: None } => {
+                matches!(expr.unlocated(), Expression::Identifier(name) if name == "builtins")
+                    && matches!(AttrKey::static_names(path), Some([name]) if name == "import")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Visitor for ImportCollector {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        let Some(location) = self.locations.enter(expr) else {
+            return VisitControl::Continue;
+        };
+        if let Some(path) = Self::imported_path(expr) {
+            self.graph.add_dependency(Dependency {
+                path: path.clone(),
+                location,
+            });
+        }
+        VisitControl::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn dependencies_of(source: &str) -> Vec<Dependency> {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        let graph = DependencyAnalyzer::new().analyze(&expr).unwrap();
+        graph.dependencies().to_vec()
+    }
+
+    #[test]
+    fn test_relative_import() {
+        let deps = dependencies_of("import ./lib.nix");
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].path, PathType::Relative("./lib.nix".to_string()));
+        assert_eq!(deps[0].location.map(|loc| loc.start_byte), Some(0));
+        assert!(!deps[0].is_search_path());
+    }
+
+    #[test]
+    fn test_search_path_import() {
+        let deps = dependencies_of("import <nixpkgs> {}");
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].path, PathType::Search("nixpkgs".to_string()));
+        assert!(deps[0].is_search_path());
+    }
+
+    #[test]
+    fn test_path_kinds() {
+        let deps = dependencies_of(
+            "[ (import /etc/a.nix) (builtins.import ~/b.nix) (import ./c.nix) (import x) ]",
+        );
+        let paths: Vec<PathType> = deps.into_iter().map(|dep| dep.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathType::Absolute("/etc/a.nix".to_string()),
+                PathType::Home("~/b.nix".to_string()),
+                PathType::Relative("./c.nix".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_imports() {
+        let deps = dependencies_of("let lib = import ./lib.nix; in { x = import ./x.nix { inherit lib; }; }");
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[1].path, PathType::Relative("./x.nix".to_string()));
+    }
+
+    #[test]
+    fn test_graph_without_cycle() {
+        let mut graph = DependencyGraph::new();
+        let default = graph.add_node("default.nix");
+        let lib = graph.add_node("lib.nix");
+        let pkgs = graph.add_node("pkgs.nix");
+        graph.add_edge(default, pkgs);
+        graph.add_edge(default, lib);
+        graph.add_edge(pkgs, lib);
+        graph.add_edge(pkgs, lib);
+
+        assert_eq!(graph.add_node("lib.nix"), lib);
+        assert_eq!(graph.nodes().len(), 3);
+        assert_eq!(graph.edges().len(), 3);
+        assert_eq!(graph.node_id("pkgs.nix"), Some(pkgs));
+
+        assert!(graph.find_cycles().is_empty());
+        assert_eq!(graph.topological_order().unwrap(), vec![default, pkgs, lib]);
+    }
+
+    #[test]
+    fn test_graph_with_cycle() {
+        let mut graph = DependencyGraph::new();
+        let a = graph.add_node("a.nix");
+        let b = graph.add_node("b.nix");
+        let c = graph.add_node("c.nix");
+        let d = graph.add_node("d.nix");
+        graph.add_edge(a, b);
+        graph.add_edge(b, c);
+        graph.add_edge(c, a);
+        graph.add_edge(d, d);
+
+        assert_eq!(graph.find_cycles(), vec![vec![a, b, c], vec![d]]);
+
+        let error = graph.topological_order().unwrap_err();
+        assert!(error.is_semantic_error());
+        assert!(error.to_string().contains("a.nix -> b.nix -> c.nix -> a.nix"));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = DependencyGraph::new();
+        let default = graph.add_node("default.nix");
+        let a = graph.add_node("lib/a.nix");
+        let b = graph.add_node("lib/\"b\".nix");
+        graph.add_edge(default, a);
+        graph.add_edge(a, b);
+        graph.add_edge(b, a);
+
+        let expected = [
+            "digraph dependencies {",
+            "    n0 [label=\"default.nix\"];",
+            "    n1 [label=\"lib/a.nix\"];",
+            r#"    n2 [label="lib/\"b\".nix"];"#,
+            "    n0 -> n1;",
+            "    n1 -> n2 [color=red];",
+            "    n2 -> n1 [color=red];",
+            "}",
+        ];
+        assert_eq!(graph.to_dot(), expected.join("\n") + "\n");
+        assert_eq!(DependencyGraph::new().to_dot(), "digraph dependencies {\n}\n");
+    }
+}
@@ -0,0 +1,177 @@
+//! Detection of derivations
+//!
+//! Packages are written as calls to `derivation` or to one of the
+//! `mkDerivation` functions of nixpkgs. Finding those calls, and the
+//! attributes passed to them, lets tooling reason about packages without
+//! evaluating anything.
+
+use crate::ast::{AttrKey, Expression, SourceLocation};
+
+/// Call of `derivation` or of a `mkDerivation` function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationCall {
+    /// Function called, as written: `derivation`, `stdenv.mkDerivation`,
+    /// `pkgs.foo.mkDerivation`, ...
+    pub function: String,
+    /// Names of the attributes of the argument, in source order
+    ///
+    /// Nested keys such as `meta.description` contribute their first
+    /// segment once. `None` if the argument is not an attribute set
+    /// literal, or a function returning one like `finalAttrs: { ... }`.
+    pub attributes: Option<Vec<String>>,
+    /// Location of the call, if the AST carries locations
+    pub location: Option<SourceLocation>,
+}
+
+impl DerivationCall {
+    /// Get the derivation call `expr` is, or `None` if it is another
+    /// expression
+    pub fn of(expr: &Expression) -> Option<Self> {
+        let Expression::Application { function, argument } = expr.unlocated() else {
+            return None;
+        };
+        let name = function_name(function)?;
+        let is_derivation = match name.rsplit('.').next() {
+            Some("derivation") => name == "derivation" || name == "builtins.derivation",
+            Some("mkDerivation") => true,
+            _ => false,
+        };
+        is_derivation.then(|| Self {
+            function: name,
+            attributes: argument_keys(argument),
+            location: expr.location(),
+        })
+    }
+
+    /// Check if the builtin `derivation` is called rather than a
+    /// `mkDerivation` wrapper
+    pub fn is_builtin(&self) -> bool {
+        self.function == "derivation" || self.function == "builtins.derivation"
+    }
+
+    /// Check if the argument is known to define `name`
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes
+            .as_ref()
+            .is_some_and(|attributes| attributes.iter().any(|attribute| attribute == name))
+    }
+}
+
+/// Find every derivation call in `expr`, in source order
+///
+/// Calls nested in the arguments of other calls, such as a `src` built
+/// with `stdenv.mkDerivation`, are listed after the call containing them.
+pub fn find_derivations(expr: &Expression) -> Vec<DerivationCall> {
+    let mut derivations = Vec::new();
+    // Trees can be deep, so walk them with an explicit stack
+    let mut stack = vec![expr];
+    while let Some(mut expr) = stack.pop() {
+        if let Some(call) = DerivationCall::of(expr) {
+            derivations.push(call);
+            // Skip the location wrappers, which are the same call
+            expr = expr.unlocated();
+        }
+        stack.extend(expr.subexpressions().into_iter().rev());
+    }
+    derivations
+}
+
+/// Get the dotted name of a variable or of a selection from one, such as
+/// `pkgs.stdenv.mkDerivation`
+fn function_name(expr: &Expression) -> Option<String> {
+    match expr.unlocated() {
+        Expression::Identifier(name) => Some(name.clone()),
+        Expression::Select { expr, path, default: None } => {
+            let mut name = function_name(expr)?;
+            for segment in AttrKey::static_names(path)? {
+                name.push('.');
+                name.push_str(segment);
+            }
+            Some(name)
+        }
+        _ => None,
+    }
+}
+
+/// Get the top-level keys of a derivation argument
+fn argument_keys(argument: &Expression) -> Option<Vec<String>> {
+    let attributes = match argument.unlocated() {
+        Expression::AttributeSet { attributes, .. } => attributes,
+        Expression::Function { body, .. } => match body.unlocated() {
+            Expression::AttributeSet { attributes, .. } => attributes,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut keys: Vec<String> = Vec::new();
+    for name in attributes.iter().filter_map(|attr| attr.key.static_path()?.first()) {
+        if !keys.contains(name) {
+            keys.push(name.clone());
+        }
+    }
+    Some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn find(source: &str) -> Vec<DerivationCall> {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        find_derivations(&expr)
+    }
+
+    fn keys(call: &DerivationCall) -> Vec<&str> {
+        call.attributes.iter().flatten().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_mk_derivation() {
+        let source = r#"{ stdenv, fetchurl, zlib }:
+stdenv.mkDerivation rec {
+  pname = "hello";
+  version = "2.12";
+  src = fetchurl {
+    url = "mirror://gnu/hello/hello-${version}.tar.gz";
+    sha256 = "1ayhp9v4m4rdhjmnl2bq3cibrbqqkgjbl3s7yk2nhlh8vj3ay16g";
+  };
+  buildInputs = [ zlib ];
+  inherit (zlib) meta;
+  meta.description = "A program that produces a familiar, friendly greeting";
+}"#;
+        let [call] = &find(source)[..] else {
+            panic!("expected one derivation");
+        };
+        assert_eq!(call.function, "stdenv.mkDerivation");
+        assert!(!call.is_builtin());
+        assert_eq!(keys(call), ["pname", "version", "src", "buildInputs", "meta"]);
+        assert!(call.has_attribute("src") && !call.has_attribute("name"));
+        assert_eq!(call.location.map(|location| location.line), Some(2));
+    }
+
+    #[test]
+    fn test_derivation_shapes() {
+        let source = r#"{
+  raw = derivation { name = "raw"; builder = ./builder.sh; system = "x86_64-linux"; };
+  fixed = pkgs.foo.mkDerivation (finalAttrs: { name = "fixed"; src = builtins.derivation { name = "src"; }; });
+  opaque = mkDerivation args;
+  other = pkgs.callPackage ./other.nix { };
+}"#;
+        let calls = find(source);
+        let shapes: Vec<(&str, Vec<&str>)> = calls.iter().map(|call| (call.function.as_str(), keys(call))).collect();
+        assert_eq!(
+            shapes,
+            [
+                ("derivation", vec!["name", "builder", "system"]),
+                ("pkgs.foo.mkDerivation", vec!["name", "src"]),
+                ("builtins.derivation", vec!["name"]),
+                ("mkDerivation", vec![]),
+            ]
+        );
+        assert!(calls[0].is_builtin() && calls[2].is_builtin());
+        assert_eq!(calls[3].attributes, None);
+        assert!(find("lib.derivation { name = \"x\"; }").is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! Structural validation of flakes
+
+use crate::ast::{AttrKey, Attribute, Expression, SourceLocation};
+use crate::parser::{DiagnosticCode, ParseDiagnostic};
+
+/// Attributes Nix accepts at the top level of a flake
+const FLAKE_ATTRIBUTES: &[&str] = &["description", "inputs", "outputs", "nixConfig"];
+
+/// Check if `expr` looks like a flake: an attribute set defining `inputs`
+/// or `outputs`
+pub fn is_flake(expr: &Expression) -> bool {
+    let Expression::AttributeSet { attributes, .. } = expr.unlocated() else {
+        return false;
+    };
+    attributes
+        .iter()
+        .any(|attr| matches!(first_segment(attr), Some("inputs" | "outputs")))
+}
+
+/// Check the structure of a flake, as found in `flake.nix`
+///
+/// Expressions that are not flakes (see [`is_flake`]) yield no
+/// diagnostics. Otherwise errors are reported for:
+///
+/// - a missing `outputs`, or one that is not a function
+/// - a `description` that is not a string
+/// - `inputs` entries that are neither attribute sets nor flake
+///   references, and `url` or `follows` attributes that are not strings
+///
+/// and warnings for top-level attributes Nix does not know. Values that are
+/// not literals, such as variables, cannot be checked and are accepted.
+/// Diagnostics are located at the offending value, or at the start of the
+/// source for expressions without locations.
+pub fn validate_flake(expr: &Expression) -> Vec<ParseDiagnostic> {
+    let mut diagnostics = Vec::new();
+    if !is_flake(expr) {
+        return diagnostics;
+    }
+    let Expression::AttributeSet { attributes, .. } = expr.unlocated() else {
+        return diagnostics;
+    };
+    let flake_location = locate(expr, None);
+    let mut error = |value: &Expression, message: String| {
+        diagnostics.push(
+            ParseDiagnostic::error(locate(value, Some(flake_location)), message).with_code(DiagnosticCode::InvalidFlake),
+        );
+    };
+
+    let mut has_outputs = false;
+    let mut unknown = Vec::new();
+    for attr in attributes {
+        let AttrKey::Static(path) = &attr.key else {
+            continue;
+        };
+        match path.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["outputs", ..] => {
+                has_outputs = true;
+                if path.len() > 1 || !may_be_function(&attr.value) {
+                    error(&attr.value, "flake `outputs` must be a function of the inputs".to_string());
+                }
+            }
+            ["description"] if !may_be_string(&attr.value) => {
+                error(&attr.value, "flake `description` must be a string".to_string());
+            }
+            ["inputs"] => match attr.value.unlocated() {
+                Expression::AttributeSet { attributes: inputs, .. } => {
+                    for input in inputs {
+                        if let AttrKey::Static(path) = &input.key {
+                            check_input(path, &input.value, &mut error);
+                        }
+                    }
+                }
+                value if is_literal(value) => error(&attr.value, "flake `inputs` must be an attribute set".to_string()),
+                _ => {}
+            },
+            ["inputs", ..] => check_input(&path[1..], &attr.value, &mut error),
+            [name, ..] if !FLAKE_ATTRIBUTES.contains(&name) => unknown.push((name.to_string(), &attr.value)),
+            _ => {}
+        }
+    }
+
+    if !has_outputs {
+        error(expr, "flake has no `outputs`".to_string());
+    }
+    for (name, value) in unknown {
+        diagnostics.push(
+            ParseDiagnostic::warning(
+                locate(value, Some(flake_location)),
+                format!("unknown flake attribute `{name}`"),
+            )
+            .with_code(DiagnosticCode::InvalidFlake)
+            .with_suggestion(format!("flakes may only define {}", FLAKE_ATTRIBUTES.join(", "))),
+        );
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.location.start_byte);
+    diagnostics
+}
+
+/// Check the value of `inputs.<path>`, where `path` starts with the name
+/// of the input
+fn check_input(path: &[String], value: &Expression, error: &mut impl FnMut(&Expression, String)) {
+    let Some(name) = path.first() else {
+        return;
+    };
+    match path.get(1).map(String::as_str) {
+        None if !matches!(value.unlocated(), Expression::AttributeSet { .. }) && !may_be_string(value) => {
+            error(value, format!("flake input `{name}` must be an attribute set or a flake reference"));
+        }
+        None => {
+            if let Expression::AttributeSet { attributes, .. } = value.unlocated() {
+                for attr in attributes {
+                    if let AttrKey::Static(rest) = &attr.key {
+                        let path: Vec<String> = path.iter().chain(rest).cloned().collect();
+                        check_input(&path, &attr.value, error);
+                    }
+                }
+            }
+        }
+        Some(field @ ("url" | "follows")) if path.len() == 2 && !may_be_string(value) => {
+            error(value, format!("`{field}` of flake input `{name}` must be a string"));
+        }
+        _ => {}
+    }
+}
+
+/// Get the first segment of a static attribute key
+fn first_segment(attr: &Attribute) -> Option<&str> {
+    match &attr.key {
+        AttrKey::Static(path) => path.first().map(String::as_str),
+        AttrKey::Dynamic(_) => None,
+    }
+}
+
+/// Check if `expr` is a literal, whose kind of value is known statically
+fn is_literal(expr: &Expression) -> bool {
+    matches!(
+        expr.unlocated(),
+        Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::String(_)
+            | Expression::StringInterpolation { .. }
+            | Expression::Path(_)
+            | Expression::Uri(_)
+            | Expression::Boolean(_)
+            | Expression::Null
+            | Expression::List(_)
+            | Expression::AttributeSet { .. }
+            | Expression::Function { .. }
+    )
+}
+
+/// Check if `expr` is a function, or could evaluate to one
+fn may_be_function(expr: &Expression) -> bool {
+    matches!(expr.unlocated(), Expression::Function { .. }) || !is_literal(expr)
+}
+
+/// Check if `expr` is a string or URI, or could evaluate to one
+fn may_be_string(expr: &Expression) -> bool {
+    matches!(
+        expr.unlocated(),
+        Expression::String(_) | Expression::StringInterpolation { .. } | Expression::Uri(_)
+    ) || !is_literal(expr)
+}
+
+/// Get the location of `expr`, falling back to `fallback` or the start of
+/// the source
+fn locate(expr: &Expression, fallback: Option<SourceLocation>) -> SourceLocation {
+    expr.location()
+        .or(fallback)
+        .unwrap_or_else(|| SourceLocation::new(1, 1, 0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{DiagnosticSeverity, NixParser};
+
+    fn validate(source: &str) -> Vec<(usize, usize, String)> {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        validate_flake(&expr)
+            .into_iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.code, Some(DiagnosticCode::InvalidFlake));
+                (diagnostic.location.line, diagnostic.location.column, diagnostic.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_flake() {
+        let source = r#"{
+  description = "A flake";
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+  inputs.utils = { url = github:numtide/flake-utils; inputs.nixpkgs.follows = "nixpkgs"; };
+  inputs.home = "github:nix-community/home-manager";
+  outputs = { self, nixpkgs, ... }@inputs: { packages = { }; };
+  nixConfig.bash-prompt = "> ";
+}"#;
+        assert_eq!(validate(source), []);
+        assert!(validate("{ outputs = import ./outputs.nix; }").is_empty());
+        // Attribute sets without inputs or outputs are not flakes
+        assert!(validate("{ description = 1; }").is_empty());
+        assert!(!is_flake(&Expression::Integer(1)));
+    }
+
+    #[test]
+    fn test_outputs_not_a_function() {
+        let source = "{\n  description = \"A flake\";\n  outputs = { packages = { }; };\n}";
+        assert_eq!(validate(source), [(3, 13, "flake `outputs` must be a function of the inputs".to_string())]);
+    }
+
+    #[test]
+    fn test_invalid_flake_structure() {
+        let source = "{
+  description = [ ];
+  inputs.nixpkgs = 42;
+  inputs.utils.url = true;
+  packages = { };
+}";
+        let messages: Vec<String> = validate(source).into_iter().map(|(_, _, message)| message).collect();
+        assert_eq!(
+            messages,
+            [
+                "flake has no `outputs`",
+                "flake `description` must be a string",
+                "flake input `nixpkgs` must be an attribute set or a flake reference",
+                "`url` of flake input `utils` must be a string",
+                "unknown flake attribute `packages`",
+            ]
+        );
+
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse("{ inputs = { }; outputs = _: { }; extra = 1; }").unwrap().expression().unwrap().unwrap();
+        let [warning] = &validate_flake(&expr)[..] else {
+            panic!("expected one diagnostic");
+        };
+        assert_eq!(warning.severity, DiagnosticSeverity::Warning);
+        assert_eq!(warning.suggestions, ["flakes may only define description, inputs, outputs, nixConfig"]);
+    }
+}
@@ -1,42 +1,353 @@
 //! Linting rules and analysis
 
-use crate::ast::Expression;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analysis::scope::{ScopeAnalyzer, ScopeType, references};
+use crate::ast::{Binding, Expression, SourceLocation};
 use crate::error::Result;
+use crate::parser::DiagnosticSeverity;
+use crate::visitor::{LocationTracker, VisitControl, Visitor};
 
 /// Static analysis linter for Nix code
-/// 
+///
 /// Applies configurable linting rules to detect potential issues,
 /// style violations, and best practice deviations in Nix expressions.
-pub struct Linter {}
+pub struct Linter {
+    rules: Vec<LintRule>,
+    severities: HashMap<LintRule, DiagnosticSeverity>,
+}
 impl Linter {
     /// Create a new linter with default rules
-    pub fn new() -> Self { Self {} }
+    pub fn new() -> Self {
+        Self {
+            rules: LintRule::all().to_vec(),
+            severities: HashMap::new(),
+        }
+    }
     /// Run linting analysis on an expression
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_expr` - The expression to analyze
-    /// 
+    ///
+    /// * `expr` - The expression to analyze
+    ///
     /// # Returns
-    /// 
-    /// A vector of lint results containing any issues found
-    pub fn lint(&mut self, _expr: &Expression) -> Result<Vec<LintResult>> { Ok(Vec::new()) }
+    ///
+    /// A vector of lint results containing any issues found, in source order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scope analysis behind
+    /// `LintRule::ShadowedVariable` fails.
+    pub fn lint(&mut self, expr: &Expression) -> Result<Vec<LintResult>> {
+        let mut collector = LintCollector {
+            linter: self,
+            results: Vec::new(),
+            locations: LocationTracker::default(),
+        };
+        collector.visit_expression(expr);
+        let mut results = collector.results;
+
+        if self.rules.contains(&LintRule::ShadowedVariable) {
+            results.extend(self.check_shadowed_variables(expr)?);
+            results.sort_by_key(|result| result.location.map(|location| location.start_byte));
+        }
+        Ok(results)
+    }
     /// Configure the linter with custom rules and settings
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_config` - Linting configuration options
-    pub fn with_config(self, _config: Config) -> Self { self }
+    ///
+    /// * `config` - Linting configuration options
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.rules = config.rules;
+        self.severities = config.severities;
+        self
+    }
+
+    /// Get the severity of a rule's results, taking overrides into account
+    fn severity(&self, rule: LintRule) -> DiagnosticSeverity {
+        self.severities.get(&rule).copied().unwrap_or(rule.severity())
+    }
+
+    /// Report `let` bindings and function parameters that hide a binding
+    /// of an enclosing scope
+    fn check_shadowed_variables(&self, expr: &Expression) -> Result<Vec<LintResult>> {
+        let scopes = ScopeAnalyzer::new().analyze(expr)?;
+        let mut results = Vec::new();
+
+        for scope in &scopes {
+            if !matches!(scope.scope_type, ScopeType::Let | ScopeType::Function) {
+                continue;
+            }
+            for (name, location) in scope.bindings.iter().zip(&scope.locations) {
+                let mut parent = scope.parent;
+                while let Some(index) = parent
+                    && !scopes[index].binds(name)
+                {
+                    parent = scopes[index].parent;
+                }
+                let Some(outer) = parent else {
+                    continue;
+                };
+
+                let rule = LintRule::ShadowedVariable;
+                results.push(LintResult {
+                    rule,
+                    message: format!("`{name}` shadows an outer binding of the same name"),
+                    severity: self.severity(rule),
+                    location: *location,
+                    related_location: scopes[outer].location_of(name),
+                });
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// A single linting rule that can be applied to Nix code
-#[derive(Debug, Clone)]
-pub struct LintRule {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// A `let` binding that is never referenced by the body or its siblings
+    UnusedLetBinding,
+    /// A `let` binding or function parameter with the name of a binding
+    /// of an enclosing scope, which it hides
+    ShadowedVariable,
+}
+
+impl LintRule {
+    /// Get all lint rules
+    pub const fn all() -> &'static [LintRule] {
+        &[LintRule::UnusedLetBinding, LintRule::ShadowedVariable]
+    }
+
+    /// Get the rule name, as used in configuration and output
+    pub const fn name(self) -> &'static str {
+        match self {
+            LintRule::UnusedLetBinding => "unused-let-binding",
+            LintRule::ShadowedVariable => "shadowed-variable",
+        }
+    }
+
+    /// Get the default severity of the rule's results
+    pub const fn severity(self) -> DiagnosticSeverity {
+        match self {
+            LintRule::UnusedLetBinding | LintRule::ShadowedVariable => DiagnosticSeverity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// Result of applying a lint rule, containing any issues found
-#[derive(Debug, Clone)]
-pub struct LintResult {}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintResult {
+    /// Rule that produced the result
+    pub rule: LintRule,
+    /// Human-readable message
+    pub message: String,
+    /// Severity of the issue
+    pub severity: DiagnosticSeverity,
+    /// Location of the offending code, if the AST carries locations
+    pub location: Option<SourceLocation>,
+    /// Location of related code, such as the binding a shadowing binding
+    /// hides
+    pub related_location: Option<SourceLocation>,
+}
+
+impl fmt::Display for LintResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.severity, self.message, self.rule)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}:{}", location.line, location.column)?;
+        }
+        Ok(())
+    }
+}
 
 /// Configuration options for the linter
 #[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+pub struct Config {
+    /// Rules to apply
+    pub rules: Vec<LintRule>,
+    /// Severities overriding the default `LintRule::severity` of rules
+    pub severities: HashMap<LintRule, DiagnosticSeverity>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rules: LintRule::all().to_vec(),
+            severities: HashMap::new(),
+        }
+    }
+}
+
+/// Visitor that applies the enabled rules to every expression
+struct LintCollector<'a> {
+    linter: &'a Linter,
+    results: Vec<LintResult>,
+    locations: LocationTracker,
+}
+
+impl LintCollector<'_> {
+    /// Report `let` bindings that neither the body nor a sibling refers to
+    ///
+    /// `let` bindings are recursive, so a binding used only by another
+    /// binding counts as used, but one used only by itself does not.
+    /// Bindings without a location are reported at the `let`'s `location`.
+    fn check_unused_let_bindings(
+        &mut self,
+        bindings: &[Binding],
+        body: &Expression,
+        location: Option<SourceLocation>,
+    ) {
+        for (index, binding) in bindings.iter().enumerate() {
+            let used = references(body, &binding.name)
+                || bindings
+                    .iter()
+                    .enumerate()
+                    .any(|(other, sibling)| other != index && references(&sibling.value, &binding.name));
+            if used {
+                continue;
+            }
+
+            let rule = LintRule::UnusedLetBinding;
+            self.results.push(LintResult {
+                rule,
+                message: format!("unused let binding `{}`", binding.name),
+                severity: self.linter.severity(rule),
+                location: binding.value.location().or(location),
+                related_location: None,
+            });
+        }
+    }
+}
+
+impl Visitor for LintCollector<'_> {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        let Some(location) = self.locations.enter(expr) else {
+            return VisitControl::Continue;
+        };
+
+        if let Expression::LetIn { bindings, body } = expr
+            && self.linter.rules.contains(&LintRule::UnusedLetBinding)
+        {
+            self.check_unused_let_bindings(bindings, body, location);
+        }
+        VisitControl::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn lint(source: &str) -> Vec<LintResult> {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        Linter::new().lint(&expr).unwrap()
+    }
+
+    fn unused_names(source: &str) -> Vec<String> {
+        lint(source)
+            .into_iter()
+            .filter(|result| result.rule == LintRule::UnusedLetBinding)
+            .map(|result| result.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_let_binding() {
+        let results = lint("let a = 1; b = 2; in a");
+        assert_eq!(results.len(), 1);
+
+        let result = &results[0];
+        assert_eq!(result.rule, LintRule::UnusedLetBinding);
+        assert_eq!(result.message, "unused let binding `b`");
+        assert_eq!(result.severity, DiagnosticSeverity::Warning);
+        let location = result.location.unwrap();
+        assert_eq!((location.line, location.column), (1, 16));
+    }
+
+    #[test]
+    fn test_bindings_used_by_siblings() {
+        assert!(unused_names("let a = b; b = 2; in a").is_empty());
+        assert!(unused_names("let a = 1; in rec { x = a; y = x; }").is_empty());
+        assert_eq!(unused_names("let a = a; in 1"), vec!["unused let binding `a`"]);
+    }
+
+    #[test]
+    fn test_shadowed_references() {
+        assert_eq!(unused_names("let a = 1; in (a: a)"), vec!["unused let binding `a`"]);
+        assert_eq!(unused_names("let a = 1; in rec { a = 2; b = a; }"), vec!["unused let binding `a`"]);
+        assert!(unused_names("let a = 1; in { a = 2; b = a; }").is_empty());
+        assert!(unused_names("let a = 1; in let inherit a; in a").is_empty());
+        assert!(unused_names("let a = 1; in { x ? a }: x").is_empty());
+    }
+
+    #[test]
+    fn test_disabled_rule() {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse("let a = 1; in 2").unwrap().expression().unwrap().unwrap();
+        let mut linter = Linter::new().with_config(Config { rules: Vec::new(), ..Config::default() });
+        assert!(linter.lint(&expr).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_variable() {
+        let source = "let x = 1; in (x: x)";
+        let results: Vec<LintResult> = lint(source)
+            .into_iter()
+            .filter(|result| result.rule == LintRule::ShadowedVariable)
+            .collect();
+        assert_eq!(results.len(), 1);
+
+        let result = &results[0];
+        assert_eq!(result.message, "`x` shadows an outer binding of the same name");
+        assert_eq!(result.severity, DiagnosticSeverity::Warning);
+        assert_eq!(result.location.map(|location| location.start_byte), Some(15));
+        assert_eq!(result.related_location.map(|location| location.start_byte), Some(8));
+    }
+
+    #[test]
+    fn test_shadowing_across_scopes() {
+        let shadowed = |source: &str| -> Vec<String> {
+            lint(source)
+                .into_iter()
+                .filter(|result| result.rule == LintRule::ShadowedVariable)
+                .map(|result| result.message)
+                .collect()
+        };
+
+        assert_eq!(shadowed("{ a, b }: let c = a; in with b; let b = c; in b").len(), 1);
+        assert_eq!(shadowed("rec { f = f: f; }").len(), 1);
+        assert_eq!(shadowed("x: y: x: x + y").len(), 1);
+        assert!(shadowed("[ (x: x) (x: x) ]").is_empty());
+        assert!(shadowed("let a = 1; in { a = 2; b = a; }").is_empty());
+    }
+
+    #[test]
+    fn test_configured_severity() {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse("x: x: x").unwrap().expression().unwrap().unwrap();
+        let config = Config {
+            severities: HashMap::from([(LintRule::ShadowedVariable, DiagnosticSeverity::Info)]),
+            ..Config::default()
+        };
+        let results = Linter::new().with_config(config).lint(&expr).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].severity, DiagnosticSeverity::Info);
+    }
+}
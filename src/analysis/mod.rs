@@ -3,15 +3,25 @@
 //! This module provides tools for analyzing parsed Nix code,
 //! including semantic validation, dependency analysis, and code quality checks.
 
+pub mod cache;
 pub mod semantic;
 pub mod dependency;
+pub mod derivation;
+pub mod flake;
 pub mod lint;
 pub mod scope;
+pub mod signature;
+pub mod workspace;
 
+pub use self::cache::AnalysisCache;
 pub use self::semantic::{SemanticAnalyzer, SemanticError};
-pub use self::dependency::{DependencyAnalyzer, Dependency, DependencyGraph};
+pub use self::dependency::{DependencyAnalyzer, Dependency, DependencyGraph, NodeId};
+pub use self::derivation::{DerivationCall, find_derivations};
+pub use self::flake::{is_flake, validate_flake};
 pub use self::lint::{Linter, LintRule, LintResult};
 pub use self::scope::{ScopeAnalyzer, Scope, ScopeType};
+pub use self::signature::{FunctionSignature, ParameterSignature, FieldSignature, function_signatures};
+pub use self::workspace::{WorkspaceAnalyzer, WorkspaceReport, FileReport};
 
 use crate::ast::Expression;
 use crate::error::{ParseError, Result};
@@ -25,6 +35,7 @@ pub struct Analyzer {
     dependency: DependencyAnalyzer,
     linter: Linter,
     scope: ScopeAnalyzer,
+    cache: Option<AnalysisCache>,
 }
 
 impl Analyzer {
@@ -35,11 +46,44 @@ impl Analyzer {
             dependency: DependencyAnalyzer::new(),
             linter: Linter::new(),
             scope: ScopeAnalyzer::new(),
+            cache: None,
         }
     }
     
     /// Run all analysis passes on an expression
+    ///
+    /// With a cache, the result of an expression analyzed before is
+    /// returned without running the passes again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scope analysis or linting fails. Problems
+    /// found in the expression itself are collected in
+    /// [`AnalysisResult::errors`] instead.
     pub fn analyze(&mut self, expression: &Expression) -> Result<AnalysisResult> {
+        if let Some(result) = self.cache.as_mut().and_then(|cache| cache.get(expression)) {
+            return Ok(result);
+        }
+        let result = self.run_passes(expression)?;
+        if let Some(cache) = &mut self.cache {
+            cache.insert(expression, result.clone());
+        }
+        Ok(result)
+    }
+    
+    /// Remember analysis results in an [`AnalysisCache`]
+    pub fn with_cache(mut self) -> Self {
+        self.cache = Some(AnalysisCache::new());
+        self
+    }
+    
+    /// Get the cache of analysis results, if caching is enabled
+    pub fn cache(&self) -> Option<&AnalysisCache> {
+        self.cache.as_ref()
+    }
+    
+    /// Run every analysis pass, without consulting the cache
+    fn run_passes(&mut self, expression: &Expression) -> Result<AnalysisResult> {
         let mut result = AnalysisResult::new();
         
         // Scope analysis (foundation for other analyses)
@@ -48,7 +92,10 @@ impl Analyzer {
         
         // Semantic analysis
         match self.semantic.analyze(expression) {
-            Ok(semantic_info) => result.semantic = Some(semantic_info),
+            Ok(semantic_info) => {
+                result.errors.extend(semantic_info.errors.iter().cloned());
+                result.semantic = Some(semantic_info);
+            }
             Err(error) => result.errors.push(error),
         }
         
@@ -66,7 +113,13 @@ impl Analyzer {
     }
     
     /// Configure the analyzer
+    ///
+    /// Cached results were computed with the old configuration, so they
+    /// are dropped.
     pub fn with_config(mut self, config: AnalyzerConfig) -> Self {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
         if let Some(semantic_config) = config.semantic {
             self.semantic = self.semantic.with_config(semantic_config);
         }
@@ -136,6 +189,11 @@ impl AnalysisResult {
     }
     
     /// Get all errors as a combined result
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors found, combined with [`ParseError::combine`], if
+    /// there are any.
     pub fn into_result(self) -> Result<Self> {
         if self.has_errors() {
             Err(ParseError::combine(self.errors))
@@ -169,4 +227,20 @@ mod tests {
         assert!(result.scopes.is_empty());
         assert!(result.semantic.is_none());
     }
+
+    #[test]
+    fn test_semantic_errors_in_result() {
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let expr = parser.parse("{\n  a = 1;\n  a = 2;\n}").unwrap().expression().unwrap().unwrap();
+        let result = Analyzer::new().analyze(&expr).unwrap();
+
+        assert!(result.has_errors());
+        let [ParseError::SemanticError { message, span: Some(span), .. }] = result.errors.as_slice() else {
+            panic!("expected one located semantic error, got {:?}", result.errors);
+        };
+        assert_eq!(message, "Duplicate attribute: a");
+        // The span covers the second binding, from its key to the `;`
+        assert_eq!((span.start.line, span.start.column), (3, 3));
+        assert_eq!((span.end.line, span.end.column), (3, 9));
+    }
 }
\ No newline at end of file
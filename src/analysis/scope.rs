@@ -1,36 +1,534 @@
 //! Scope analysis for variable resolution
 
-use crate::ast::Expression;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::ast::{Attribute, Binding, Expression, Parameter, SourceLocation};
 use crate::error::Result;
+use crate::visitor::{LocationTracker, VisitControl, Visitor};
 
 /// Analyzer for tracking variable scopes and bindings
-/// 
+///
 /// Analyzes Nix expressions to determine variable visibility,
 /// binding locations, and scope hierarchies.
+#[derive(Default)]
 pub struct ScopeAnalyzer {}
 impl ScopeAnalyzer {
     /// Create a new scope analyzer
     pub fn new() -> Self { Self {} }
     /// Analyze an expression to determine its scope structure
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_expr` - The expression to analyze for scope information
-    /// 
+    ///
+    /// * `expr` - The expression to analyze for scope information
+    ///
     /// # Returns
-    /// 
-    /// A vector of scopes found in the expression
-    pub fn analyze(&mut self, _expr: &Expression) -> Result<Vec<Scope>> { Ok(Vec::new()) }
+    ///
+    /// A flat vector of scopes in the order they are opened; each scope
+    /// refers to its enclosing scope through `parent`
+    ///
+    /// # Errors
+    ///
+    /// Never fails; every expression has a scope structure.
+    pub fn analyze(&mut self, expr: &Expression) -> Result<Vec<Scope>> {
+        let mut collector = ScopeCollector::default();
+        collector.visit_expression(expr);
+        Ok(collector.scopes)
+    }
 }
 
 /// Represents a variable scope with its bindings and type
-#[derive(Debug, Clone)]
-pub struct Scope {}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    /// Kind of expression that opened the scope
+    pub scope_type: ScopeType,
+    /// Byte range of that expression, if the AST carries source locations
+    pub range: Option<Range<usize>>,
+    /// Names bound by the scope, in source order; empty for `with`
+    pub bindings: Vec<String>,
+    /// Where each of `bindings` is defined, if the AST carries source
+    /// locations: the bound value for `let` and `rec` bindings, and the
+    /// whole function for parameters
+    pub locations: Vec<Option<SourceLocation>>,
+    /// Index of the enclosing scope in the analysis result
+    pub parent: Option<usize>,
+}
+
+impl Scope {
+    /// Check if the scope binds `name`
+    pub fn binds(&self, name: &str) -> bool {
+        self.bindings.iter().any(|binding| binding == name)
+    }
+
+    /// Get where the scope defines `name`
+    pub fn location_of(&self, name: &str) -> Option<SourceLocation> {
+        let index = self.bindings.iter().position(|binding| binding == name)?;
+        self.locations[index]
+    }
+}
 
 /// Different types of scopes in Nix expressions
-#[derive(Debug, Clone)]
-pub enum ScopeType {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeType {
+    /// `let ... in ...`
+    Let,
+    /// Function body, binding the parameter or pattern fields
+    Function,
+    /// `rec { ... }`, whose attributes can refer to each other
+    RecAttrSet,
+    /// `with expr; ...`, whose names are only known at evaluation time
+    With,
+}
 
 /// Configuration options for scope analysis
 #[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+pub struct Config {}
+
+/// Visitor that records a scope for every binding construct
+#[derive(Default)]
+struct ScopeCollector {
+    scopes: Vec<Scope>,
+    /// Indices of the scopes enclosing the current expression
+    stack: Vec<usize>,
+    locations: LocationTracker,
+    /// Location of the expression currently being visited
+    current: Option<SourceLocation>,
+}
+
+impl ScopeCollector {
+    /// Open a scope for the expression currently being visited
+    fn push_scope(
+        &mut self,
+        scope_type: ScopeType,
+        names: impl IntoIterator<Item = (String, Option<SourceLocation>)>,
+    ) {
+        let mut bindings: Vec<String> = Vec::new();
+        let mut locations = Vec::new();
+        for (name, location) in names {
+            if !bindings.contains(&name) {
+                bindings.push(name);
+                locations.push(location);
+            }
+        }
+
+        self.scopes.push(Scope {
+            scope_type,
+            range: self.current.take().map(|loc| loc.start_byte..loc.end_byte),
+            bindings,
+            locations,
+            parent: self.stack.last().copied(),
+        });
+        self.stack.push(self.scopes.len() - 1);
+    }
+
+    fn pop_scope(&mut self, control: VisitControl) -> VisitControl {
+        self.stack.pop();
+        control
+    }
+}
+
+impl Visitor for ScopeCollector {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        if let Some(location) = self.locations.enter(expr) {
+            self.current = location;
+        }
+        VisitControl::Continue
+    }
+
+    fn visit_let_in(&mut self, bindings: &[Binding], body: &Expression) -> VisitControl {
+        // Let bindings are recursive: values see their siblings
+        let names = bindings.iter().map(|b| (b.name.clone(), b.value.location()));
+        self.push_scope(ScopeType::Let, names);
+        for binding in bindings {
+            if self.visit_expression(&binding.value).is_stop() {
+                return self.pop_scope(VisitControl::Stop);
+            }
+        }
+        let control = self.visit_expression(body);
+        self.pop_scope(control)
+    }
+
+    fn visit_function(&mut self, parameter: &Parameter, body: &Expression) -> VisitControl {
+        let location = self.current;
+        let names: Vec<String> = match parameter {
+            Parameter::Identifier(name) => vec![name.clone()],
+            Parameter::Pattern { fields, bind, .. } => fields
+                .iter()
+                .map(|field| field.name.clone())
+                .chain(bind.clone())
+                .collect(),
+        };
+        self.push_scope(ScopeType::Function, names.into_iter().map(|name| (name, location)));
+
+        // Defaults are evaluated with the other arguments in scope
+        if let Parameter::Pattern { fields, .. } = parameter {
+            for default in fields.iter().filter_map(|field| field.default.as_ref()) {
+                if self.visit_expression(default).is_stop() {
+                    return self.pop_scope(VisitControl::Stop);
+                }
+            }
+        }
+        let control = self.visit_expression(body);
+        self.pop_scope(control)
+    }
+
+    fn visit_attribute_set(&mut self, recursive: bool, attributes: &[Attribute]) -> VisitControl {
+        if recursive {
+            let names = attributes
+                .iter()
+                .filter_map(|attr| Some((attr.key.static_path()?.first()?.clone(), attr.value.location())));
+            self.push_scope(ScopeType::RecAttrSet, names);
+        }
+
+        let mut control = VisitControl::Continue;
+        for attr in attributes {
+            if self.visit_attr_key(&attr.key).is_stop() || self.visit_expression(&attr.value).is_stop() {
+                control = VisitControl::Stop;
+                break;
+            }
+        }
+
+        if recursive {
+            self.pop_scope(control)
+        } else {
+            control
+        }
+    }
+
+    fn visit_with(&mut self, scope: &Expression, body: &Expression) -> VisitControl {
+        // The namespace expression is evaluated outside the `with`
+        let location = self.current.take();
+        if self.visit_expression(scope).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.current = location;
+        self.push_scope(ScopeType::With, Vec::new());
+        let control = self.visit_expression(body);
+        self.pop_scope(control)
+    }
+}
+
+/// Check if `name` occurs free in `expr`
+pub(crate) fn references(expr: &Expression, name: &str) -> bool {
+    let mut finder = ReferenceFinder { name, found: false };
+    finder.visit_expression(expr);
+    finder.found
+}
+
+/// Visitor that looks for a free occurrence of one name
+///
+/// Scopes that rebind the name are skipped, except for `inherit name;`
+/// entries, which always refer to the enclosing scope.
+struct ReferenceFinder<'a> {
+    name: &'a str,
+    found: bool,
+}
+
+impl ReferenceFinder<'_> {
+    fn found(&mut self) -> VisitControl {
+        self.found = true;
+        VisitControl::Stop
+    }
+}
+
+impl Visitor for ReferenceFinder<'_> {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        match expr {
+            Expression::Identifier(id) if id == self.name => self.found(),
+            _ if expr.inherits(self.name) => self.found(),
+            _ => VisitControl::Continue,
+        }
+    }
+
+    fn visit_let_in(&mut self, bindings: &[Binding], body: &Expression) -> VisitControl {
+        if bindings.iter().any(|binding| binding.name == self.name) {
+            if bindings.iter().any(|binding| binding.value.inherits(self.name)) {
+                return self.found();
+            }
+            return VisitControl::Continue;
+        }
+
+        for binding in bindings {
+            if self.visit_expression(&binding.value).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        self.visit_expression(body)
+    }
+
+    fn visit_function(&mut self, parameter: &Parameter, body: &Expression) -> VisitControl {
+        match parameter {
+            Parameter::Identifier(param) if param == self.name => VisitControl::Continue,
+            Parameter::Identifier(_) => self.visit_expression(body),
+            Parameter::Pattern { fields, bind, .. } => {
+                let shadowed = fields.iter().any(|field| field.name == self.name)
+                    || bind.as_deref() == Some(self.name);
+                if shadowed {
+                    return VisitControl::Continue;
+                }
+
+                for default in fields.iter().filter_map(|field| field.default.as_ref()) {
+                    if self.visit_expression(default).is_stop() {
+                        return VisitControl::Stop;
+                    }
+                }
+                self.visit_expression(body)
+            }
+        }
+    }
+
+    fn visit_attribute_set(&mut self, recursive: bool, attributes: &[Attribute]) -> VisitControl {
+        let shadowed = recursive
+            && attributes
+                .iter()
+                .filter_map(|attr| attr.key.static_path()?.first())
+                .any(|first| first == self.name);
+        if shadowed {
+            if attributes.iter().any(|attr| attr.value.inherits(self.name)) {
+                return self.found();
+            }
+            return VisitControl::Continue;
+        }
+
+        for attr in attributes {
+            if self.visit_attr_key(&attr.key).is_stop() || self.visit_expression(&attr.value).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        VisitControl::Continue
+    }
+}
+
+impl Expression {
+    /// Get the variables that occur free in the expression
+    ///
+    /// `let` bindings, function parameters (including pattern fields and
+    /// `@` binds) and the attributes of `rec` sets bind names; `inherit x;`
+    /// refers to `x` in the enclosing scope. A `with` expression can only
+    /// supply names at evaluation time, so variables used in its body are
+    /// still reported as free unless they are bound lexically.
+    pub fn free_variables(&self) -> HashSet<String> {
+        let mut collector = VariableCollector::default();
+        collector.visit_expression(self);
+        collector.free
+    }
+
+    /// Get the variables bound anywhere in the expression
+    ///
+    /// These are the names of `let` bindings, function parameters and
+    /// pattern fields, `@` binds, and the top-level attributes of `rec`
+    /// sets. Attributes of non-recursive sets and `with` namespaces do not
+    /// bind variables.
+    pub fn bound_variables(&self) -> HashSet<String> {
+        let mut collector = VariableCollector::default();
+        collector.visit_expression(self);
+        collector.bound
+    }
+}
+
+/// Visitor that collects the free and bound variables of an expression
+#[derive(Default)]
+struct VariableCollector {
+    /// Names bound by the enclosing scopes, innermost last
+    scopes: Vec<Vec<String>>,
+    free: HashSet<String>,
+    bound: HashSet<String>,
+}
+
+impl VariableCollector {
+    fn reference(&mut self, name: &str) {
+        if !self.scopes.iter().flatten().any(|bound| bound == name) {
+            self.free.insert(name.to_string());
+        }
+    }
+
+    /// Record the names copied by `inherit` entries without source, which
+    /// refer to the scope enclosing the bindings, and return the remaining
+    /// values
+    fn split_inherited<'e>(&mut self, values: impl Iterator<Item = &'e Expression>) -> Vec<&'e Expression> {
+        let mut rest = Vec::new();
+        for value in values {
+            if let Expression::Inherit { source: None, attributes } = value.unlocated() {
+                for name in attributes {
+                    self.reference(name);
+                }
+            } else {
+                rest.push(value);
+            }
+        }
+        rest
+    }
+
+    /// Visit `exprs` with `names` bound
+    fn visit_in_scope<'e>(
+        &mut self,
+        names: Vec<String>,
+        exprs: impl IntoIterator<Item = &'e Expression>,
+    ) -> VisitControl {
+        self.bound.extend(names.iter().cloned());
+        self.scopes.push(names);
+        for expr in exprs {
+            self.visit_expression(expr);
+        }
+        self.scopes.pop();
+        VisitControl::Continue
+    }
+}
+
+impl Visitor for VariableCollector {
+    fn visit_identifier(&mut self, id: &str) {
+        self.reference(id);
+    }
+
+    fn visit_inherit(&mut self, source: Option<&Expression>, attributes: &[String]) -> VisitControl {
+        if let Some(source_expr) = source {
+            return self.visit_expression(source_expr);
+        }
+        for name in attributes {
+            self.reference(name);
+        }
+        VisitControl::Continue
+    }
+
+    fn visit_let_in(&mut self, bindings: &[Binding], body: &Expression) -> VisitControl {
+        let values = self.split_inherited(bindings.iter().map(|binding| &binding.value));
+        let names = bindings.iter().map(|binding| binding.name.clone()).collect();
+        self.visit_in_scope(names, values.into_iter().chain([body]))
+    }
+
+    fn visit_function(&mut self, parameter: &Parameter, body: &Expression) -> VisitControl {
+        match parameter {
+            Parameter::Identifier(name) => self.visit_in_scope(vec![name.clone()], [body]),
+            Parameter::Pattern { fields, bind, .. } => {
+                let names = fields.iter().map(|field| field.name.clone()).chain(bind.clone()).collect();
+                let defaults = fields.iter().filter_map(|field| field.default.as_ref());
+                self.visit_in_scope(names, defaults.chain([body]))
+            }
+        }
+    }
+
+    fn visit_attribute_set(&mut self, recursive: bool, attributes: &[Attribute]) -> VisitControl {
+        for attr in attributes {
+            self.visit_attr_key(&attr.key);
+        }
+        if !recursive {
+            for attr in attributes {
+                self.visit_expression(&attr.value);
+            }
+            return VisitControl::Continue;
+        }
+
+        let values = self.split_inherited(attributes.iter().map(|attr| &attr.value));
+        let names = attributes
+            .iter()
+            .filter_map(|attr| attr.key.static_path()?.first().cloned())
+            .collect();
+        self.visit_in_scope(names, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{NixParser, ParserConfig};
+
+    fn scopes_of(source: &str) -> Vec<Scope> {
+        let mut parser = NixParser::new().unwrap();
+        let result = parser.parse(source).unwrap();
+        let expr = result.expression().unwrap().unwrap();
+        ScopeAnalyzer::new().analyze(&expr).unwrap()
+    }
+
+    #[test]
+    fn test_let_with_nested_function() {
+        let source = "let a = 1; in (b: a + b)";
+        let scopes = scopes_of(source);
+
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes[0].scope_type, ScopeType::Let);
+        assert_eq!(scopes[0].bindings, vec!["a"]);
+        assert_eq!(scopes[0].parent, None);
+        assert_eq!(scopes[0].range, Some(0..source.len()));
+
+        assert_eq!(scopes[1].scope_type, ScopeType::Function);
+        assert_eq!(scopes[1].bindings, vec!["b"]);
+        assert_eq!(scopes[1].parent, Some(0));
+        assert_eq!(scopes[1].range, Some(15..23));
+
+        assert_eq!(scopes[0].location_of("a").map(|loc| loc.start_byte), Some(8));
+        assert_eq!(scopes[1].location_of("b").map(|loc| loc.start_byte), Some(15));
+        assert_eq!(scopes[1].location_of("a"), None);
+    }
+
+    #[test]
+    fn test_scope_types() {
+        let scopes = scopes_of(
+            "{ x ? 1, ... }@args: with args; rec { a.b = x; a.c = 2; d = { e = a; }; }",
+        );
+        let types: Vec<ScopeType> = scopes.iter().map(|s| s.scope_type).collect();
+        assert_eq!(types, vec![ScopeType::Function, ScopeType::With, ScopeType::RecAttrSet]);
+
+        assert_eq!(scopes[0].bindings, vec!["x", "args"]);
+        assert!(scopes[1].bindings.is_empty());
+        assert_eq!(scopes[1].parent, Some(0));
+        assert_eq!(scopes[2].bindings, vec!["a", "d"]);
+        assert!(scopes[2].binds("d"));
+        assert_eq!(scopes[2].parent, Some(1));
+    }
+
+    #[test]
+    fn test_sibling_scopes() {
+        let scopes = scopes_of("[ (x: x) (let y = 1; in y) ]");
+        assert_eq!(scopes.len(), 2);
+        assert!(scopes.iter().all(|scope| scope.parent.is_none()));
+    }
+
+    #[test]
+    fn test_scopes_without_locations() {
+        let config = ParserConfig::builder().include_locations(false).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+        let expr = parser.parse("x: x").unwrap().expression().unwrap().unwrap();
+        let scopes = ScopeAnalyzer::new().analyze(&expr).unwrap();
+        assert_eq!(scopes.len(), 1);
+        assert_eq!(scopes[0].range, None);
+    }
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(std::string::ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_free_and_bound_variables() {
+        let parse = |source: &str| {
+            let mut parser = NixParser::new().unwrap();
+            parser.parse(source).unwrap().expression().unwrap().unwrap()
+        };
+
+        let expr = parse("x: x + y");
+        assert_eq!(expr.free_variables(), names(&["y"]));
+        assert_eq!(expr.bound_variables(), names(&["x"]));
+
+        let expr = parse("let a = b; in a");
+        assert_eq!(expr.free_variables(), names(&["b"]));
+        assert_eq!(expr.bound_variables(), names(&["a"]));
+
+        let expr = parse("{ a, b ? a + c, ... }@args: args.d + e");
+        assert_eq!(expr.free_variables(), names(&["c", "e"]));
+        assert_eq!(expr.bound_variables(), names(&["a", "b", "args"]));
+
+        // Inherited names refer to the enclosing scope
+        let expr = parse("let inherit x; y = x; inherit (y) z; in { inherit y w; v = z; }");
+        assert_eq!(expr.free_variables(), names(&["x", "w"]));
+        assert_eq!(expr.bound_variables(), names(&["x", "y", "z"]));
+
+        let expr = parse("rec { a.b = c; c = d; }");
+        assert_eq!(expr.free_variables(), names(&["d"]));
+        assert_eq!(expr.bound_variables(), names(&["a", "c"]));
+        assert_eq!(parse("{ a = a; }").free_variables(), names(&["a"]));
+
+        // `with` may or may not provide `hello`
+        let expr = parse("pkgs: with pkgs; [ hello pkgs ]");
+        assert_eq!(expr.free_variables(), names(&["hello"]));
+        assert_eq!(expr.bound_variables(), names(&["pkgs"]));
+    }
+}
@@ -1,12 +1,16 @@
 //! Semantic analysis for Nix expressions
 
-use crate::ast::Expression;
-use crate::error::Result;
+use std::collections::HashMap;
+
+use crate::ast::{AttrKey, Attribute, Expression};
+use crate::error::{ErrorSpan, ParseError, Result, SemanticError as SemanticErrorKind};
+use crate::visitor::{VisitControl, Visitor};
 
 /// Semantic analyzer for Nix code
 /// 
 /// Performs semantic validation and analysis on parsed Nix expressions,
 /// checking for type consistency, variable scoping, and other semantic rules.
+#[derive(Default)]
 pub struct SemanticAnalyzer {
     // Implementation will be added later
 }
@@ -26,9 +30,15 @@ impl SemanticAnalyzer {
     /// # Returns
     /// 
     /// Returns semantic information about the expression or an error if analysis fails
-    pub fn analyze(&mut self, _expression: &Expression) -> Result<SemanticInfo> {
-        // Placeholder implementation
-        Ok(SemanticInfo {})
+    ///
+    /// # Errors
+    ///
+    /// Never fails: the problems found are reported in
+    /// [`SemanticInfo::errors`].
+    pub fn analyze(&mut self, expression: &Expression) -> Result<SemanticInfo> {
+        let mut finder = DuplicateAttributeFinder::default();
+        finder.visit_expression(expression);
+        Ok(SemanticInfo { errors: finder.errors })
     }
     
     /// Configure the analyzer with custom settings
@@ -44,7 +54,8 @@ impl SemanticAnalyzer {
 /// Information gathered from semantic analysis
 #[derive(Debug, Clone)]
 pub struct SemanticInfo {
-    // Semantic information will be added later
+    /// Semantic errors found in the expression
+    pub errors: Vec<ParseError>,
 }
 
 /// Configuration options for semantic analysis
@@ -57,4 +68,168 @@ pub struct Config {
 #[derive(Debug, Clone)]
 pub struct SemanticError {
     // Semantic error details will be added later
-}
\ No newline at end of file
+}
+
+/// Visitor that reports attributes defined more than once in a set
+///
+/// Dotted paths extend the same nested set, so `{ a.b = 1; a.c = 2; }` is
+/// fine, as is extending a set literal: `{ a = { b = 1; }; a.c = 2; }`.
+/// Quoted keys denote the same name as identifiers, while computed keys
+/// other than string literals cannot be checked.
+#[derive(Default)]
+struct DuplicateAttributeFinder {
+    errors: Vec<ParseError>,
+}
+
+impl Visitor for DuplicateAttributeFinder {
+    fn visit_attribute_set(&mut self, _recursive: bool, attributes: &[Attribute]) -> VisitControl {
+        let mut defined = AttrTree::default();
+        for (index, attr) in attributes.iter().enumerate() {
+            let Some(path) = static_key(&attr.key) else {
+                continue;
+            };
+            if let Err(name) = defined.define(&path, &attr.value, index) {
+                let message = SemanticErrorKind::DuplicateAttribute { name }.to_string();
+                self.errors.push(match attr.location {
+                    Some(location) => ParseError::semantic_error_at(message, ErrorSpan::from(&location)),
+                    None => ParseError::semantic_error(message),
+                });
+            }
+        }
+
+        for attr in attributes {
+            if self.visit_attr_key(&attr.key).is_stop() || self.visit_expression(&attr.value).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        VisitControl::Continue
+    }
+}
+
+/// Get the attribute path of a key, if it is known without evaluation
+fn static_key(key: &AttrKey) -> Option<Vec<String>> {
+    match key {
+        AttrKey::Static(path) => Some(path.clone()),
+        AttrKey::Dynamic(expr) => match expr.unlocated() {
+            Expression::String(name) => Some(vec![name.clone()]),
+            _ => None,
+        },
+    }
+}
+
+/// Get the attributes of a non-recursive set literal, which later
+/// attributes may extend
+fn extensible_attributes(value: &Expression) -> Option<&[Attribute]> {
+    match value.unlocated() {
+        Expression::AttributeSet { recursive: false, attributes } => Some(attributes),
+        _ => None,
+    }
+}
+
+/// Attribute names defined so far in a set, nested along dotted paths
+#[derive(Default)]
+struct AttrTree {
+    entries: HashMap<String, AttrEntry>,
+}
+
+/// An attribute in an [`AttrTree`]
+struct AttrEntry {
+    /// Index of the attribute that assigned a value, if any; entries
+    /// created as the prefix of a longer path have none
+    assigned_by: Option<usize>,
+    /// Nested attributes, if the value is a set that can be extended
+    children: Option<AttrTree>,
+}
+
+impl AttrTree {
+    /// Define `path` as assigned `value` by the attribute at `index`
+    ///
+    /// Returns the dotted name of the attribute that was already defined
+    /// on conflict. Conflicts within one attribute's set literal are left
+    /// to the check of that literal.
+    fn define(&mut self, path: &[String], value: &Expression, index: usize) -> std::result::Result<(), String> {
+        let Some((first, rest)) = path.split_first() else {
+            return Ok(());
+        };
+        let prefixed = |name: String| format!("{first}.{name}");
+
+        if !rest.is_empty() {
+            let entry = self.entries.entry(first.clone()).or_insert_with(|| AttrEntry {
+                assigned_by: None,
+                children: Some(AttrTree::default()),
+            });
+            return match &mut entry.children {
+                Some(children) => children.define(rest, value, index).map_err(prefixed),
+                None if entry.assigned_by == Some(index) => Ok(()),
+                None => Err(first.clone()),
+            };
+        }
+
+        let entry = self.entries.entry(first.clone()).or_insert_with(|| AttrEntry {
+            assigned_by: None,
+            children: None,
+        });
+        match entry.assigned_by {
+            Some(other) if other == index => return Ok(()),
+            Some(_) => return Err(first.clone()),
+            None => {}
+        }
+        let literal = extensible_attributes(value);
+        if entry.children.is_some() && literal.is_none() {
+            return Err(first.clone());
+        }
+
+        entry.assigned_by = Some(index);
+        let Some(attributes) = literal else {
+            return Ok(());
+        };
+        let children = entry.children.get_or_insert_with(AttrTree::default);
+        for attr in attributes {
+            if let Some(path) = static_key(&attr.key) {
+                children.define(&path, &attr.value, index).map_err(prefixed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn duplicates(source: &str) -> Vec<String> {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        let info = SemanticAnalyzer::new().analyze(&expr).unwrap();
+        info.errors
+            .iter()
+            .map(|error| match error {
+                ParseError::SemanticError { message, .. } => message.clone(),
+                other => panic!("unexpected error {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        assert_eq!(duplicates("{ a = 1; a = 2; }"), ["Duplicate attribute: a"]);
+        assert_eq!(duplicates("rec { a = 1; \"a\" = 2; ${\"a\"} = 3; }").len(), 2);
+        assert_eq!(duplicates("{ inherit a; a = 1; }"), ["Duplicate attribute: a"]);
+        assert_eq!(duplicates("[ { b = 1; } { x = { b = 1; b = 2; }; } ]"), ["Duplicate attribute: b"]);
+        assert!(duplicates("{ a = 1; b = 1; ${a} = 2; }").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_attribute_paths() {
+        assert_eq!(duplicates("{ a.b = 1; a.b = 2; }"), ["Duplicate attribute: a.b"]);
+        assert_eq!(duplicates("{ a = 1; a.b = 2; }"), ["Duplicate attribute: a"]);
+        assert_eq!(duplicates("{ a.b = 1; a = 2; }"), ["Duplicate attribute: a"]);
+        assert_eq!(duplicates("{ a.b.c = 1; a = { b.c = 2; }; }"), ["Duplicate attribute: a.b.c"]);
+        assert_eq!(duplicates("{ a = { }; a = { }; }"), ["Duplicate attribute: a"]);
+
+        assert!(duplicates("{ a.b = 1; a.c = 2; }").is_empty());
+        assert!(duplicates("{ a = { b = 1; }; a.c = 2; }").is_empty());
+        assert!(duplicates("{ a.c = 2; \"a\" = { b = 1; }; }").is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! Parameter shapes of functions
+
+use crate::ast::{Expression, Parameter, SourceLocation};
+
+/// Parameters of a function, as needed for documentation and arity checks
+///
+/// Curried functions such as `a: { b, c }: body` take their parameters one
+/// at a time; all of them are listed, outermost first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    /// Parameters in the order they are applied
+    pub parameters: Vec<ParameterSignature>,
+    /// Location of the outermost function, if the AST carries locations
+    pub location: Option<SourceLocation>,
+}
+
+impl FunctionSignature {
+    /// Get the signature of `expr`, or `None` if it is not a function
+    pub fn of(expr: &Expression) -> Option<Self> {
+        let mut parameters = Vec::new();
+        let mut current = expr.unlocated();
+        while let Expression::Function { parameter, body } = current {
+            parameters.push(ParameterSignature::of(parameter));
+            current = body.unlocated();
+        }
+        (!parameters.is_empty()).then(|| Self {
+            parameters,
+            location: expr.location(),
+        })
+    }
+
+    /// Get the number of arguments the function takes before returning a
+    /// value other than a function literal
+    pub fn arity(&self) -> usize {
+        self.parameters.len()
+    }
+}
+
+/// Shape of a single function parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterSignature {
+    /// A plain parameter, `x: body`
+    Identifier(String),
+    /// An attribute set pattern, `{ a, b ? 1, ... }@args: body`
+    Pattern {
+        /// Fields of the pattern, in source order
+        fields: Vec<FieldSignature>,
+        /// Whether attributes other than the fields are accepted (`...`)
+        ellipsis: bool,
+        /// Name the whole argument is bound to with `@`, if any
+        bind: Option<String>,
+    },
+}
+
+impl ParameterSignature {
+    /// Get the shape of a parameter
+    pub fn of(parameter: &Parameter) -> Self {
+        match parameter {
+            Parameter::Identifier(name) => ParameterSignature::Identifier(name.clone()),
+            Parameter::Pattern { fields, ellipsis, bind } => ParameterSignature::Pattern {
+                fields: fields
+                    .iter()
+                    .map(|field| FieldSignature {
+                        name: field.name.clone(),
+                        has_default: field.default.is_some(),
+                    })
+                    .collect(),
+                ellipsis: *ellipsis,
+                bind: bind.clone(),
+            },
+        }
+    }
+
+    /// Get the names of the pattern fields an argument must provide
+    ///
+    /// Plain parameters accept any argument, so they require nothing.
+    pub fn required_fields(&self) -> impl Iterator<Item = &str> {
+        let fields = match self {
+            ParameterSignature::Identifier(_) => &[][..],
+            ParameterSignature::Pattern { fields, .. } => fields,
+        };
+        fields.iter().filter(|field| !field.has_default).map(|field| field.name.as_str())
+    }
+}
+
+/// Field of an attribute set pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSignature {
+    /// Name of the attribute
+    pub name: String,
+    /// Whether the field has a default value (`name ? default`)
+    pub has_default: bool,
+}
+
+/// Get the signature of every function in `expr`, in source order
+///
+/// The functions a curried function returns are part of its signature, so
+/// they are not listed separately.
+pub fn function_signatures(expr: &Expression) -> Vec<FunctionSignature> {
+    let mut signatures = Vec::new();
+    // Trees can be deep, so walk them with an explicit stack
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        let Some(signature) = FunctionSignature::of(expr) else {
+            stack.extend(expr.subexpressions().into_iter().rev());
+            continue;
+        };
+        signatures.push(signature);
+
+        // Continue with the pattern defaults and the innermost body
+        let mut nested = Vec::new();
+        let mut current = expr.unlocated();
+        while let Expression::Function { parameter, body } = current {
+            if let Parameter::Pattern { fields, .. } = parameter {
+                nested.extend(fields.iter().filter_map(|field| field.default.as_ref()));
+            }
+            current = body.unlocated();
+        }
+        nested.push(current);
+        stack.extend(nested.into_iter().rev());
+    }
+    signatures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn signature_of(source: &str) -> FunctionSignature {
+        let mut parser = NixParser::new().unwrap();
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        FunctionSignature::of(&expr).unwrap()
+    }
+
+    fn field(name: &str, has_default: bool) -> FieldSignature {
+        FieldSignature { name: name.to_string(), has_default }
+    }
+
+    #[test]
+    fn test_identifier_parameter() {
+        let signature = signature_of("x: x + 1");
+        assert_eq!(signature.parameters, [ParameterSignature::Identifier("x".to_string())]);
+        assert_eq!(signature.arity(), 1);
+        assert_eq!(signature.location.map(|location| location.start_byte), Some(0));
+        assert_eq!(signature.parameters[0].required_fields().count(), 0);
+    }
+
+    #[test]
+    fn test_pattern_parameter() {
+        let signature = signature_of("{ a, b ? 1 }: a + b");
+        assert_eq!(
+            signature.parameters,
+            [ParameterSignature::Pattern {
+                fields: vec![field("a", false), field("b", true)],
+                ellipsis: false,
+                bind: None,
+            }]
+        );
+        assert_eq!(signature.parameters[0].required_fields().collect::<Vec<_>>(), ["a"]);
+
+        let signature = signature_of("{ a, ... }@args: a");
+        assert_eq!(
+            signature.parameters,
+            [ParameterSignature::Pattern {
+                fields: vec![field("a", false)],
+                ellipsis: true,
+                bind: Some("args".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_curried_signatures() {
+        assert_eq!(signature_of("a: { b }: c: [ a b c ]").arity(), 3);
+        assert!(FunctionSignature::of(&Expression::Integer(1)).is_none());
+
+        let mut parser = NixParser::new().unwrap();
+        let source = "{ f = x: y: x; g = { h ? (z: z) }: [ (w: w) ]; }";
+        let expr = parser.parse(source).unwrap().expression().unwrap().unwrap();
+        let arities: Vec<(usize, usize)> = function_signatures(&expr)
+            .iter()
+            .map(|signature| (signature.location.unwrap().start_byte, signature.arity()))
+            .collect();
+        assert_eq!(arities, [(6, 2), (19, 1), (26, 1), (38, 1)]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Analysis of every Nix file in a project
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use crate::analysis::{Analyzer, AnalyzerConfig, DependencyGraph, LintResult, NodeId};
+use crate::ast::PathType;
+use crate::error::{ParseError, Result};
+use crate::parser::{DiagnosticSeverity, NixParser, ParseDiagnostic, ParserConfig};
+
+/// Analyzer running [`Analyzer`] over every Nix file under a directory
+///
+/// Files are parsed and analyzed one by one, and their imports are joined
+/// into a single [`DependencyGraph`] with a node per file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use nix_parser::analysis::WorkspaceAnalyzer;
+///
+/// let report = WorkspaceAnalyzer::new()?.analyze_directory("./project")?;
+/// for (path, file) in &report.files {
+///     for lint in &file.lint_results {
+///         println!("{}: {}", path.display(), lint);
+///     }
+/// }
+/// # Ok::<(), nix_parser::ParseError>(())
+/// ```
+pub struct WorkspaceAnalyzer {
+    parser: NixParser,
+    analyzer: Analyzer,
+}
+
+impl WorkspaceAnalyzer {
+    /// Create a workspace analyzer with default configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::LanguageError` if the parser cannot be created.
+    pub fn new() -> Result<Self> {
+        Self::with_config(ParserConfig::default(), AnalyzerConfig::default())
+    }
+
+    /// Create a workspace analyzer parsing and analyzing files as configured
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::LanguageError` if the parser cannot be created.
+    pub fn with_config(parser_config: ParserConfig, config: AnalyzerConfig) -> Result<Self> {
+        Ok(Self {
+            parser: NixParser::with_config(parser_config)?,
+            analyzer: Analyzer::new().with_config(config),
+        })
+    }
+
+    /// Analyze every Nix file under `dir`, recursively
+    ///
+    /// Imports of relative paths, and of absolute paths inside `dir`, are
+    /// resolved to the files they name, with `default.nix` standing for a
+    /// directory like in Nix. Imports that resolve to a file of the
+    /// workspace become edges of the graph; the others are only kept as
+    /// dependencies.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if the directory cannot be walked.
+    /// Files that fail to parse or analyze are reported in their
+    /// [`FileReport`] instead.
+    pub fn analyze_directory(&mut self, dir: impl AsRef<Path>) -> Result<WorkspaceReport> {
+        let root = normalize(dir.as_ref());
+        let mut report = WorkspaceReport {
+            root: root.clone(),
+            files: BTreeMap::new(),
+            graph: DependencyGraph::new(),
+        };
+
+        // `.` normalizes to an empty path, which cannot be walked
+        let walked = if root.as_os_str().is_empty() { Path::new(".") } else { root.as_path() };
+        let parsed = self.parser.parse_directory(walked)?;
+        let mut nodes = HashMap::new();
+        for (path, _) in &parsed {
+            let path = normalize(path);
+            let name = relative_name(&root, &path);
+            nodes.insert(path, report.graph.add_node(name));
+        }
+
+        for (path, result) in parsed {
+            let path = normalize(&path);
+            let mut file = FileReport::default();
+            let analysis = result.and_then(|result| {
+                file.diagnostics = result.diagnostics().to_vec();
+                match result.expression()? {
+                    Some(expression) => self.analyzer.analyze(&expression).map(Some),
+                    None => Ok(None),
+                }
+            });
+
+            match analysis {
+                Ok(Some(analysis)) => {
+                    let from = nodes[&path];
+                    for dependency in analysis.dependencies.dependencies() {
+                        if let Some(to) = resolve_import(&root, &path, &dependency.path)
+                            .and_then(|target| nodes.get(&target))
+                        {
+                            report.graph.add_edge(from, *to);
+                        }
+                        report.graph.add_dependency(dependency.clone());
+                    }
+                    file.lint_results = analysis.lint_results;
+                    file.errors = analysis.errors;
+                }
+                Ok(None) => {}
+                Err(error) => file.errors.push(error),
+            }
+            report.files.insert(path.strip_prefix(&root).unwrap_or(&path).to_path_buf(), file);
+        }
+        Ok(report)
+    }
+}
+
+/// Findings of a [`WorkspaceAnalyzer`] for a whole directory
+#[derive(Debug, Clone)]
+pub struct WorkspaceReport {
+    /// Directory that was analyzed
+    pub root: PathBuf,
+    /// Findings for each file, keyed by its path relative to `root`
+    pub files: BTreeMap<PathBuf, FileReport>,
+    /// Imports between files, with a node per file named by its path
+    /// relative to `root`, and the dependencies of every file
+    pub graph: DependencyGraph,
+}
+
+impl WorkspaceReport {
+    /// Get the findings for a file, by its path relative to the root
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&FileReport> {
+        self.files.get(path.as_ref())
+    }
+
+    /// Check if any file has syntax, semantic or analysis errors
+    pub fn has_errors(&self) -> bool {
+        self.files.values().any(FileReport::has_errors)
+    }
+
+    /// Get the lint results of every file, with the file they were found in
+    pub fn lint_results(&self) -> impl Iterator<Item = (&Path, &LintResult)> {
+        self.files
+            .iter()
+            .flat_map(|(path, file)| file.lint_results.iter().map(move |lint| (path.as_path(), lint)))
+    }
+
+    /// Get the graph node of a file, by its path relative to the root
+    pub fn node(&self, path: impl AsRef<Path>) -> Option<NodeId> {
+        self.graph.node_id(&path.as_ref().to_string_lossy())
+    }
+}
+
+/// Findings for a single file of a workspace
+#[derive(Debug, Clone, Default)]
+pub struct FileReport {
+    /// Diagnostics reported while parsing the file
+    pub diagnostics: Vec<ParseDiagnostic>,
+    /// Lint results
+    pub lint_results: Vec<LintResult>,
+    /// Semantic errors, or the error that kept the file from being parsed
+    /// or analyzed
+    pub errors: Vec<ParseError>,
+}
+
+impl FileReport {
+    /// Check if the file has syntax, semantic or analysis errors
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty() || self.diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+    }
+}
+
+/// Resolve an imported path to the file it names, if it can be found
+/// statically
+fn resolve_import(root: &Path, file: &Path, import: &PathType) -> Option<PathBuf> {
+    let target = match import {
+        PathType::Relative(path) => normalize(&file.parent()?.join(path)),
+        PathType::Absolute(path) => normalize(Path::new(path)),
+        _ => return None,
+    };
+    if !target.starts_with(root) {
+        return None;
+    }
+    Some(if target.is_dir() { target.join("default.nix") } else { target })
+}
+
+/// Remove `.` and `..` components from a path without touching the file
+/// system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Name a file of the workspace by its path relative to the root
+fn relative_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::LintRule;
+
+    #[test]
+    fn test_analyze_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        std::fs::write(dir.path().join("default.nix"), "let lib = import ./lib; in lib.f (import ./missing.nix)").unwrap();
+        std::fs::write(dir.path().join("lib/default.nix"), "let unused = 1; in { f = x: import ../default.nix; }")
+            .unwrap();
+
+        let report = WorkspaceAnalyzer::new().unwrap().analyze_directory(dir.path()).unwrap();
+        let names: Vec<_> = report.files.keys().map(|path| path.to_str().unwrap()).collect();
+        assert_eq!(names, ["default.nix", "lib/default.nix"]);
+        assert!(!report.has_errors());
+
+        // The import of the directory resolves to its `default.nix`
+        let default = report.node("default.nix").unwrap();
+        let lib = report.node("lib/default.nix").unwrap();
+        assert_eq!(report.graph.edges(), [(default, lib), (lib, default)]);
+        assert_eq!(report.graph.dependencies().len(), 3);
+
+        let lints: Vec<_> = report.lint_results().map(|(path, lint)| (path.to_str().unwrap(), lint.rule)).collect();
+        assert_eq!(lints, [("lib/default.nix", LintRule::UnusedLetBinding)]);
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("broken.nix"), "{ x = ; }").unwrap();
+        std::fs::write(dir.path().join("binary.nix"), [0x7b, 0xff, 0x7d]).unwrap();
+        std::fs::write(dir.path().join("duplicate.nix"), "{ a = 1; a = 2; }").unwrap();
+
+        let report = WorkspaceAnalyzer::new().unwrap().analyze_directory(dir.path()).unwrap();
+        assert!(report.has_errors());
+        assert!(matches!(report.file("binary.nix").unwrap().errors[..], [ParseError::Utf8Error(_)]));
+        let broken = report.file("broken.nix").unwrap();
+        assert!(broken.errors.is_empty() && broken.has_errors());
+        assert!(report.file("duplicate.nix").unwrap().errors[0].is_semantic_error());
+        assert_eq!(report.graph.nodes().len(), 3);
+        assert!(report.graph.edges().is_empty());
+
+        assert!(WorkspaceAnalyzer::new().unwrap().analyze_directory(dir.path().join("missing")).is_err());
+    }
+}
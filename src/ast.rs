@@ -1,21 +1,46 @@
+//! Abstract syntax tree of Nix expressions
+//!
+//! [`Expression`] is built from a Tree-sitter tree by
+//! [`Expression::from_tree_sitter_node`], and can be printed back as Nix
+//! source with its `Display` implementation.
+
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem;
+use std::num::IntErrorKind;
+use tree_sitter::Node as TSNode;
+
+use crate::error::{ErrorContext, ParseError, Result};
+use crate::grammar::is_interpolated_path;
+use crate::parser::ParserConfig;
+use crate::utils::NodeExt;
 
 /// Base trait for all AST nodes
 pub trait Node: fmt::Debug {
+    /// Get the location of the node in the source, if known
     fn location(&self) -> Option<SourceLocation>;
+    /// Get the direct children of the node
     fn children(&self) -> Vec<&dyn Node>;
+    /// Walk the node and its descendants with `visitor`
     fn accept(&self, visitor: &mut dyn crate::visitor::Visitor);
 }
 
 /// Source location information
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SourceLocation {
+    /// Byte offset of the start
     pub start_byte: usize,
+    /// Byte offset of the end, exclusive
     pub end_byte: usize,
+    /// Row and byte column of the start, as Tree-sitter reports them
     pub start_position: (usize, usize),
+    /// Row and byte column of the end, as Tree-sitter reports them
     pub end_position: (usize, usize),
+    /// Line of the start (1-based)
     pub line: usize,
+    /// Column of the start (1-based)
     pub column: usize,
 }
 
@@ -43,251 +68,3923 @@ impl SourceLocation {
             column: node.start_position().column + 1, // Convert to 1-based
         }
     }
+
+    /// Get the smallest location spanning both `self` and `other`
+    ///
+    /// The start is taken from whichever location starts first and the end
+    /// from whichever ends last, so any gap between them is included.
+    pub fn union(&self, other: &SourceLocation) -> SourceLocation {
+        let start = if other.start_byte < self.start_byte { other } else { self };
+        let end = if other.end_byte > self.end_byte { other } else { self };
+        SourceLocation {
+            start_byte: start.start_byte,
+            end_byte: end.end_byte,
+            start_position: start.start_position,
+            end_position: end.end_position,
+            line: start.line,
+            column: start.column,
+        }
+    }
 }
 
 /// Main expression types in Nix
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expression {
     // Literals
+    /// Integer literal
     Integer(i64),
+    /// Float literal
     Float(f64),
+    /// String without interpolations, with escapes resolved
     String(String),
+    /// String with `${}` interpolations
     StringInterpolation {
+        /// Literal and interpolated parts, in order
         parts: Vec<StringPart>,
     },
+    /// Path literal
     Path(PathType),
+    /// Unquoted URI literal such as `https://example.org`, as written
+    ///
+    /// Nix reads these as strings, but they are deprecated and rejected by
+    /// some language versions, so they are kept apart from `String`.
+    Uri(String),
+    /// `true` or `false`
     Boolean(bool),
+    /// `null`
     Null,
     
     // Identifiers and references
+    /// Variable reference
     Identifier(String),
     
     // Collections
+    /// List literal `[ ... ]`
     List(Vec<Expression>),
+    /// Attribute set `{ ... }` or `rec { ... }`
     AttributeSet {
+        /// Whether the set is `rec`
         recursive: bool,
+        /// Bindings of the set, in source order
         attributes: Vec<Attribute>,
     },
     
     // Functions
+    /// Function `parameter: body`
     Function {
+        /// Name or pattern the argument is bound to
         parameter: Parameter,
+        /// Body of the function
         body: Box<Expression>,
     },
+    /// Function call `function argument`
     Application {
+        /// Function called
         function: Box<Expression>,
+        /// Argument passed
         argument: Box<Expression>,
     },
     
     // Control flow
+    /// `let bindings in body`
     LetIn {
+        /// Bindings, in source order
         bindings: Vec<Binding>,
+        /// Expression the bindings are in scope for
         body: Box<Expression>,
     },
+    /// `with scope; body`
     With {
+        /// Attribute set whose attributes are brought into scope
         scope: Box<Expression>,
+        /// Expression the attributes are in scope for
         body: Box<Expression>,
     },
+    /// `if condition then then_branch else else_branch`
     If {
+        /// Condition tested
         condition: Box<Expression>,
+        /// Value if the condition is true
         then_branch: Box<Expression>,
+        /// Value if the condition is false
         else_branch: Box<Expression>,
     },
+    /// `assert condition; body`
     Assert {
+        /// Condition that must hold
         condition: Box<Expression>,
+        /// Value if it does
         body: Box<Expression>,
     },
     
     // Operators
+    /// Binary operation such as `a + b`
     BinaryOp {
+        /// Operator applied
         op: BinaryOperator,
+        /// Left operand
         left: Box<Expression>,
+        /// Right operand
         right: Box<Expression>,
     },
+    /// Unary operation such as `!a` or `-a`
     UnaryOp {
+        /// Operator applied
         op: UnaryOperator,
+        /// Operand
         operand: Box<Expression>,
     },
     
     // Selection and interpolation
+    /// Attribute selection `expr.path`, optionally with `or default`
     Select {
+        /// Expression selected from
         expr: Box<Expression>,
-        path: Vec<String>,
+        /// Attribute path selected, grouped into keys like attribute set keys
+        path: Vec<AttrKey>,
+        /// Value used if the path is missing
         default: Option<Box<Expression>>,
     },
+    /// Attribute test `expr ? path`
     HasAttr {
+        /// Expression tested
         expr: Box<Expression>,
-        path: Vec<String>,
+        /// Attribute path looked for, grouped into keys like attribute set keys
+        path: Vec<AttrKey>,
     },
     
     // Import expression
+    /// `import path`
     Import {
+        /// Expression giving the imported file
         path: Box<Expression>,
     },
     
     // Inherit statement
+    /// `inherit attributes;` or `inherit (source) attributes;`
     Inherit {
+        /// Expression the attributes are taken from, if any
         source: Option<Box<Expression>>,
+        /// Names of the inherited attributes
         attributes: Vec<String>,
     },
+
+    /// Expression annotated with its span in the source
+    ///
+    /// The converter wraps expressions in this variant when
+    /// `ParserConfig::include_locations` is enabled. Equality and hashing
+    /// look through the wrapper, so a located expression equals the same
+    /// expression without location; see `location()` and `unlocated()`.
+    Located {
+        /// Span of `expr` in the source
+        location: SourceLocation,
+        /// The located expression
+        expr: Box<Expression>,
+    },
+}
+
+impl PartialEq for Expression {
+    /// Structural equality; location wrappers are ignored
+    ///
+    /// Floats are compared by bit pattern, except that `0.0` equals `-0.0`,
+    /// so the equality is reflexive even for NaN.
+    fn eq(&self, other: &Self) -> bool {
+        use Expression as E;
+        match (self.unlocated(), other.unlocated()) {
+            (E::Integer(a), E::Integer(b)) => a == b,
+            (E::Float(a), E::Float(b)) => float_bits(*a) == float_bits(*b),
+            (E::String(a), E::String(b))
+            | (E::Identifier(a), E::Identifier(b))
+            | (E::Uri(a), E::Uri(b)) => a == b,
+            (E::StringInterpolation { parts: a }, E::StringInterpolation { parts: b }) => a == b,
+            (E::Path(a), E::Path(b)) => a == b,
+            (E::Boolean(a), E::Boolean(b)) => a == b,
+            (E::Null, E::Null) => true,
+            (E::List(a), E::List(b)) => a == b,
+            (
+                E::AttributeSet { recursive: r1, attributes: a1 },
+                E::AttributeSet { recursive: r2, attributes: a2 },
+            ) => r1 == r2 && a1 == a2,
+            (
+                E::Function { parameter: p1, body: b1 },
+                E::Function { parameter: p2, body: b2 },
+            ) => p1 == p2 && b1 == b2,
+            (
+                E::Application { function: f1, argument: a1 },
+                E::Application { function: f2, argument: a2 },
+            ) => f1 == f2 && a1 == a2,
+            (E::LetIn { bindings: b1, body: e1 }, E::LetIn { bindings: b2, body: e2 }) => {
+                b1 == b2 && e1 == e2
+            }
+            (E::With { scope: s1, body: b1 }, E::With { scope: s2, body: b2 }) => {
+                s1 == s2 && b1 == b2
+            }
+            (
+                E::If { condition: c1, then_branch: t1, else_branch: e1 },
+                E::If { condition: c2, then_branch: t2, else_branch: e2 },
+            ) => c1 == c2 && t1 == t2 && e1 == e2,
+            (E::Assert { condition: c1, body: b1 }, E::Assert { condition: c2, body: b2 }) => {
+                c1 == c2 && b1 == b2
+            }
+            (
+                E::BinaryOp { op: o1, left: l1, right: r1 },
+                E::BinaryOp { op: o2, left: l2, right: r2 },
+            ) => o1 == o2 && l1 == l2 && r1 == r2,
+            (E::UnaryOp { op: o1, operand: a }, E::UnaryOp { op: o2, operand: b }) => {
+                o1 == o2 && a == b
+            }
+            (
+                E::Select { expr: e1, path: p1, default: d1 },
+                E::Select { expr: e2, path: p2, default: d2 },
+            ) => e1 == e2 && p1 == p2 && d1 == d2,
+            (E::HasAttr { expr: e1, path: p1 }, E::HasAttr { expr: e2, path: p2 }) => {
+                e1 == e2 && p1 == p2
+            }
+            (E::Import { path: a }, E::Import { path: b }) => a == b,
+            (
+                E::Inherit { source: s1, attributes: a1 },
+                E::Inherit { source: s2, attributes: a2 },
+            ) => s1 == s2 && a1 == a2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Expression {}
+
+impl Hash for Expression {
+    /// Structural hash, consistent with `PartialEq`
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let expr = self.unlocated();
+        mem::discriminant(expr).hash(state);
+        match expr {
+            Expression::Integer(n) => n.hash(state),
+            Expression::Float(n) => float_bits(*n).hash(state),
+            Expression::String(s) | Expression::Uri(s) | Expression::Identifier(s) => s.hash(state),
+            Expression::StringInterpolation { parts } => parts.hash(state),
+            Expression::Path(path) => path.hash(state),
+            Expression::Boolean(b) => b.hash(state),
+            Expression::Null => {}
+            Expression::List(elements) => elements.hash(state),
+            Expression::AttributeSet { recursive, attributes } => {
+                recursive.hash(state);
+                attributes.hash(state);
+            }
+            Expression::Function { parameter, body } => {
+                parameter.hash(state);
+                body.hash(state);
+            }
+            Expression::Application { function, argument } => {
+                function.hash(state);
+                argument.hash(state);
+            }
+            Expression::LetIn { bindings, body } => {
+                bindings.hash(state);
+                body.hash(state);
+            }
+            Expression::With { scope: first, body: second }
+            | Expression::Assert { condition: first, body: second } => {
+                first.hash(state);
+                second.hash(state);
+            }
+            Expression::If { condition, then_branch, else_branch } => {
+                condition.hash(state);
+                then_branch.hash(state);
+                else_branch.hash(state);
+            }
+            Expression::BinaryOp { op, left, right } => {
+                op.hash(state);
+                left.hash(state);
+                right.hash(state);
+            }
+            Expression::UnaryOp { op, operand } => {
+                op.hash(state);
+                operand.hash(state);
+            }
+            Expression::Select { expr, path, default } => {
+                expr.hash(state);
+                path.hash(state);
+                default.hash(state);
+            }
+            Expression::HasAttr { expr, path } => {
+                expr.hash(state);
+                path.hash(state);
+            }
+            Expression::Import { path } => path.hash(state),
+            Expression::Inherit { source, attributes } => {
+                source.hash(state);
+                attributes.hash(state);
+            }
+            Expression::Located { .. } => unreachable!("unlocated() strips location wrappers"),
+        }
+    }
+}
+
+/// Add a binding to those of a `let`, merging `a.b = 1; a.c = 2;` into
+/// one `a` binding like Nix merges the attributes of a set
+fn push_let_binding(bindings: &mut Vec<Binding>, binding: Binding) {
+    let previous = bindings.iter_mut().find(|previous| !previous.inherit && previous.name == binding.name);
+    if let Some(previous) = previous
+        && let Expression::AttributeSet { recursive: false, attributes } = &mut previous.value
+        && let Expression::AttributeSet { recursive: false, attributes: added } = binding.value
+    {
+        attributes.extend(added);
+        return;
+    }
+    bindings.push(binding);
+}
+
+/// Message for an integer literal that does not fit in an `i64`
+pub(crate) fn integer_out_of_range(text: &str) -> String {
+    format!("integer literal '{text}' out of range for i64")
+}
+
+/// Get the `ERROR` node holding the `let` of a legacy `let { ... }` whose
+/// attribute set is `node`
+///
+/// The grammar does not know the legacy form, so it parses the keyword as
+/// an error followed by a plain attribute set.
+pub(crate) fn legacy_let_keyword(node: TSNode) -> Option<TSNode> {
+    if node.kind() != "attrset" {
+        return None;
+    }
+    let error = node.prev_sibling()?;
+    let keyword = error.child(0)?;
+    (error.is_error() && error.child_count() == 1 && keyword.kind() == "let").then_some(error)
+}
+
+/// Bit pattern of a float for equality and hashing, with `-0.0` as `0.0`
+fn float_bits(n: f64) -> u64 {
+    if n == 0.0 { 0 } else { n.to_bits() }
 }
 
 /// String parts for interpolation
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum StringPart {
+    /// Literal text, with escapes resolved
     Literal(String),
+    /// Interpolated expression `${...}`
     Interpolation(Box<Expression>),
 }
 
 /// Path types in Nix
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PathType {
+    /// Absolute path such as `/etc/nixos`
     Absolute(String),
+    /// Relative path such as `./foo.nix`
     Relative(String),
+    /// Path in the home directory such as `~/foo.nix`
     Home(String),
+    /// Search path such as `<nixpkgs>`
     Search(String),
+    /// Path containing `${}` segments such as `./foo/${name}.nix`; the
+    /// literal parts keep the path prefix (`./`, `/` or `~/`) as written
+    Interpolated(Vec<StringPart>),
 }
 
 /// Function parameter patterns
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Parameter {
+    /// Plain parameter `x: ...`
     Identifier(String),
+    /// Set pattern `{ a, b ? 1, ... }@args: ...`
     Pattern {
+        /// Fields of the pattern, in source order
         fields: Vec<PatternField>,
+        /// Whether the pattern ends with `...`
         ellipsis: bool,
+        /// Name the whole argument is bound to with `@`, if any
         bind: Option<String>,
     },
 }
 
 /// Pattern field in function parameters
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PatternField {
+    /// Name of the field
     pub name: String,
+    /// Default value given with `?`, if any
     pub default: Option<Expression>,
 }
 
 /// Attribute in an attribute set
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Like location wrappers, `location` is ignored by equality and hashing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attribute {
-    pub path: Vec<String>,
+    /// Key the value is bound to, such as `a.b` in `a.b = 1;`
+    pub key: AttrKey,
+    /// Value bound to the key
     pub value: Expression,
+    /// Span of the whole binding, or of the `inherit` it comes from, if
+    /// the AST carries locations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
+}
+
+impl Attribute {
+    /// Create an attribute without location
+    pub fn new(key: AttrKey, value: Expression) -> Self {
+        Self { key, value, location: None }
+    }
+}
+
+impl PartialEq for Attribute {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.value == other.value
+    }
+}
+
+impl Eq for Attribute {}
+
+impl Hash for Attribute {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+        self.value.hash(state);
+    }
+}
+
+/// Key of an attribute set binding
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AttrKey {
+    /// Statically known path such as `a.b."c"`
+    Static(Vec<String>),
+    /// Computed key such as `${k}` or `"${k}"`
+    Dynamic(Box<Expression>),
+}
+
+impl AttrKey {
+    /// Get the path of a static key
+    pub fn static_path(&self) -> Option<&[String]> {
+        match self {
+            AttrKey::Static(path) => Some(path),
+            AttrKey::Dynamic(_) => None,
+        }
+    }
+
+    /// Get the names of the attribute path of a selection or `?` test, if
+    /// none of its segments is dynamic
+    ///
+    /// Consecutive static segments are grouped in a single key, so a static
+    /// path is made of one key at most.
+    pub fn static_names(path: &[AttrKey]) -> Option<&[String]> {
+        match path {
+            [] => Some(&[]),
+            [key] => key.static_path(),
+            _ => None,
+        }
+    }
 }
 
 /// Binding in let expressions
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Binding {
+    /// Name bound
     pub name: String,
+    /// Value bound to the name
     pub value: Expression,
+    /// Whether the binding comes from an `inherit`
     pub inherit: bool,
+    /// Source of an `inherit (from) name;`, if any
     pub from: Option<Expression>,
 }
 
 /// Binary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BinaryOperator {
     // Arithmetic
+    /// `+`, which also concatenates strings and paths
     Add,
+    /// `-`
     Subtract,
+    /// `*`
     Multiply,
+    /// `/`
     Divide,
     
     // Comparison
+    /// `==`
     Equal,
+    /// `!=`
     NotEqual,
+    /// `<`
     Less,
+    /// `<=`
     LessEqual,
+    /// `>`
     Greater,
+    /// `>=`
     GreaterEqual,
     
     // Logical
+    /// `&&`
     And,
+    /// `||`
     Or,
+    /// `->`
     Implies,
     
     // Other
+    /// `//`, merging two attribute sets
     Update,
+    /// `++`, concatenating two lists
     Concat,
 }
 
 /// Unary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnaryOperator {
+    /// `!`
     Not,
+    /// `-`
     Negate,
 }
 
 /// Parts of string interpolation
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum InterpolationPart {
+    /// Literal text
     String(String),
+    /// Interpolated expression
     Expression(Box<Expression>),
 }
 
 impl Expression {
     /// Create an Expression from a Tree-sitter node
-    pub fn from_tree_sitter_node(_node: tree_sitter::Node, _source: &str) -> crate::error::Result<Expression> {
-        // Placeholder implementation - would parse the actual node
-        Ok(Expression::Integer(0))
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::UnknownNodeType` if the node (or one of its
+    /// descendants) has a kind that cannot be represented in the AST, and
+    /// `ParseError::InvalidNode` if a node is missing a required child, and
+    /// `ParseError::ResourceLimitExceeded` if the tree is nested deeper than
+    /// `ParserConfig::max_nesting_depth`.
+    pub fn from_tree_sitter_node(node: TSNode, source: &str) -> Result<Expression> {
+        Self::from_tree_sitter_node_with_config(node, source, &ParserConfig::default())
     }
-}
 
-impl Node for Expression {
-    fn location(&self) -> Option<SourceLocation> {
-        // In a full implementation, each variant would track its location
-        None
+    /// Create an Expression from a Tree-sitter node using the given parser configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Expression::from_tree_sitter_node`].
+    pub fn from_tree_sitter_node_with_config(
+        node: TSNode,
+        source: &str,
+        config: &ParserConfig,
+    ) -> Result<Expression> {
+        NodeConverter::new(source, config).node_to_ast(node)
     }
-    
-    fn children(&self) -> Vec<&dyn Node> {
+
+    /// Get the source span this expression was parsed from
+    ///
+    /// Returns `None` for expressions built by hand or parsed with
+    /// `ParserConfig::include_locations` disabled.
+    pub fn location(&self) -> Option<SourceLocation> {
         match self {
-            Expression::Function { body, .. } => vec![body.as_ref()],
-            Expression::Application { function, argument } => {
-                vec![function.as_ref(), argument.as_ref()]
+            Expression::Located { location, .. } => Some(*location),
+            _ => None,
+        }
+    }
+
+    /// Get the expression with any location wrappers removed
+    pub fn unlocated(&self) -> &Expression {
+        let mut expr = self;
+        while let Expression::Located { expr: inner, .. } = expr {
+            expr = inner;
+        }
+        expr
+    }
+
+    /// Consume the expression, removing any location wrappers around it
+    pub fn into_unlocated(self) -> Expression {
+        match self {
+            Expression::Located { expr, .. } => expr.into_unlocated(),
+            expr => expr,
+        }
+    }
+
+    /// Check if the expression is an `inherit` without source that copies
+    /// `name` from the enclosing scope
+    pub fn inherits(&self, name: &str) -> bool {
+        matches!(
+            self.unlocated(),
+            Expression::Inherit { source: None, attributes } if attributes.iter().any(|a| a == name)
+        )
+    }
+
+    /// Get the expressions directly nested in this one, in source order
+    ///
+    /// Unlike `Node::children`, this covers every nested expression,
+    /// including list items, attribute values and keys, binding values,
+    /// pattern defaults and interpolations.
+    pub fn subexpressions(&self) -> Vec<&Expression> {
+        fn interpolations(parts: &[StringPart]) -> impl Iterator<Item = &Expression> {
+            parts.iter().filter_map(|part| match part {
+                StringPart::Interpolation(expr) => Some(expr.as_ref()),
+                StringPart::Literal(_) => None,
+            })
+        }
+
+        fn dynamic_keys(path: &[AttrKey]) -> impl Iterator<Item = &Expression> {
+            path.iter().filter_map(|key| match key {
+                AttrKey::Dynamic(expr) => Some(expr.as_ref()),
+                AttrKey::Static(_) => None,
+            })
+        }
+
+        match self {
+            Expression::Integer(_)
+            | Expression::Float(_)
+            | Expression::String(_)
+            | Expression::Uri(_)
+            | Expression::Boolean(_)
+            | Expression::Null
+            | Expression::Identifier(_)
+            | Expression::Path(_) => {
+                if let Expression::Path(PathType::Interpolated(parts)) = self {
+                    return interpolations(parts).collect();
+                }
+                Vec::new()
             }
-            Expression::LetIn { body, .. } => vec![body.as_ref()],
-            Expression::With { scope, body } => vec![scope.as_ref(), body.as_ref()],
+            Expression::StringInterpolation { parts } => interpolations(parts).collect(),
+            Expression::List(items) => items.iter().collect(),
+            Expression::AttributeSet { attributes, .. } => attributes
+                .iter()
+                .flat_map(|attr| {
+                    let key = match &attr.key {
+                        AttrKey::Dynamic(key) => Some(key.as_ref()),
+                        AttrKey::Static(_) => None,
+                    };
+                    key.into_iter().chain([&attr.value])
+                })
+                .collect(),
+            Expression::Function { parameter, body } => {
+                let defaults: Vec<&Expression> = match parameter {
+                    Parameter::Pattern { fields, .. } => {
+                        fields.iter().filter_map(|field| field.default.as_ref()).collect()
+                    }
+                    Parameter::Identifier(_) => Vec::new(),
+                };
+                defaults.into_iter().chain([body.as_ref()]).collect()
+            }
+            Expression::Application { function, argument } => vec![function, argument],
+            Expression::LetIn { bindings, body } => bindings
+                .iter()
+                .map(|binding| &binding.value)
+                .chain([body.as_ref()])
+                .collect(),
+            Expression::With { scope: first, body: second }
+            | Expression::Assert { condition: first, body: second }
+            | Expression::BinaryOp { left: first, right: second, .. } => vec![first, second],
             Expression::If { condition, then_branch, else_branch } => {
-                vec![condition.as_ref(), then_branch.as_ref(), else_branch.as_ref()]
+                vec![condition, then_branch, else_branch]
             }
-            Expression::Assert { condition, body } => {
-                vec![condition.as_ref(), body.as_ref()]
+            Expression::UnaryOp { operand: expr, .. }
+            | Expression::Import { path: expr }
+            | Expression::Located { expr, .. } => vec![expr],
+            Expression::HasAttr { expr, path } => {
+                std::iter::once(expr.as_ref()).chain(dynamic_keys(path)).collect()
             }
-            Expression::BinaryOp { left, right, .. } => {
-                vec![left.as_ref(), right.as_ref()]
+            Expression::Select { expr, path, default } => std::iter::once(expr.as_ref())
+                .chain(dynamic_keys(path))
+                .chain(default.as_deref())
+                .collect(),
+            Expression::Inherit { source, .. } => source.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// Find the expressions whose source span contains a byte offset
+    ///
+    /// Returns the chain of located expressions from the outermost to the
+    /// smallest one containing `offset`, so the last element is the
+    /// expression under the cursor and the others are its ancestors. The
+    /// chain is empty if the expression carries no locations or does not
+    /// contain the offset.
+    ///
+    /// Spans include their end offset, so a cursor just after an
+    /// identifier finds the identifier. Where one span ends and the next
+    /// starts, the expression starting at the offset wins.
+    pub fn find_at_offset(&self, offset: usize) -> Vec<&Expression> {
+        self.find_enclosing(offset, offset)
+    }
+
+    /// Find the expressions whose source span contains a byte range
+    ///
+    /// Like [`Expression::find_at_offset`], but the last element of the
+    /// chain is the smallest expression containing all of
+    /// `start_byte..end_byte`. A range covering several siblings, such as
+    /// both operands of `a + b`, is contained by their common parent.
+    pub fn find_enclosing(&self, start_byte: usize, end_byte: usize) -> Vec<&Expression> {
+        let mut chain = Vec::new();
+        self.collect_enclosing(start_byte, end_byte, true, &mut chain);
+        chain
+    }
+
+    /// Push the located expressions containing `start..end` onto `chain`,
+    /// returning whether any did
+    ///
+    /// Unless `include_end` is set, an empty range at the end of a span is
+    /// not contained by it, so that the expression starting there wins.
+    fn collect_enclosing<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+        include_end: bool,
+        chain: &mut Vec<&'a Expression>,
+    ) -> bool {
+        if let Some(location) = self.location() {
+            let contains = location.start_byte <= start
+                && (end < location.end_byte || end == location.end_byte && (include_end || start < end));
+            if !contains {
+                return false;
             }
-            Expression::UnaryOp { operand, .. } => vec![operand.as_ref()],
-            Expression::Select { expr, .. } => vec![expr.as_ref()],
-            Expression::HasAttr { expr, .. } => vec![expr.as_ref()],
-            _ => vec![],
+            chain.push(self);
         }
+
+        let children = self.unlocated().subexpressions();
+        let found = children.iter().any(|child| child.collect_enclosing(start, end, false, chain))
+            || children.iter().any(|child| child.collect_enclosing(start, end, true, chain));
+        found || self.location().is_some()
     }
-    
-    fn accept(&self, visitor: &mut dyn crate::visitor::Visitor) {
-        visitor.visit_expression(self);
+
+    /// Hash the structure of the expression, ignoring source locations
+    ///
+    /// Structurally equal expressions have equal hashes. The hash is only
+    /// stable within one build of the crate, so it should not be persisted.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Constructors for building expressions by hand
+impl Expression {
+    /// Create an integer literal
+    pub fn int(value: i64) -> Self {
+        Expression::Integer(value)
+    }
 
-    #[test]
-    fn test_expression_creation() {
-        let expr = Expression::Integer(42);
-        assert_eq!(expr, Expression::Integer(42));
-        
-        let expr = Expression::String("hello".to_string());
-        assert_eq!(expr, Expression::String("hello".to_string()));
+    /// Create a float literal
+    pub fn float(value: f64) -> Self {
+        Expression::Float(value)
     }
-    
-    #[test]
-    fn test_binary_op() {
-        let expr = Expression::BinaryOp {
-            op: BinaryOperator::Add,
-            left: Box::new(Expression::Integer(1)),
-            right: Box::new(Expression::Integer(2)),
+
+    /// Create a string literal without interpolations
+    pub fn string(value: impl Into<String>) -> Self {
+        Expression::String(value.into())
+    }
+
+    /// Create a boolean literal
+    pub fn boolean(value: bool) -> Self {
+        Expression::Boolean(value)
+    }
+
+    /// Create a variable reference
+    pub fn ident(name: impl Into<String>) -> Self {
+        Expression::Identifier(name.into())
+    }
+
+    /// Create a list
+    pub fn list(items: impl IntoIterator<Item = Expression>) -> Self {
+        Expression::List(items.into_iter().collect())
+    }
+
+    /// Create a function application `function argument`
+    pub fn app(function: Expression, argument: Expression) -> Self {
+        Expression::Application {
+            function: Box::new(function),
+            argument: Box::new(argument),
+        }
+    }
+
+    /// Create a binary operation `left op right`
+    pub fn binop(op: BinaryOperator, left: Expression, right: Expression) -> Self {
+        Expression::BinaryOp {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Create a unary operation
+    pub fn unop(op: UnaryOperator, operand: Expression) -> Self {
+        Expression::UnaryOp {
+            op,
+            operand: Box::new(operand),
+        }
+    }
+
+    /// Create a function with a single named parameter, `param: body`
+    pub fn lambda(param: impl Into<String>, body: Expression) -> Self {
+        Expression::Function {
+            parameter: Parameter::Identifier(param.into()),
+            body: Box::new(body),
+        }
+    }
+
+    /// Create an attribute selection `expr.a.b` without default
+    pub fn select<S: Into<String>>(expr: Expression, path: impl IntoIterator<Item = S>) -> Self {
+        Expression::Select {
+            expr: Box::new(expr),
+            path: vec![AttrKey::Static(path.into_iter().map(Into::into).collect())],
+            default: None,
+        }
+    }
+
+    /// Create an attribute selection with a default, `expr.a.b or default`
+    ///
+    /// `or` associates to the right, so a chain such as `a.b or c.d or e`
+    /// nests the rest of the chain in the default:
+    ///
+    /// ```rust
+    /// use nix_parser::ast::Expression;
+    ///
+    /// let chain = Expression::select_or(
+    ///     Expression::ident("a"),
+    ///     ["b"],
+    ///     Expression::select_or(Expression::ident("c"), ["d"], Expression::ident("e")),
+    /// );
+    /// assert_eq!(chain.to_string(), "a.b or c.d or e");
+    /// ```
+    pub fn select_or<S: Into<String>>(
+        expr: Expression,
+        path: impl IntoIterator<Item = S>,
+        default: Expression,
+    ) -> Self {
+        Expression::Select {
+            expr: Box::new(expr),
+            path: vec![AttrKey::Static(path.into_iter().map(Into::into).collect())],
+            default: Some(Box::new(default)),
+        }
+    }
+
+    /// Create a `let` expression from name and value pairs
+    pub fn let_in<S: Into<String>>(
+        bindings: impl IntoIterator<Item = (S, Expression)>,
+        body: Expression,
+    ) -> Self {
+        Expression::LetIn {
+            bindings: bindings
+                .into_iter()
+                .map(|(name, value)| Binding {
+                    name: name.into(),
+                    value,
+                    inherit: false,
+                    from: None,
+                })
+                .collect(),
+            body: Box::new(body),
+        }
+    }
+
+    /// Create a `with` expression
+    pub fn with(scope: Expression, body: Expression) -> Self {
+        Expression::With {
+            scope: Box::new(scope),
+            body: Box::new(body),
+        }
+    }
+}
+
+/// Builder for attribute set expressions
+///
+/// Attributes are kept in the order they are added, and `inherit` entries
+/// are split into one attribute per name, as the parser does.
+#[derive(Debug, Clone, Default)]
+pub struct AttrSetBuilder {
+    recursive: bool,
+    attributes: Vec<Attribute>,
+}
+
+impl AttrSetBuilder {
+    /// Create a builder for an empty, non-recursive attribute set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether the attribute set is `rec`
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Add `name = value;`
+    pub fn attr(self, name: impl Into<String>, value: Expression) -> Self {
+        self.path([name], value)
+    }
+
+    /// Add `a.b.c = value;`
+    pub fn path<S: Into<String>>(mut self, path: impl IntoIterator<Item = S>, value: Expression) -> Self {
+        self.attributes.push(Attribute {
+            key: AttrKey::Static(path.into_iter().map(Into::into).collect()),
+            value,
+            location: None,
+        });
+        self
+    }
+
+    /// Add `${key} = value;`
+    pub fn dynamic(mut self, key: Expression, value: Expression) -> Self {
+        self.attributes.push(Attribute {
+            key: AttrKey::Dynamic(Box::new(key)),
+            value,
+            location: None,
+        });
+        self
+    }
+
+    /// Add `inherit a b;`
+    pub fn inherit<S: Into<String>>(self, names: impl IntoIterator<Item = S>) -> Self {
+        self.add_inherit(None, names)
+    }
+
+    /// Add `inherit (source) a b;`
+    pub fn inherit_from<S: Into<String>>(self, source: Expression, names: impl IntoIterator<Item = S>) -> Self {
+        self.add_inherit(Some(source), names)
+    }
+
+    fn add_inherit<S: Into<String>>(
+        mut self,
+        source: Option<Expression>,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let source = source.map(Box::new);
+        for name in names {
+            let name = name.into();
+            self.attributes.push(Attribute {
+                key: AttrKey::Static(vec![name.clone()]),
+                value: Expression::Inherit {
+                    source: source.clone(),
+                    attributes: vec![name],
+                },
+                location: None,
+            });
+        }
+        self
+    }
+
+    /// Build the attribute set expression
+    pub fn build(self) -> Expression {
+        Expression::AttributeSet {
+            recursive: self.recursive,
+            attributes: self.attributes,
+        }
+    }
+}
+
+/// Stack space below which conversion moves to a new stack segment
+const STACK_RED_ZONE: usize = 64 * 1024;
+
+/// Size of each stack segment allocated during conversion
+const STACK_SEGMENT_SIZE: usize = 1024 * 1024;
+
+/// Converts Tree-sitter nodes into `Expression` trees
+struct NodeConverter<'a> {
+    source: &'a str,
+    config: &'a ParserConfig,
+    depth: Cell<usize>,
+    /// Number of subtractions at the top of the expression converted last,
+    /// for operators around it to re-associate them
+    subtractions: Cell<usize>,
+}
+
+impl<'a> NodeConverter<'a> {
+    fn new(source: &'a str, config: &'a ParserConfig) -> Self {
+        Self { source, config, depth: Cell::new(0), subtractions: Cell::new(0) }
+    }
+
+    fn node_to_ast(&self, node: TSNode) -> Result<Expression> {
+        // Conversion recurses once per level, so bound it before the stack does
+        let depth = self.depth.get() + 1;
+        self.check_depth(depth)?;
+
+        self.depth.set(depth);
+        // Debug builds use kilobytes of stack per level, so grow the stack
+        // on demand to make sure the depth limit is reached first
+        let expr = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || self.convert_node(node));
+        self.depth.set(depth - 1);
+        let expr = expr?;
+        // Only operators leave subtractions at the top of their expression
+        if !matches!(node.kind(), "application" | "binary_expression") {
+            self.subtractions.set(0);
+        }
+
+        // Wrapper nodes pass through their (already located) inner expression
+        if matches!(node.kind(), "source_file" | "parenthesized_expression" | "ERROR") {
+            return Ok(expr);
+        }
+
+        Ok(match self.location(node) {
+            Some(location) => Expression::Located { location, expr: Box::new(expr) },
+            None => expr,
+        })
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        match self.config.max_nesting_depth {
+            Some(limit) if depth > limit => {
+                Err(ParseError::resource_limit("nesting_depth", limit.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn convert_node(&self, node: TSNode) -> Result<Expression> {
+        if node.is_missing() {
+            return Err(ParseError::InvalidNode(format!("Missing {}", node.kind())));
+        }
+
+        match node.kind() {
+            // Entry points and wrappers
+            "source_file" | "parenthesized_expression" => {
+                let child = self.field(node, "expression")?;
+                self.node_to_ast(child)
+            }
+
+            // Literals
+            "integer" => self.integer_to_ast(node),
+            "float" => self.float_to_ast(node),
+            // The scanner lexes interpolated paths as strings without quotes
+            "string" if is_interpolated_path(node, self.source) => self.path_to_ast(node),
+            "string" => self.string_to_ast(node, 1),
+            "path" => self.path_to_ast(node),
+            "indented_string" => self.indented_string_to_ast(node),
+            "boolean" => Ok(Expression::Boolean(self.text(node)? == "true")),
+            "null" => Ok(Expression::Null),
+            "identifier" | "or_kw" => Ok(Expression::Identifier(self.text(node)?.to_string())),
+            "uri" => self.uri_to_ast(node),
+
+            // Collections
+            "list" => self.list_to_ast(node),
+            "attrset" | "rec_attrset" => self.attrset_to_ast(node),
+
+            // Binary operations
+            "binary_expression" => self.binary_to_ast(node),
+
+            // Unary operations
+            "unary_expression" => self.unary_to_ast(node),
+
+            // Selection
+            "select" => self.select_to_ast(node),
+
+            // Has-attribute operator
+            "has_attr" => self.has_attr_to_ast(node),
+
+            // Application (`import` is a builtin applied like any other function)
+            "application" => self.application_to_ast(node),
+
+            // Let expression
+            "let_expression" => self.let_to_ast(node),
+
+            // If expression
+            "if_expression" => self.if_to_ast(node),
+
+            // Function
+            "function_expression" => self.function_to_ast(node),
+
+            // With expression
+            "with_expression" => self.with_to_ast(node),
+
+            // Assert expression
+            "assert_expression" => self.assert_to_ast(node),
+
+            // Inherit statement
+            "inherit" => {
+                let (source, attributes) = self.inherit_parts(node)?;
+                Ok(Expression::Inherit {
+                    source: source.map(Box::new),
+                    attributes,
+                })
+            }
+
+            // Syntax errors, when the recovery strategy skips over them
+            "ERROR" if self.config.recovery_strategy.recovers() => self.error_to_ast(node),
+
+            other => Err(ParseError::UnknownNodeType(other.to_string())),
+        }
+    }
+
+    /// Recover the widest expression inside an `ERROR` node
+    fn error_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let mut cursor = node.walk();
+        let mut best: Option<(usize, Expression)> = None;
+
+        for child in node.named_children(&mut cursor) {
+            let width = child.end_byte() - child.start_byte();
+            if best.as_ref().is_some_and(|(best_width, _)| *best_width >= width) {
+                continue;
+            }
+            if let Ok(expr) = self.node_to_ast(child) {
+                best = Some((width, expr));
+            }
+        }
+
+        best.map(|(_, expr)| expr)
+            .ok_or_else(|| ParseError::InvalidNode("No expression could be recovered".into()))
+    }
+
+    /// Convert one item of a list, set or `let`, dropping it instead if it
+    /// contains a syntax error and the recovery strategy skips over those
+    fn recover_item<T>(&self, node: TSNode, convert: impl FnOnce() -> Result<T>) -> Result<Option<T>> {
+        match convert() {
+            Ok(item) => Ok(Some(item)),
+            Err(_) if node.has_error() && self.config.recovery_strategy.recovers() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Build a syntax error pointing at the start of a node
+    fn syntax_error(&self, node: TSNode, message: String) -> ParseError {
+        let location = SourceLocation::from_tree_sitter_node(&node);
+        let context = ErrorContext::from_location(self.source, location.line, location.column);
+        ParseError::syntax_error_with_context(location.line, location.column, message, context)
+    }
+
+    /// Convert an integer literal, which like in Nix must fit in an `i64`
+    fn integer_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let text = self.text(node)?;
+        text.parse::<i64>().map(Expression::Integer).map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow => self.syntax_error(node, integer_out_of_range(text)),
+            _ => ParseError::ParseFailed(format!("invalid integer literal '{text}': {e}")),
+        })
+    }
+
+    /// Convert a path literal, classifying its kind
+    fn path_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let parts = self.interpolated_parts(node, node.start_byte(), node.end_byte())?;
+        if parts.iter().any(|part| matches!(part, StringPart::Interpolation(_))) {
+            return Ok(Expression::Path(PathType::Interpolated(parts)));
+        }
+
+        let text = self.text(node)?;
+        let path_type = if text.starts_with('<') && text.ends_with('>') {
+            PathType::Search(text[1..text.len() - 1].to_string())
+        } else if text.starts_with('/') {
+            PathType::Absolute(text.to_string())
+        } else if text.starts_with("~/") {
+            PathType::Home(text.to_string())
+        } else {
+            PathType::Relative(text.to_string())
         };
-        
-        let children = expr.children();
-        assert_eq!(children.len(), 2);
+        Ok(Expression::Path(path_type))
+    }
+
+    /// Convert a URI literal, if the target language version accepts it
+    fn uri_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let text = self.text(node)?;
+        self.config.language_version.check_literal(node.kind(), text)?;
+        Ok(Expression::Uri(text.to_string()))
     }
-}
\ No newline at end of file
+
+    /// Convert a list, flattening its element nodes
+    fn list_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let mut elements = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children_by_field_name("elements", &mut cursor) {
+            self.collect_list_elements(child, &mut elements)?;
+        }
+        Ok(Expression::List(elements))
+    }
+
+    /// Convert a (possibly recursive) attribute set
+    fn attrset_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let mut attributes = Vec::new();
+        let mut cursor = node.walk();
+
+        for child in node.children_by_field_name("bindings", &mut cursor) {
+            match child.kind() {
+                "binding" => {
+                    let attribute = self.recover_item(child, || {
+                        let path_node = self.field(child, "attrpath")?;
+                        let value_node = self.field(child, "expression")?;
+                        let value = self.node_to_ast(value_node)?;
+                        self.binding_to_attribute(path_node, value)
+                    })?;
+                    attributes.extend(attribute.map(|attribute| Attribute {
+                        location: self.location(child),
+                        ..attribute
+                    }));
+                }
+                "inherit" => {
+                    let (source, names) = self.inherit_parts(child)?;
+                    for name in names {
+                        attributes.push(Attribute {
+                            value: Self::inherited_value(source.as_ref(), &name),
+                            key: AttrKey::Static(vec![name]),
+                            location: self.location(child),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if self.config.language_version.supports_legacy_let() && legacy_let_keyword(node).is_some() {
+            // `let { ... }` is the `body` attribute of the recursive set
+            let set = Expression::AttributeSet { recursive: true, attributes };
+            let set = with_location(set, self.location(node));
+            return Ok(Expression::Select {
+                expr: Box::new(set),
+                path: vec![AttrKey::Static(vec!["body".to_string()])],
+                default: None,
+            });
+        }
+
+        Ok(Expression::AttributeSet {
+            recursive: node.kind() == "rec_attrset",
+            attributes,
+        })
+    }
+
+    /// Convert a binary operation
+    fn binary_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let left = self.field(node, "left")?;
+        let right = self.field(node, "right")?;
+
+        // The operator is the anonymous token between the operands
+        let mut cursor = node.walk();
+        let op_text = node.children(&mut cursor)
+            .filter(|child| !child.is_named())
+            .find_map(|child| {
+                self.text(child).ok().filter(|text| matches!(*text,
+                    "+" | "-" | "*" | "/" | "==" | "!=" | "<" | "<=" | ">" | ">=" |
+                    "&&" | "||" | "->" | "++" | "//"))
+            })
+            .ok_or_else(|| ParseError::InvalidNode("Invalid binary operation".into()))?;
+
+        let op = match op_text {
+            "+" => BinaryOperator::Add,
+            "-" => BinaryOperator::Subtract,
+            "*" => BinaryOperator::Multiply,
+            "/" => BinaryOperator::Divide,
+            "==" => BinaryOperator::Equal,
+            "!=" => BinaryOperator::NotEqual,
+            "<" => BinaryOperator::Less,
+            "<=" => BinaryOperator::LessEqual,
+            ">" => BinaryOperator::Greater,
+            ">=" => BinaryOperator::GreaterEqual,
+            "&&" => BinaryOperator::And,
+            "||" => BinaryOperator::Or,
+            "->" => BinaryOperator::Implies,
+            "++" => BinaryOperator::Concat,
+            "//" => BinaryOperator::Update,
+            _ => return Err(ParseError::UnknownNodeType(
+                format!("Unknown operator: {op_text}")
+            )),
+        };
+
+        let left = self.node_to_ast(left)?;
+        let left_subtractions = self.subtractions.get();
+        let right = self.node_to_ast(right)?;
+        let right_subtractions = self.subtractions.get();
+
+        let (expr, subtractions) = reassociate(op, (left, left_subtractions), (right, right_subtractions));
+        self.subtractions.set(subtractions);
+        // The caller locates the outermost expression from the node itself
+        Ok(without_location(expr))
+    }
+
+    /// Convert a unary operation
+    fn unary_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let operand = self.field(node, "argument")?;
+
+        let mut cursor = node.walk();
+        let op = node.children(&mut cursor)
+            .filter(|child| !child.is_named())
+            .find_map(|child| match self.text(child).ok()? {
+                "!" => Some(UnaryOperator::Not),
+                "-" => Some(UnaryOperator::Negate),
+                _ => None,
+            })
+            .ok_or_else(|| ParseError::InvalidNode("Invalid unary operation".into()))?;
+
+        Ok(Expression::UnaryOp {
+            op,
+            operand: Box::new(self.node_to_ast(operand)?),
+        })
+    }
+
+    /// Convert a `?` has-attribute test
+    fn has_attr_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let expr = self.field(node, "expression")?;
+        let path = self.attrpath_keys(self.field(node, "attrpath")?)?;
+
+        Ok(Expression::HasAttr {
+            expr: Box::new(self.node_to_ast(expr)?),
+            path,
+        })
+    }
+
+    /// Convert a function application
+    ///
+    /// The grammar only knows `-` as a prefix operator binding tighter than
+    /// application, so `a - b` arrives as `a (-b)` and `-f x` as `(-f) x`.
+    /// Like Nix, every argument starting with `-` is read as a subtraction
+    /// instead, splitting the spine into terms, and negations in front of a
+    /// term apply to the whole term.
+    fn application_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let spine = self.application_spine(node)?;
+        // Each application in the spine is a level of nesting, even though
+        // the spine is converted without recursing
+        self.check_depth(self.depth.get() + spine.len() - 1)?;
+
+        let mut terms = Vec::new();
+        for (index, element) in spine.into_iter().enumerate() {
+            let expr = self.node_to_ast(element)?;
+            if index == 0 {
+                terms.push((element, expr, 0, Vec::new()));
+            } else if self.starts_with_minus(element)? {
+                terms.push((element, without_leading_negation(expr), 1, Vec::new()));
+            } else if let Some((_, _, _, arguments)) = terms.last_mut() {
+                arguments.push((element, expr));
+            }
+        }
+        self.subtractions.set(terms.len() - 1);
+
+        let mut expr: Option<Expression> = None;
+        for (head_node, head, removed, arguments) in terms {
+            let end = arguments.last().map_or(head_node, |(argument, _)| *argument);
+            let term = self.build_term(head_node, head, removed, arguments)?;
+            expr = Some(match expr {
+                Some(left) => with_location(Expression::BinaryOp {
+                    op: BinaryOperator::Subtract,
+                    left: Box::new(left),
+                    right: Box::new(term),
+                }, self.node_span(node, end)),
+                None => term,
+            });
+        }
+
+        // The caller locates the outermost expression from the node itself
+        expr.map(without_location)
+            .ok_or_else(|| ParseError::InvalidNode("application has no function".into()))
+    }
+
+    /// Apply the head of a term to its arguments
+    ///
+    /// Negations left in front of the head apply to the whole application.
+    /// `removed` is the number of `-` already taken off the head.
+    fn build_term(
+        &self,
+        head_node: TSNode,
+        head: Expression,
+        removed: usize,
+        arguments: Vec<(TSNode, Expression)>,
+    ) -> Result<Expression> {
+        let Some((end, _)) = arguments.last() else {
+            return Ok(head);
+        };
+        let end = self.location(*end);
+
+        let mut negations = Vec::new();
+        let mut head = head;
+        while matches!(head.unlocated(), Expression::UnaryOp { op: UnaryOperator::Negate, .. }) {
+            negations.push(head.location());
+            let Expression::UnaryOp { operand, .. } = head.into_unlocated() else {
+                unreachable!("checked to be a negation");
+            };
+            head = *operand;
+        }
+
+        // The application starts after the `-` signs, which are only
+        // separate nodes when written right before the head
+        let mut start_node = head_node;
+        for _ in 0..removed + negations.len() {
+            if start_node.kind() == "unary_expression" {
+                start_node = self.field(start_node, "argument")?;
+            }
+        }
+        let start = if self.starts_with_minus(start_node)? {
+            head.location()
+        } else {
+            self.location(start_node)
+        };
+        let span_to = |start: Option<SourceLocation>, node: TSNode| {
+            start.zip(self.location(node)).map(|(start, end)| start.union(&end))
+        };
+
+        let is_import = head_node.kind() == "identifier" && self.text(head_node)? == "import";
+        let mut expr = head;
+        for (index, (argument_node, argument)) in arguments.into_iter().enumerate() {
+            let location = span_to(start, argument_node);
+            expr = with_location(if index == 0 && is_import {
+                Expression::Import { path: Box::new(argument) }
+            } else {
+                Expression::Application {
+                    function: Box::new(expr),
+                    argument: Box::new(argument),
+                }
+            }, location);
+        }
+
+        Ok(negations.into_iter().rev().fold(expr, |expr, negation| {
+            let location = negation.zip(end).map(|(start, end)| start.union(&end));
+            with_location(Expression::UnaryOp {
+                op: UnaryOperator::Negate,
+                operand: Box::new(expr),
+            }, location)
+        }))
+    }
+
+    /// Unfold a left-nested application into its function and arguments
+    fn application_spine<'t>(&self, node: TSNode<'t>) -> Result<Vec<TSNode<'t>>> {
+        let mut spine = Vec::new();
+        let mut current = node;
+        while current.kind() == "application" {
+            spine.push(self.field(current, "argument")?);
+            current = self.field(current, "function")?;
+        }
+        spine.push(current);
+        spine.reverse();
+        Ok(spine)
+    }
+
+    /// Whether the source of a node starts with a `-` operator
+    fn starts_with_minus(&self, node: TSNode) -> Result<bool> {
+        Ok(self.text(node)?.starts_with('-'))
+    }
+
+    /// Get the location from the start of `first` to the end of `last`
+    fn node_span(&self, first: TSNode, last: TSNode) -> Option<SourceLocation> {
+        self.location(first).zip(self.location(last)).map(|(first, last)| first.union(&last))
+    }
+
+    /// Convert a `let ... in` expression
+    fn let_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let mut bindings = Vec::new();
+        let mut cursor = node.walk();
+
+        for child in node.children_by_field_name("bindings", &mut cursor) {
+            match child.kind() {
+                "binding" => {
+                    let binding = self.recover_item(child, || {
+                        let path_node = self.field(child, "attrpath")?;
+                        let value_node = self.field(child, "expression")?;
+                        let value = self.node_to_ast(value_node)?;
+                        self.let_binding(path_node, value)
+                    })?;
+                    if let Some(binding) = binding {
+                        push_let_binding(&mut bindings, binding);
+                    }
+                }
+                "inherit" => {
+                    let (source, names) = self.inherit_parts(child)?;
+                    for name in names {
+                        bindings.push(Binding {
+                            value: Self::inherited_value(source.as_ref(), &name),
+                            name,
+                            inherit: true,
+                            from: source.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let body = self.field(node, "body")?;
+        Ok(Expression::LetIn {
+            bindings,
+            body: Box::new(self.node_to_ast(body)?),
+        })
+    }
+
+    /// Convert an `if ... then ... else` expression
+    fn if_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let condition = self.field(node, "condition")?;
+        let then_branch = self.field(node, "consequence")?;
+        let else_branch = self.field(node, "alternative")?;
+
+        Ok(Expression::If {
+            condition: Box::new(self.node_to_ast(condition)?),
+            then_branch: Box::new(self.node_to_ast(then_branch)?),
+            else_branch: Box::new(self.node_to_ast(else_branch)?),
+        })
+    }
+
+    /// Convert a function definition
+    fn function_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let param_node = self.field(node, "parameter")?;
+        let body_node = self.field(node, "body")?;
+
+        let parameter = match param_node.kind() {
+            "identifier" => Parameter::Identifier(self.text(param_node)?.to_string()),
+            "formals" => self.parse_pattern_parameter(param_node)?,
+            other => return Err(ParseError::InvalidNode(
+                format!("Unexpected function parameter: {other}")
+            )),
+        };
+
+        Ok(Expression::Function {
+            parameter,
+            body: Box::new(self.node_to_ast(body_node)?),
+        })
+    }
+
+    /// Convert a `with` expression
+    fn with_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let namespace_node = self.field(node, "expression")?;
+        let body_node = self.field(node, "body")?;
+
+        Ok(Expression::With {
+            scope: Box::new(self.node_to_ast(namespace_node)?),
+            body: Box::new(self.node_to_ast(body_node)?),
+        })
+    }
+
+    /// Convert an `assert` expression
+    fn assert_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let condition_node = self.field(node, "condition")?;
+        let body_node = self.field(node, "body")?;
+
+        Ok(Expression::Assert {
+            condition: Box::new(self.node_to_ast(condition_node)?),
+            body: Box::new(self.node_to_ast(body_node)?),
+        })
+    }
+
+    /// Convert a float literal, rejecting exponents the target version lacks
+    fn float_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let text = self.text(node)?;
+        self.config.language_version.check_literal(node.kind(), text)?;
+
+        let value = text.parse::<f64>()
+            .map_err(|e| ParseError::ParseFailed(format!("invalid float literal '{text}': {e}")))?;
+        if !value.is_finite() {
+            return Err(ParseError::ParseFailed(
+                format!("float literal '{text}' is out of range")
+            ));
+        }
+
+        Ok(Expression::Float(value))
+    }
+
+    /// Convert a string node, splitting it into literal and interpolated parts
+    ///
+    /// The scanner's content tokens are hidden, so literal parts are recovered
+    /// from the source text between the interpolation children.
+    fn string_to_ast(&self, node: TSNode, delimiter_len: usize) -> Result<Expression> {
+        let content_start = node.start_byte() + delimiter_len;
+        let content_end = node.end_byte().saturating_sub(delimiter_len).max(content_start);
+        let parts = self.interpolated_parts(node, content_start, content_end)?;
+        let parts = unescape_parts(parts, crate::utils::string::unescape_nix_string)?;
+        Ok(Self::string_from_parts(parts))
+    }
+
+    /// Convert an indented string, removing its common indentation unless
+    /// `ParserConfig::raw_indented_strings` asks for the content as written
+    fn indented_string_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let content_start = node.start_byte() + 2;
+        let content_end = node.end_byte().saturating_sub(2).max(content_start);
+        let parts = self.interpolated_parts(node, content_start, content_end)?;
+
+        if self.config.raw_indented_strings {
+            return Ok(Self::string_from_parts(parts));
+        }
+
+        // Indentation is measured on the source text, where escapes such as
+        // `''\t` are never whitespace, so unescape only after stripping it
+        let parts = strip_indentation(parts);
+        let parts = unescape_parts(parts, crate::utils::string::unescape_indented_string)?;
+        Ok(Self::string_from_parts(parts))
+    }
+
+    fn string_from_parts(parts: Vec<StringPart>) -> Expression {
+        if parts.iter().any(|part| matches!(part, StringPart::Interpolation(_))) {
+            Expression::StringInterpolation { parts }
+        } else {
+            // Simple string - concatenate all literal parts
+            let mut result = String::new();
+            for part in parts {
+                if let StringPart::Literal(s) = part {
+                    result.push_str(&s);
+                }
+            }
+            Expression::String(result)
+        }
+    }
+
+    /// Split the bytes `content_start..content_end` of `node` into literal
+    /// text and its `string_interpolation` children
+    fn interpolated_parts(
+        &self,
+        node: TSNode,
+        content_start: usize,
+        content_end: usize,
+    ) -> Result<Vec<StringPart>> {
+        let mut parts = Vec::new();
+        let mut literal_start = content_start;
+        let mut cursor = node.walk();
+
+        for child in node.children(&mut cursor) {
+            if child.kind() != "string_interpolation" {
+                continue;
+            }
+
+            if child.start_byte() > literal_start {
+                parts.push(StringPart::Literal(
+                    self.slice(literal_start, child.start_byte())?.to_string()
+                ));
+            }
+
+            let expr_node = self.field(child, "expression")?;
+            parts.push(StringPart::Interpolation(Box::new(self.node_to_ast(expr_node)?)));
+            literal_start = child.end_byte();
+        }
+
+        if content_end > literal_start {
+            parts.push(StringPart::Literal(
+                self.slice(literal_start, content_end)?.to_string()
+            ));
+        }
+
+        Ok(parts)
+    }
+
+    /// Convert a chain of select nodes into a single `Select` expression
+    ///
+    /// The grammar parses `a.b.c` as nested single-segment selections and
+    /// attaches `or` defaults to the left, so `a.b or c.d` arrives as
+    /// `(a.b or c).d`. The links of the chain are collected innermost-first
+    /// and rebuilt with Nix's semantics, where everything after an `or`
+    /// belongs to the default.
+    fn select_to_ast(&self, node: TSNode) -> Result<Expression> {
+        let mut links = Vec::new();
+        let mut current = node;
+        while current.kind() == "select" {
+            let path = self.attrpath_keys(self.field(current, "attrpath")?)?;
+            links.push((path, current.child_by_field_name("default")));
+            current = self.field(current, "expression")?;
+        }
+        links.reverse();
+
+        // `?` also binds tighter than `.` in the grammar, so `a ? b.c` arrives
+        // as `(a ? b).c`. Fold the selection back into the has-attr path
+        // unless it was explicitly parenthesized.
+        if current.kind() == "has_attr"
+            && links.iter().all(|(_, default)| default.is_none())
+            && let Expression::HasAttr { expr, mut path } = self.convert_node(current)?
+        {
+            for (keys, _) in links {
+                extend_attr_path(&mut path, keys);
+            }
+            return Ok(Expression::HasAttr { expr, path });
+        }
+
+        // Negation binds more loosely than selection, but the grammar puts
+        // it on the base: `-x.y` arrives as `(-x).y`
+        let mut negations = Vec::new();
+        while current.kind() == "unary_expression" && self.starts_with_minus(current)? {
+            negations.push(current);
+            current = self.field(current, "argument")?;
+        }
+
+        let base = self.node_to_ast(current)?;
+        if negations.is_empty() {
+            return self.build_select(base, &links);
+        }
+
+        // Everything rebuilt extends to the end of the chain
+        let to_end = |start: Option<SourceLocation>| {
+            start.zip(self.location(node)).map(|(start, end)| SourceLocation {
+                end_byte: end.end_byte,
+                end_position: end.end_position,
+                ..start
+            })
+        };
+        let location = to_end(base.location());
+        let mut expr = with_location(self.build_select(base, &links)?, location);
+        for negation in negations.into_iter().rev() {
+            expr = with_location(Expression::UnaryOp {
+                op: UnaryOperator::Negate,
+                operand: Box::new(expr),
+            }, to_end(self.location(negation)));
+        }
+
+        // The caller locates the outermost expression from the node itself
+        Ok(without_location(expr))
+    }
+
+    fn build_select(
+        &self,
+        base: Expression,
+        links: &[(Vec<AttrKey>, Option<TSNode>)],
+    ) -> Result<Expression> {
+        let mut path = Vec::new();
+
+        for (index, (keys, default)) in links.iter().enumerate() {
+            extend_attr_path(&mut path, keys.clone());
+
+            if let Some(default_node) = default {
+                let default_expr = self.node_to_ast(*default_node)?;
+                let rest = &links[index + 1..];
+                let default_expr = if rest.is_empty() {
+                    default_expr
+                } else {
+                    self.build_select(default_expr, rest)?
+                };
+
+                return Ok(Expression::Select {
+                    expr: Box::new(base),
+                    path,
+                    default: Some(Box::new(default_expr)),
+                });
+            }
+        }
+
+        Ok(Expression::Select {
+            expr: Box::new(base),
+            path,
+            default: None,
+        })
+    }
+
+    /// Get the optional `(source)` expression and the names of an inherit node
+    fn inherit_parts(&self, node: TSNode) -> Result<(Option<Expression>, Vec<String>)> {
+        let mut cursor = node.walk();
+        // The `from` field spans `(`, the expression and `)`
+        let source = node.children_by_field_name("from", &mut cursor)
+            .find(TSNode::is_named)
+            .map(|expr_node| self.node_to_ast(expr_node))
+            .transpose()?;
+
+        let mut cursor = node.walk();
+        let names = node.children_by_field_name("attributes", &mut cursor)
+            .map(|attr_node| match attr_node.kind() {
+                "string" => match self.convert_node(attr_node)? {
+                    Expression::String(name) => Ok(name),
+                    _ => Err(ParseError::InvalidNode(format!(
+                        "Inherited attribute cannot be interpolated: {}",
+                        self.text(attr_node)?
+                    ))),
+                },
+                _ => self.text(attr_node).map(str::to_string),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((source, names))
+    }
+
+    /// The value bound by inheriting a single name
+    ///
+    /// Inherited names are represented as a one-name `Expression::Inherit`,
+    /// since `inherit a;` refers to `a` in the enclosing scope rather than
+    /// the scope being defined.
+    fn inherited_value(source: Option<&Expression>, name: &str) -> Expression {
+        Expression::Inherit {
+            source: source.cloned().map(Box::new),
+            attributes: vec![name.to_string()],
+        }
+    }
+
+    /// Build an attribute set entry from a binding's attrpath and value
+    ///
+    /// Paths that mix static and dynamic segments, like `a.${k}.c = v`, are
+    /// expanded into nested attribute sets (`a = { ${k} = { c = v; }; }`) so
+    /// that every key is either a static path or a single dynamic expression.
+    fn binding_to_attribute(&self, path_node: TSNode, value: Expression) -> Result<Attribute> {
+        let mut keys = self.attrpath_keys(path_node)?.into_iter();
+        let key = keys.next()
+            .ok_or_else(|| ParseError::InvalidNode("attrpath has no segments".into()))?;
+        let value = keys.rev().fold(value, |value, key| Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute::new(key, value)],
+        });
+
+        Ok(Attribute::new(key, value))
+    }
+
+    /// Convert a `let` binding, desugaring `a.b = v` into `a = { b = v; }`
+    ///
+    /// Like in Nix, the first segment must be a name: `let ${k} = v;` and
+    /// `let "${k}" = v;` are rejected.
+    fn let_binding(&self, path_node: TSNode, value: Expression) -> Result<Binding> {
+        let mut keys = self.attrpath_keys(path_node)?;
+        let name = match keys.first_mut() {
+            Some(AttrKey::Static(names)) => names.remove(0),
+            Some(AttrKey::Dynamic(_)) => {
+                return Err(self.syntax_error(path_node, "dynamic attributes not allowed in let".to_string()));
+            }
+            None => return Err(ParseError::InvalidNode("attrpath has no segments".into())),
+        };
+        if keys.first().and_then(AttrKey::static_path).is_some_and(<[String]>::is_empty) {
+            keys.remove(0);
+        }
+        let value = keys.into_iter().rev().fold(value, |value, key| Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute::new(key, value)],
+        });
+
+        Ok(Binding { name, value, inherit: false, from: None })
+    }
+
+    /// Group the segments of an attribute path into keys
+    ///
+    /// Quoted segments such as `"weird.key"` are static names; `${k}` and
+    /// strings with interpolations are dynamic keys of their own, between
+    /// which consecutive static segments are grouped.
+    fn attrpath_keys(&self, node: TSNode) -> Result<Vec<AttrKey>> {
+        let mut keys = Vec::new();
+        let mut cursor = node.walk();
+
+        for (index, child) in (0u32..).zip(node.named_children(&mut cursor)) {
+            let dynamic = if node.field_name_for_named_child(index) == Some("expression") {
+                Some(self.node_to_ast(child)?)
+            } else if child.kind() == "string" {
+                match self.convert_node(child)? {
+                    Expression::String(name) => {
+                        push_static_segment(&mut keys, name);
+                        None
+                    }
+                    _ => Some(self.node_to_ast(child)?),
+                }
+            } else {
+                push_static_segment(&mut keys, self.text(child)?.to_string());
+                None
+            };
+
+            if let Some(expr) = dynamic {
+                keys.push(AttrKey::Dynamic(Box::new(expr)));
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Collect the elements of a list from one of its element nodes
+    ///
+    /// The grammar's list/application conflict makes `[ a b c ]` parse as a
+    /// single `application` node, so the application spine is unfolded back
+    /// into separate elements. Applications written inside parentheses are
+    /// wrapped in a `parenthesized_expression` and are left intact.
+    fn collect_list_elements(&self, node: TSNode, elements: &mut Vec<Expression>) -> Result<()> {
+        if node.kind() == "application" {
+            self.collect_list_elements(self.field(node, "function")?, elements)?;
+            let argument = self.field(node, "argument")?;
+            elements.extend(self.recover_item(argument, || self.node_to_ast(argument))?);
+        } else {
+            elements.extend(self.recover_item(node, || self.node_to_ast(node))?);
+        }
+        Ok(())
+    }
+
+    fn parse_pattern_parameter(&self, node: TSNode) -> Result<Parameter> {
+        let mut fields = Vec::new();
+        let mut ellipsis = false;
+        let mut cursor = node.walk();
+
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "formal" => {
+                    let name_node = self.field(child, "name")?;
+                    let default = child.child_by_field_name("default")
+                        .map(|default_node| self.node_to_ast(default_node))
+                        .transpose()?;
+                    fields.push(PatternField {
+                        name: self.text(name_node)?.to_string(),
+                        default,
+                    });
+                }
+                "..." => ellipsis = true,
+                _ => {}
+            }
+        }
+
+        let bind = node.child_by_field_name("name")
+            .map(|name_node| self.text(name_node).map(str::to_string))
+            .transpose()?;
+
+        Ok(Parameter::Pattern { fields, ellipsis, bind })
+    }
+
+    fn text(&self, node: TSNode) -> Result<&'a str> {
+        node.text(self.source)
+    }
+
+    /// Get the location of a node, if the configuration asks for locations
+    fn location(&self, node: TSNode) -> Option<SourceLocation> {
+        self.config.include_locations.then(|| SourceLocation::from_tree_sitter_node(&node))
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Result<&'a str> {
+        self.source.get(start..end).ok_or_else(|| ParseError::InvalidNode(
+            format!("Invalid source range {start}..{end}")
+        ))
+    }
+
+    #[allow(clippy::unused_self)]
+    fn field<'t>(&self, node: TSNode<'t>, name: &str) -> Result<TSNode<'t>> {
+        node.child_by_field_name(name).ok_or_else(|| ParseError::InvalidNode(
+            format!("{} missing field '{}'", node.kind(), name)
+        ))
+    }
+}
+
+/// Apply `unescape` to every literal part, reporting invalid escapes as parse failures
+fn unescape_parts(
+    parts: Vec<StringPart>,
+    unescape: fn(&str) -> std::result::Result<String, String>,
+) -> Result<Vec<StringPart>> {
+    parts.into_iter()
+        .map(|part| match part {
+            StringPart::Literal(text) => unescape(&text)
+                .map(StringPart::Literal)
+                .map_err(ParseError::ParseFailed),
+            interpolation @ StringPart::Interpolation(_) => Ok(interpolation),
+        })
+        .collect()
+}
+
+/// Remove the common leading indentation from the parts of an indented string
+///
+/// Mirrors Nix's `stripIndentation`: a first line holding only spaces is
+/// dropped, the indentation is the smallest number of leading spaces over
+/// lines that contain anything other than spaces (an interpolation counts),
+/// and a final line holding only spaces is removed. Only spaces count as
+/// indentation; tabs end it like any other character.
+fn strip_indentation(mut parts: Vec<StringPart>) -> Vec<StringPart> {
+    if let Some(StringPart::Literal(first)) = parts.first_mut() {
+        let spaces = first.bytes().take_while(|&b| b == b' ').count();
+        if first[spaces..].starts_with('\n') {
+            first.drain(..=spaces);
+        }
+    }
+
+    let mut min_indent = usize::MAX;
+    let mut at_line_start = true;
+    let mut indent = 0;
+    for part in &parts {
+        match part {
+            StringPart::Interpolation(_) => {
+                if at_line_start {
+                    at_line_start = false;
+                    min_indent = min_indent.min(indent);
+                }
+            }
+            StringPart::Literal(text) => {
+                for c in text.chars() {
+                    if at_line_start {
+                        match c {
+                            ' ' => indent += 1,
+                            // Blank lines don't affect the indentation
+                            '\n' => indent = 0,
+                            _ => {
+                                at_line_start = false;
+                                min_indent = min_indent.min(indent);
+                            }
+                        }
+                    } else if c == '\n' {
+                        at_line_start = true;
+                        indent = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    let last = parts.len().saturating_sub(1);
+    let mut at_line_start = true;
+    let mut dropped = 0;
+    for (index, part) in parts.iter_mut().enumerate() {
+        let StringPart::Literal(text) = part else {
+            at_line_start = false;
+            dropped = 0;
+            continue;
+        };
+
+        let mut stripped = String::with_capacity(text.len());
+        for c in text.chars() {
+            if at_line_start {
+                match c {
+                    ' ' => {
+                        if dropped >= min_indent {
+                            stripped.push(c);
+                        }
+                        dropped += 1;
+                    }
+                    '\n' => {
+                        dropped = 0;
+                        stripped.push(c);
+                    }
+                    _ => {
+                        at_line_start = false;
+                        dropped = 0;
+                        stripped.push(c);
+                    }
+                }
+            } else {
+                stripped.push(c);
+                if c == '\n' {
+                    at_line_start = true;
+                }
+            }
+        }
+
+        if index == last
+            && let Some(newline) = stripped.rfind('\n')
+            && stripped[newline + 1..].bytes().all(|b| b == b' ')
+        {
+            stripped.truncate(newline + 1);
+        }
+        *text = stripped;
+    }
+
+    parts.retain(|part| !matches!(part, StringPart::Literal(text) if text.is_empty()));
+    parts
+}
+
+/// Wrap `expr` in a location, if there is one
+fn with_location(expr: Expression, location: Option<SourceLocation>) -> Expression {
+    match location {
+        Some(location) => Expression::Located { location, expr: Box::new(expr) },
+        None => expr,
+    }
+}
+
+/// Remove the outermost location wrapper of `expr`, if any
+fn without_location(expr: Expression) -> Expression {
+    match expr {
+        Expression::Located { expr, .. } => *expr,
+        expr => expr,
+    }
+}
+
+/// Get the location spanning two located expressions
+fn span(first: &Expression, last: &Expression) -> Option<SourceLocation> {
+    first.location().zip(last.location()).map(|(first, last)| first.union(&last))
+}
+
+/// Build a binary operation, located from its operands
+fn binary(op: BinaryOperator, left: Expression, right: Expression) -> Expression {
+    let location = span(&left, &right);
+    with_location(Expression::BinaryOp {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }, location)
+}
+
+/// Build `left op right`, moving up the subtractions at the top of the
+/// operands, given with their number, if `op` binds tighter
+///
+/// Subtractions come out of applications, which the grammar binds tighter
+/// than any operator: `a * b - c` arrives as `a * (b - c)` and `a - b * c`
+/// as `(a - b) * c`. Returns the expression and the number of subtractions
+/// at its top.
+fn reassociate(
+    op: BinaryOperator,
+    (left, left_subtractions): (Expression, usize),
+    (right, right_subtractions): (Expression, usize),
+) -> (Expression, usize) {
+    let subtract = BinaryOperator::Subtract.precedence();
+
+    // `a * b - c - d` is `((a * b) - c) - d`, so the innermost minuend of
+    // the right operand becomes the right operand of `op`
+    let mut subtrahends = Vec::new();
+    let mut right = right;
+    if op.precedence() >= subtract {
+        for _ in 0..right_subtractions {
+            let (minuend, subtrahend) = subtraction_operands(right);
+            subtrahends.push(subtrahend);
+            right = minuend;
+        }
+    }
+
+    // `a - b * c` is `a - (b * c)`
+    let (expr, subtractions) = if op.precedence() > subtract && left_subtractions > 0 {
+        let (minuend, subtrahend) = subtraction_operands(left);
+        (binary(BinaryOperator::Subtract, minuend, binary(op, subtrahend, right)), left_subtractions)
+    } else {
+        (binary(op, left, right), 0)
+    };
+
+    let count = subtractions + subtrahends.len();
+    let expr = subtrahends.into_iter().rev()
+        .fold(expr, |expr, subtrahend| binary(BinaryOperator::Subtract, expr, subtrahend));
+    (expr, count)
+}
+
+/// Get the operands of an expression counted as a subtraction
+fn subtraction_operands(expr: Expression) -> (Expression, Expression) {
+    match expr.into_unlocated() {
+        Expression::BinaryOp { op: BinaryOperator::Subtract, left, right } => (*left, *right),
+        other => unreachable!("counted as a subtraction: {other:?}"),
+    }
+}
+
+/// Remove the leftmost negation of an expression starting with `-`
+///
+/// That `-` is a subtraction the grammar mistook for a negation. It is
+/// found at the start of the operand of `?`, which binds tighter than
+/// subtraction.
+fn without_leading_negation(expr: Expression) -> Expression {
+    match expr {
+        Expression::Located { location, expr } => match *expr {
+            Expression::UnaryOp { op: UnaryOperator::Negate, operand } => *operand,
+            expr => Expression::Located { location, expr: Box::new(without_leading_negation(expr)) },
+        },
+        Expression::UnaryOp { op: UnaryOperator::Negate, operand } => *operand,
+        Expression::HasAttr { expr, path } => Expression::HasAttr {
+            expr: Box::new(without_leading_negation(*expr)),
+            path,
+        },
+        expr => expr,
+    }
+}
+
+/// Append a static segment, extending the previous key if it is also static
+fn push_static_segment(keys: &mut Vec<AttrKey>, name: String) {
+    if let Some(AttrKey::Static(path)) = keys.last_mut() {
+        path.push(name);
+    } else {
+        keys.push(AttrKey::Static(vec![name]));
+    }
+}
+
+/// Append the keys of an attribute path, keeping static segments grouped
+fn extend_attr_path(path: &mut Vec<AttrKey>, keys: Vec<AttrKey>) {
+    for key in keys {
+        match key {
+            AttrKey::Static(names) => {
+                for name in names {
+                    push_static_segment(path, name);
+                }
+            }
+            key @ AttrKey::Dynamic(_) => path.push(key),
+        }
+    }
+}
+
+impl Node for Expression {
+    fn location(&self) -> Option<SourceLocation> {
+        Expression::location(self)
+    }
+    
+    fn children(&self) -> Vec<&dyn Node> {
+        match self {
+            Expression::Function { body, .. } | Expression::LetIn { body, .. } => vec![body.as_ref()],
+            Expression::Application { function, argument } => {
+                vec![function.as_ref(), argument.as_ref()]
+            }
+            Expression::With { scope, body } => vec![scope.as_ref(), body.as_ref()],
+            Expression::If { condition, then_branch, else_branch } => {
+                vec![condition.as_ref(), then_branch.as_ref(), else_branch.as_ref()]
+            }
+            Expression::Assert { condition, body } => {
+                vec![condition.as_ref(), body.as_ref()]
+            }
+            Expression::BinaryOp { left, right, .. } => {
+                vec![left.as_ref(), right.as_ref()]
+            }
+            Expression::UnaryOp { operand, .. } => vec![operand.as_ref()],
+            Expression::Select { expr, .. } => vec![expr.as_ref()],
+            Expression::HasAttr { expr, .. } | Expression::Located { expr, .. } => {
+                vec![expr.as_ref()]
+            }
+            _ => vec![],
+        }
+    }
+    
+    fn accept(&self, visitor: &mut dyn crate::visitor::Visitor) {
+        visitor.visit_expression(self);
+    }
+}
+
+/// Operator associativity, used to decide which operand needs parentheses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+impl BinaryOperator {
+    /// Get the operator as written in Nix source
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            BinaryOperator::Implies => "->",
+            BinaryOperator::Update => "//",
+            BinaryOperator::Concat => "++",
+        }
+    }
+
+    /// Binding power of the operator; higher binds tighter
+    pub(crate) const fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Concat => 60,
+            BinaryOperator::Multiply | BinaryOperator::Divide => 55,
+            BinaryOperator::Add | BinaryOperator::Subtract => 50,
+            BinaryOperator::Update => 40,
+            BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual => 35,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 30,
+            BinaryOperator::And => 25,
+            BinaryOperator::Or => 20,
+            BinaryOperator::Implies => 15,
+        }
+    }
+
+    pub(crate) const fn associativity(self) -> Associativity {
+        match self {
+            BinaryOperator::Concat | BinaryOperator::Update | BinaryOperator::Implies => {
+                Associativity::Right
+            }
+            BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Equal
+            | BinaryOperator::NotEqual => Associativity::None,
+            _ => Associativity::Left,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl UnaryOperator {
+    /// Get the operator as written in Nix source
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            UnaryOperator::Not => "!",
+            UnaryOperator::Negate => "-",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Precedence levels used when printing expressions
+pub(crate) mod precedence {
+    /// Literals, identifiers, lists, attribute sets and parenthesized forms
+    pub const ATOM: u8 = 100;
+    /// Attribute selection `e.a`
+    pub const SELECT: u8 = 90;
+    /// Function application `f x`
+    pub const APPLICATION: u8 = 80;
+    /// Arithmetic negation `-e`
+    pub const NEGATE: u8 = 70;
+    /// Attribute test `e ? a`
+    pub const HAS_ATTR: u8 = 65;
+    /// Logical negation `!e`
+    pub const NOT: u8 = 45;
+    /// `if`, `let`, `with`, `assert` and functions, which extend as far right as possible
+    pub const LOWEST: u8 = 0;
+}
+
+impl Expression {
+    /// Binding power of the expression's outermost construct; higher binds tighter
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expression::Integer(n) if *n < 0 => precedence::NEGATE,
+            Expression::Float(n) if n.is_sign_negative() => precedence::NEGATE,
+            Expression::Select { .. } => precedence::SELECT,
+            Expression::Application { .. } | Expression::Import { .. } => {
+                precedence::APPLICATION
+            }
+            Expression::UnaryOp { op: UnaryOperator::Negate, .. } => precedence::NEGATE,
+            Expression::UnaryOp { op: UnaryOperator::Not, .. } => precedence::NOT,
+            Expression::HasAttr { .. } => precedence::HAS_ATTR,
+            Expression::BinaryOp { op, .. } => op.precedence(),
+            Expression::Located { expr, .. } => expr.precedence(),
+            Expression::Function { .. }
+            | Expression::LetIn { .. }
+            | Expression::With { .. }
+            | Expression::If { .. }
+            | Expression::Assert { .. }
+            | Expression::Inherit { .. } => precedence::LOWEST,
+            _ => precedence::ATOM,
+        }
+    }
+}
+
+/// Write `expr`, parenthesized if it binds more loosely than `min_precedence`
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expression, min_precedence: u8) -> fmt::Result {
+    if expr.precedence() < min_precedence {
+        write!(f, "({expr})")
+    } else {
+        write!(f, "{expr}")
+    }
+}
+
+/// Write the parts of an interpolated string, quotes included
+fn write_string_parts(f: &mut fmt::Formatter<'_>, parts: &[StringPart]) -> fmt::Result {
+    write!(f, "\"")?;
+    let mut parts = parts.iter().peekable();
+    while let Some(part) = parts.next() {
+        match part {
+            StringPart::Literal(s) => {
+                let mut escaped = crate::utils::string::escape_nix_string(s);
+                // A `$` before an interpolation would start `$${`
+                if matches!(parts.peek(), Some(StringPart::Interpolation(_))) && escaped.ends_with('$') {
+                    escaped.insert(escaped.len() - 1, '\\');
+                }
+                write!(f, "{escaped}")?;
+            }
+            StringPart::Interpolation(expr) => write!(f, "${{{expr}}}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn write_path(f: &mut fmt::Formatter<'_>, path: &PathType) -> fmt::Result {
+    match path {
+        PathType::Absolute(p) | PathType::Relative(p) | PathType::Home(p) => write!(f, "{p}"),
+        PathType::Search(p) => {
+            if p.starts_with('<') {
+                write!(f, "{p}")
+            } else {
+                write!(f, "<{p}>")
+            }
+        }
+        PathType::Interpolated(parts) => {
+            for part in parts {
+                match part {
+                    StringPart::Literal(s) => write!(f, "{s}")?,
+                    StringPart::Interpolation(expr) => write!(f, "${{{expr}}}")?,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write the braces and attributes of an attribute set
+fn write_attributes(f: &mut fmt::Formatter<'_>, attributes: &[Attribute]) -> fmt::Result {
+    write!(f, "{{")?;
+    for attr in attributes {
+        write!(f, " ")?;
+        if let Expression::Inherit { .. } = attr.value.unlocated() {
+            write!(f, "{}", attr.value)?;
+            continue;
+        }
+        write_attr_key(f, &attr.key)?;
+        write!(f, " = {};", attr.value)?;
+    }
+    write!(f, " }}")
+}
+
+/// Write a binary operation, parenthesizing operands as its associativity requires
+fn write_binary_op(f: &mut fmt::Formatter<'_>, op: BinaryOperator, left: &Expression, right: &Expression) -> fmt::Result {
+    let level = op.precedence();
+    let (left_min, right_min) = match op.associativity() {
+        Associativity::Left => (level, level + 1),
+        Associativity::Right => (level + 1, level),
+        Associativity::None => (level + 1, level + 1),
+    };
+    write_operand(f, left, left_min)?;
+    write!(f, " {op} ")?;
+    write_operand(f, right, right_min)
+}
+
+/// Write an attribute name, quoting it when it is not a plain identifier
+fn write_attr_name(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    if crate::utils::string::needs_quoting(name) {
+        write!(f, "\"{}\"", crate::utils::string::escape_nix_string(name))
+    } else {
+        write!(f, "{name}")
+    }
+}
+
+fn write_attr_path(f: &mut fmt::Formatter<'_>, path: &[String]) -> fmt::Result {
+    for (i, segment) in path.iter().enumerate() {
+        if i > 0 {
+            write!(f, ".")?;
+        }
+        write_attr_name(f, segment)?;
+    }
+    Ok(())
+}
+
+/// Write the keys of a selection or `?` path, separated by dots
+fn write_attr_keys(f: &mut fmt::Formatter<'_>, path: &[AttrKey]) -> fmt::Result {
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 {
+            write!(f, ".")?;
+        }
+        write_attr_key(f, key)?;
+    }
+    Ok(())
+}
+
+fn write_attr_key(f: &mut fmt::Formatter<'_>, key: &AttrKey) -> fmt::Result {
+    match key {
+        AttrKey::Static(path) => write_attr_path(f, path),
+        // Strings are valid keys as written; anything else needs `${}`
+        AttrKey::Dynamic(expr) => match expr.unlocated() {
+            Expression::String(_) | Expression::StringInterpolation { .. } => {
+                write!(f, "{expr}")
+            }
+            _ => write!(f, "${{{expr}}}"),
+        },
+    }
+}
+
+fn write_binding(f: &mut fmt::Formatter<'_>, binding: &Binding) -> fmt::Result {
+    if binding.inherit {
+        write!(f, "inherit ")?;
+        if let Some(from) = &binding.from {
+            write!(f, "({from}) ")?;
+        }
+        write_attr_name(f, &binding.name)?;
+    } else {
+        write_attr_name(f, &binding.name)?;
+        write!(f, " = {}", binding.value)?;
+    }
+    write!(f, ";")
+}
+
+fn write_parameter(f: &mut fmt::Formatter<'_>, parameter: &Parameter) -> fmt::Result {
+    match parameter {
+        Parameter::Identifier(name) => write!(f, "{name}"),
+        Parameter::Pattern { fields, ellipsis, bind } => {
+            write!(f, "{{")?;
+            for (i, field) in fields.iter().enumerate() {
+                write!(f, "{}{}", if i > 0 { ", " } else { " " }, field.name)?;
+                if let Some(default) = &field.default {
+                    write!(f, " ? {default}")?;
+                }
+            }
+            if *ellipsis {
+                write!(f, "{}...", if fields.is_empty() { " " } else { ", " })?;
+            }
+            write!(f, " }}")?;
+            if let Some(bind) = bind {
+                write!(f, "@{bind}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    /// Print the expression as Nix source on a single line
+    ///
+    /// Parentheses are only inserted where needed to preserve precedence
+    /// and associativity, so printing and re-parsing yields the same tree.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Integer(n) => write!(f, "{n}"),
+            // Debug formatting always keeps a decimal point or exponent
+            Expression::Float(n) => write!(f, "{n:?}"),
+            Expression::String(s) => {
+                write!(f, "\"{}\"", crate::utils::string::escape_nix_string(s))
+            }
+            Expression::StringInterpolation { parts } => write_string_parts(f, parts),
+            Expression::Path(path) => write_path(f, path),
+            Expression::Uri(uri) => write!(f, "{uri}"),
+            Expression::Boolean(b) => write!(f, "{b}"),
+            Expression::Null => write!(f, "null"),
+            Expression::Identifier(name) => write!(f, "{name}"),
+            Expression::List(elements) => {
+                write!(f, "[")?;
+                for element in elements {
+                    write!(f, " ")?;
+                    // Juxtaposed list elements would otherwise parse as an application
+                    write_operand(f, element, precedence::SELECT)?;
+                }
+                write!(f, " ]")
+            }
+            Expression::AttributeSet { recursive, attributes } => {
+                if *recursive {
+                    write!(f, "rec ")?;
+                }
+                write_attributes(f, attributes)
+            }
+            Expression::Function { parameter, body } => {
+                write_parameter(f, parameter)?;
+                write!(f, ": {body}")
+            }
+            Expression::Application { function, argument } => {
+                write_operand(f, function, precedence::APPLICATION)?;
+                write!(f, " ")?;
+                write_operand(f, argument, precedence::SELECT)
+            }
+            Expression::LetIn { bindings, body } => {
+                write!(f, "let")?;
+                for binding in bindings {
+                    write!(f, " ")?;
+                    write_binding(f, binding)?;
+                }
+                write!(f, " in {body}")
+            }
+            Expression::With { scope, body } => write!(f, "with {scope}; {body}"),
+            Expression::If { condition, then_branch, else_branch } => {
+                write!(f, "if {condition} then {then_branch} else {else_branch}")
+            }
+            Expression::Assert { condition, body } => {
+                write!(f, "assert {condition}; {body}")
+            }
+            Expression::BinaryOp { op, left, right } => write_binary_op(f, *op, left, right),
+            Expression::UnaryOp { op, operand } => {
+                write!(f, "{op}")?;
+                match op {
+                    // The grammar lets `-` bind tighter than selection and
+                    // application, so anything but an atom is parenthesized
+                    UnaryOperator::Negate => write_operand(f, operand, precedence::ATOM),
+                    UnaryOperator::Not => {
+                        write_operand(f, operand, BinaryOperator::Add.precedence())
+                    }
+                }
+            }
+            Expression::Select { expr, path, default } => {
+                // A selection base must be an atom, so `(a.b).c` keeps its shape
+                write_operand(f, expr, precedence::ATOM)?;
+                write!(f, ".")?;
+                write_attr_keys(f, path)?;
+                if let Some(default) = default {
+                    write!(f, " or ")?;
+                    write_operand(f, default, precedence::SELECT)?;
+                }
+                Ok(())
+            }
+            Expression::HasAttr { expr, path } => {
+                write_operand(f, expr, precedence::ATOM)?;
+                write!(f, " ? ")?;
+                write_attr_keys(f, path)
+            }
+            Expression::Import { path } => {
+                write!(f, "import ")?;
+                write_operand(f, path, precedence::SELECT)
+            }
+            Expression::Inherit { source, attributes } => {
+                write!(f, "inherit")?;
+                if let Some(source) = source {
+                    write!(f, " ({source})")?;
+                }
+                for attr in attributes {
+                    write!(f, " ")?;
+                    write_attr_name(f, attr)?;
+                }
+                write!(f, ";")
+            }
+            Expression::Located { expr, .. } => write!(f, "{expr}"),
+        }
+    }
+}
+
+impl Expression {
+    /// Render the expression as a Lisp-style S-expression
+    ///
+    /// The output is compact and stable, which makes it convenient for
+    /// debugging and golden tests: `1 + 2` renders as
+    /// `(binary Add (integer 1) (integer 2))`. Location wrappers are
+    /// omitted.
+    pub fn to_sexpr(&self) -> String {
+        Sexpr(self).to_string()
+    }
+}
+
+/// Display adapter printing an expression as an S-expression
+struct Sexpr<'a>(&'a Expression);
+
+impl fmt::Display for Sexpr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expression::Integer(n) => write!(f, "(integer {n})"),
+            Expression::Float(n) => write!(f, "(float {n:?})"),
+            Expression::String(s) => write!(f, "(string {s:?})"),
+            Expression::StringInterpolation { parts } => {
+                write!(f, "(interpolation")?;
+                write_sexpr_parts(f, parts)?;
+                write!(f, ")")
+            }
+            Expression::Path(path) => match path {
+                PathType::Absolute(p) => write!(f, "(path absolute {p:?})"),
+                PathType::Relative(p) => write!(f, "(path relative {p:?})"),
+                PathType::Home(p) => write!(f, "(path home {p:?})"),
+                PathType::Search(p) => write!(f, "(path search {p:?})"),
+                PathType::Interpolated(parts) => {
+                    write!(f, "(path interpolated")?;
+                    write_sexpr_parts(f, parts)?;
+                    write!(f, ")")
+                }
+            },
+            Expression::Uri(uri) => write!(f, "(uri {uri:?})"),
+            Expression::Boolean(b) => write!(f, "(bool {b})"),
+            Expression::Null => write!(f, "(null)"),
+            Expression::Identifier(name) => write!(f, "(identifier {name})"),
+            Expression::List(elements) => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {}", Sexpr(element))?;
+                }
+                write!(f, ")")
+            }
+            Expression::AttributeSet { recursive, attributes } => {
+                write!(f, "({}", if *recursive { "rec-attrs" } else { "attrs" })?;
+                write_sexpr_attributes(f, attributes)?;
+                write!(f, ")")
+            }
+            Expression::Function { parameter, body } => {
+                write!(f, "(lambda ")?;
+                write_sexpr_parameter(f, parameter)?;
+                write!(f, " {})", Sexpr(body))
+            }
+            Expression::Application { function, argument } => {
+                write!(f, "(apply {} {})", Sexpr(function), Sexpr(argument))
+            }
+            Expression::LetIn { bindings, body } => {
+                write!(f, "(let (")?;
+                write_sexpr_bindings(f, bindings)?;
+                write!(f, ") {})", Sexpr(body))
+            }
+            Expression::With { scope, body } => write!(f, "(with {} {})", Sexpr(scope), Sexpr(body)),
+            Expression::If { condition, then_branch, else_branch } => write!(
+                f,
+                "(if {} {} {})",
+                Sexpr(condition),
+                Sexpr(then_branch),
+                Sexpr(else_branch)
+            ),
+            Expression::Assert { condition, body } => {
+                write!(f, "(assert {} {})", Sexpr(condition), Sexpr(body))
+            }
+            Expression::BinaryOp { op, left, right } => {
+                write!(f, "(binary {:?} {} {})", op, Sexpr(left), Sexpr(right))
+            }
+            Expression::UnaryOp { op, operand } => write!(f, "(unary {:?} {})", op, Sexpr(operand)),
+            Expression::Select { expr, path, default } => {
+                write!(f, "(select {} ", Sexpr(expr))?;
+                write_sexpr_keys(f, path)?;
+                if let Some(default) = default {
+                    write!(f, " {}", Sexpr(default))?;
+                }
+                write!(f, ")")
+            }
+            Expression::HasAttr { expr, path } => {
+                write!(f, "(has-attr {} ", Sexpr(expr))?;
+                write_sexpr_keys(f, path)?;
+                write!(f, ")")
+            }
+            Expression::Import { path } => write!(f, "(import {})", Sexpr(path)),
+            Expression::Inherit { source, attributes } => {
+                write!(f, "(inherit")?;
+                if let Some(source) = source {
+                    write!(f, " {}", Sexpr(source))?;
+                }
+                for attr in attributes {
+                    write!(f, " ")?;
+                    write_attr_name(f, attr)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Located { expr, .. } => write!(f, "{}", Sexpr(expr)),
+        }
+    }
+}
+
+/// Write the parts of an interpolated string or path, each preceded by a space
+fn write_sexpr_parts(f: &mut fmt::Formatter<'_>, parts: &[StringPart]) -> fmt::Result {
+    for part in parts {
+        match part {
+            StringPart::Literal(s) => write!(f, " {s:?}")?,
+            StringPart::Interpolation(expr) => write!(f, " {}", Sexpr(expr))?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the attributes of an attribute set, each preceded by a space
+fn write_sexpr_attributes(f: &mut fmt::Formatter<'_>, attributes: &[Attribute]) -> fmt::Result {
+    for attr in attributes {
+        if let Expression::Inherit { .. } = attr.value.unlocated() {
+            write!(f, " {}", Sexpr(&attr.value))?;
+            continue;
+        }
+        write!(f, " (= ")?;
+        write_sexpr_key(f, &attr.key)?;
+        write!(f, " {})", Sexpr(&attr.value))?;
+    }
+    Ok(())
+}
+
+fn write_sexpr_key(f: &mut fmt::Formatter<'_>, key: &AttrKey) -> fmt::Result {
+    match key {
+        AttrKey::Static(path) => write_attr_path(f, path),
+        AttrKey::Dynamic(key) => write!(f, "(dynamic {})", Sexpr(key)),
+    }
+}
+
+/// Write the keys of a selection or `?` path, separated by dots
+fn write_sexpr_keys(f: &mut fmt::Formatter<'_>, path: &[AttrKey]) -> fmt::Result {
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 {
+            write!(f, ".")?;
+        }
+        write_sexpr_key(f, key)?;
+    }
+    Ok(())
+}
+
+/// Write the bindings of a `let`, separated by spaces
+fn write_sexpr_bindings(f: &mut fmt::Formatter<'_>, bindings: &[Binding]) -> fmt::Result {
+    for (i, binding) in bindings.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        if binding.inherit {
+            write!(f, "{}", Sexpr(&binding.value))?;
+        } else {
+            write!(f, "(= ")?;
+            write_attr_name(f, &binding.name)?;
+            write!(f, " {})", Sexpr(&binding.value))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_sexpr_parameter(f: &mut fmt::Formatter<'_>, parameter: &Parameter) -> fmt::Result {
+    match parameter {
+        Parameter::Identifier(name) => write!(f, "{name}"),
+        Parameter::Pattern { fields, ellipsis, bind } => {
+            write!(f, "(pattern")?;
+            for field in fields {
+                match &field.default {
+                    Some(default) => write!(f, " ({} {})", field.name, Sexpr(default))?,
+                    None => write!(f, " {}", field.name)?,
+                }
+            }
+            if *ellipsis {
+                write!(f, " ...")?;
+            }
+            if let Some(bind) = bind {
+                write!(f, " @{bind}")?;
+            }
+            write!(f, ")")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn test_expression_creation() {
+        let expr = Expression::Integer(42);
+        assert_eq!(expr, Expression::Integer(42));
+        
+        let expr = Expression::String("hello".to_string());
+        assert_eq!(expr, Expression::String("hello".to_string()));
+    }
+    
+    #[test]
+    fn test_binary_op() {
+        let expr = Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(Expression::Integer(1)),
+            right: Box::new(Expression::Integer(2)),
+        };
+        
+        let children = expr.children();
+        assert_eq!(children.len(), 2);
+    }
+
+    #[allow(clippy::unnecessary_box_returns)]
+    fn ident(name: &str) -> Box<Expression> {
+        Box::new(Expression::Identifier(name.to_string()))
+    }
+
+    #[test]
+    fn test_convert_attribute_set() {
+        let expr = parse_expr("{ x = 1; }");
+        assert_eq!(expr, Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Static(path(&["x"])),
+                value: Expression::Integer(1),
+                location: None,
+            }],
+        });
+    }
+
+    #[test]
+    fn test_convert_nested_attribute_path() {
+        assert_eq!(parse_expr("{ a.b.c = 1; }"), Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Static(path(&["a", "b", "c"])),
+                value: Expression::Integer(1),
+                location: None,
+            }],
+        });
+    }
+
+    #[test]
+    fn test_constructors() {
+        let built = Expression::let_in(
+            [("pkgs", Expression::app(Expression::ident("fetch"), Expression::ident("nixpkgs")))],
+            Expression::with(
+                Expression::ident("pkgs"),
+                AttrSetBuilder::new()
+                    .recursive(true)
+                    .attr("name", Expression::string("hello"))
+                    .path(["meta", "priority"], Expression::binop(
+                        BinaryOperator::Add,
+                        Expression::int(1),
+                        Expression::select(Expression::ident("lib"), ["prio"]),
+                    ))
+                    .inherit(["stdenv"])
+                    .inherit_from(Expression::ident("lib"), ["licenses"])
+                    .attr("deps", Expression::list([Expression::lambda("x", Expression::ident("x"))]))
+                    .build(),
+            ),
+        );
+
+        let manual = Expression::LetIn {
+            bindings: vec![Binding {
+                name: "pkgs".to_string(),
+                value: Expression::Application { function: ident("fetch"), argument: ident("nixpkgs") },
+                inherit: false,
+                from: None,
+            }],
+            body: Box::new(Expression::With {
+                scope: ident("pkgs"),
+                body: Box::new(Expression::AttributeSet {
+                    recursive: true,
+                    attributes: vec![
+                        Attribute {
+                            key: AttrKey::Static(path(&["name"])),
+                            value: Expression::String("hello".to_string()),
+                            location: None,
+                        },
+                        Attribute {
+                            key: AttrKey::Static(path(&["meta", "priority"])),
+                            value: Expression::BinaryOp {
+                                op: BinaryOperator::Add,
+                                left: Box::new(Expression::Integer(1)),
+                                right: Box::new(Expression::Select {
+                                    expr: ident("lib"),
+                                    path: keys(&["prio"]),
+                                    default: None,
+                                }),
+                            },
+                            location: None,
+                        },
+                        Attribute {
+                            key: AttrKey::Static(path(&["stdenv"])),
+                            value: Expression::Inherit { source: None, attributes: path(&["stdenv"]) },
+                            location: None,
+                        },
+                        Attribute {
+                            key: AttrKey::Static(path(&["licenses"])),
+                            value: Expression::Inherit {
+                                source: Some(ident("lib")),
+                                attributes: path(&["licenses"]),
+                            },
+                            location: None,
+                        },
+                        Attribute {
+                            key: AttrKey::Static(path(&["deps"])),
+                            value: Expression::List(vec![Expression::Function {
+                                parameter: Parameter::Identifier("x".to_string()),
+                                body: ident("x"),
+                            }]),
+                            location: None,
+                        },
+                    ],
+                }),
+            }),
+        };
+        assert_eq!(built, manual);
+        assert_eq!(
+            built,
+            parse_expr(
+                "let pkgs = fetch nixpkgs; in with pkgs; rec { name = \"hello\"; meta.priority = 1 + lib.prio; \
+                 inherit stdenv; inherit (lib) licenses; deps = [ (x: x) ]; }"
+            )
+        );
+    }
+
+    #[test]
+    fn test_subexpressions() {
+        let expr = parse_expr("{ a ? 1 }: { ${a} = [ 2 3 ]; b = \"${c}\"; inherit (d) e; }").into_unlocated();
+        let texts = |expr: &Expression| -> Vec<String> {
+            expr.subexpressions().iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(texts(&expr), ["1", "{ ${a} = [ 2 3 ]; b = \"${c}\"; inherit (d) e; }"]);
+        let attrset = expr.subexpressions()[1].unlocated();
+        assert_eq!(attrset.subexpressions().len(), 4);
+        assert_eq!(texts(attrset.subexpressions()[2].unlocated()), ["c"]);
+        assert_eq!(texts(attrset.subexpressions()[3]), ["d"]);
+    }
+
+    #[test]
+    fn test_find_at_offset() {
+        let source = "{ x = foo + bar; }";
+        let expr = parse_expr(source);
+        let under = |offset: usize| -> Vec<String> {
+            expr.find_at_offset(offset).iter().map(|expr| expr.unlocated().to_string()).collect()
+        };
+
+        assert_eq!(under(7), [source, "foo + bar", "foo"]);
+        assert_eq!(expr.find_at_offset(7).last().unwrap().unlocated(), &Expression::ident("foo"));
+        // Boundaries: the end of `foo`, the start and end of `bar`
+        assert_eq!(under(9).last().unwrap(), "foo");
+        assert_eq!(under(12).last().unwrap(), "bar");
+        assert_eq!(under(15).last().unwrap(), "bar");
+        // Between the operands, and outside of any operand
+        assert_eq!(under(10).last().unwrap(), "foo + bar");
+        assert_eq!(under(0), [source]);
+        assert!(under(100).is_empty());
+
+        let expr = parse_expr("\"a${b.c}d\"");
+        let chain = expr.find_at_offset(4);
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[2].unlocated(), &Expression::ident("b"));
+
+        let unlocated = Expression::ident("x");
+        assert!(unlocated.find_at_offset(0).is_empty());
+    }
+
+    #[test]
+    fn test_convert_implication() {
+        let implies = |left, right| Expression::BinaryOp { op: BinaryOperator::Implies, left, right };
+        let and = |left, right| Expression::BinaryOp { op: BinaryOperator::And, left, right };
+
+        assert_eq!(parse_expr("a -> b"), implies(ident("a"), ident("b")));
+        assert_eq!(parse_expr("a && b -> c"), implies(Box::new(and(ident("a"), ident("b"))), ident("c")));
+        assert_eq!(parse_expr("a -> b -> c"), implies(ident("a"), Box::new(implies(ident("b"), ident("c")))));
+        assert_eq!(parse_expr("a -> b || c").to_string(), "a -> b || c");
+    }
+
+    #[test]
+    fn test_convert_with() {
+        assert_eq!(parse_expr("with a; b"), Expression::With { scope: ident("a"), body: ident("b") });
+        assert_eq!(parse_expr("with a; with b.c; d"), Expression::With {
+            scope: ident("a"),
+            body: Box::new(Expression::With {
+                scope: Box::new(Expression::Select { expr: ident("b"), path: keys(&["c"]), default: None }),
+                body: ident("d"),
+            }),
+        });
+
+        // A `with` without body is an error, not an identifier named "with"
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let result = parser.parse("with a;").unwrap();
+        assert!(result.has_errors());
+        assert!(result.expression().is_err());
+    }
+
+    #[test]
+    fn test_convert_quoted_attribute_path_segments() {
+        let Expression::AttributeSet { attributes, .. } =
+            parse_expr(r#"rec { a."b".c = 1; "x y".z = 2; }"#).into_unlocated()
+        else {
+            panic!("Expected attribute set");
+        };
+        assert_eq!(attributes[0].key, AttrKey::Static(path(&["a", "b", "c"])));
+        assert_eq!(attributes[1].key.static_path(), Some(&path(&["x y", "z"])[..]));
+    }
+
+    #[test]
+    fn test_convert_dynamic_attribute_keys() {
+        let expected = Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Dynamic(ident("k")),
+                value: Expression::Identifier("v".to_string()),
+                location: None,
+            }],
+        };
+        assert_eq!(parse_expr("{ ${k} = v; }"), expected);
+
+        assert_eq!(parse_expr(r#"{ "${k}" = v; }"#), Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Dynamic(Box::new(Expression::StringInterpolation {
+                    parts: vec![StringPart::Interpolation(ident("k"))],
+                })),
+                value: Expression::Identifier("v".to_string()),
+                location: None,
+            }],
+        });
+    }
+
+    #[test]
+    fn test_convert_mixed_dynamic_attribute_path_nests() {
+        // `a.${k}.c = 1` is `a = { ${k} = { c = 1; }; }`
+        assert_eq!(parse_expr("{ a.${k}.c = 1; }"), Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Static(path(&["a"])),
+                value: Expression::AttributeSet {
+                    recursive: false,
+                    attributes: vec![Attribute {
+                        key: AttrKey::Dynamic(ident("k")),
+                        value: Expression::AttributeSet {
+                            recursive: false,
+                            attributes: vec![Attribute {
+                                key: AttrKey::Static(path(&["c"])),
+                                value: Expression::Integer(1),
+                                location: None,
+                            }],
+                        },
+                        location: None,
+                    }],
+                },
+                location: None,
+            }],
+        });
+    }
+
+    #[test]
+    fn test_display_dynamic_attribute_keys() {
+        for source in ["{ ${k} = v; }", r#"{ "${k}" = v; }"#, "{ a.${f x}.c = 1; }"] {
+            let expr = parse_expr(source);
+            assert_eq!(parse_expr(&expr.to_string()), expr, "printed as: {expr}");
+        }
+        assert_eq!(parse_expr("{ ${k} = v; }").to_string(), "{ ${k} = v; }");
+    }
+
+    fn inherited(source: Option<Expression>, name: &str) -> Expression {
+        Expression::Inherit {
+            source: source.map(Box::new),
+            attributes: vec![name.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_convert_inherit_in_attribute_set() {
+        assert_eq!(parse_expr("{ inherit a b; inherit (x) c; }"), Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![
+                Attribute { key: AttrKey::Static(path(&["a"])), value: inherited(None, "a"), location: None },
+                Attribute { key: AttrKey::Static(path(&["b"])), value: inherited(None, "b"), location: None },
+                Attribute {
+                    key: AttrKey::Static(path(&["c"])),
+                    value: inherited(Some(*ident("x")), "c"),
+                    location: None,
+                },
+            ],
+        });
+    }
+
+    #[test]
+    fn test_convert_inherit_in_let() {
+        let expr = parse_expr("let inherit (pkgs) lib stdenv; inherit x; y = 1; in lib");
+        let Expression::LetIn { bindings, .. } = expr.unlocated() else {
+            panic!("Expected let expression");
+        };
+
+        let names: Vec<_> = bindings.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["lib", "stdenv", "x", "y"]);
+
+        assert!(bindings[0].inherit);
+        assert_eq!(bindings[0].from, Some(*ident("pkgs")));
+        assert_eq!(bindings[1].value, inherited(Some(*ident("pkgs")), "stdenv"));
+        assert!(bindings[2].inherit);
+        assert_eq!(bindings[2].from, None);
+        assert!(!bindings[3].inherit);
+    }
+
+    #[test]
+    fn test_convert_let_attribute_paths() {
+        let expr = parse_expr(r#"let "x y" = 1; a.b = 2; a.${k}.c = 3; in a"#);
+        let Expression::LetIn { bindings, .. } = expr.into_unlocated() else {
+            panic!("Expected let expression");
+        };
+
+        let names: Vec<_> = bindings.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["x y", "a"]);
+        assert_eq!(bindings[1].value, Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![
+                Attribute::new(AttrKey::Static(path(&["b"])), Expression::Integer(2)),
+                Attribute::new(AttrKey::Dynamic(ident("k")), Expression::AttributeSet {
+                    recursive: false,
+                    attributes: vec![Attribute::new(AttrKey::Static(path(&["c"])), Expression::Integer(3))],
+                }),
+            ],
+        });
+
+        for source in ["let ${k} = 1; in k", r#"let "${k}".a = 1; in k"#] {
+            let err = parse_expr_with(ParserConfig::default(), source).unwrap_err();
+            assert!(err.to_string().contains("dynamic attributes not allowed in let"), "{source}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_convert_inherit_node() {
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let result = parser.parse("{ inherit (x) a b; }").unwrap();
+        let attrset = result.tree().root_node().child_by_field_name("expression").unwrap();
+        let inherit = attrset.child_by_field_name("bindings").unwrap();
+
+        let expr = Expression::from_tree_sitter_node(inherit, result.source()).unwrap();
+        assert_eq!(expr, Expression::Inherit {
+            source: Some(ident("x")),
+            attributes: path(&["a", "b"]),
+        });
+    }
+
+    #[test]
+    fn test_display_inherit() {
+        for source in ["{ inherit a; inherit (x) b c; }", "let inherit (p) q; in q"] {
+            let expr = parse_expr(source);
+            assert_eq!(parse_expr(&expr.to_string()), expr, "printed as: {expr}");
+        }
+        assert_eq!(parse_expr("{ inherit (x) b; }").to_string(), "{ inherit (x) b; }");
+    }
+
+    #[test]
+    fn test_convert_application_is_left_associative() {
+        let expr = parse_expr("f g h");
+        assert_eq!(expr, Expression::Application {
+            function: Box::new(Expression::Application {
+                function: ident("f"),
+                argument: ident("g"),
+            }),
+            argument: ident("h"),
+        });
+    }
+
+    #[test]
+    fn test_convert_if_expression() {
+        let expr = parse_expr("if a then b else c");
+        assert_eq!(expr, Expression::If {
+            condition: ident("a"),
+            then_branch: ident("b"),
+            else_branch: ident("c"),
+        });
+    }
+
+    #[test]
+    fn test_convert_function_and_let() {
+        let expr = parse_expr("let y = 2; in { a, b ? 1, ... }@args: a + y");
+        let Expression::LetIn { bindings, body } = expr.into_unlocated() else {
+            panic!("Expected let expression");
+        };
+        assert_eq!(bindings[0].name, "y");
+        assert_eq!(bindings[0].value, Expression::Integer(2));
+
+        let Expression::Function { parameter, body } = body.into_unlocated() else {
+            panic!("Expected function");
+        };
+        assert_eq!(parameter, Parameter::Pattern {
+            fields: vec![
+                PatternField { name: "a".to_string(), default: None },
+                PatternField { name: "b".to_string(), default: Some(Expression::Integer(1)) },
+            ],
+            ellipsis: true,
+            bind: Some("args".to_string()),
+        });
+        assert_eq!(*body, Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: ident("a"),
+            right: ident("y"),
+        });
+    }
+
+    #[test]
+    fn test_convert_string_interpolation() {
+        let expr = parse_expr(r#""hello ${name}!""#);
+        assert_eq!(expr, Expression::StringInterpolation {
+            parts: vec![
+                StringPart::Literal("hello ".to_string()),
+                StringPart::Interpolation(ident("name")),
+                StringPart::Literal("!".to_string()),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_convert_list_elements() {
+        assert_eq!(parse_expr("[]"), Expression::List(vec![]));
+        assert_eq!(parse_expr("[ 1 ]"), Expression::List(vec![Expression::Integer(1)]));
+        assert_eq!(
+            parse_expr("[ 1 2 ]"),
+            Expression::List(vec![Expression::Integer(1), Expression::Integer(2)])
+        );
+        assert_eq!(parse_expr("[ (x+y) \"s\" { a = 1; } ]"), Expression::List(vec![
+            Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                left: ident("x"),
+                right: ident("y"),
+            },
+            Expression::String("s".to_string()),
+            Expression::AttributeSet {
+                recursive: false,
+                attributes: vec![Attribute {
+                    key: AttrKey::Static(path(&["a"])),
+                    value: Expression::Integer(1),
+                    location: None,
+                }],
+            },
+        ]));
+    }
+
+    #[test]
+    fn test_convert_list_keeps_parenthesized_application() {
+        assert_eq!(parse_expr("[ f (g x) ]"), Expression::List(vec![
+            Expression::Identifier("f".to_string()),
+            Expression::Application {
+                function: ident("g"),
+                argument: ident("x"),
+            },
+        ]));
+    }
+
+    #[test]
+    fn test_convert_unary_operators() {
+        assert_eq!(parse_expr("!true"), Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            operand: Box::new(Expression::Boolean(true)),
+        });
+        assert_eq!(parse_expr("-5"), Expression::UnaryOp {
+            op: UnaryOperator::Negate,
+            operand: Box::new(Expression::Integer(5)),
+        });
+        assert_eq!(parse_expr("-(a + b)"), Expression::UnaryOp {
+            op: UnaryOperator::Negate,
+            operand: Box::new(Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                left: ident("a"),
+                right: ident("b"),
+            }),
+        });
+    }
+
+    #[test]
+    fn test_convert_negation_binds_tighter_than_addition() {
+        assert_eq!(parse_expr("-a + b"), Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(Expression::UnaryOp {
+                op: UnaryOperator::Negate,
+                operand: ident("a"),
+            }),
+            right: ident("b"),
+        });
+    }
+
+    #[test]
+    fn test_convert_subtraction() {
+        use Expression as E;
+        let subtract = |left, right| E::binop(BinaryOperator::Subtract, left, right);
+
+        assert_eq!(parse_expr("a - b"), subtract(E::ident("a"), E::ident("b")));
+        assert_eq!(parse_expr("a -b"), parse_expr("a - b"));
+        assert_eq!(
+            parse_expr("1 - 2 - 3"),
+            subtract(subtract(E::Integer(1), E::Integer(2)), E::Integer(3)),
+        );
+        assert_eq!(
+            parse_expr("a - f x"),
+            subtract(E::ident("a"), E::app(E::ident("f"), E::ident("x"))),
+        );
+        assert_eq!(
+            parse_expr("a - -b"),
+            subtract(E::ident("a"), E::unop(UnaryOperator::Negate, E::ident("b"))),
+        );
+    }
+
+    #[test]
+    fn test_convert_subtraction_precedence() {
+        assert_eq!(parse_expr("a * b - c"), parse_expr("(a * b) - c"));
+        assert_eq!(parse_expr("a - b * c"), parse_expr("a - (b * c)"));
+        assert_eq!(parse_expr("a - b * c - d"), parse_expr("(a - (b * c)) - d"));
+        assert_eq!(parse_expr("x + a - b"), parse_expr("(x + a) - b"));
+        assert_eq!(parse_expr("a - b + c"), parse_expr("(a - b) + c"));
+        assert_eq!(parse_expr("a - b ? c"), parse_expr("a - (b ? c)"));
+        assert_eq!(parse_expr("a - b == c"), parse_expr("(a - b) == c"));
+    }
+
+    #[test]
+    fn test_convert_negation_binds_looser_than_application_and_select() {
+        use Expression as E;
+        let negate = |operand| E::unop(UnaryOperator::Negate, operand);
+
+        assert_eq!(parse_expr("-f x"), negate(E::app(E::ident("f"), E::ident("x"))));
+        assert_eq!(parse_expr("-x.y"), negate(E::select(E::ident("x"), ["y"])));
+        assert_eq!(parse_expr("-f x - 1"), parse_expr("(-(f x)) - 1"));
+        assert_eq!(parse_expr("-x ? y"), parse_expr("(-x) ? y"));
+    }
+
+    #[test]
+    fn test_convert_has_attr() {
+        assert_eq!(parse_expr("attrs ? key"), Expression::HasAttr {
+            expr: ident("attrs"),
+            path: keys(&["key"]),
+        });
+        assert_eq!(parse_expr("attrs ? a.b"), Expression::HasAttr {
+            expr: ident("attrs"),
+            path: keys(&["a", "b"]),
+        });
+        assert_eq!(parse_expr("attrs ? \"weird.key\""), Expression::HasAttr {
+            expr: ident("attrs"),
+            path: keys(&["weird.key"]),
+        });
+    }
+
+    #[test]
+    fn test_convert_parenthesized_has_attr_is_not_folded() {
+        assert_eq!(parse_expr("(attrs ? a).b"), Expression::Select {
+            expr: Box::new(Expression::HasAttr {
+                expr: ident("attrs"),
+                path: keys(&["a"]),
+            }),
+            path: keys(&["b"]),
+            default: None,
+        });
+    }
+
+    fn path(segments: &[&str]) -> Vec<String> {
+        segments.iter().map(|s| (*s).to_string()).collect()
+    }
+
+    fn keys(segments: &[&str]) -> Vec<AttrKey> {
+        vec![AttrKey::Static(path(segments))]
+    }
+
+    #[test]
+    fn test_convert_select_without_default() {
+        assert_eq!(parse_expr("a.b.c"), Expression::Select {
+            expr: ident("a"),
+            path: keys(&["b", "c"]),
+            default: None,
+        });
+    }
+
+    #[test]
+    fn test_convert_select_with_default() {
+        assert_eq!(parse_expr("a.b.c or 42"), Expression::Select {
+            expr: ident("a"),
+            path: keys(&["b", "c"]),
+            default: Some(Box::new(Expression::Integer(42))),
+        });
+    }
+
+    #[test]
+    fn test_convert_chained_select_defaults() {
+        // `a.b or c.d or e` is `a.b or (c.d or e)`
+        assert_eq!(parse_expr("a.b or c.d or e"), Expression::Select {
+            expr: ident("a"),
+            path: keys(&["b"]),
+            default: Some(Box::new(Expression::Select {
+                expr: ident("c"),
+                path: keys(&["d"]),
+                default: Some(ident("e")),
+            })),
+        });
+    }
+
+    #[test]
+    fn test_convert_select_default_levels() {
+        use Expression as E;
+
+        assert_eq!(
+            parse_expr("a.b.c or d.e or f"),
+            E::select_or(E::ident("a"), ["b", "c"], E::select_or(E::ident("d"), ["e"], E::ident("f")))
+        );
+        // A parenthesized selection keeps its default at the inner level
+        assert_eq!(
+            parse_expr("(a.b or c).d or e"),
+            E::select_or(E::select_or(E::ident("a"), ["b"], E::ident("c")), ["d"], E::ident("e"))
+        );
+        assert_eq!(
+            parse_expr("a.b or (c.d or e).f"),
+            E::select_or(E::ident("a"), ["b"], E::select(E::select_or(E::ident("c"), ["d"], E::ident("e")), ["f"]))
+        );
+    }
+
+    #[test]
+    fn test_convert_dynamic_selection() {
+        assert_eq!(parse_expr("x.${y}"), Expression::Select {
+            expr: ident("x"),
+            path: vec![AttrKey::Dynamic(ident("y"))],
+            default: None,
+        });
+        assert_eq!(parse_expr("x ? ${y}"), Expression::HasAttr {
+            expr: ident("x"),
+            path: vec![AttrKey::Dynamic(ident("y"))],
+        });
+        assert_eq!(parse_expr(r#"x.a."${b}".c or d"#), Expression::Select {
+            expr: ident("x"),
+            path: vec![
+                AttrKey::Static(path(&["a"])),
+                AttrKey::Dynamic(Box::new(Expression::StringInterpolation {
+                    parts: vec![StringPart::Interpolation(ident("b"))],
+                })),
+                AttrKey::Static(path(&["c"])),
+            ],
+            default: Some(ident("d")),
+        });
+    }
+
+    #[test]
+    fn test_display_dynamic_selection() {
+        for source in ["x.${y}", "x ? ${y}", r#"x.a."${b}".c or d"#, "x.${f y}.z"] {
+            let expr = parse_expr(source);
+            assert_eq!(parse_expr(&expr.to_string()), expr, "printed as: {expr}");
+        }
+        assert_eq!(parse_expr("x.${y}").to_string(), "x.${y}");
+        assert_eq!(parse_expr("x.${y}").to_sexpr(), "(select (identifier x) (dynamic (identifier y)))");
+    }
+
+    #[test]
+    fn test_convert_select_default_is_a_selection() {
+        assert_eq!(parse_expr("a.b or c.d"), Expression::Select {
+            expr: ident("a"),
+            path: keys(&["b"]),
+            default: Some(Box::new(Expression::Select {
+                expr: ident("c"),
+                path: keys(&["d"]),
+                default: None,
+            })),
+        });
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let source = r#"
+            let
+              lib = import <nixpkgs>;
+              double = x: x * 2;
+            in
+            { pkgs, enable ? false, ... }@args:
+            rec {
+              name = "demo-${args.version or version}";
+              deps = [ pkgs.hello (double 2) (-1) ] ++ lib.optional enable pkgs.git;
+              nested = (a.b).c;
+              check = !(x ? y) && (1 + (2 + 3)) * 4 == 4 || -x.y < 0;
+              merged = { a = 1; } // { b = if enable then 1.5 else null; };
+              body = with lib; assert enable || true; f (g x) (y: y);
+            }
+        "#;
+        let expr = parse_expr(source);
+        let printed = expr.to_string();
+        assert_eq!(parse_expr(&printed), expr, "printed as: {printed}");
+    }
+
+    #[test]
+    fn test_display_dollar_signs() {
+        let interpolated = Expression::StringInterpolation {
+            parts: vec![
+                StringPart::Literal("costs $5, $".to_string()),
+                StringPart::Interpolation(Box::new(Expression::ident("x"))),
+                StringPart::Literal("${y}".to_string()),
+            ],
+        };
+        assert_eq!(interpolated.to_string(), r#""costs $5, \$${x}\${y}""#);
+        assert_eq!(parse_expr(&interpolated.to_string()), interpolated);
+        assert_eq!(Expression::string("$5").to_string(), r#""$5""#);
+    }
+
+    #[test]
+    fn test_display_parenthesizes_by_precedence() {
+        let add = |left, right| Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        let mul = Expression::BinaryOp {
+            op: BinaryOperator::Multiply,
+            left: Box::new(add(Expression::Integer(1), Expression::Integer(2))),
+            right: Box::new(Expression::Integer(3)),
+        };
+        assert_eq!(mul.to_string(), "(1 + 2) * 3");
+
+        let right_nested = add(
+            Expression::Integer(1),
+            add(Expression::Integer(2), Expression::Integer(3)),
+        );
+        assert_eq!(right_nested.to_string(), "1 + (2 + 3)");
+
+        let app = Expression::Application {
+            function: ident("f"),
+            argument: Box::new(Expression::Application {
+                function: ident("g"),
+                argument: Box::new(Expression::Integer(-1)),
+            }),
+        };
+        assert_eq!(app.to_string(), "f (g (-1))");
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let a = parse_expr("let x = { a = 1.5; b = [ ./p \"s${y}\" ]; }; in x.a or null");
+        let b = parse_expr("let\n  x = {\n    a = 1.5;\n    b = [ ./p \"s${y}\" ];\n  };\nin\nx.a or null");
+        assert!(a.location().is_some());
+        assert_eq!(a, b);
+        assert_eq!(a.structural_hash(), b.structural_hash());
+        assert_eq!(a.structural_hash(), a.clone().into_unlocated().structural_hash());
+        assert_eq!(Expression::Float(0.0).structural_hash(), Expression::Float(-0.0).structural_hash());
+
+        let distinct = ["1", "1.0", "\"1\"", "x", "[ 1 ]", "{ x = 1; }", "rec { x = 1; }", "x: 1", "f 1", "1 + 2", "2 + 1", "1 - 2"];
+        let hashes: HashSet<u64> = distinct.iter().map(|source| parse_expr(source).structural_hash()).collect();
+        assert_eq!(hashes.len(), distinct.len());
+
+        // Expressions can be used as map keys, for example to count repeats
+        let mut counts: HashMap<Expression, usize> = HashMap::new();
+        for element in [parse_expr("a.b"), parse_expr("c"), parse_expr("a.b")] {
+            *counts.entry(element).or_default() += 1;
+        }
+        assert_eq!(counts[&parse_expr("a.b")], 2);
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_to_sexpr() {
+        let cases = [
+            ("1 + 2", "(binary Add (integer 1) (integer 2))"),
+            ("-x.y or 2.5", "(unary Negate (select (identifier x) y (float 2.5)))"),
+            (r#"[ null true "a\"b" ./c ]"#, r#"(list (null) (bool true) (string "a\"b") (path relative "./c"))"#),
+            (r#""v${toString n}s""#, r#"(interpolation "v" (apply (identifier toString) (identifier n)) "s")"#),
+            (
+                r#"rec { a.b = 1; inherit (p) c; "${k}" = {}; }"#,
+                "(rec-attrs (= a.b (integer 1)) (inherit (identifier p) c) (= (dynamic (interpolation (identifier k))) (attrs)))",
+            ),
+            (
+                "{ a, b ? 1, ... }@args: if a ? x then import b else throw args",
+                "(lambda (pattern a (b (integer 1)) ... @args) (if (has-attr (identifier a) x) (import (identifier b)) (apply (identifier throw) (identifier args))))",
+            ),
+            (
+                "let inherit a; f = x: x; in with f; assert !a; f a",
+                "(let ((inherit a) (= f (lambda x (identifier x)))) (with (identifier f) (assert (unary Not (identifier a)) (apply (identifier f) (identifier a)))))",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expr(source).to_sexpr(), expected, "rendering {source}");
+        }
+    }
+
+    #[test]
+    fn test_display_quotes_attribute_names() {
+        let expr = Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![
+                Attribute {
+                    key: AttrKey::Static(path(&["a", "b-c"])),
+                    value: Expression::Integer(1),
+                    location: None,
+                },
+                Attribute {
+                    key: AttrKey::Static(path(&["with space"])),
+                    value: Expression::Float(2.0),
+                    location: None,
+                },
+                Attribute {
+                    key: AttrKey::Static(path(&["in"])),
+                    value: Expression::Null,
+                    location: None,
+                },
+            ],
+        };
+        assert_eq!(expr.to_string(), r#"{ a.b-c = 1; "with space" = 2.0; "in" = null; }"#);
+    }
+
+    #[test]
+    fn test_expression_locations() {
+        let expr = parse_expr("1 + 2");
+        let location = expr.location().unwrap();
+        assert_eq!((location.start_byte, location.end_byte), (0, 5));
+
+        let Expression::BinaryOp { left, right, .. } = expr.unlocated() else {
+            panic!("Expected binary operation");
+        };
+        let left = left.location().unwrap();
+        assert_eq!((left.start_byte, left.end_byte), (0, 1));
+        let right = right.location().unwrap();
+        assert_eq!((right.start_byte, right.end_byte), (4, 5));
+        assert_eq!((right.line, right.column), (1, 5));
+    }
+
+    #[test]
+    fn test_parenthesized_location_is_inner_expression() {
+        let expr = parse_expr("(x)");
+        assert!(matches!(expr, Expression::Located { .. }));
+        assert_eq!(expr.location().unwrap().start_byte, 1);
+        assert!(matches!(expr.unlocated(), Expression::Identifier(_)));
+    }
+
+    #[test]
+    fn test_locations_can_be_disabled() {
+        let config = ParserConfig::builder().include_locations(false).build();
+        let mut parser = crate::parser::NixParser::with_config(config).unwrap();
+        let expr = parser.parse("[ 1 ]").unwrap().expression().unwrap().unwrap();
+        assert_eq!(expr.location(), None);
+        let Expression::List(items) = &expr else {
+            panic!("Expected list");
+        };
+        assert_eq!(items[0].location(), None);
+    }
+
+    fn parse_expr_with(config: ParserConfig, source: &str) -> Result<Expression> {
+        let mut parser = crate::parser::NixParser::with_config(config).unwrap();
+        parser.parse(source)?.expression().map(Option::unwrap)
+    }
+
+    #[test]
+    fn test_convert_float_scientific_notation() {
+        assert_eq!(parse_expr("1.5e10"), Expression::Float(1.5e10));
+        assert_eq!(parse_expr("2E-3"), Expression::Float(2e-3));
+        assert_eq!(parse_expr("0.25"), Expression::Float(0.25));
+    }
+
+    #[test]
+    fn test_convert_float_scientific_notation_is_version_gated() {
+        use crate::parser::LanguageVersion;
+
+        for version in [LanguageVersion::Nix23, LanguageVersion::Nix24] {
+            let config = ParserConfig::builder().language_version(version).build();
+            let err = parse_expr_with(config, "1.5e10").unwrap_err();
+            assert!(matches!(err, ParseError::FeatureNotSupported { .. }), "{err:?}");
+
+            // Plain floats are still accepted
+            let config = ParserConfig::builder().language_version(version).build();
+            assert_eq!(parse_expr_with(config, "1.5").unwrap(), Expression::Float(1.5));
+        }
+
+        let config = ParserConfig::builder().language_version(LanguageVersion::Nix218).build();
+        assert_eq!(parse_expr_with(config, "1.5e10").unwrap(), Expression::Float(1.5e10));
+    }
+
+    #[test]
+    fn test_convert_float_out_of_range() {
+        let err = parse_expr_with(ParserConfig::default(), "1.0e999").unwrap_err();
+        assert!(matches!(err, ParseError::ParseFailed(ref msg) if msg.contains("1.0e999")));
+    }
+
+    #[test]
+    fn test_convert_uri() {
+        let url = "github:NixOS/nixpkgs/nixos-unstable";
+        assert_eq!(parse_expr(url), Expression::Uri(url.to_string()));
+        assert_eq!(parse_expr(url).to_string(), url);
+
+        let expr = parse_expr("{ inputs.nixpkgs.url = https://example.com/foo.tar.gz; }");
+        assert_eq!(expr, Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Static(path(&["inputs", "nixpkgs", "url"])),
+                value: Expression::Uri("https://example.com/foo.tar.gz".to_string()),
+                location: None,
+            }],
+        });
+    }
+
+    #[test]
+    fn test_convert_uri_is_version_gated() {
+        use crate::parser::LanguageVersion;
+
+        let config = ParserConfig::builder()
+            .language_version(LanguageVersion::Experimental)
+            .build();
+        let err = parse_expr_with(config, "https://example.com").unwrap_err();
+        assert!(matches!(err, ParseError::FeatureNotSupported { suggestion: Some(_), .. }));
+
+        let config = ParserConfig::builder().language_version(LanguageVersion::Nix23).build();
+        assert!(parse_expr_with(config, "https://example.com").is_ok());
+    }
+
+    #[test]
+    fn test_convert_path_kinds() {
+        let cases = [
+            ("./x", PathType::Relative("./x".to_string())),
+            ("/abs", PathType::Absolute("/abs".to_string())),
+            ("~/home", PathType::Home("~/home".to_string())),
+            ("<nixpkgs>", PathType::Search("nixpkgs".to_string())),
+        ];
+        for (source, expected) in cases {
+            let expr = parse_expr(source);
+            assert_eq!(expr, Expression::Path(expected));
+            assert_eq!(expr.to_string(), source);
+        }
+    }
+
+    #[test]
+    fn test_display_interpolated_path() {
+        let expr = Expression::Path(PathType::Interpolated(vec![
+            StringPart::Literal("./a/".to_string()),
+            StringPart::Interpolation(ident("b")),
+            StringPart::Literal("/c".to_string()),
+        ]));
+        assert_eq!(expr.to_string(), "./a/${b}/c");
+    }
+
+    #[test]
+    fn test_convert_interpolated_path() {
+        assert_eq!(parse_expr("./a/${b}/c"), Expression::Path(PathType::Interpolated(vec![
+            StringPart::Literal("./a/".to_string()),
+            StringPart::Interpolation(ident("b")),
+            StringPart::Literal("/c".to_string()),
+        ])));
+
+        for source in [
+            "/${a}",
+            "~/${a}.nix",
+            "../a${b}${c}d",
+            "./${\"a\"}/${b + \"/c\"}",
+            "import ./a/${b}.nix { }",
+            "[ ./a/${b} ]",
+            "{ a = ./a/${b}; }",
+            "\"${./a/${b}}\"",
+            "a: ./a/${a}/b.nix",
+        ] {
+            let expr = parse_expr(source);
+            assert_eq!(expr.to_string(), source);
+            assert_eq!(parse_expr(&expr.to_string()), expr);
+        }
+    }
+
+    #[test]
+    fn test_convert_string_scanner_contexts() {
+        // Whitespace and comments inside strings are content, and code inside
+        // interpolations skips them
+        assert_eq!(parse_expr("\"# a /* b */\""), Expression::String("# a /* b */".to_string()));
+        assert_eq!(parse_expr("\"$\""), Expression::String("$".to_string()));
+        assert_eq!(parse_expr("\"a$\""), Expression::String("a$".to_string()));
+        assert_eq!(
+            parse_expr("\"a${ \"b${ c /* d */ }\" }\""),
+            Expression::StringInterpolation {
+                parts: vec![
+                    StringPart::Literal("a".to_string()),
+                    StringPart::Interpolation(Box::new(Expression::StringInterpolation {
+                        parts: vec![
+                            StringPart::Literal("b".to_string()),
+                            StringPart::Interpolation(ident("c")),
+                        ],
+                    })),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_convert_indented_string_strips_indentation() {
+        // Example from the Nix manual
+        let source = concat!(
+            "''\n",
+            "  This is the first line.\n",
+            "  This is the second line.\n",
+            "    This is the third line.\n",
+            "''",
+        );
+        assert_eq!(parse_expr(source), Expression::String(
+            "This is the first line.\nThis is the second line.\n  This is the third line.\n"
+                .to_string()
+        ));
+
+        assert_eq!(parse_expr("''x''"), Expression::String("x".to_string()));
+        // Blank lines don't count towards the indentation, and tabs are not indentation
+        assert_eq!(
+            parse_expr("''\n    a\n\n      b\n  ''"),
+            Expression::String("a\n\n  b\n".to_string())
+        );
+        assert_eq!(
+            parse_expr("''\n  \ta\n  b''"),
+            Expression::String("\ta\nb".to_string())
+        );
+    }
+
+    #[test]
+    fn test_convert_indented_string_first_line() {
+        // Only a first line made of spaces belongs to the opening quotes
+        assert_eq!(parse_expr("''   \n  a\n''"), Expression::String("a\n".to_string()));
+        assert_eq!(parse_expr("''a\n  b\n''"), Expression::String("a\n  b\n".to_string()));
+    }
+
+    #[test]
+    fn test_convert_indented_string_with_interpolation() {
+        assert_eq!(parse_expr("''\n  ${x}\n    y\n''"), Expression::StringInterpolation {
+            parts: vec![
+                StringPart::Interpolation(ident("x")),
+                StringPart::Literal("\n  y\n".to_string()),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_convert_raw_indented_string() {
+        let config = ParserConfig::builder().raw_indented_strings(true).build();
+        assert_eq!(
+            parse_expr_with(config, "''\n  foo\n  bar\n''").unwrap(),
+            Expression::String("\n  foo\n  bar\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_convert_string_escapes() {
+        assert_eq!(parse_expr(r#""tab\there""#), Expression::String("tab\there".to_string()));
+        assert_eq!(
+            parse_expr(r#""a\nb\r\\ \" \${x}""#),
+            Expression::String("a\nb\r\\ \" ${x}".to_string())
+        );
+        assert_eq!(parse_expr(r#""\"${x}\"""#), Expression::StringInterpolation {
+            parts: vec![
+                StringPart::Literal("\"".to_string()),
+                StringPart::Interpolation(ident("x")),
+                StringPart::Literal("\"".to_string()),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_convert_invalid_string_escape() {
+        let err = parse_expr_with(ParserConfig::default(), r#""\x41""#).unwrap_err();
+        assert!(matches!(err, ParseError::ParseFailed(ref msg) if msg.contains("\\x")), "{err:?}");
+    }
+
+    #[test]
+    fn test_convert_indented_string_escapes() {
+        assert_eq!(
+            parse_expr(r"''a''' ''${x} ''\t''"),
+            Expression::String("a'' ${x} \t".to_string())
+        );
+        // Escapes are not indentation
+        assert_eq!(
+            parse_expr("''\n  ''\\tx\n    y\n''"),
+            Expression::String("\tx\n  y\n".to_string())
+        );
+
+        let config = ParserConfig::builder().raw_indented_strings(true).build();
+        assert_eq!(
+            parse_expr_with(config, "''a'''b''").unwrap(),
+            Expression::String("a'''b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_convert_nesting_depth_limit() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let config = ParserConfig::builder().max_nesting_depth(Some(50)).build();
+
+        assert_eq!(parse_expr_with(config.clone(), &nested(10)).unwrap(), Expression::Integer(1));
+
+        // Input far deeper than the limit is rejected rather than overflowing the stack
+        let err = parse_expr_with(config, &nested(20_000)).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::ResourceLimitExceeded { ref resource, ref limit }
+                if resource == "nesting_depth" && limit == "50"
+        ), "{err:?}");
+    }
+
+    #[test]
+    fn test_convert_unknown_node_type() {
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let result = parser.parse("if true then").unwrap();
+        let root = result.tree().root_node();
+
+        let config = ParserConfig::builder()
+            .recovery_strategy(crate::error::RecoveryStrategy::FailFast)
+            .build();
+        let err = Expression::from_tree_sitter_node_with_config(root, result.source(), &config).unwrap_err();
+        assert!(matches!(&err, ParseError::UnknownNodeType(kind) if kind == "ERROR"), "{err:?}");
+
+        // Other strategies recover what they can from the `ERROR` node
+        let expr = Expression::from_tree_sitter_node(root, result.source()).unwrap();
+        assert_eq!(expr, Expression::Boolean(true));
+    }
+
+    #[test]
+    fn test_source_location_union() {
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let result = parser.parse("{\n  a = 1;\n  b = 2;\n}").unwrap();
+        let attrset = result.tree().root_node().child(0).unwrap();
+        let location = |index| SourceLocation::from_tree_sitter_node(&attrset.named_child(index).unwrap());
+        let (a, b) = (location(0), location(1));
+
+        // Disjoint locations are joined with the gap between them
+        let both = a.union(&b);
+        assert_eq!(both, b.union(&a));
+        assert_eq!((both.start_byte, both.end_byte), (4, 19));
+        assert_eq!((both.start_position, both.end_position), ((1, 2), (2, 8)));
+        assert_eq!((both.line, both.column), (2, 3));
+
+        // Overlapping and adjacent locations
+        let whole = SourceLocation::from_tree_sitter_node(&attrset);
+        assert_eq!(whole.union(&a), whole);
+        assert_eq!(a.union(&a), a);
+        let first = SourceLocation::new(1, 1, 0, 4);
+        assert_eq!(first.union(&a).start_byte, 0);
+        assert_eq!(first.union(&a).end_byte, a.end_byte);
+    }
+}
@@ -1,41 +1,200 @@
 //! Caching infrastructure for parse results
 
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use std::num::NonZeroUsize;
 
-use crate::parser::ParseResult;
+use crate::error::{ParseError, Result};
+use crate::parser::{ParseResult, ParseResultJson};
+
+/// Capacity used when a cache is created with a capacity of 0
+const DEFAULT_CAPACITY: NonZeroUsize = NonZeroUsize::new(100).unwrap();
 
 /// Cache for storing parse results
+///
+/// Results are kept in an in-memory LRU cache keyed by a 64-bit hash of
+/// the source, and a hit is confirmed by comparing the source with the one
+/// the cached result holds, so a hash collision is a miss. A cache
+/// created with [`ParseCache::with_disk`] also writes the serializable view
+/// of every result to a directory, so later processes can reuse it through
+/// [`ParseCache::get_view`]. Results of parsing files can also be cached
+/// by path with [`ParseCache::insert_for_file`], and are dropped once the
+/// file changes.
 pub struct ParseCache {
-    cache: Arc<Mutex<LruCache<String, ParseResult>>>,
+    /// Results by source hash
+    cache: Arc<Mutex<LruCache<u64, ParseResult>>>,
+    files: Arc<Mutex<LruCache<PathBuf, (FileStamp, ParseResult)>>>,
+    disk: Option<DiskCache>,
+    counters: CacheCounters,
 }
 
 impl ParseCache {
     /// Create a new cache with the specified capacity
     pub fn new(capacity: usize) -> Self {
-        let cache = LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(100).unwrap()));
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(DEFAULT_CAPACITY);
         Self {
-            cache: Arc::new(Mutex::new(cache)),
+            cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+            files: Arc::new(Mutex::new(LruCache::new(capacity))),
+            disk: None,
+            counters: CacheCounters::default(),
         }
     }
-    
+
+    /// Create a cache that also persists results under `path`
+    ///
+    /// `capacity` bounds the in-memory layer only; the directory is created
+    /// if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if the directory cannot be created.
+    pub fn with_disk(path: impl Into<PathBuf>, capacity: usize) -> Result<Self> {
+        let dir = path.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| ParseError::IoError(format!("{}: {}", dir.display(), e)))?;
+
+        Ok(Self {
+            disk: Some(DiskCache { dir }),
+            ..Self::new(capacity)
+        })
+    }
+
     /// Get a cached parse result
-    pub fn get(&self, key: &str) -> Option<ParseResult> {
-        let mut cache = self.cache.lock().unwrap();
-        cache.get(key).cloned()
+    ///
+    /// Only the in-memory layer holds full results (a syntax tree cannot be
+    /// stored on disk); use [`ParseCache::get_view`] to also consult the disk.
+    pub fn get(&self, source: &str) -> Option<ParseResult> {
+        let result = self.get_from_memory(source);
+        self.counters.record_lookup(result.is_some());
+        result
     }
-    
+
+    /// Get the serializable view of the cached result for `source`
+    ///
+    /// Falls back to the disk layer when the result is not in memory. A
+    /// missing, unreadable or corrupt cache file counts as a miss.
+    pub fn get_view(&self, source: &str) -> Option<ParseResultJson> {
+        let view = match self.get_from_memory(source) {
+            Some(result) => result.to_json_view().ok(),
+            None => self.disk.as_ref().and_then(|disk| disk.read(source)),
+        };
+        self.counters.record_lookup(view.is_some());
+        view
+    }
+
     /// Insert a parse result into the cache
-    pub fn insert(&self, key: String, value: ParseResult) {
+    ///
+    /// With a disk layer, the result's view is written to disk as well.
+    /// Writing is best-effort: results whose AST cannot be built, and I/O
+    /// failures, are only cached in memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the cache's lock.
+    pub fn insert(&self, source: &str, value: ParseResult) {
+        if let Some(disk) = &self.disk
+            && let Ok(view) = value.to_json_view()
+        {
+            disk.write(source, view);
+        }
+
+        let key = content_hash(source.as_bytes());
         let mut cache = self.cache.lock().unwrap();
-        cache.put(key, value);
+        let replacing = cache.contains(&key);
+        let evicted = cache.push(key, value).is_some() && !replacing;
+        self.counters.record_insertion(evicted);
+    }
+
+    /// Get the cached result of parsing the file at `path`
+    ///
+    /// Returns `None`, forcing a re-parse, if the file's modification time,
+    /// size or contents differ from when the result was inserted, or if the
+    /// file can no longer be read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the cache's lock.
+    pub fn get_for_file(&self, path: impl AsRef<Path>) -> Option<ParseResult> {
+        let path = path.as_ref();
+        let mut files = self.files.lock().unwrap();
+        let result = match files.get(path) {
+            Some((stamp, result)) if FileStamp::read(path).as_ref() == Some(stamp) => Some(result.clone()),
+            Some(_) => {
+                files.pop(path);
+                None
+            }
+            None => None,
+        };
+        self.counters.record_lookup(result.is_some());
+        result
     }
-    
+
+    /// Cache the result of parsing the file at `path`
+    ///
+    /// The file's current modification time, size and content hash are
+    /// recorded, so the result should come from its current contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if the file cannot be read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the cache's lock.
+    pub fn insert_for_file(&self, path: impl Into<PathBuf>, value: ParseResult) -> Result<()> {
+        let path = path.into();
+        let stamp = FileStamp::read(&path)
+            .ok_or_else(|| ParseError::IoError(format!("{}: cannot read file", path.display())))?;
+
+        let mut files = self.files.lock().unwrap();
+        let replacing = files.contains(&path);
+        let evicted = files.push(path, (stamp, value)).is_some() && !replacing;
+        self.counters.record_insertion(evicted);
+        Ok(())
+    }
+
     /// Clear the cache
+    ///
+    /// Entries already written to disk are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while holding the cache's lock.
     pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+        self.files.lock().unwrap().clear();
+    }
+
+    /// Get the directory of the disk layer, if there is one
+    pub fn disk_path(&self) -> Option<&Path> {
+        self.disk.as_ref().map(|disk| disk.dir.as_path())
+    }
+
+    /// Get hit, miss, insertion and eviction counts since the cache was created
+    ///
+    /// Lookups through `get`, `get_view` and `get_for_file` and insertions
+    /// through `insert` and `insert_for_file` are all counted.
+    pub fn stats(&self) -> CacheStats {
+        self.counters.snapshot()
+    }
+
+    /// Reset all statistics to zero
+    pub fn reset_stats(&self) {
+        self.counters.reset();
+    }
+
+    fn get_from_memory(&self, source: &str) -> Option<ParseResult> {
         let mut cache = self.cache.lock().unwrap();
-        cache.clear();
+        cache
+            .get(&content_hash(source.as_bytes()))
+            .filter(|result| result.source() == source)
+            .cloned()
     }
 }
 
@@ -43,4 +202,296 @@ impl Default for ParseCache {
     fn default() -> Self {
         Self::new(100)
     }
-}
\ No newline at end of file
+}
+
+/// Snapshot of a cache's effectiveness
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a result
+    pub hits: u64,
+    /// Lookups that found nothing, or only a stale result
+    pub misses: u64,
+    /// Results inserted
+    pub insertions: u64,
+    /// Results dropped to make room for newer ones
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Get the fraction of lookups that were hits, or 0 without lookups
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Lock-free counters behind `CacheStats`
+///
+/// The counters are independent, so relaxed ordering is enough; a snapshot
+/// taken during concurrent use may be off by the operations in flight.
+#[derive(Debug, Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl CacheCounters {
+    fn record_lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_insertion(&self, evicted: bool) {
+        self.insertions.fetch_add(1, Ordering::Relaxed);
+        if evicted {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [&self.hits, &self.misses, &self.insertions, &self.evictions] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// What a file looked like when its parse result was cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    /// Stamp the file at `path` as it is now
+    ///
+    /// The content hash catches edits that keep the size and land within
+    /// the file system's timestamp granularity.
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let contents = fs::read(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            hash: content_hash(&contents),
+        })
+    }
+}
+
+/// Directory of parse result views, one JSON file per source
+struct DiskCache {
+    dir: PathBuf,
+}
+
+/// Contents of a disk cache file
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    /// The parsed source, since files are only named by its hash
+    source: String,
+    result: ParseResultJson,
+}
+
+impl DiskCache {
+    fn entry_path(&self, source: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", content_hash(source.as_bytes())))
+    }
+
+    fn read(&self, source: &str) -> Option<ParseResultJson> {
+        let path = self.entry_path(source);
+        let bytes = fs::read(&path).ok()?;
+        match serde_json::from_slice::<DiskEntry>(&bytes) {
+            Ok(entry) if entry.source == source
+                && entry.result.version == ParseResultJson::VERSION => Some(entry.result),
+            // Stale or corrupt; the next insert overwrites it
+            _ => None,
+        }
+    }
+
+    fn write(&self, source: &str, result: ParseResultJson) {
+        let path = self.entry_path(source);
+        let entry = DiskEntry { source: source.to_string(), result };
+        let Ok(json) = serde_json::to_vec(&entry) else {
+            return;
+        };
+
+        // Write then rename, so readers never see a partially written file
+        let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        let written = fs::File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(&json))
+            .and_then(|()| fs::rename(&tmp_path, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+    }
+}
+
+/// Hash content with 64-bit FNV-1a
+///
+/// Unlike `DefaultHasher`, the result is stable across processes and Rust
+/// versions, so it can name files on disk.
+fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    fn parse(source: &str) -> ParseResult {
+        NixParser::new().unwrap().parse(source).unwrap()
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = ParseCache::new(1);
+        cache.insert("1", parse("1"));
+        assert!(cache.get("1").is_some());
+        assert!(cache.get_view("1").is_some());
+        assert!(cache.disk_path().is_none());
+
+        // Capacity 1 evicts the first entry
+        cache.insert("2", parse("2"));
+        assert!(cache.get("1").is_none());
+        assert!(cache.get_view("1").is_none());
+    }
+
+    #[test]
+    fn test_entries_keyed_by_source_hash() {
+        let cache = ParseCache::new(4);
+        let source = String::from("{ a = 1; }");
+        cache.insert(&source, parse(&source));
+
+        // An equal source allocated separately hits the same entry
+        let copy = ["{ a = ", "1; }"].concat();
+        assert_eq!(cache.get(&copy).unwrap().source(), "{ a = 1; }");
+        assert!(cache.get("{ a = 2; }").is_none());
+
+        // A different source under the same hash is a miss, even of the same length
+        let key = content_hash(b"{ b = 1; }");
+        cache.cache.lock().unwrap().push(key, parse("{ b = 2; }"));
+        assert!(cache.get("{ b = 1; }").is_none());
+        assert!(cache.get_view("{ b = 1; }").is_none());
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let cache = ParseCache::new(2);
+        assert_eq!(cache.stats(), CacheStats::default());
+        assert!(cache.stats().hit_rate().abs() < f64::EPSILON);
+
+        assert!(cache.get("1").is_none());
+        cache.insert("1", parse("1"));
+        cache.insert("2", parse("2"));
+        assert!(cache.get("1").is_some());
+        // Replacing an entry is not an eviction, but pushing out "2" is
+        cache.insert("1", parse("1"));
+        cache.insert("3", parse("3"));
+        assert!(cache.get("2").is_none());
+        assert!(cache.get_view("3").is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats, CacheStats { hits: 2, misses: 2, insertions: 4, evictions: 1 });
+        assert!((stats.hit_rate() - 0.5).abs() < f64::EPSILON);
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn test_disk_cache_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = "{ x = 1.5; y = \"a${b}\"; }";
+        let result = parse(source);
+
+        let cache = ParseCache::with_disk(dir.path(), 10).unwrap();
+        assert!(cache.get_view(source).is_none());
+        cache.insert(source, result.clone());
+
+        // A fresh cache over the same directory, as in a new process
+        let cache = ParseCache::with_disk(dir.path(), 10).unwrap();
+        assert!(cache.get(source).is_none());
+        let view = cache.get_view(source).unwrap();
+        assert_eq!(view, result.to_json_view().unwrap());
+        assert!(cache.get_view("{ x = 2; }").is_none());
+
+        // Another source whose hash names the same file is a miss
+        let disk = cache.disk.as_ref().unwrap();
+        fs::rename(disk.entry_path(source), disk.entry_path("{ x = 2; }")).unwrap();
+        assert!(cache.get_view("{ x = 2; }").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_disk_entries_are_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ParseCache::with_disk(dir.path().join("nested"), 10).unwrap();
+        cache.insert("1", parse("1"));
+
+        let entry = cache.disk.as_ref().unwrap().entry_path("1");
+        fs::write(&entry, b"{ not json").unwrap();
+        let cache = ParseCache::with_disk(dir.path().join("nested"), 10).unwrap();
+        assert!(cache.get_view("1").is_none());
+
+        // The next insert repairs the entry
+        cache.insert("1", parse("1"));
+        let cache = ParseCache::with_disk(dir.path().join("nested"), 10).unwrap();
+        assert!(cache.get_view("1").is_some());
+
+        fs::remove_file(&entry).unwrap();
+        assert!(cache.get_view("1").is_none());
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash(b"{ x = 1; }"), content_hash(b"{ x = 2; }"));
+    }
+
+    #[test]
+    fn test_file_entries_invalidated_by_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("default.nix");
+        fs::write(&path, "{ x = 1; }").unwrap();
+
+        let cache = ParseCache::new(10);
+        assert!(cache.get_for_file(&path).is_none());
+        cache.insert_for_file(&path, parse("{ x = 1; }")).unwrap();
+        assert_eq!(cache.get_for_file(&path).unwrap().source(), "{ x = 1; }");
+
+        // Touching the file invalidates the entry, even with the same contents
+        let later = fs::metadata(&path).unwrap().modified().unwrap() + std::time::Duration::from_secs(5);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(cache.get_for_file(&path).is_none());
+
+        // Same size and timestamp, different contents
+        cache.insert_for_file(&path, parse("{ x = 1; }")).unwrap();
+        fs::write(&path, "{ x = 2; }").unwrap();
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(cache.get_for_file(&path).is_none());
+
+        fs::remove_file(&path).unwrap();
+        assert!(cache.insert_for_file(&path, parse("1")).is_err());
+        assert!(cache.get_for_file(&path).is_none());
+    }
+}
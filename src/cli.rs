@@ -0,0 +1,804 @@
+//! Command-line interface of the `nix-parser` binary
+//!
+//! Every subcommand takes one or more inputs: Nix files, directories (which
+//! are searched recursively for Nix files), or `-` for standard input.
+//! `watch` keeps running, re-parsing its inputs as they change on disk.
+//! With `--cache-dir`, `parse` and `check` reuse the results an earlier run
+//! stored for unchanged files.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::ast::Expression;
+use crate::cache::ParseCache;
+use crate::error::{DiagnosticBuilder, ParseError, Result};
+use crate::parser::{
+    IncrementalParser, NixParser, ParseDiagnostic, ParseResult, ParseResultJson, ParserConfig, TextChange,
+};
+use crate::transform::CodeGenerator;
+use crate::utils::fs::{find_nix_files, is_nix_file};
+
+/// Exit status when `check` or `fmt` finds syntax errors, or `fmt --check`
+/// finds unformatted files
+const EXIT_SYNTAX_ERRORS: u8 = 1;
+
+/// Exit status when an input cannot be read or parsed at all
+const EXIT_FAILURE: u8 = 2;
+
+/// How long `watch` waits for more events before re-parsing, so that a
+/// file written in several steps is only parsed once it is complete
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Results `--cache-dir` keeps in memory; a run parses each input once,
+/// so only the disk layer saves work
+const CACHE_CAPACITY: usize = 1;
+
+/// Parse, check and format Nix files
+#[derive(Debug, Parser)]
+#[command(name = "nix-parser", version)]
+pub struct Cli {
+    /// The command to run
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Output format; commands that only print text treat `debug` and
+    /// `sexpr` like `pretty`
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Pretty)]
+    pub format: OutputFormat,
+
+    /// Directory where `parse` and `check` store parse results, so that
+    /// later runs do not parse unchanged files again; `check --flake` does
+    /// not use it
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// A `nix-parser` subcommand
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Parse files and print their syntax trees
+    Parse(Inputs),
+    /// Report diagnostics, exiting with status 1 if there are syntax errors
+    Check(CheckArgs),
+    /// Print files in canonical formatting, or rewrite them with `--write`
+    Fmt(FmtArgs),
+    /// Print parse statistics
+    Stats(Inputs),
+    /// Report diagnostics, then again for every file that changes
+    Watch(Inputs),
+}
+
+/// Inputs of a subcommand
+#[derive(Debug, Args)]
+pub struct Inputs {
+    /// Files or directories to read, or `-` for standard input
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+impl Command {
+    /// Get the inputs of the subcommand
+    pub fn inputs(&self) -> &Inputs {
+        match self {
+            Command::Parse(inputs) | Command::Stats(inputs) | Command::Watch(inputs) => inputs,
+            Command::Check(args) => &args.inputs,
+            Command::Fmt(args) => &args.inputs,
+        }
+    }
+}
+
+/// Arguments of the `check` subcommand
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Files to check
+    #[command(flatten)]
+    pub inputs: Inputs,
+
+    /// Also check that files named `flake.nix` have the structure Nix
+    /// expects of flakes, such as `outputs` being a function
+    #[arg(long)]
+    pub flake: bool,
+}
+
+/// Arguments of the `fmt` subcommand
+#[derive(Debug, Args)]
+pub struct FmtArgs {
+    /// Files to format
+    #[command(flatten)]
+    pub inputs: Inputs,
+
+    /// Write the formatted source back to the files instead of printing it
+    #[arg(long, conflicts_with = "check")]
+    pub write: bool,
+
+    /// Print the files that are not formatted instead of formatting them,
+    /// exiting with status 1 if there are any
+    #[arg(long)]
+    pub check: bool,
+}
+
+/// Output format of the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Single-line Rust debug output of the AST
+    Debug,
+    /// JSON, one document per input
+    Json,
+    /// S-expression of the AST, see `Expression::to_sexpr`
+    Sexpr,
+    /// Human-readable output, showing diagnostics with their source lines
+    Pretty,
+    /// Diagnostics on one line each, as `file:line:column: severity: message`
+    Short,
+}
+
+/// Parse the command line and run it, printing to standard output
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    run(&cli, &mut io::stdout().lock())
+}
+
+/// Run a parsed command line, writing its output to `out`
+///
+/// Problems with individual inputs are reported on standard error, and
+/// the remaining inputs are still processed. The returned exit code is 2
+/// if any input could not be read, parsed or formatted, 1 if `check` or
+/// `fmt` found syntax errors or `fmt --check` found unformatted files, and
+/// 0 otherwise. `watch` only returns if its inputs cannot be watched.
+pub fn run(cli: &Cli, out: &mut impl Write) -> ExitCode {
+    if let Command::Watch(inputs) = &cli.command {
+        return match watch(cli, inputs, out) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::from(EXIT_FAILURE)
+            }
+        };
+    }
+
+    let cache = match cli.cache_dir.as_ref().map(|dir| ParseCache::with_disk(dir, CACHE_CAPACITY)).transpose() {
+        Ok(cache) => cache.map(Arc::new),
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    };
+    // Flake validation adds diagnostics that results cached by source alone cannot tell apart
+    let cacheable = match &cli.command {
+        Command::Parse(_) => true,
+        Command::Check(args) => !args.flake,
+        _ => false,
+    };
+
+    let mut status = 0;
+    for input in expand_inputs(&cli.command.inputs().paths, &mut status) {
+        let outcome = match &cache {
+            Some(cache) if cacheable => parse_input_view(&input, cache)
+                .and_then(|(source, view)| print_view(cli, &input, &source, &view, out)),
+            _ => parse_input(&cli.command, &input).and_then(|result| print_result(cli, &input, &result, out)),
+        };
+        match outcome {
+            Ok(true) => {}
+            Ok(false) => status = status.max(EXIT_SYNTAX_ERRORS),
+            Err(err) => {
+                eprintln!("{}: {}", input.display(), err);
+                status = EXIT_FAILURE;
+            }
+        }
+    }
+    ExitCode::from(status)
+}
+
+/// Replace directories in `paths` with the Nix files they contain
+fn expand_inputs(paths: &[PathBuf], status: &mut u8) -> Vec<PathBuf> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            inputs.push(path.clone());
+            continue;
+        }
+        match find_nix_files(path) {
+            Ok(mut files) => {
+                files.sort();
+                inputs.extend(files);
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                *status = EXIT_FAILURE;
+            }
+        }
+    }
+    inputs
+}
+
+/// Parse one input, reading standard input for `-`
+fn parse_input(command: &Command, input: &Path) -> Result<ParseResult> {
+    let config = ParserConfig::builder()
+        .collect_statistics(matches!(command, Command::Stats(_)))
+        .validate_flakes(matches!(command, Command::Check(args) if args.flake) && is_flake_file(input))
+        .build();
+    let mut parser = NixParser::with_config(config)?;
+
+    if input == Path::new("-") {
+        parser.parse(&read_stdin()?)
+    } else {
+        parser.parse_file(input)
+    }
+}
+
+/// Check if `input` is a file that `check --flake` validates
+fn is_flake_file(input: &Path) -> bool {
+    input.file_name().is_some_and(|name| name == "flake.nix")
+}
+
+/// Parse one input into the view of its result, reusing the view `cache`
+/// holds for an unchanged source
+///
+/// Returns the source with the view, which does not include it.
+fn parse_input_view(input: &Path, cache: &Arc<ParseCache>) -> Result<(String, ParseResultJson)> {
+    let stdin = input == Path::new("-");
+    let source = if stdin { read_stdin()? } else { NixParser::read_file(input)? };
+    let mut parser = NixParser::new()?;
+    parser.enable_cache(Arc::clone(cache));
+
+    let mut view = parser.parse_view(&source)?;
+    view.file_path = (!stdin).then(|| input.to_path_buf());
+    Ok((source, view))
+}
+
+/// Read all of standard input
+fn read_stdin() -> Result<String> {
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+    Ok(source)
+}
+
+/// Print the output of the command for one parse result
+///
+/// Returns whether the input was free of syntax errors, for the commands
+/// whose exit status depends on it.
+fn print_result(cli: &Cli, input: &Path, result: &ParseResult, out: &mut impl Write) -> Result<bool> {
+    let name = input.display();
+    match &cli.command {
+        Command::Parse(_) => print_view(cli, input, result.source(), &result.to_json_view()?, out),
+        Command::Check(_) | Command::Watch(_) => {
+            print_diagnostics(cli.format, input, result.source(), &result.sorted_diagnostics(), out)?;
+            Ok(!result.has_errors())
+        }
+        Command::Fmt(args) => {
+            if result.has_errors() {
+                if let Some(summary) = result.error_summary() {
+                    eprintln!("{name}: {summary}");
+                }
+                return Ok(false);
+            }
+            let formatted = format_source(result)?;
+            let unchanged = formatted == result.source();
+
+            if args.check {
+                if !unchanged {
+                    writeln!(out, "{name}")?;
+                }
+                Ok(unchanged)
+            } else if args.write && input != Path::new("-") {
+                if !unchanged {
+                    fs::write(input, formatted)?;
+                }
+                Ok(true)
+            } else {
+                write!(out, "{formatted}")?;
+                Ok(true)
+            }
+        }
+        Command::Stats(_) => {
+            let Some(stats) = result.statistics() else {
+                return Err(ParseError::ValidationError("no statistics collected".to_string()));
+            };
+            if cli.format == OutputFormat::Json {
+                let report = serde_json::json!({ "file": name.to_string(), "statistics": stats });
+                writeln!(out, "{report}")?;
+            } else {
+                writeln!(
+                    out,
+                    "{}: {} bytes, {} nodes, {} errors, {} warnings, {}",
+                    name,
+                    stats.source_size,
+                    stats.node_count,
+                    stats.error_count,
+                    stats.warning_count,
+                    stats.timing.format()
+                )?;
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Print the output of `parse` or `check` for the view of a parse result
+///
+/// Returns whether the input was free of syntax errors, like
+/// [`print_result`].
+fn print_view(cli: &Cli, input: &Path, source: &str, view: &ParseResultJson, out: &mut impl Write) -> Result<bool> {
+    if !matches!(cli.command, Command::Parse(_)) {
+        print_diagnostics(cli.format, input, source, &view.sorted_diagnostics(), out)?;
+        return Ok(!view.has_errors());
+    }
+
+    let tree = match cli.format {
+        OutputFormat::Json => view.to_json()?,
+        OutputFormat::Sexpr => view.expression.as_ref().map_or_else(String::new, Expression::to_sexpr),
+        OutputFormat::Debug => format!("{:?}", view.expression),
+        OutputFormat::Pretty | OutputFormat::Short => format!("{:#?}", view.expression),
+    };
+    writeln!(out, "{tree}")?;
+    Ok(true)
+}
+
+/// Print the diagnostics of a parse result, one per line
+fn print_diagnostics(
+    format: OutputFormat,
+    input: &Path,
+    source: &str,
+    diagnostics: &[ParseDiagnostic],
+    out: &mut impl Write,
+) -> Result<()> {
+    let name = input.display();
+    match format {
+        OutputFormat::Json => {
+            let report = serde_json::json!({
+                "file": name.to_string(),
+                "diagnostics": diagnostics,
+            });
+            writeln!(out, "{report}")?;
+        }
+        OutputFormat::Short => {
+            for diagnostic in diagnostics {
+                let location = &diagnostic.location;
+                writeln!(
+                    out,
+                    "{}:{}:{}: {}: {}",
+                    name, location.line, location.column, diagnostic.severity, diagnostic.message
+                )?;
+            }
+        }
+        OutputFormat::Pretty | OutputFormat::Debug | OutputFormat::Sexpr => {
+            let builder = DiagnosticBuilder::new(source).file_path(name.to_string());
+            for diagnostic in diagnostics {
+                writeln!(out, "{}", builder.render(&diagnostic.to_diagnostic()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report diagnostics for the inputs, then watch them for changes
+///
+/// Runs until the watcher stops delivering events.
+fn watch(cli: &Cli, inputs: &Inputs, out: &mut impl Write) -> Result<()> {
+    // Events carry absolute paths, which documents are tracked by
+    let roots = inputs.paths.iter().map(fs::canonicalize).collect::<io::Result<Vec<_>>>()?;
+    let mut status = 0;
+    let mut session = WatchSession::new()?;
+    for input in expand_inputs(&roots, &mut status) {
+        session.report(cli.format, &input, out);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|err| watch_error(&err))?;
+    for root in &roots {
+        watcher.watch(root, RecursiveMode::Recursive).map_err(|err| watch_error(&err))?;
+    }
+    while let Ok(first) = receiver.recv() {
+        let mut events = Vec::new();
+        for event in std::iter::once(first).chain(std::iter::from_fn(|| receiver.recv_timeout(WATCH_DEBOUNCE).ok())) {
+            match event {
+                Ok(event) => events.push(event),
+                Err(err) => eprintln!("{}", watch_error(&err)),
+            }
+        }
+        session.handle_events(cli.format, &events, out);
+    }
+    Ok(())
+}
+
+/// Convert a filesystem watcher error
+fn watch_error(err: &notify::Error) -> ParseError {
+    ParseError::ValidationError(format!("cannot watch files: {err}"))
+}
+
+/// Nix files tracked by `watch`, with their latest syntax trees
+///
+/// Documents are identified by path. A file that changes is re-parsed
+/// incrementally from the single edit between its old and new contents.
+struct WatchSession {
+    parser: IncrementalParser,
+}
+
+impl WatchSession {
+    fn new() -> Result<Self> {
+        Ok(Self { parser: IncrementalParser::new()? })
+    }
+
+    /// Re-parse the files that events are about, once each
+    fn handle_events(&mut self, format: OutputFormat, events: &[Event], out: &mut impl Write) {
+        let mut paths: Vec<&PathBuf> = Vec::new();
+        for event in events.iter().filter(|event| !matches!(event.kind, EventKind::Access(_))) {
+            for path in event.paths.iter().filter(|path| is_nix_file(path)) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        for path in paths {
+            self.report(format, path, out);
+        }
+    }
+
+    /// Re-parse a file and print its diagnostics, or that it was removed
+    ///
+    /// In pretty output, files without diagnostics are reported as `ok`,
+    /// so that fixed errors do not look like they are still there.
+    fn report(&mut self, format: OutputFormat, path: &Path, out: &mut impl Write) {
+        let printed = match self.update(path) {
+            Ok(DocumentUpdate::Parsed(result)) if format != OutputFormat::Json && result.diagnostics().is_empty() => {
+                writeln!(out, "{}: ok", path.display()).map_err(ParseError::from)
+            }
+            Ok(DocumentUpdate::Parsed(result)) => {
+                print_diagnostics(format, path, result.source(), &result.sorted_diagnostics(), out)
+            }
+            Ok(DocumentUpdate::Unchanged) => Ok(()),
+            Ok(DocumentUpdate::Removed) => writeln!(out, "{}: removed", path.display()).map_err(ParseError::from),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = printed {
+            eprintln!("{}: {}", path.display(), err);
+        }
+    }
+
+    /// Bring the document of a file up to date with its contents
+    fn update(&mut self, path: &Path) -> Result<DocumentUpdate> {
+        let id = path.to_string_lossy();
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(if self.parser.remove_document(&id) {
+                    DocumentUpdate::Removed
+                } else {
+                    DocumentUpdate::Unchanged
+                });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(old_source) = self.parser.document_source(&id) else {
+            return self.parser.parse_document(id, &source).map(DocumentUpdate::parsed);
+        };
+        match TextChange::between(old_source, &source) {
+            Some(change) => self.parser.update_document(&id, &[change], &source).map(DocumentUpdate::parsed),
+            None => Ok(DocumentUpdate::Unchanged),
+        }
+    }
+}
+
+/// What happened to the document of a watched file
+enum DocumentUpdate {
+    /// The file is new or changed, and was parsed again
+    Parsed(Box<ParseResult>),
+    /// The file did not change, or was never tracked
+    Unchanged,
+    /// The file was deleted, and its document removed
+    Removed,
+}
+
+impl DocumentUpdate {
+    fn parsed(result: ParseResult) -> Self {
+        Self::Parsed(Box::new(result))
+    }
+}
+
+/// Format a parsed source, ending it with a newline
+///
+/// Comments are not part of the AST, so sources containing them are
+/// rejected rather than formatted without them. The output is parsed
+/// again to make sure formatting did not change the expression.
+fn format_source(result: &ParseResult) -> Result<String> {
+    let Some(expr) = result.expression()? else {
+        return Err(ParseError::ValidationError("no expression to format".to_string()));
+    };
+    if has_comments(result) {
+        return Err(ParseError::ValidationError("formatting would remove comments".to_string()));
+    }
+
+    let formatted = format!("{}\n", CodeGenerator::new().generate(&expr));
+    let reparsed = NixParser::with_config(result.config().clone())?.parse(&formatted)?;
+    if reparsed.has_errors() || reparsed.expression()?.as_ref() != Some(&expr) {
+        return Err(ParseError::ValidationError("formatting would change the expression".to_string()));
+    }
+    Ok(formatted)
+}
+
+/// Check if a parsed source contains comments
+///
+/// Comments are hidden from the syntax tree, so look for text between the
+/// tokens of the tree that is not whitespace, except for string contents.
+/// The quotes and the `${` and `}` of interpolations are hidden as well.
+fn has_comments(result: &ParseResult) -> bool {
+    let source = result.source();
+    let mut nodes = vec![(result.tree().root_node(), 0..source.len())];
+    while let Some((node, range)) = nodes.pop() {
+        let content = matches!(node.kind(), "string" | "indented_string");
+        let mut cursor = node.walk();
+        let mut start = range.start;
+        for child in node.children(&mut cursor) {
+            if !content && !source[start..child.start_byte()].trim().is_empty() {
+                return true;
+            }
+            start = child.end_byte();
+            let range = child.byte_range();
+            match child.kind() {
+                "string_interpolation" => nodes.push((child, range.start + 2..range.end - 1)),
+                _ => nodes.push((child, range)),
+            }
+        }
+        if node.child_count() > 0 && !content && !source[start..range.end].trim().is_empty() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_parse_arguments() {
+        let cli = Cli::try_parse_from(["nix-parser", "check", "a.nix", "dir", "--format", "json"]).unwrap();
+        assert_eq!(cli.format, OutputFormat::Json);
+        let Command::Check(args) = cli.command else {
+            panic!("expected check, got {:?}", cli.command);
+        };
+        assert_eq!(args.inputs.paths, [PathBuf::from("a.nix"), PathBuf::from("dir")]);
+        assert!(!args.flake);
+
+        let cli = Cli::try_parse_from(["nix-parser", "parse", "-"]).unwrap();
+        assert_eq!(cli.format, OutputFormat::Pretty);
+
+        assert!(Cli::try_parse_from(["nix-parser", "parse"]).is_err());
+        assert!(Cli::try_parse_from(["nix-parser", "parse", "a.nix", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_run_reports_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.nix");
+        let bad = dir.path().join("bad.nix");
+        std::fs::write(&good, "{ x = 1; }").unwrap();
+        std::fs::write(&bad, "{ x = ; }").unwrap();
+
+        let check = |path: &Path| {
+            let args = ["nix-parser".as_ref(), "check".as_ref(), "--format=short".as_ref(), path.as_os_str()];
+            let cli = Cli::try_parse_from(args).unwrap();
+            let mut out = Vec::new();
+            let status = run(&cli, &mut out);
+            (status, String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(check(&good), (ExitCode::SUCCESS, String::new()));
+        let (status, output) = check(&bad);
+        assert_eq!(status, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        assert!(output.starts_with(&format!("{}:1:", bad.display())), "{}", output);
+        assert!(output.contains(": error: "));
+        let (status, output) = check(dir.path());
+        assert_eq!(status, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        assert_eq!(output.lines().count(), 1);
+        assert_eq!(check(&dir.path().join("missing.nix")).0, ExitCode::from(EXIT_FAILURE));
+
+        let cli = Cli::try_parse_from(["nix-parser".as_ref(), "check".as_ref(), bad.as_os_str()]).unwrap();
+        let mut out = Vec::new();
+        run(&cli, &mut out);
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.starts_with("error: "), "{}", output);
+        assert!(output.contains(&format!(" --> {}:1:", bad.display())), "{}", output);
+        assert!(output.contains("\n1 | { x = ; }\n"), "{}", output);
+    }
+
+    #[test]
+    fn test_check_flake() {
+        let dir = tempfile::tempdir().unwrap();
+        let flake = dir.path().join("flake.nix");
+        let source = "{\n  inputs.nixpkgs.url = \"github:NixOS/nixpkgs\";\n  outputs = { };\n}\n";
+        std::fs::write(&flake, source).unwrap();
+
+        let check = |flags: &[&str], path: &Path| {
+            let args = ["nix-parser", "check", "--format=short"].iter().chain(flags).map(OsStr::new);
+            let cli = Cli::try_parse_from(args.chain([path.as_os_str()])).unwrap();
+            let mut out = Vec::new();
+            let status = run(&cli, &mut out);
+            (status, String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(check(&[], &flake), (ExitCode::SUCCESS, String::new()));
+        let (status, output) = check(&["--flake"], &flake);
+        assert_eq!(status, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        let error = format!("{}:3:13: error: flake `outputs` must be a function of the inputs\n", flake.display());
+        assert_eq!(output, error);
+
+        // Other files are not flakes, even if they look like one
+        let other = dir.path().join("other.nix");
+        std::fs::write(&other, source).unwrap();
+        assert_eq!(check(&["--flake"], &other), (ExitCode::SUCCESS, String::new()));
+        assert_eq!(check(&["--flake"], dir.path()), (ExitCode::from(EXIT_SYNTAX_ERRORS), error));
+    }
+
+    #[test]
+    fn test_cache_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let bad = dir.path().join("bad.nix");
+        std::fs::write(&bad, "{ x = ; }").unwrap();
+
+        let run_with = |command: &str, format: &str, cached: bool| {
+            let mut args = vec![OsStr::new("nix-parser"), OsStr::new(command), OsStr::new(format), bad.as_os_str()];
+            if cached {
+                args.extend([OsStr::new("--cache-dir"), cache_dir.as_os_str()]);
+            }
+            let cli = Cli::try_parse_from(args).unwrap();
+            let mut out = Vec::new();
+            let status = run(&cli, &mut out);
+            (status, String::from_utf8(out).unwrap())
+        };
+        let entries = || std::fs::read_dir(&cache_dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+
+        // The first run stores the result, and the output does not change
+        let check = run_with("check", "--format=pretty", false);
+        assert_eq!(check.0, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        assert_eq!(run_with("check", "--format=pretty", true), check);
+        assert_eq!(run_with("check", "--format=pretty", true), check);
+        for format in ["--format=json", "--format=sexpr"] {
+            assert_eq!(run_with("parse", format, true), run_with("parse", format, false));
+        }
+        let [entry] = &entries()[..] else {
+            panic!("expected one cache entry");
+        };
+
+        // Later runs read the stored result instead of parsing
+        let stored = std::fs::read_to_string(entry).unwrap();
+        std::fs::write(entry, stored.replace("Missing: identifier", "Stored: identifier")).unwrap();
+        let (_, output) = run_with("check", "--format=short", true);
+        assert!(output.ends_with(": error: Stored: identifier\n"), "{}", output);
+
+        // A changed file is parsed again
+        std::fs::write(&bad, "{ x = 1; }").unwrap();
+        assert_eq!(run_with("check", "--format=short", true), (ExitCode::SUCCESS, String::new()));
+        assert_eq!(entries().len(), 2);
+    }
+
+    #[test]
+    fn test_fmt_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let formatted = dir.path().join("formatted.nix");
+        let unformatted = dir.path().join("unformatted.nix");
+        let invalid = dir.path().join("invalid.nix");
+        std::fs::write(&formatted, "{ x = 1; }\n").unwrap();
+        std::fs::write(&unformatted, "{x=1;}").unwrap();
+        std::fs::write(&invalid, "{x=;}").unwrap();
+
+        let fmt = |flag: &str, path: &Path| {
+            let args = ["nix-parser".as_ref(), "fmt".as_ref(), flag.as_ref(), path.as_os_str()];
+            let cli = Cli::try_parse_from(args).unwrap();
+            let mut out = Vec::new();
+            let status = run(&cli, &mut out);
+            (status, String::from_utf8(out).unwrap())
+        };
+
+        assert_eq!(fmt("--check", &formatted), (ExitCode::SUCCESS, String::new()));
+        assert_eq!(fmt("--write", &formatted), (ExitCode::SUCCESS, String::new()));
+        assert_eq!(std::fs::read_to_string(&formatted).unwrap(), "{ x = 1; }\n");
+
+        let (status, output) = fmt("--check", &unformatted);
+        assert_eq!(status, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        assert_eq!(output, format!("{}\n", unformatted.display()));
+        assert_eq!(std::fs::read_to_string(&unformatted).unwrap(), "{x=1;}");
+        assert_eq!(fmt("--write", &unformatted).0, ExitCode::SUCCESS);
+        assert_eq!(std::fs::read_to_string(&unformatted).unwrap(), "{ x = 1; }\n");
+
+        // A binding to a variable of the same name is not an inherit
+        let same_name = dir.path().join("same_name.nix");
+        std::fs::write(&same_name, "x: {x=x;}").unwrap();
+        assert_eq!(fmt("--write", &same_name).0, ExitCode::SUCCESS);
+        assert_eq!(std::fs::read_to_string(&same_name).unwrap(), "x: { x = x; }\n");
+
+        // Files with syntax errors or comments are left alone
+        assert_eq!(fmt("--write", &invalid).0, ExitCode::from(EXIT_SYNTAX_ERRORS));
+        assert_eq!(std::fs::read_to_string(&invalid).unwrap(), "{x=;}");
+        let commented = dir.path().join("commented.nix");
+        std::fs::write(&commented, "# comment\n{x=1;}").unwrap();
+        assert_eq!(fmt("--write", &commented).0, ExitCode::from(EXIT_FAILURE));
+        assert_eq!(std::fs::read_to_string(&commented).unwrap(), "# comment\n{x=1;}");
+        std::fs::write(&commented, "{ x = \"not # a /* comment */\";}").unwrap();
+        assert_eq!(fmt("--write", &commented).0, ExitCode::SUCCESS);
+        assert_eq!(std::fs::read_to_string(&commented).unwrap(), "{ x = \"not # a /* comment */\"; }\n");
+
+        assert!(Cli::try_parse_from(["nix-parser", "fmt", "--write", "--check", "a.nix"]).is_err());
+    }
+
+    #[test]
+    fn test_fmt_write_keeps_meaning() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.nix");
+        let cases = [
+            ("a -b", "a - b\n"),
+            // `a-b` is a single identifier
+            ("a-b", "a-b\n"),
+            ("a - (b - c) - d", "a - (b - c) - d\n"),
+            ("-f x", "-(f x)\n"),
+            ("-x.y or 2", "-(x.y or 2)\n"),
+            ("f (-1)", "f (-1)\n"),
+            ("x.${y}", "x.${y}\n"),
+            ("x?${y}", "x ? ${y}\n"),
+            (r#"x.a."${b}".c or d"#, "x.a.\"${b}\".c or d\n"),
+        ];
+        for (source, expected) in cases {
+            std::fs::write(&file, source).unwrap();
+            let cli = Cli::try_parse_from(["nix-parser".as_ref(), "fmt".as_ref(), "--write".as_ref(), file.as_os_str()])
+                .unwrap();
+            assert_eq!(run(&cli, &mut Vec::new()), ExitCode::SUCCESS, "formatting {source}");
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), expected, "formatting {source}");
+        }
+    }
+
+    #[test]
+    fn test_watch_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.nix");
+        let b = dir.path().join("b.nix");
+        let c = dir.path().join("c.nix");
+        std::fs::write(&a, "{ x = 1; }").unwrap();
+        std::fs::write(&b, "{ y = 2; }").unwrap();
+
+        let mut session = WatchSession::new().unwrap();
+        let mut out = Vec::new();
+        session.report(OutputFormat::Pretty, &a, &mut out);
+        session.report(OutputFormat::Pretty, &b, &mut out);
+        let version = |session: &WatchSession, path: &Path| session.parser.document_version(&path.to_string_lossy());
+        assert_eq!((version(&session, &a), version(&session, &b)), (Some(1), Some(1)));
+
+        let handle = |session: &mut WatchSession, kind: EventKind, path: &Path| {
+            let mut out = Vec::new();
+            let events = [Event::new(kind).add_path(path.to_path_buf())];
+            session.handle_events(OutputFormat::Short, &events, &mut out);
+            String::from_utf8(out).unwrap()
+        };
+        let modify = EventKind::Modify(notify::event::ModifyKind::Any);
+
+        // Only the edited document is parsed again
+        std::fs::write(&a, "{ x = ; }").unwrap();
+        let output = handle(&mut session, modify, &a);
+        assert!(output.starts_with(&format!("{}:1:", a.display())), "{}", output);
+        assert_eq!(handle(&mut session, modify, &b), "");
+        std::fs::write(&a, "{ x = 2; }").unwrap();
+        assert_eq!(handle(&mut session, modify, &a), format!("{}: ok\n", a.display()));
+        assert_eq!(handle(&mut session, EventKind::Access(notify::event::AccessKind::Any), &a), "");
+        assert_eq!((version(&session, &a), version(&session, &b)), (Some(3), Some(1)));
+        assert_eq!(session.parser.document_source(&a.to_string_lossy()), Some("{ x = 2; }"));
+
+        std::fs::write(&c, "{ z = 3; }").unwrap();
+        let output = handle(&mut session, EventKind::Create(notify::event::CreateKind::File), &c);
+        assert_eq!(output, format!("{}: ok\n", c.display()));
+        assert_eq!(version(&session, &c), Some(1));
+        std::fs::remove_file(&b).unwrap();
+        let output = handle(&mut session, EventKind::Remove(notify::event::RemoveKind::File), &b);
+        assert_eq!(output, format!("{}: removed\n", b.display()));
+        assert_eq!(version(&session, &b), None);
+        assert_eq!(handle(&mut session, modify, &dir.path().join("notes.txt")), "");
+    }
+}
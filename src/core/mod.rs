@@ -1,3 +0,0 @@
-pub mod parser;
-pub mod grammar;
-pub mod cache;
\ No newline at end of file
@@ -1,7 +1,10 @@
 //! Error context and span information
 
+use crate::ast::SourceLocation;
+use crate::utils::text::TextUtils;
+
 /// Additional context information for error reporting
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ErrorContext {
     /// The file path where the error occurred
     pub file_path: Option<String>,
@@ -11,6 +14,41 @@ pub struct ErrorContext {
     pub suggestions: Vec<String>,
 }
 
+impl ErrorContext {
+    /// Capture the source around a 1-based line and column
+    ///
+    /// The snippet holds the line before the location, if any, the line of
+    /// the location and a line with a caret under its column. It is `None`
+    /// if the line does not exist in `source`.
+    pub fn from_location(source: &str, line: usize, column: usize) -> Self {
+        let text = TextUtils::new(source);
+        let source_snippet = text.line(line).map(|info| {
+            let mut snippet = String::new();
+            if let Some(previous) = line.checked_sub(1).and_then(|number| text.line(number)) {
+                snippet.push_str(previous.content);
+                snippet.push('\n');
+            }
+            // Keep tabs so that the caret lines up with the content
+            let indent: String = info.content
+                .get(..column.saturating_sub(1).min(info.content.len()))
+                .unwrap_or_default()
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            snippet.push_str(info.content);
+            snippet.push('\n');
+            snippet.push_str(&indent);
+            snippet.push('^');
+            snippet
+        });
+        Self {
+            file_path: None,
+            source_snippet,
+            suggestions: Vec::new(),
+        }
+    }
+}
+
 /// A span representing a range in the source code
 #[derive(Debug, Clone)]
 pub struct ErrorSpan {
@@ -20,6 +58,18 @@ pub struct ErrorSpan {
     pub end: Position,
 }
 
+impl From<&SourceLocation> for ErrorSpan {
+    fn from(location: &SourceLocation) -> Self {
+        Self {
+            start: Position { line: location.line, column: location.column },
+            end: Position {
+                line: location.end_position.0 + 1,
+                column: location.end_position.1 + 1,
+            },
+        }
+    }
+}
+
 /// A position in source code with line and column information
 #[derive(Debug, Clone, Copy)]
 pub struct Position {
@@ -27,4 +77,21 @@ pub struct Position {
     pub line: usize,
     /// Column number (1-based)
     pub column: usize,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_location() {
+        let source = "{\n  x = 1;\n  y = ;\n}\n";
+        let context = ErrorContext::from_location(source, 3, 7);
+        assert_eq!(context.source_snippet.as_deref(), Some("  x = 1;\n  y = ;\n      ^"));
+        assert_eq!(context.file_path, None);
+
+        let context = ErrorContext::from_location("\ta = ;", 1, 6);
+        assert_eq!(context.source_snippet.as_deref(), Some("\ta = ;\n\t    ^"));
+
+        assert_eq!(ErrorContext::from_location(source, 9, 1).source_snippet, None);
+    }
+}
@@ -1,35 +1,357 @@
 //! Diagnostic formatting and utilities
 
+use std::fmt;
+
+use super::ErrorContext;
+
 /// A diagnostic message with severity level and location information
-#[derive(Debug, Clone)]
-pub struct Diagnostic {}
+///
+/// A line and column of `0` mean the location is unknown. Columns count
+/// bytes, like Tree-sitter does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column number (1-based)
+    pub column: usize,
+    /// Line and column where the diagnosed span ends, exclusive, if it is
+    /// not a single point
+    pub end: Option<(usize, usize)>,
+    /// Human-readable message
+    pub message: String,
+    /// Severity level of the diagnostic
+    pub severity: Severity,
+    /// Additional context, such as the file and a source snippet
+    pub context: Option<ErrorContext>,
+    /// Suggested fixes
+    pub suggestions: Vec<String>,
+}
 
 impl Diagnostic {
+    /// Create a new diagnostic with the given severity
+    pub fn new(severity: Severity, line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            end: None,
+            message: message.into(),
+            severity,
+            context: None,
+            suggestions: Vec::new(),
+        }
+    }
+
     /// Create a new error-level diagnostic
     /// 
     /// # Arguments
     /// 
-    /// * `_line` - The line number where the error occurred
-    /// * `_column` - The column number where the error occurred  
-    /// * `_message` - The diagnostic message
-    pub fn error(_line: usize, _column: usize, _message: String) -> Self {
-        Self {}
+    /// * `line` - The line number where the error occurred
+    /// * `column` - The column number where the error occurred  
+    /// * `message` - The diagnostic message
+    pub fn error(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, line, column, message)
+    }
+
+    /// Create a new warning-level diagnostic
+    pub fn warning(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, line, column, message)
+    }
+
+    /// Create a new info-level diagnostic
+    pub fn info(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, line, column, message)
+    }
+
+    /// Create a new hint-level diagnostic
+    pub fn hint(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Hint, line, column, message)
     }
     
     /// Add additional context information to this diagnostic
     /// 
     /// # Arguments
     /// 
-    /// * `_context` - Additional error context to attach
-    pub fn with_context(self, _context: super::ErrorContext) -> Self {
+    /// * `context` - Additional error context to attach
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        self.context = Some(context);
         self
     }
+
+    /// Set where the diagnosed span ends, exclusive
+    pub fn with_end(mut self, line: usize, column: usize) -> Self {
+        self.end = Some((line, column));
+        self
+    }
+
+    /// Add a suggested fix to this diagnostic
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestions.push(suggestion.into());
+        self
+    }
+
+    /// Check if the diagnostic has a known location
+    pub const fn has_location(&self) -> bool {
+        self.line > 0
+    }
 }
 
-/// Builder for constructing complex diagnostic messages
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if self.has_location() {
+            write!(f, " at ")?;
+            if let Some(file_path) = self.context.as_ref().and_then(|c| c.file_path.as_ref()) {
+                write!(f, "{file_path}:")?;
+            }
+            write!(f, "{}:{}", self.line, self.column)?;
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "\n  help: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Renderer of diagnostics in the style of rustc
+///
+/// Shows the diagnosed source lines with the span underlined by carets,
+/// followed by the suggestions of the diagnostic and its context:
+///
+/// ```text
+/// error: Missing: identifier
+///  --> default.nix:3:6
+///   |
+/// 3 |   y = ;
+///   |      ^
+///   = help: add a value
+/// ```
+///
+/// Spans over several lines are marked in a gutter left of the source,
+/// and lines in their middle are elided if there are many.
 #[derive(Debug, Clone)]
-pub struct DiagnosticBuilder {}
+pub struct DiagnosticBuilder<'a> {
+    source: &'a str,
+    file_path: Option<String>,
+}
+
+impl<'a> DiagnosticBuilder<'a> {
+    /// Lines of a multi-line span shown at its start and at its end
+    const CONTEXT_LINES: usize = 2;
+
+    /// Create a renderer for diagnostics about `source`
+    pub fn new(source: &'a str) -> Self {
+        Self { source, file_path: None }
+    }
+
+    /// Set the file name shown in the location line
+    ///
+    /// Defaults to the file path of the diagnostic's context, if any.
+    pub fn file_path(mut self, file_path: impl Into<String>) -> Self {
+        self.file_path = Some(file_path.into());
+        self
+    }
+
+    /// Render a diagnostic, ending with a newline
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        // Writing to a `String` cannot fail
+        let _ = self.write(diagnostic, &mut out);
+        out
+    }
+
+    /// Write a rendered diagnostic to `out`
+    fn write(&self, diagnostic: &Diagnostic, out: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(out, "{}: {}", diagnostic.severity, diagnostic.message)?;
+        let context = diagnostic.context.as_ref();
+        let suggestions = diagnostic
+            .suggestions
+            .iter()
+            .chain(context.iter().flat_map(|context| &context.suggestions));
+
+        let lines: Vec<&str> = self.source.lines().collect();
+        if !diagnostic.has_location() || diagnostic.line > lines.len().max(1) {
+            for suggestion in suggestions {
+                writeln!(out, "  = help: {suggestion}")?;
+            }
+            return Ok(());
+        }
+
+        let (start_line, start_column) = (diagnostic.line, diagnostic.column.max(1));
+        let (end_line, end_column) = diagnostic
+            .end
+            .filter(|&end| end > (start_line, start_column))
+            .map_or((start_line, start_column + 1), |(line, column)| (line.min(lines.len()), column));
+        let width = end_line.to_string().len();
+        let gutter = " ".repeat(width);
+
+        let file_path = self.file_path.as_deref().or(context.and_then(|c| c.file_path.as_deref()));
+        let file_path = file_path.map_or_else(String::new, |path| format!("{path}:"));
+        writeln!(out, "{gutter}--> {file_path}{start_line}:{start_column}")?;
+        writeln!(out, "{gutter} |")?;
+
+        let line = |number: usize| lines.get(number - 1).copied().unwrap_or("");
+        if start_line == end_line {
+            let text = line(start_line);
+            let start = Self::display_column(text, start_column);
+            let carets = Self::display_column(text, end_column).saturating_sub(start).max(1);
+            writeln!(out, "{start_line:>width$} | {text}")?;
+            writeln!(out, "{} | {}{}", gutter, " ".repeat(start), "^".repeat(carets))?;
+        } else {
+            let text = line(start_line);
+            writeln!(out, "{start_line:>width$} |   {text}")?;
+            let start = Self::display_column(text, start_column);
+            writeln!(out, "{} |  {}^", gutter, "_".repeat(start + 1))?;
+
+            for number in start_line + 1..end_line {
+                let shown = number - start_line <= Self::CONTEXT_LINES || end_line - number <= Self::CONTEXT_LINES;
+                if shown {
+                    writeln!(out, "{:>width$} | | {}", number, line(number))?;
+                } else if number == start_line + Self::CONTEXT_LINES + 1 {
+                    writeln!(out, "{gutter} | | ...")?;
+                }
+            }
+            let text = line(end_line);
+            writeln!(out, "{end_line:>width$} | | {text}")?;
+            // The last character of the span, as the end is exclusive
+            let end = Self::display_column(text, end_column).max(1);
+            writeln!(out, "{} | |{}^", gutter, "_".repeat(end))?;
+        }
+
+        for suggestion in suggestions {
+            writeln!(out, "{gutter} = help: {suggestion}")?;
+        }
+        Ok(())
+    }
+
+    /// Convert a 1-based byte column to the number of characters before it
+    fn display_column(line: &str, column: usize) -> usize {
+        let offset = column.saturating_sub(1).min(line.len());
+        line.get(..offset).map_or(offset, |prefix| prefix.chars().count())
+    }
+}
 
 /// Severity levels for diagnostic messages
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Severity {}
\ No newline at end of file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// Invalid code that cannot be evaluated
+    Error,
+    /// Suspicious code that is still valid
+    Warning,
+    /// Informational message
+    Info,
+    /// Suggestion for an improvement
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+            Severity::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diag = Diagnostic::warning(3, 7, "unused binding").with_suggestion("remove it");
+        assert_eq!(diag.to_string(), "warning: unused binding at 3:7\n  help: remove it");
+
+        let context = ErrorContext {
+            file_path: Some("default.nix".to_string()),
+            source_snippet: None,
+            suggestions: Vec::new(),
+        };
+        let diag = Diagnostic::error(1, 2, "unexpected `}`").with_context(context);
+        assert_eq!(diag.to_string(), "error: unexpected `}` at default.nix:1:2");
+
+        assert_eq!(Diagnostic::hint(0, 0, "no location").to_string(), "hint: no location");
+    }
+
+    #[test]
+    fn test_render_diagnostic() {
+        let source = "{\n  x = 1;\n  y = ;\n}\n";
+        let result = crate::parser::NixParser::new().unwrap().parse(source).unwrap();
+        let diag = result.diagnostics()[0].to_diagnostic().with_suggestion("add a value");
+        let rendered = DiagnosticBuilder::new(source).file_path("default.nix").render(&diag);
+        insta::assert_snapshot!(rendered, @r"
+        error: Missing: identifier
+         --> default.nix:3:6
+          |
+        3 |   y = ;
+          |      ^
+          = help: add a value
+        ");
+
+        let context = ErrorContext {
+            file_path: Some("flake.nix".to_string()),
+            source_snippet: None,
+            suggestions: vec!["use `inherit`".to_string()],
+        };
+        let diag = Diagnostic::warning(1, 5, "redundant binding").with_end(1, 10).with_context(context);
+        insta::assert_snapshot!(DiagnosticBuilder::new("{ a = a; }").render(&diag), @r"
+        warning: redundant binding
+         --> flake.nix:1:5
+          |
+        1 | { a = a; }
+          |     ^^^^^
+          = help: use `inherit`
+        ");
+    }
+
+    #[test]
+    fn test_render_multiline_diagnostic() {
+        let source = "let\n  a = {\n    b = 1;\n    c = 2;\n  };\nin a";
+        let diag = Diagnostic::warning(2, 7, "set could be inherited").with_end(5, 4);
+        insta::assert_snapshot!(DiagnosticBuilder::new(source).render(&diag), @r"
+        warning: set could be inherited
+         --> 2:7
+          |
+        2 |     a = {
+          |  _______^
+        3 | |     b = 1;
+        4 | |     c = 2;
+        5 | |   };
+          | |___^
+        ");
+
+        // A span ending at column 0 ends at the start of its last line
+        let diag = Diagnostic::warning(1, 1, "ends early").with_end(2, 0);
+        insta::assert_snapshot!(DiagnosticBuilder::new("a\nb").render(&diag), @r"
+        warning: ends early
+         --> 1:1
+          |
+        1 |   a
+          |  _^
+        2 | | b
+          | |_^
+        ");
+
+        // Lines in the middle of long spans are elided
+        let source = (1..=12).map(|n| format!("x{n}")).collect::<Vec<_>>().join("\n");
+        let diag = Diagnostic::error(1, 1, "too long").with_end(12, 4);
+        insta::assert_snapshot!(DiagnosticBuilder::new(&source).render(&diag), @r"
+        error: too long
+          --> 1:1
+           |
+         1 |   x1
+           |  _^
+         2 | | x2
+         3 | | x3
+           | | ...
+        10 | | x10
+        11 | | x11
+        12 | | x12
+           | |___^
+        ");
+
+        let diag = Diagnostic::error(0, 0, "no location").with_suggestion("try again");
+        assert_eq!(DiagnosticBuilder::new(&source).render(&diag), "error: no location\n  = help: try again\n");
+    }
+}
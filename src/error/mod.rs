@@ -7,7 +7,7 @@ mod recovery;
 mod diagnostic;
 mod context;
 
-pub use self::recovery::{RecoveryStrategy, ErrorRecovery};
+pub use self::recovery::{RecoveryStrategy, ErrorRecovery, AppliedFix};
 pub use self::diagnostic::{Diagnostic, DiagnosticBuilder, Severity};
 pub use self::context::{ErrorContext, ErrorSpan};
 
@@ -54,6 +54,10 @@ pub enum ParseError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    /// Tree-sitter query that failed to compile
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    
     /// Syntax error with location information
     #[error("Syntax error at line {line}, column {column}: {message}")]
     SyntaxError {
@@ -188,10 +192,10 @@ impl ParseError {
     }
     
     /// Combine multiple errors into one
-    pub fn combine(errors: Vec<ParseError>) -> Self {
+    pub fn combine(mut errors: Vec<ParseError>) -> Self {
         match errors.len() {
             0 => ParseError::ParseFailed("No specific error".to_string()),
-            1 => errors.into_iter().next().unwrap(),
+            1 => errors.remove(0),
             _ => ParseError::Multiple(errors),
         }
     }
@@ -214,8 +218,7 @@ impl ParseError {
     /// Get the primary error message
     pub fn primary_message(&self) -> String {
         match self {
-            ParseError::SyntaxError { message, .. } => message.clone(),
-            ParseError::SemanticError { message, .. } => message.clone(),
+            ParseError::SyntaxError { message, .. } | ParseError::SemanticError { message, .. } => message.clone(),
             _ => self.to_string(),
         }
     }
@@ -231,6 +234,14 @@ impl ParseError {
         }
     }
     
+    /// Get the context attached to this error, if any
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ParseError::SyntaxError { context, .. } | ParseError::SemanticError { context, .. } => context.as_ref(),
+            _ => None,
+        }
+    }
+
     /// Add context to this error
     pub fn with_context(self, context: ErrorContext) -> Self {
         match self {
@@ -265,7 +276,7 @@ impl ParseError {
             }
             ParseError::SemanticError { message, span, context } => {
                 let mut diag = if let Some(s) = span {
-                    Diagnostic::error(s.start.line, s.start.column, message.clone())
+                    Diagnostic::error(s.start.line, s.start.column, message.clone()).with_end(s.end.line, s.end.column)
                 } else {
                     Diagnostic::error(0, 0, message.clone())
                 };
@@ -279,20 +290,24 @@ impl ParseError {
     }
 }
 
-/// Specialized error types for different parsing phases
+// Specialized error types for different parsing phases
 
 /// Lexical analysis errors
 #[derive(Error, Debug)]
 pub enum LexError {
+    /// Character that cannot start a token
     #[error("Invalid character: {0}")]
     InvalidCharacter(char),
     
+    /// String missing its closing quote
     #[error("Unterminated string")]
     UnterminatedString,
     
+    /// Unknown escape sequence in a string
     #[error("Invalid escape sequence: {0}")]
     InvalidEscape(String),
     
+    /// Malformed number literal
     #[error("Invalid number format: {0}")]
     InvalidNumber(String),
 }
@@ -300,20 +315,27 @@ pub enum LexError {
 /// Syntax analysis errors
 #[derive(Error, Debug)]
 pub enum SyntaxError {
+    /// Token that cannot appear here
     #[error("Unexpected token: {found}, expected: {expected}")]
     UnexpectedToken {
+        /// Token found
         found: String,
+        /// Tokens that could appear instead
         expected: String,
     },
     
+    /// Bracket, brace or parenthesis left open
     #[error("Missing closing delimiter: {delimiter}")]
     MissingClosingDelimiter {
+        /// Delimiter expected
         delimiter: String,
     },
     
+    /// Expression that cannot be parsed
     #[error("Invalid expression")]
     InvalidExpression,
     
+    /// Expression nested deeper than the configured limit
     #[error("Maximum nesting depth exceeded")]
     MaxNestingDepthExceeded,
 }
@@ -321,22 +343,30 @@ pub enum SyntaxError {
 /// Semantic analysis errors
 #[derive(Error, Debug)]
 pub enum SemanticError {
+    /// Variable used without being bound
     #[error("Undefined variable: {name}")]
     UndefinedVariable {
+        /// Name of the variable
         name: String,
     },
     
+    /// Value of the wrong type
     #[error("Type mismatch: expected {expected}, found {found}")]
     TypeMismatch {
+        /// Type expected
         expected: String,
+        /// Type found
         found: String,
     },
     
+    /// Attribute defined twice in the same set
     #[error("Duplicate attribute: {name}")]
     DuplicateAttribute {
+        /// Name of the attribute
         name: String,
     },
     
+    /// Call of a value that is not a function
     #[error("Invalid function application")]
     InvalidFunctionApplication,
 }
@@ -345,13 +375,13 @@ pub enum SemanticError {
 
 impl From<LexError> for ParseError {
     fn from(err: LexError) -> Self {
-        ParseError::ParseFailed(format!("Lexical error: {}", err))
+        ParseError::ParseFailed(format!("Lexical error: {err}"))
     }
 }
 
 impl From<SyntaxError> for ParseError {
     fn from(err: SyntaxError) -> Self {
-        ParseError::ParseFailed(format!("Syntax error: {}", err))
+        ParseError::ParseFailed(format!("Syntax error: {err}"))
     }
 }
 
@@ -384,6 +414,28 @@ mod tests {
         assert_eq!(err.primary_message(), "Unexpected token");
     }
 
+    #[test]
+    fn test_to_diagnostic() {
+        let diag = ParseError::syntax_error(10, 5, "x").to_diagnostic();
+
+        assert_eq!((diag.line, diag.column), (10, 5));
+        assert_eq!(diag.message, "x");
+        assert_eq!(diag.severity, Severity::Error);
+        assert!(diag.context.is_none());
+
+        let context = ErrorContext {
+            file_path: Some("flake.nix".to_string()),
+            source_snippet: None,
+            suggestions: vec!["add `;`".to_string()],
+        };
+        let diag = ParseError::syntax_error_with_context(2, 3, "missing `;`", context.clone()).to_diagnostic();
+        assert_eq!(diag.context, Some(context));
+
+        let diag = ParseError::timeout(10).to_diagnostic();
+        assert!(!diag.has_location());
+        assert_eq!(diag.message, "Parsing timed out after 10ms");
+    }
+
     #[test]
     fn test_error_combination() {
         let errors = vec![
@@ -1,10 +1,106 @@
 //! Error recovery strategies
 
-#[derive(Debug, Clone, Copy)]
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::ast::SourceLocation;
+
+/// Missing tokens that `RecoveryStrategy::AutoFix` knows how to insert
+const INSERTABLE_TOKENS: &[&str] = &[";", "}", "]", ")", "\"", "''"];
+
+/// How the parser reacts to syntax errors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum RecoveryStrategy {
+    /// Return the first syntax error as a `ParseError`
     FailFast,
+    /// Collect diagnostics and build a best-effort AST, skipping the
+    /// subtrees that failed to parse
+    #[default]
     Continue,
+    /// Insert missing punctuation (such as `;` or a closing delimiter) and
+    /// parse the fixed source, then continue as `Continue` does
     AutoFix,
 }
 
-pub struct ErrorRecovery {}
\ No newline at end of file
+impl RecoveryStrategy {
+    /// Check if the strategy builds an AST despite syntax errors
+    pub const fn recovers(self) -> bool {
+        !matches!(self, RecoveryStrategy::FailFast)
+    }
+}
+
+/// A change made to the source by `RecoveryStrategy::AutoFix`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedFix {
+    /// Where the text was inserted, in the original source
+    pub location: SourceLocation,
+    /// The inserted text
+    pub inserted: String,
+}
+
+impl fmt::Display for AppliedFix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "inserted missing `{}` at {}:{}", self.inserted, self.location.line, self.location.column)
+    }
+}
+
+/// Applies a recovery strategy to a syntax tree containing errors
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorRecovery {
+    strategy: RecoveryStrategy,
+}
+
+impl ErrorRecovery {
+    /// Create a new error recovery for the given strategy
+    pub const fn new(strategy: RecoveryStrategy) -> Self {
+        Self { strategy }
+    }
+
+    /// Get the recovery strategy
+    pub const fn strategy(&self) -> RecoveryStrategy {
+        self.strategy
+    }
+
+    /// Insert the missing tokens Tree-sitter recovered from into `source`
+    ///
+    /// Returns the fixed source and the fixes that were applied, or `None`
+    /// if the strategy is not `AutoFix` or nothing could be fixed.
+    pub fn auto_fix(&self, tree: &Tree, source: &str) -> Option<(String, Vec<AppliedFix>)> {
+        if self.strategy != RecoveryStrategy::AutoFix || !tree.root_node().has_error() {
+            return None;
+        }
+
+        let mut fixes = Vec::new();
+        Self::collect_fixes(tree.root_node(), &mut fixes);
+        if fixes.is_empty() {
+            return None;
+        }
+
+        // Insert back to front so earlier offsets stay valid
+        let mut fixed_source = source.to_string();
+        for fix in fixes.iter().rev() {
+            fixed_source.insert_str(fix.location.start_byte, &fix.inserted);
+        }
+        Some((fixed_source, fixes))
+    }
+
+    /// Collect insertable missing tokens in source order
+    fn collect_fixes(node: Node, fixes: &mut Vec<AppliedFix>) {
+        if node.is_missing() && !node.is_named() && INSERTABLE_TOKENS.contains(&node.kind()) {
+            fixes.push(AppliedFix {
+                location: SourceLocation::from_tree_sitter_node(&node),
+                inserted: node.kind().to_string(),
+            });
+        }
+
+        if !node.has_error() {
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            Self::collect_fixes(child, fixes);
+        }
+    }
+}
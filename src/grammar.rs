@@ -5,7 +5,7 @@
 
 use tree_sitter::Language;
 
-extern "C" {
+unsafe extern "C" {
     fn tree_sitter_nix() -> Language;
 }
 
@@ -19,60 +19,102 @@ pub fn language() -> Language {
     unsafe { tree_sitter_nix() }
 }
 
+/// Check if a node is a path with interpolations, such as `./${name}.nix`
+///
+/// The grammar has no node kind for these paths: the external scanner lexes
+/// them as a `string` without quotes. Consumers of the syntax tree should
+/// use this, or [`NodeType::of`], to tell them apart from strings.
+pub fn is_interpolated_path(node: tree_sitter::Node, source: &str) -> bool {
+    node.kind() == "string" && source.get(node.byte_range()).is_some_and(|text| !text.starts_with('"'))
+}
+
 /// Nix language node types
 ///
 /// These correspond to the node types defined in the Tree-sitter grammar.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NodeType {
     // Root
+    /// Root node of a file
     SourceFile,
 
     // Literals
+    /// Integer literal
     Integer,
+    /// Float literal
     Float,
+    /// Double-quoted string
     String,
+    /// Indented string `''...''`
     IndentedString,
+    /// `true` or `false`
     Boolean,
+    /// `null`
     Null,
+    /// Variable or attribute name
     Identifier,
+    /// Path literal
     Path,
+    /// Unquoted URI literal
     Uri,
 
     // Collections
+    /// List `[ ... ]`
     List,
+    /// Attribute set `{ ... }`
     Attrset,
+    /// Recursive attribute set `rec { ... }`
     RecAttrset,
 
     // Expressions
+    /// Binary operation
     BinaryExpression,
+    /// Unary operation
     UnaryExpression,
+    /// Function call
     Application,
+    /// Function definition
     FunctionExpression,
+    /// `let ... in ...`
     LetExpression,
+    /// `if ... then ... else ...`
     IfExpression,
+    /// `with ...; ...`
     WithExpression,
+    /// `assert ...; ...`
     AssertExpression,
+    /// Expression in parentheses
     ParenthesizedExpression,
 
     // Attribute operations
+    /// Attribute selection `a.b`
     Select,
+    /// Attribute test `a ? b`
     HasAttr,
 
     // Structural
+    /// Binding `name = value;`
     Binding,
+    /// `inherit` statement
     Inherit,
+    /// Attribute path `a.b.c`
     Attrpath,
+    /// Set pattern of a function
     Formals,
+    /// Field of a set pattern
     Formal,
 
     // String parts
+    /// Interpolation `${...}`
     StringInterpolation,
 
     // Comments and whitespace
+    /// Line or block comment
     Comment,
 
     // Error nodes
+    /// Node Tree-sitter could not parse
     Error,
+    /// Node Tree-sitter inserted to recover from an error
     Missing,
 }
 
@@ -155,7 +197,22 @@ impl NodeType {
         }
     }
 
+    /// Get the type of a syntax node
+    ///
+    /// Unlike looking up the node's kind, this classifies paths with
+    /// interpolations as [`NodeType::Path`] rather than as strings.
+    pub fn of(node: tree_sitter::Node, source: &str) -> Option<Self> {
+        if is_interpolated_path(node, source) {
+            return Some(NodeType::Path);
+        }
+        Self::from_str(node.kind())
+    }
+
     /// Parse a node type from a string
+    ///
+    /// Unlike the [`FromStr`](std::str::FromStr) implementation, unknown
+    /// kinds give `None` rather than an error message.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "source_file" => Some(NodeType::SourceFile),
@@ -243,7 +300,7 @@ impl std::str::FromStr for NodeType {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        NodeType::from_str(s).ok_or_else(|| format!("Unknown node type: {}", s))
+        NodeType::from_str(s).ok_or_else(|| format!("Unknown node type: {s}"))
     }
 }
 
@@ -253,41 +310,59 @@ impl std::str::FromStr for NodeType {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FieldName {
     // General
+    /// Main subexpression of a node, such as the value of a binding
     Expression,
+    /// Body of a function, `let`, `with` or `assert`
     Body,
 
     // Binary expressions
+    /// Left operand
     Left,
+    /// Right operand
     Right,
+    /// Operator of an operation
     Operator,
 
     // Unary expressions
+    /// Operand of a unary operation, or argument of a call
     Argument,
 
     // Functions
+    /// Function of a call
     Function,
+    /// Parameter of a function
     Parameter,
 
     // Control flow
+    /// Condition of an `if` or `assert`
     Condition,
+    /// Branch taken when the condition holds
     Consequence,
+    /// Branch taken otherwise
     Alternative,
 
     // Let expressions
+    /// Bindings of a `let`
     Bindings,
 
     // Attribute sets
+    /// Attribute path of a binding or selection
     Attrpath,
 
     // Lists
+    /// Elements of a list
     Elements,
 
     // Attributes
+    /// Name of a formal, or of the `@` binding of a set pattern
     Name,
+    /// Default value of a formal or selection
     Default,
 
     // Inheritance
+    /// Source of an `inherit (from)`
     From,
+    /// Names of an `inherit`
     Attributes,
 }
 
@@ -323,9 +398,185 @@ impl std::fmt::Display for FieldName {
     }
 }
 
+/// Grammar layers for progressive complexity
+///
+/// Each layer accepts the constructs of the layers before it plus its own,
+/// so a parser can be restricted to a subset of the language for teaching
+/// or sandboxing; see `ParserConfig::grammar_layer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GrammarLayer {
+    /// Basic literals and operators
+    Basic,
+    /// Standard Nix features
+    Standard,
+    /// Advanced features including imports
+    Advanced,
+    /// Experimental features
+    Experimental,
+}
+
+impl GrammarLayer {
+    /// Get all layers, from the most restrictive to the least
+    pub const fn all() -> &'static [GrammarLayer] {
+        &[
+            GrammarLayer::Basic,
+            GrammarLayer::Standard,
+            GrammarLayer::Advanced,
+            GrammarLayer::Experimental,
+        ]
+    }
+
+    /// Get the layer name
+    pub const fn name(self) -> &'static str {
+        match self {
+            GrammarLayer::Basic => "basic",
+            GrammarLayer::Standard => "standard",
+            GrammarLayer::Advanced => "advanced",
+            GrammarLayer::Experimental => "experimental",
+        }
+    }
+
+    /// Get the node kinds this layer adds to the layers before it
+    ///
+    /// Besides Tree-sitter node kinds, `import`, `derivation` and
+    /// `builtins` stand for references to those builtins.
+    pub fn allowed_nodes(&self) -> &'static [&'static str] {
+        match self {
+            GrammarLayer::Basic => &[
+                "source_file", "comment", "integer", "float", "boolean",
+                "null", "string",
+                "indented_string", "string_interpolation", "identifier",
+                "path", "uri", "binary_expression", "unary_expression",
+                "list", "attrset", "binding", "attrpath",
+                "parenthesized_expression",
+            ],
+            GrammarLayer::Standard => &[
+                // All basic nodes plus:
+                "function_expression", "formals", "formal", "application",
+                "let_expression", "inherit", "rec_attrset", "if_expression",
+                "select", "or_kw", "has_attr",
+            ],
+            GrammarLayer::Advanced => &[
+                // All standard nodes plus:
+                "with_expression", "assert_expression",
+                "import", "derivation", "builtins",
+            ],
+            GrammarLayer::Experimental => &[
+                // All nodes allowed
+            ],
+        }
+    }
+
+    /// Check if the layer allows a node kind, directly or through the
+    /// layers before it
+    pub fn allows(self, kind: &str) -> bool {
+        self == GrammarLayer::Experimental
+            || Self::all()
+                .iter()
+                .filter(|layer| **layer <= self)
+                .any(|layer| layer.allowed_nodes().contains(&kind))
+    }
+
+    /// Check that a syntax node is allowed by the layer
+    ///
+    /// The node itself is checked, along with the builtins its identifier
+    /// children refer to, but not the rest of its children. Error and
+    /// missing nodes and anonymous tokens are always allowed.
+    ///
+    /// Every reference to `import`, `scopedImport` or `derivation` is
+    /// checked, not only calls, so aliasing them does not get around the
+    /// layer. `builtins` and the `__`-prefixed aliases of builtins reach
+    /// the same functions, so they count as `builtins`. Shadowing is not
+    /// resolved: a local variable named `import` is checked like the
+    /// builtin.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::FeatureNotSupported`, suggesting the first
+    /// layer that allows it, if the node is outside of the layer.
+    pub fn check_node(self, node: tree_sitter::Node, source: &str) -> crate::error::Result<()> {
+        if !node.is_named() || node.is_error() || node.is_missing() {
+            return Ok(());
+        }
+        self.check_feature(Self::feature(node, source))?;
+
+        // Whether an identifier is a variable depends on its parent, so
+        // variables are checked from there
+        let mut cursor = node.walk();
+        if cursor.goto_first_child() {
+            loop {
+                if Self::is_variable(node, cursor.field_name())
+                    && let Some(builtin) = Self::builtin(cursor.node(), source)
+                {
+                    self.check_feature(builtin)?;
+                }
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the layer allows a node kind or builtin
+    fn check_feature(self, kind: &str) -> crate::error::Result<()> {
+        if self.allows(kind) {
+            return Ok(());
+        }
+        let required = Self::all()
+            .iter()
+            .find(|layer| layer.allows(kind))
+            .unwrap_or(&GrammarLayer::Experimental);
+        Err(crate::error::ParseError::feature_not_supported_with_suggestion(
+            format!("{} (grammar layer {})", kind, self.name()),
+            format!("use the {} grammar layer", required.name()),
+        ))
+    }
+
+    /// Get the kind a node is checked as: its node kind, `path` for an
+    /// interpolated path, or the builtin an application calls
+    fn feature(node: tree_sitter::Node, source: &str) -> &'static str {
+        if is_interpolated_path(node, source) {
+            return NodeType::Path.as_str();
+        }
+        if node.kind() == "application"
+            && let Some(function) = node.child_by_field_name(FieldName::Function.as_str())
+            && let Some(builtin) = Self::builtin(function, source)
+        {
+            return builtin;
+        }
+        node.kind()
+    }
+
+    /// Get the builtin an identifier names, if it is one of the gated ones
+    fn builtin(node: tree_sitter::Node, source: &str) -> Option<&'static str> {
+        if node.kind() != "identifier" {
+            return None;
+        }
+        match node.utf8_text(source.as_bytes()).ok()? {
+            "import" | "scopedImport" => Some("import"),
+            "derivation" => Some("derivation"),
+            name if name == "builtins" || name.starts_with("__") => Some("builtins"),
+            _ => None,
+        }
+    }
+
+    /// Check if the child of `parent` in `field` is a variable, rather than
+    /// the name of an attribute or of a function parameter
+    fn is_variable(parent: tree_sitter::Node, field: Option<&str>) -> bool {
+        match parent.kind() {
+            "attrpath" | "formal" | "formals" => false,
+            "function_expression" => field != Some("parameter"),
+            // `inherit (from) names;` names attributes of `from`
+            "inherit" => field == Some("from") || parent.child_by_field_name("from").is_none(),
+            _ => true,
+        }
+    }
+}
+
 /// Grammar validation utilities
 pub mod validation {
-    use super::*;
+    use super::{NodeType, FieldName};
     use tree_sitter::Node;
 
     /// Check if a node has the expected type
@@ -350,7 +601,7 @@ pub mod validation {
         // Check required fields
         for field in required_fields {
             if !has_required_field(node, *field) {
-                errors.push(format!("Missing required field: {}", field));
+                errors.push(format!("Missing required field: {field}"));
             }
         }
 
@@ -368,6 +619,21 @@ mod tests {
         assert!(lang.abi_version() >= crate::MIN_TREE_SITTER_ABI as usize);
     }
 
+    #[test]
+    fn test_interpolated_path_node_type() {
+        for (source, kind, node_type) in [
+            ("./a/${b}.nix", "string", NodeType::Path),
+            (r#""c${d}""#, "string", NodeType::String),
+            ("./e", "path", NodeType::Path),
+        ] {
+            let result = crate::parser::parse(source).unwrap();
+            let node = result.tree().root_node().child_by_field_name("expression").unwrap();
+            assert_eq!(node.kind(), kind, "{source}");
+            assert_eq!(is_interpolated_path(node, source), source == "./a/${b}.nix");
+            assert_eq!(NodeType::of(node, source), Some(node_type), "{source}");
+        }
+    }
+
     #[test]
     fn test_node_type_conversion() {
         assert_eq!(NodeType::Integer.as_str(), "integer");
@@ -400,6 +666,28 @@ mod tests {
         assert_eq!(format!("{}", FieldName::Right), "right");
     }
 
+    #[test]
+    fn test_grammar_layers() {
+        let basic = GrammarLayer::Basic;
+        assert!(basic.allowed_nodes().contains(&"integer"));
+        assert!(!basic.allowed_nodes().contains(&"import"));
+
+        let advanced = GrammarLayer::Advanced;
+        assert!(advanced.allowed_nodes().contains(&"import"));
+
+        // Layers include the nodes of the layers before them
+        assert!(advanced.allows("integer"));
+        assert!(advanced.allows("let_expression"));
+        assert!(!basic.allows("let_expression"));
+        assert!(!GrammarLayer::Standard.allows("with_expression"));
+        assert!(GrammarLayer::Experimental.allows("anything"));
+
+        // Every node kind of the grammar belongs to some layer
+        for node_type in NodeType::all().iter().filter(|node_type| !node_type.is_error()) {
+            assert!(advanced.allows(node_type.as_str()), "{node_type} is in no layer");
+        }
+    }
+
     #[test]
     fn test_node_types_coverage() {
         // Ensure all node types have string representations
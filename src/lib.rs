@@ -12,6 +12,9 @@
 //!
 //! let mut parser = NixParser::new()?;
 //! let result = parser.parse("{ x = 1; y = 2; }")?;
+//!
+//! // Or, for a one-off expression
+//! let expr = parse_expr("{ x = 1; y = 2; }")?;
 //! # Ok::<(), nix_parser::ParseError>(())
 //! ```
 //!
@@ -22,11 +25,21 @@
 //! - **Extensible**: Plugin system for custom analysis and transformations
 //! - **Specification Compliant**: Follows official Nix language grammar
 //! - **Memory Safe**: Written in Rust with zero-cost abstractions
+//!
+//! The `memory-stats` feature measures allocations by installing a
+//! `#[global_allocator]`. It is meant for binaries only: a library enabling
+//! it would clash with the allocator of any program depending on it, so it
+//! is not part of the `all` feature.
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
+// Getters and builders are not annotated one by one
+#![allow(clippy::must_use_candidate, clippy::return_self_not_must_use)]
+// `ParseError` carries its context inline; boxing it would change the
+// signature of every fallible function of the API
+#![allow(clippy::result_large_err)]
 
 // Core parsing functionality
 pub mod parser;
@@ -36,6 +49,7 @@ pub mod scanner;
 // AST and node types
 pub mod ast;
 pub mod visitor;
+pub mod query;
 
 // Error handling
 pub mod error;
@@ -68,8 +82,12 @@ pub mod python;
 #[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
 pub mod cli;
 
+#[cfg(feature = "lsp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lsp")))]
+pub mod lsp;
+
 // Re-exports for convenience
-pub use crate::parser::NixParser;
+pub use crate::parser::{NixParser, parse, parse_expr};
 pub use crate::ast::{Expression, Node, SourceLocation};
 pub use crate::error::{ParseError, Result};
 
@@ -78,17 +96,18 @@ pub use crate::error::{ParseError, Result};
 /// This module re-exports the most commonly used types and traits
 /// for convenient importing.
 pub mod prelude {
-    //! Common imports for working with the Nix parser
-    
-    pub use crate::parser::NixParser;
+    pub use crate::parser::{NixParser, parse, parse_expr};
     pub use crate::ast::{Expression, Node, SourceLocation};
     pub use crate::error::{ParseError, Result};
-    pub use crate::visitor::Visitor;
+    pub use crate::visitor::{VisitControl, Visitor, VisitorMut};
     
     #[cfg(feature = "plugins")]
     pub use crate::plugins::Plugin;
 }
 
+#[cfg(test)]
+mod test_utils;
+
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -106,6 +125,6 @@ mod tests {
     fn test_version_info() {
         assert!(!VERSION.is_empty());
         assert!(!SUPPORTED_NIX_VERSION.is_empty());
-        assert!(MIN_TREE_SITTER_ABI >= 13);
+        const { assert!(MIN_TREE_SITTER_ABI >= 13) };
     }
 }
\ No newline at end of file
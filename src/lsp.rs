@@ -0,0 +1,160 @@
+//! Conversion of diagnostics to Language Server Protocol types
+//!
+//! Tree-sitter reports columns in bytes, while LSP positions count UTF-16
+//! code units, so every conversion needs the source the diagnostic was
+//! produced from.
+
+use lsp_types::{DiagnosticSeverity as LspSeverity, NumberOrString, Position, Range};
+
+use crate::error::{Diagnostic, Severity};
+use crate::parser::{DiagnosticSeverity, ParseDiagnostic};
+
+impl ParseDiagnostic {
+    /// Convert to an LSP diagnostic
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source code the diagnostic refers to
+    pub fn to_lsp_diagnostic(&self, source: &str) -> lsp_types::Diagnostic {
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => LspSeverity::ERROR,
+            DiagnosticSeverity::Warning => LspSeverity::WARNING,
+            DiagnosticSeverity::Info => LspSeverity::INFORMATION,
+        };
+
+        lsp_types::Diagnostic {
+            range: Range::new(
+                byte_offset_to_lsp_position(source, self.location.start_byte),
+                byte_offset_to_lsp_position(source, self.location.end_byte),
+            ),
+            severity: Some(severity),
+            code: self.code.clone().map(|code| NumberOrString::String(code.into())),
+            source: self.source.clone(),
+            message: self.message.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Convert to an LSP diagnostic
+    ///
+    /// The LSP range ends at `Diagnostic::end`, and is empty if the
+    /// diagnostic is a single point. Diagnostics without a location are
+    /// placed at the start of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source code the diagnostic refers to
+    pub fn to_lsp_diagnostic(&self, source: &str) -> lsp_types::Diagnostic {
+        let severity = match self.severity {
+            Severity::Error => LspSeverity::ERROR,
+            Severity::Warning => LspSeverity::WARNING,
+            Severity::Info => LspSeverity::INFORMATION,
+            Severity::Hint => LspSeverity::HINT,
+        };
+
+        let range = if self.has_location() {
+            let start = line_column_to_lsp_position(source, self.line, self.column);
+            let end = self.end.map_or(start, |(line, column)| line_column_to_lsp_position(source, line, column));
+            Range::new(start, end)
+        } else {
+            Range::default()
+        };
+
+        lsp_types::Diagnostic {
+            range,
+            severity: Some(severity),
+            source: Some("nix-parser".to_string()),
+            message: self.message.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Convert a byte offset into `source` to a 0-based LSP position
+///
+/// Offsets past the end of the source, or inside a character, are moved
+/// back to the nearest character boundary.
+pub fn byte_offset_to_lsp_position(source: &str, byte_offset: usize) -> Position {
+    let mut offset = byte_offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+    // LSP positions are 32-bit; sources that long are clamped
+    Position::new(u32::try_from(line).unwrap_or(u32::MAX), u32::try_from(character).unwrap_or(u32::MAX))
+}
+
+/// Convert a 1-based line and byte column to a 0-based LSP position
+fn line_column_to_lsp_position(source: &str, line: usize, column: usize) -> Position {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let line_end = source[line_start..].find('\n').map_or(source.len(), |end| line_start + end);
+    let offset = (line_start + column.saturating_sub(1)).min(line_end);
+    byte_offset_to_lsp_position(source, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use crate::parser::NixParser;
+
+    fn first_diagnostic(source: &str) -> ParseDiagnostic {
+        let mut parser = NixParser::new().unwrap();
+        parser.parse(source).unwrap().diagnostics()[0].clone()
+    }
+
+    #[test]
+    fn test_parse_diagnostic_to_lsp() {
+        // The emoji is 4 bytes but 2 UTF-16 code units
+        let source = "[ \"😀\" ) ]";
+        let diagnostic = first_diagnostic(source).to_lsp_diagnostic(source);
+
+        assert_eq!(diagnostic.range, Range::new(Position::new(0, 7), Position::new(0, 8)));
+        assert_eq!(diagnostic.severity, Some(LspSeverity::ERROR));
+        assert_eq!(diagnostic.code, Some(NumberOrString::String("syntax_error".to_string())));
+        assert_eq!(diagnostic.source.as_deref(), Some("nix-parser"));
+        assert_eq!(diagnostic.message, "Syntax error near: ')'");
+
+        let source = "{\n  s = \"ü\";\n  x = ;\n}";
+        let diagnostic = first_diagnostic(source).to_lsp_diagnostic(source);
+        assert_eq!(diagnostic.range, Range::new(Position::new(2, 5), Position::new(2, 5)));
+        assert_eq!(diagnostic.code, Some(NumberOrString::String("missing_node".to_string())));
+    }
+
+    #[test]
+    fn test_diagnostic_to_lsp() {
+        let source = "let\n  ü = 1; x = ;\nin x";
+        let diagnostic = ParseError::syntax_error(2, 15, "missing value").to_diagnostic();
+        let lsp = diagnostic.to_lsp_diagnostic(source);
+        assert_eq!(lsp.range, Range::new(Position::new(1, 13), Position::new(1, 13)));
+        assert_eq!(lsp.severity, Some(LspSeverity::ERROR));
+
+        let spanned = Diagnostic::warning(2, 3, "unused").with_end(3, 3).to_lsp_diagnostic(source);
+        assert_eq!(spanned.range, Range::new(Position::new(1, 2), Position::new(2, 2)));
+
+        let hint = Diagnostic::hint(0, 0, "no location").to_lsp_diagnostic(source);
+        assert_eq!(hint.range, Range::default());
+        assert_eq!(hint.severity, Some(LspSeverity::HINT));
+    }
+
+    #[test]
+    fn test_byte_offset_to_lsp_position() {
+        let source = "a\n😀b\n";
+        assert_eq!(byte_offset_to_lsp_position(source, 0), Position::new(0, 0));
+        assert_eq!(byte_offset_to_lsp_position(source, 2), Position::new(1, 0));
+        assert_eq!(byte_offset_to_lsp_position(source, 6), Position::new(1, 2));
+        // Inside the emoji, and past the end
+        assert_eq!(byte_offset_to_lsp_position(source, 4), Position::new(1, 0));
+        assert_eq!(byte_offset_to_lsp_position(source, 100), Position::new(2, 0));
+    }
+}
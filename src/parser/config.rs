@@ -2,15 +2,25 @@
 
 use std::collections::HashMap;
 
+use crate::error::{ParseError, RecoveryStrategy, Result};
+use crate::grammar::GrammarLayer;
+
+/// URI schemes that are shorthand for flake references
+const FLAKE_URL_SCHEMES: &[&str] = &["flake", "github", "gitlab", "sourcehut"];
+
 /// Configuration for the Nix parser
 ///
 /// This struct controls various aspects of parser behavior,
 /// including error handling, optimization settings, and language features.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ParserConfig {
     /// Whether to allow parsing with syntax errors
     pub allow_errors: bool,
     
+    /// How to react to syntax errors
+    pub recovery_strategy: RecoveryStrategy,
+    
     /// Whether to validate output after parsing
     pub validate_output: bool,
     
@@ -20,6 +30,10 @@ pub struct ParserConfig {
     /// Language version to target
     pub language_version: LanguageVersion,
     
+    /// Grammar layer restricting the accepted constructs;
+    /// `GrammarLayer::Experimental` accepts everything
+    pub grammar_layer: GrammarLayer,
+    
     /// Whether to include location information in AST nodes
     pub include_locations: bool,
     
@@ -29,9 +43,17 @@ pub struct ParserConfig {
     /// Whether to preserve whitespace information
     pub preserve_whitespace: bool,
     
+    /// Whether to keep indented strings as written instead of removing
+    /// their common indentation
+    pub raw_indented_strings: bool,
+    
     /// Maximum nesting depth to prevent stack overflow
     pub max_nesting_depth: Option<usize>,
     
+    /// Maximum size of the source in bytes, beyond which it is rejected
+    /// without being parsed
+    pub max_source_bytes: Option<usize>,
+    
     /// Custom feature flags
     pub feature_flags: HashMap<String, bool>,
     
@@ -40,22 +62,31 @@ pub struct ParserConfig {
     
     /// Whether to collect parsing statistics
     pub collect_statistics: bool,
+    
+    /// Whether to check the structure of flakes, reporting problems as
+    /// diagnostics
+    pub validate_flakes: bool,
 }
 
 impl Default for ParserConfig {
     fn default() -> Self {
         Self {
             allow_errors: true,
+            recovery_strategy: RecoveryStrategy::Continue,
             validate_output: false,
             incremental_parsing: true,
             language_version: LanguageVersion::Latest,
+            grammar_layer: GrammarLayer::Experimental,
             include_locations: true,
             include_comments: false,
             preserve_whitespace: false,
+            raw_indented_strings: false,
             max_nesting_depth: Some(1000),
+            max_source_bytes: None,
             feature_flags: HashMap::new(),
             timeout_ms: None,
             collect_statistics: false,
+            validate_flakes: false,
         }
     }
 }
@@ -121,7 +152,7 @@ impl ParserConfig {
 ///
 /// Different versions of Nix have slightly different language features.
 /// This enum allows targeting specific versions for compatibility.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum LanguageVersion {
     /// Nix 2.3 LTS
     Nix23,
@@ -132,17 +163,12 @@ pub enum LanguageVersion {
     /// Nix 2.18 (current stable)
     Nix218,
     /// Latest version (may include experimental features)
+    #[default]
     Latest,
     /// Experimental features enabled
     Experimental,
 }
 
-impl Default for LanguageVersion {
-    fn default() -> Self {
-        Self::Latest
-    }
-}
-
 impl LanguageVersion {
     /// Get all supported language versions
     pub const fn all() -> &'static [LanguageVersion] {
@@ -178,12 +204,28 @@ impl LanguageVersion {
                  LanguageVersion::Experimental)
     }
     
+    /// Check if the legacy `let { ... }` form is accepted
+    ///
+    /// It evaluates to the `body` attribute of the recursive set, and has
+    /// been deprecated since before flakes were introduced.
+    pub const fn supports_legacy_let(self) -> bool {
+        !self.supports_flakes()
+    }
+    
     /// Check if the `or` keyword is supported
     pub const fn supports_or_keyword(self) -> bool {
         // All versions support 'or' keyword for backward compatibility
         true
     }
     
+    /// Check if unquoted URI literals such as `https://example.com` are accepted
+    ///
+    /// URI literals are deprecated; experimental mode behaves as if the
+    /// `no-url-literals` feature were enabled.
+    pub const fn supports_uri_literals(self) -> bool {
+        !matches!(self, LanguageVersion::Experimental)
+    }
+    
     /// Check if scientific notation is supported for floats
     pub const fn supports_scientific_notation(self) -> bool {
         matches!(self,
@@ -192,6 +234,44 @@ impl LanguageVersion {
                  LanguageVersion::Latest |
                  LanguageVersion::Experimental)
     }
+    
+    /// Check that a literal token is valid syntax for this version
+    ///
+    /// `kind` is the Tree-sitter node kind of the token and `text` its source.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::FeatureNotSupported`, with a suggestion, if the
+    /// literal relies on syntax this version does not accept.
+    pub fn check_literal(self, kind: &str, text: &str) -> Result<()> {
+        match kind {
+            "float" if text.contains(['e', 'E']) && !self.supports_scientific_notation() => {
+                Err(ParseError::feature_not_supported_with_suggestion(
+                    format!("scientific notation in float literal '{}' (Nix {})", text, self.as_str()),
+                    "target Nix 2.8 or later to use exponents in float literals",
+                ))
+            }
+            "uri" if !self.supports_uri_literals() => {
+                Err(ParseError::feature_not_supported_with_suggestion(
+                    format!("URI literal '{}' (Nix {})", text, self.as_str()),
+                    format!("quote the URI as a string: \"{text}\""),
+                ))
+            }
+            "uri" if is_flake_url(text) && !self.supports_flakes() => {
+                Err(ParseError::feature_not_supported_with_suggestion(
+                    format!("flake reference '{}' (Nix {})", text, self.as_str()),
+                    "target Nix 2.4 or later to use flake references",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check if a URI uses one of the flake reference shorthands such as `github:`
+fn is_flake_url(text: &str) -> bool {
+    text.split_once(':')
+        .is_some_and(|(scheme, _)| FLAKE_URL_SCHEMES.contains(&scheme))
 }
 
 /// Builder for `ParserConfig`
@@ -216,6 +296,12 @@ impl ParserConfigBuilder {
         self
     }
     
+    /// Set the error recovery strategy
+    pub fn recovery_strategy(mut self, strategy: RecoveryStrategy) -> Self {
+        self.config.recovery_strategy = strategy;
+        self
+    }
+    
     /// Set whether to validate output
     pub fn validate_output(mut self, validate: bool) -> Self {
         self.config.validate_output = validate;
@@ -228,6 +314,12 @@ impl ParserConfigBuilder {
         self
     }
     
+    /// Restrict the accepted constructs to a grammar layer
+    pub fn grammar_layer(mut self, layer: GrammarLayer) -> Self {
+        self.config.grammar_layer = layer;
+        self
+    }
+    
     /// Set whether to include locations
     pub fn include_locations(mut self, include: bool) -> Self {
         self.config.include_locations = include;
@@ -246,12 +338,24 @@ impl ParserConfigBuilder {
         self
     }
     
+    /// Set whether to keep indented strings as written
+    pub fn raw_indented_strings(mut self, raw: bool) -> Self {
+        self.config.raw_indented_strings = raw;
+        self
+    }
+    
     /// Set maximum nesting depth
     pub fn max_nesting_depth(mut self, depth: Option<usize>) -> Self {
         self.config.max_nesting_depth = depth;
         self
     }
     
+    /// Set maximum source size in bytes
+    pub fn max_source_bytes(mut self, limit: Option<usize>) -> Self {
+        self.config.max_source_bytes = limit;
+        self
+    }
+    
     /// Set timeout
     pub fn timeout_ms(mut self, timeout: Option<u64>) -> Self {
         self.config.timeout_ms = timeout;
@@ -264,6 +368,12 @@ impl ParserConfigBuilder {
         self
     }
     
+    /// Set whether to check the structure of flakes
+    pub fn validate_flakes(mut self, validate: bool) -> Self {
+        self.config.validate_flakes = validate;
+        self
+    }
+    
     /// Enable a feature flag
     pub fn enable_feature(mut self, name: impl Into<String>) -> Self {
         self.config.enable_feature(name);
@@ -322,6 +432,28 @@ mod tests {
         assert!(!LanguageVersion::Nix23.supports_flakes());
         assert!(LanguageVersion::Nix218.supports_scientific_notation());
         assert!(!LanguageVersion::Nix24.supports_scientific_notation());
+        assert!(LanguageVersion::Nix23.supports_uri_literals());
+        assert!(!LanguageVersion::Experimental.supports_uri_literals());
+    }
+
+    #[test]
+    fn test_check_literal() {
+        assert!(LanguageVersion::Latest.check_literal("float", "1e3").is_ok());
+        assert!(LanguageVersion::Nix23.check_literal("float", "1.5").is_ok());
+        assert!(LanguageVersion::Nix23.check_literal("float", "1e3").is_err());
+
+        assert!(LanguageVersion::Nix23.check_literal("uri", "https://nixos.org").is_ok());
+        assert!(LanguageVersion::Nix24.check_literal("uri", "github:NixOS/nixpkgs").is_ok());
+        assert!(LanguageVersion::Experimental.check_literal("uri", "https://nixos.org").is_err());
+
+        let err = LanguageVersion::Nix23.check_literal("uri", "github:NixOS/nixpkgs").unwrap_err();
+        match err {
+            ParseError::FeatureNotSupported { feature, suggestion } => {
+                assert!(feature.contains("github:NixOS/nixpkgs"));
+                assert!(suggestion.is_some());
+            }
+            _ => panic!("Expected FeatureNotSupported error"),
+        }
     }
 
     #[test]
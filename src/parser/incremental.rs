@@ -18,6 +18,11 @@ pub struct IncrementalParser {
 
 impl IncrementalParser {
     /// Create a new incremental parser
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::LanguageError` if the underlying [`NixParser`]
+    /// cannot be created.
     pub fn new() -> Result<Self> {
         Ok(Self {
             parser: NixParser::new()?,
@@ -35,6 +40,10 @@ impl IncrementalParser {
     /// # Returns
     ///
     /// A `ParseResult` containing the parsed tree and diagnostics.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if parsing fails, as [`NixParser::parse`] does.
     pub fn parse_document(&mut self, document_id: impl Into<String>, source: &str) -> Result<ParseResult> {
         let doc_id = document_id.into();
         let result = self.parser.parse(source)?;
@@ -53,12 +62,19 @@ impl IncrementalParser {
     /// # Arguments
     ///
     /// * `document_id` - Unique identifier for the document
-    /// * `changes` - List of changes to apply
+    /// * `changes` - List of changes to apply, in order; as in LSP, each
+    ///   change's positions refer to the document after the previous ones
     /// * `new_source` - The updated source code
     ///
     /// # Returns
     ///
     /// A `ParseResult` with the incrementally updated tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ValidationError` if the document is not tracked,
+    /// or if applying `changes` to its source does not yield `new_source`.
+    /// The stored document is left unchanged in that case.
     pub fn update_document(
         &mut self, 
         document_id: &str, 
@@ -67,14 +83,21 @@ impl IncrementalParser {
     ) -> Result<ParseResult> {
         let doc_state = self.document_trees.get_mut(document_id)
             .ok_or_else(|| ParseError::ValidationError(
-                format!("Document '{}' not found. Call parse_document first.", document_id)
+                format!("Document '{document_id}' not found. Call parse_document first.")
             ))?;
 
         // Apply edits to the existing tree
         let mut tree = doc_state.tree.clone();
+        let mut source = doc_state.source.clone();
         for change in changes {
-            let edit = change.to_input_edit(&doc_state.source, new_source);
+            let edit = change.to_input_edit(&source);
             tree.edit(&edit);
+            source.replace_range(edit.start_byte..edit.old_end_byte, &change.new_text);
+        }
+        if source != new_source {
+            return Err(ParseError::ValidationError(format!(
+                "Changes to document '{document_id}' do not produce the new source"
+            )));
         }
 
         // Re-parse with the old tree for incremental parsing
@@ -98,6 +121,16 @@ impl IncrementalParser {
         self.document_trees.get(document_id).map(|state| state.version)
     }
 
+    /// Get the latest syntax tree of a document
+    pub fn document_tree(&self, document_id: &str) -> Option<&Tree> {
+        self.document_trees.get(document_id).map(|state| &state.tree)
+    }
+
+    /// Get the latest source code of a document
+    pub fn document_source(&self, document_id: &str) -> Option<&str> {
+        self.document_trees.get(document_id).map(|state| state.source.as_str())
+    }
+
     /// Check if a document is being tracked
     pub fn has_document(&self, document_id: &str) -> bool {
         self.document_trees.contains_key(document_id)
@@ -174,19 +207,63 @@ impl TextChange {
         Self::new(start, end, text)
     }
 
-    /// Convert to Tree-sitter's InputEdit format
-    fn to_input_edit(&self, old_source: &str, new_source: &str) -> InputEdit {
-        let old_start_byte = position_to_byte_offset(old_source, self.start);
-        let old_end_byte = position_to_byte_offset(old_source, self.end);
-        let new_end_byte = old_start_byte + self.new_text.len();
+    /// Compute the single change turning `old` into `new`
+    ///
+    /// The change replaces the smallest range that differs, between the
+    /// longest common prefix and suffix of the two sources. Returns `None`
+    /// if they are equal.
+    pub fn between(old: &str, new: &str) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+
+        let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+        while !old.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+            suffix -= 1;
+        }
+
+        Some(Self::replace(
+            Position::from_byte_offset(old, prefix),
+            Position::from_byte_offset(old, old.len() - suffix),
+            &new[prefix..new.len() - suffix],
+        ))
+    }
+
+    /// Convert to Tree-sitter's `InputEdit` format
+    ///
+    /// The change's positions count UTF-16 code units, while Tree-sitter
+    /// points count bytes, so every point is derived from a byte offset.
+    fn to_input_edit(&self, old_source: &str) -> InputEdit {
+        let start_byte = position_to_byte_offset(old_source, self.start);
+        let old_end_byte = position_to_byte_offset(old_source, self.end).max(start_byte);
+        let new_end_byte = start_byte + self.new_text.len();
+
+        let start_position = byte_offset_to_point(old_source, start_byte);
+        let new_end_position = byte_offset_to_point(&self.new_text, self.new_text.len());
+        let new_end_position = if new_end_position.row == 0 {
+            Point::new(start_position.row, start_position.column + new_end_position.column)
+        } else {
+            Point::new(start_position.row + new_end_position.row, new_end_position.column)
+        };
 
         InputEdit {
-            start_byte: old_start_byte,
+            start_byte,
             old_end_byte,
             new_end_byte,
-            start_position: Point::new(self.start.line, self.start.character),
-            old_end_position: Point::new(self.end.line, self.end.character),
-            new_end_position: byte_offset_to_position(new_source, new_end_byte),
+            start_position,
+            old_end_position: byte_offset_to_point(old_source, old_end_byte),
+            new_end_position,
         }
     }
 }
@@ -211,6 +288,16 @@ impl Position {
     pub const fn zero() -> Self {
         Self::new(0, 0)
     }
+
+    /// Get the position of a byte offset into `source`
+    pub fn from_byte_offset(source: &str, byte_offset: usize) -> Self {
+        byte_offset_to_position(source, byte_offset)
+    }
+
+    /// Get the byte offset of the position in `source`
+    pub fn to_byte_offset(self, source: &str) -> usize {
+        position_to_byte_offset(source, self)
+    }
 }
 
 /// Statistics about incremental parsing state
@@ -224,45 +311,49 @@ pub struct IncrementalStats {
 }
 
 // Helper functions for position and byte offset conversion
+//
+// A `Position` counts UTF-16 code units, as LSP does, while a Tree-sitter
+// `Point` counts bytes.
 
+/// Convert a position to a byte offset
+///
+/// As in LSP, a character offset past the end of its line means the end of
+/// the line, and a line past the end of the source means its end.
 fn position_to_byte_offset(source: &str, position: Position) -> usize {
-    let mut current_line = 0;
-    let mut current_char = 0;
-    
-    for (byte_offset, ch) in source.char_indices() {
-        if current_line == position.line && current_char == position.character {
-            return byte_offset;
-        }
-        
-        if ch == '\n' {
-            current_line += 1;
-            current_char = 0;
-        } else {
-            current_char += ch.len_utf16();
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match source[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return source.len(),
         }
     }
-    
-    source.len()
-}
 
-fn byte_offset_to_position(source: &str, byte_offset: usize) -> Point {
-    let mut line = 0;
-    let mut column = 0;
-    
-    for (offset, ch) in source.char_indices() {
-        if offset >= byte_offset {
-            break;
-        }
-        
-        if ch == '\n' {
-            line += 1;
-            column = 0;
-        } else {
-            column += ch.len_utf8();
+    let line = &source[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let mut units = 0;
+    for (offset, ch) in line.char_indices() {
+        if units >= position.character {
+            return line_start + offset;
         }
+        units += ch.len_utf16();
     }
-    
-    Point::new(line, column)
+    line_start + line.len()
+}
+
+/// Convert a byte offset to a position, the inverse of `position_to_byte_offset`
+fn byte_offset_to_position(source: &str, byte_offset: usize) -> Position {
+    let point = byte_offset_to_point(source, byte_offset);
+    let line_start = byte_offset.min(source.len()) - point.column;
+    let character = source[line_start..line_start + point.column].encode_utf16().count();
+    Position::new(point.row, character)
+}
+
+/// Convert a byte offset to a Tree-sitter point
+fn byte_offset_to_point(source: &str, byte_offset: usize) -> Point {
+    let before = &source.as_bytes()[..byte_offset.min(source.len())];
+    let line_start = before.iter().rposition(|&b| b == b'\n').map_or(0, |newline| newline + 1);
+    let line = before.split(|&b| b == b'\n').count() - 1;
+    Point::new(line, before.len() - line_start)
 }
 
 #[cfg(test)]
@@ -325,9 +416,143 @@ mod tests {
         assert_eq!(byte_offset, 6); // After "line1\n"
         
         // Test byte offset to position
-        let point = byte_offset_to_position(source, 6);
+        let point = byte_offset_to_point(source, 6);
         assert_eq!(point.row, 1);
         assert_eq!(point.column, 0);
+        assert_eq!(byte_offset_to_position(source, 6), pos);
+    }
+
+    #[test]
+    fn test_document_tree_and_source() {
+        let mut parser = IncrementalParser::new().unwrap();
+        assert!(parser.document_tree("test.nix").is_none());
+        assert!(parser.document_source("test.nix").is_none());
+
+        parser.parse_document("test.nix", "[ 1 ]").unwrap();
+        assert_eq!(parser.document_source("test.nix"), Some("[ 1 ]"));
+
+        let changes = [TextChange::insert(Position::new(0, 4), "2 ")];
+        parser.update_document("test.nix", &changes, "[ 1 2 ]").unwrap();
+        assert_eq!(parser.document_source("test.nix"), Some("[ 1 2 ]"));
+
+        let root = parser.document_tree("test.nix").unwrap().root_node();
+        let full = NixParser::new().unwrap().parse("[ 1 2 ]").unwrap();
+        assert_eq!(root.end_byte(), 7);
+        assert_eq!(root.to_sexp(), full.tree().root_node().to_sexp());
+
+        parser.remove_document("test.nix");
+        assert!(parser.document_tree("test.nix").is_none());
+    }
+
+    #[test]
+    fn test_mismatched_changes_rejected() {
+        let mut parser = IncrementalParser::new().unwrap();
+        parser.parse_document("test.nix", "{ x = 1; }").unwrap();
+
+        // The change inserts `2`, but the new source has `3`
+        let changes = [TextChange::replace(Position::new(0, 6), Position::new(0, 7), "2")];
+        let result = parser.update_document("test.nix", &changes, "{ x = 3; }");
+        assert!(matches!(result, Err(ParseError::ValidationError(_))));
+
+        // No changes at all for a modified source
+        let result = parser.update_document("test.nix", &[], "{ x = 3; }");
+        assert!(matches!(result, Err(ParseError::ValidationError(_))));
+
+        assert_eq!(parser.document_version("test.nix"), Some(1));
+        assert_eq!(parser.document_source("test.nix"), Some("{ x = 1; }"));
+    }
+
+    #[test]
+    fn test_position_conversion_multibyte() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `😀` is 4 bytes and 2 units
+        let source = "é😀x\nab";
+        assert_eq!(position_to_byte_offset(source, Position::new(0, 1)), 2);
+        assert_eq!(position_to_byte_offset(source, Position::new(0, 3)), 6);
+        assert_eq!(position_to_byte_offset(source, Position::new(0, 99)), 7);
+        assert_eq!(position_to_byte_offset(source, Position::new(1, 1)), 9);
+        assert_eq!(position_to_byte_offset(source, Position::new(5, 0)), source.len());
+
+        for (offset, position) in [(0, (0, 0)), (2, (0, 1)), (6, (0, 3)), (7, (0, 4)), (9, (1, 1))] {
+            assert_eq!(byte_offset_to_position(source, offset), Position::new(position.0, position.1));
+        }
+        assert_eq!(byte_offset_to_point(source, 6), Point::new(0, 6));
+        assert_eq!(Position::new(0, 3).to_byte_offset(source), 6);
+        assert_eq!(Position::from_byte_offset(source, 6), Position::new(0, 3));
+    }
+
+    #[test]
+    fn test_update_after_multibyte_characters() {
+        let old_source = "{ s = \"😀é\"; x = 1; }";
+        let new_source = "{ s = \"😀é\"; x = [ 22 ]; }";
+        let mut parser = IncrementalParser::new().unwrap();
+        parser.parse_document("test.nix", old_source).unwrap();
+
+        // `1` is at UTF-16 column 17 but byte column 20
+        let changes = [TextChange::replace(Position::new(0, 17), Position::new(0, 18), "[ 22 ]")];
+        let edit = changes[0].to_input_edit(old_source);
+        assert_eq!((edit.start_byte, edit.old_end_byte, edit.new_end_byte), (20, 21, 26));
+        assert_eq!((edit.start_position, edit.old_end_position), (Point::new(0, 20), Point::new(0, 21)));
+        assert_eq!(edit.new_end_position, Point::new(0, 26));
+        let mut edited = old_source.to_string();
+        edited.replace_range(edit.start_byte..edit.old_end_byte, "[ 22 ]");
+        assert_eq!(edited, new_source);
+
+        let incremental = parser.update_document("test.nix", &changes, new_source).unwrap();
+        let full = NixParser::new().unwrap().parse(new_source).unwrap();
+
+        assert_eq!(incremental.tree().root_node().to_sexp(), full.tree().root_node().to_sexp());
+        assert_eq!(
+            format!("{:?}", incremental.expression().unwrap()),
+            format!("{:?}", full.expression().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_sequential_multiline_changes() {
+        let mut parser = IncrementalParser::new().unwrap();
+        parser.parse_document("test.nix", "{\n  s = \"ü\";\n}").unwrap();
+
+        // The second change refers to the document after the first
+        let changes = [
+            TextChange::insert(Position::new(1, 10), "\n  t = [\n    2\n  ];"),
+            TextChange::replace(Position::new(3, 4), Position::new(3, 5), "3"),
+        ];
+        let new_source = "{\n  s = \"ü\";\n  t = [\n    3\n  ];\n}";
+        let incremental = parser.update_document("test.nix", &changes, new_source).unwrap();
+        let full = NixParser::new().unwrap().parse(new_source).unwrap();
+
+        assert!(!incremental.has_errors());
+        assert_eq!(
+            format!("{:?}", incremental.expression().unwrap()),
+            format!("{:?}", full.expression().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_change_between() {
+        assert_eq!(TextChange::between("{ x = 1; }", "{ x = 1; }"), None);
+        assert_eq!(
+            TextChange::between("{ x = 1; }", "{ x = 12; }"),
+            Some(TextChange::insert(Position::new(0, 7), "2"))
+        );
+        assert_eq!(
+            TextChange::between("{\n  x = 1;\n}", "{\n}"),
+            Some(TextChange::delete(Position::new(1, 0), Position::new(2, 0)))
+        );
+        // `é` and `è` share their first byte, which must not be split
+        assert_eq!(
+            TextChange::between("\"é\"", "\"è\""),
+            Some(TextChange::replace(Position::new(0, 1), Position::new(0, 2), "è"))
+        );
+
+        let old_source = "let a = \"😀\"; in a";
+        let new_source = "let a = \"😀!\"; b = 2; in a";
+        let mut parser = IncrementalParser::new().unwrap();
+        parser.parse_document("test.nix", old_source).unwrap();
+        let change = TextChange::between(old_source, new_source).unwrap();
+        let incremental = parser.update_document("test.nix", &[change], new_source).unwrap();
+        assert!(!incremental.has_errors());
+        assert_eq!(parser.document_source("test.nix"), Some(new_source));
     }
 
     #[test]
@@ -343,6 +568,6 @@ mod tests {
         
         let stats = parser.stats();
         assert_eq!(stats.document_count, 2);
-        assert_eq!(stats.total_source_size, 18); // 9 + 9 characters
+        assert_eq!(stats.total_source_size, 20); // 10 + 10 bytes
     }
 }
\ No newline at end of file
@@ -5,16 +5,27 @@
 mod config;
 mod result;
 mod incremental;
+mod cancellation;
+mod suggestions;
 
 pub use self::config::{ParserConfig, LanguageVersion};
-pub use self::result::{ParseResult, ParseDiagnostic};
-pub use self::incremental::IncrementalParser;
+pub use self::result::{ParseResult, ParseResultJson, ParseDiagnostic, DiagnosticCode, DiagnosticSeverity};
+pub use self::incremental::{IncrementalParser, Position, TextChange};
+pub use self::cancellation::CancellationToken;
 
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tree_sitter::{Parser, Tree, Language};
+use std::time::{Duration, Instant};
+use tree_sitter::{Parser, Tree, Language, ParseOptions, ParseState};
 
-// use crate::ast::Expression; // Not needed for this module interface
-use crate::error::{ParseError, Result};
+use crate::analysis::validate_flake;
+use crate::ast::{Expression, SourceLocation, integer_out_of_range, legacy_let_keyword};
+use crate::error::{ErrorContext, ErrorRecovery, ParseError, RecoveryStrategy, Result};
+use crate::utils::fs::{find_nix_files, is_nix_file};
+use crate::utils::{MemoryStats, NodeExt, Timer, TimingResult};
+#[cfg(feature = "memory-stats")]
+use crate::utils::MemoryTracker;
 
 #[cfg(feature = "cache")]
 use crate::cache::ParseCache;
@@ -22,7 +33,7 @@ use crate::cache::ParseCache;
 #[cfg(feature = "plugins")]
 use crate::plugins::Plugin;
 
-extern "C" {
+unsafe extern "C" {
     fn tree_sitter_nix() -> Language;
 }
 
@@ -39,8 +50,8 @@ extern "C" {
 /// let mut parser = NixParser::new()?;
 /// let result = parser.parse("{ x = 1; y = 2; }")?;
 /// 
-/// match result.expression() {
-///     Some(Expression::AttrSet(_)) => println!("Parsed attribute set"),
+/// match result.expression()? {
+///     Some(Expression::AttributeSet { .. }) => println!("Parsed attribute set"),
 ///     _ => println!("Unexpected expression type"),
 /// }
 /// # Ok::<(), ParseError>(())
@@ -79,7 +90,7 @@ impl NixParser {
         let mut inner = Parser::new();
         
         inner.set_language(&language)
-            .map_err(|e| ParseError::LanguageError(format!("Failed to set language: {}", e)))?;
+            .map_err(|e| ParseError::LanguageError(format!("Failed to set language: {e}")))?;
 
         // Validate ABI compatibility
         if language.abi_version() < crate::MIN_TREE_SITTER_ABI as usize {
@@ -120,6 +131,148 @@ impl NixParser {
         self.parse_with_context(source, None)
     }
 
+    /// Parse a Nix file from disk
+    ///
+    /// The path is recorded on the returned `ParseResult`, and attached as
+    /// error context to any syntax or semantic error.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ValidationError` if the path does not have a Nix
+    /// extension, `ParseError::IoError` if the file cannot be read,
+    /// `ParseError::Utf8Error` if its contents are not valid UTF-8, and
+    /// otherwise the same errors as [`NixParser::parse`].
+    pub fn parse_file(&mut self, path: impl AsRef<Path>) -> Result<ParseResult> {
+        let path = path.as_ref();
+        let source = Self::read_file(path)?;
+        let mut result = self.parse(&source).map_err(|e| {
+            let context = ErrorContext {
+                file_path: Some(path.display().to_string()),
+                ..e.context().cloned().unwrap_or_default()
+            };
+            e.with_context(context)
+        })?;
+        result.set_file_path(Some(path.to_path_buf()));
+        Ok(result)
+    }
+
+    /// Read a Nix file, checking its extension and encoding like
+    /// [`NixParser::parse_file`]
+    pub(crate) fn read_file(path: &Path) -> Result<String> {
+        if !is_nix_file(path) {
+            return Err(ParseError::ValidationError(
+                format!("{} is not a Nix file", path.display())
+            ));
+        }
+
+        let bytes = std::fs::read(path)
+            .map_err(|e| ParseError::IoError(format!("{}: {}", path.display(), e)))?;
+        String::from_utf8(bytes).map_err(|e| e.utf8_error().into())
+    }
+
+    /// Parse Nix code read from `reader`, such as a pipe or a socket
+    ///
+    /// The whole input is read before parsing. With
+    /// `ParserConfig::max_source_bytes` set, at most one byte more than the
+    /// limit is read, so oversized input is rejected without being buffered.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if reading fails,
+    /// `ParseError::ResourceLimitExceeded` if the input is larger than
+    /// `ParserConfig::max_source_bytes`, `ParseError::Utf8Error` if it is
+    /// not valid UTF-8, and otherwise the same errors as
+    /// [`NixParser::parse`].
+    pub fn parse_reader(&mut self, mut reader: impl Read) -> Result<ParseResult> {
+        let mut bytes = Vec::new();
+        match self.config.max_source_bytes {
+            Some(limit) => {
+                let cap = u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1);
+                reader.take(cap).read_to_end(&mut bytes)?;
+                if bytes.len() > limit {
+                    return Err(ParseError::resource_limit("source_size", format!("{limit} bytes")));
+                }
+            }
+            None => {
+                reader.read_to_end(&mut bytes)?;
+            }
+        }
+        let source = String::from_utf8(bytes).map_err(|e| e.utf8_error())?;
+        self.parse(&source)
+    }
+
+    /// Parse several Nix files, returning each path with its result
+    ///
+    /// A failure in one file does not stop the others from being parsed.
+    pub fn parse_many(&mut self, paths: &[PathBuf]) -> Vec<(PathBuf, Result<ParseResult>)> {
+        paths.iter()
+            .map(|path| (path.clone(), self.parse_file(path)))
+            .collect()
+    }
+
+    /// Parse several Nix files in parallel, returning each path with its result
+    ///
+    /// A `tree_sitter::Parser` cannot be shared between threads, so every
+    /// worker gets its own parser with this parser's configuration (and
+    /// cache, if enabled). Plugins are not applied. Results are returned in
+    /// the same order as `paths`.
+    #[cfg(feature = "parallel")]
+    pub fn parse_many_parallel(&self, paths: &[PathBuf]) -> Vec<(PathBuf, Result<ParseResult>)> {
+        use rayon::prelude::*;
+
+        let config = &self.config;
+        #[cfg(feature = "cache")]
+        let cache = &self.cache;
+
+        paths.par_iter()
+            .map_init(
+                || {
+                    #[allow(unused_mut)]
+                    let mut parser = Self::with_config(config.clone());
+                    #[cfg(feature = "cache")]
+                    if let Ok(parser) = parser.as_mut() {
+                        parser.cache.clone_from(cache);
+                    }
+                    parser
+                },
+                |parser, path| {
+                    let result = match parser {
+                        Ok(parser) => parser.parse_file(path),
+                        Err(e) => Err(e.clone()),
+                    };
+                    (path.clone(), result)
+                },
+            )
+            .collect()
+    }
+
+    /// Parse every Nix file under a directory, recursively
+    ///
+    /// Files are parsed in path order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if the directory cannot be walked.
+    /// Per-file failures are reported alongside each path instead.
+    pub fn parse_directory(&mut self, dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, Result<ParseResult>)>> {
+        let paths = Self::nix_files_in(dir.as_ref())?;
+        Ok(self.parse_many(&paths))
+    }
+
+    /// Parse every Nix file under a directory, recursively and in parallel
+    ///
+    /// See [`NixParser::parse_many_parallel`] for how work is distributed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::IoError` if the directory cannot be walked.
+    /// Per-file failures are reported alongside each path instead.
+    #[cfg(feature = "parallel")]
+    pub fn parse_directory_parallel(&self, dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, Result<ParseResult>)>> {
+        let paths = Self::nix_files_in(dir.as_ref())?;
+        Ok(self.parse_many_parallel(&paths))
+    }
+
     /// Parse Nix source code with an existing tree for incremental parsing
     ///
     /// # Arguments
@@ -130,34 +283,166 @@ impl NixParser {
     /// # Returns
     ///
     /// A `ParseResult` containing the parsed tree and any diagnostics.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ResourceLimitExceeded` if the source is larger
+    /// than `ParserConfig::max_source_bytes`, `ParseError::Timeout` if
+    /// parsing exceeds the configured timeout.
+    ///
+    /// Under `RecoveryStrategy::FailFast`, returns the first syntax error as
+    /// `ParseError::SyntaxError`. Otherwise syntax errors are reported as
+    /// diagnostics, and `RecoveryStrategy::AutoFix` first tries to repair
+    /// the source, recording what it changed in `ParseResult::fixes`.
     pub fn parse_with_context(&mut self, source: &str, old_tree: Option<&Tree>) -> Result<ParseResult> {
+        self.parse_cancellable(source, old_tree, None)
+    }
+
+    /// Parse Nix source code, aborting if `token` is cancelled
+    ///
+    /// Tree-sitter checks the token periodically while parsing, so a parse
+    /// stops shortly after another thread cancels it. The token only
+    /// applies to this call.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ParseFailed("cancelled")` if `token` is
+    /// cancelled before parsing finishes, even when the result is cached,
+    /// and the same errors as [`NixParser::parse_with_context`] otherwise.
+    pub fn parse_with_cancellation(
+        &mut self,
+        source: &str,
+        old_tree: Option<&Tree>,
+        token: &CancellationToken,
+    ) -> Result<ParseResult> {
+        self.parse_cancellable(source, old_tree, Some(token))
+    }
+
+    /// Parse Nix source code through the cache, if any
+    fn parse_cancellable(
+        &mut self,
+        source: &str,
+        old_tree: Option<&Tree>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<ParseResult> {
+        // A shared cache may hold sources larger than this parser accepts
+        self.check_source_size(source)?;
+        // Tree-sitter only reports progress every so often, which short
+        // sources and cached ones never reach
+        if cancellation.is_some_and(CancellationToken::is_cancelled) {
+            return Err(ParseError::ParseFailed("cancelled".to_string()));
+        }
+
         // Check cache first
         #[cfg(feature = "cache")]
+        if let Some(ref cache) = self.cache
+            && let Some(cached_result) = cache.get(source)
+        {
+            return Ok(cached_result.clone());
+        }
+
+        let result = self.parse_uncached(source, old_tree, cancellation)?;
+
+        // Cache the result
+        #[cfg(feature = "cache")]
         if let Some(ref cache) = self.cache {
-            if let Some(cached_result) = cache.get(source) {
-                return Ok(cached_result.clone());
+            cache.insert(source, result.clone());
+        }
+
+        Ok(result)
+    }
+
+    /// Parse Nix source code into the serializable view of its result
+    ///
+    /// With a cache, a view found in memory or in the cache's disk layer is
+    /// returned without parsing, so a source parsed by an earlier process
+    /// is not parsed again. Results are cached by source alone, so a cache
+    /// should only be shared by parsers with the same configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`NixParser::parse`], and `ParseError` if
+    /// the AST cannot be built from the tree.
+    pub fn parse_view(&mut self, source: &str) -> Result<ParseResultJson> {
+        #[cfg(feature = "cache")]
+        if let Some(cache) = self.cache.clone() {
+            self.check_source_size(source)?;
+            if let Some(view) = cache.get_view(source) {
+                return Ok(view);
             }
+            let result = self.parse_uncached(source, None, None)?;
+            cache.insert(source, result.clone());
+            return result.to_json_view();
         }
 
+        self.parse(source)?.to_json_view()
+    }
+
+    /// Parse Nix source code without consulting or filling the cache
+    fn parse_uncached(
+        &mut self,
+        source: &str,
+        old_tree: Option<&Tree>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<ParseResult> {
         // Apply plugins before parsing
         #[cfg(feature = "plugins")]
-        let processed_source = self.apply_preprocessing_plugins(source)?;
+        let preprocessed = self.apply_preprocessing_plugins(source)?;
+        #[cfg(feature = "plugins")]
+        let processed_source = preprocessed.as_str();
         #[cfg(not(feature = "plugins"))]
         let processed_source = source;
 
         // Parse the source
-        let tree = self.inner.parse(processed_source, old_tree)
-            .ok_or_else(|| ParseError::ParseFailed("Tree-sitter parse returned None".to_string()))?;
-
-        let mut result = ParseResult::from_tree(tree, processed_source.to_string())?;
+        #[cfg(feature = "memory-stats")]
+        let memory = self.config.collect_statistics.then(MemoryTracker::start);
+        let timer = Timer::start("tree_sitter_parse");
+        let mut tree = self.parse_tree(processed_source, old_tree, cancellation)?;
+        let mut processed_source = processed_source.to_string();
+        let recovery = ErrorRecovery::new(self.config.recovery_strategy);
+        let fixes = match recovery.auto_fix(&tree, &processed_source) {
+            Some((fixed_source, fixes)) => {
+                // The old tree no longer matches the edited source
+                tree = self.parse_tree(&fixed_source, None, cancellation)?;
+                processed_source = fixed_source;
+                fixes
+            }
+            None => Vec::new(),
+        };
+        let parse_timing = timer.stop();
 
         // Apply plugins after parsing
         #[cfg(feature = "plugins")]
-        self.apply_postprocessing_plugins(&mut result)?;
+        let tree = self.apply_postprocessing_plugins(tree)?;
+
+        let mut result = ParseResult::from_tree(tree, processed_source)?;
+        result.set_config(self.config.clone());
+        for fix in &fixes {
+            result.add_diagnostic(ParseDiagnostic::info(fix.location, fix.to_string()).with_code(DiagnosticCode::AutoFix));
+        }
+        result.set_fixes(fixes);
+        self.check_legacy_let(&mut result);
+        self.check_language_features(&mut result)?;
+        if self.config.recovery_strategy == RecoveryStrategy::FailFast {
+            Self::check_no_syntax_errors(&result)?;
+        }
+        if self.config.validate_flakes {
+            self.check_flake(&mut result);
+        }
+        #[cfg(feature = "plugins")]
+        for plugin in &self.plugins {
+            for diagnostic in plugin.diagnostics(&result) {
+                result.add_diagnostic(diagnostic);
+            }
+        }
 
         // Add parsing statistics if enabled
         if self.config.collect_statistics {
-            self.add_parse_statistics(&mut result, processed_source);
+            #[cfg(feature = "memory-stats")]
+            let memory = memory.map(MemoryTracker::stop);
+            #[cfg(not(feature = "memory-stats"))]
+            let memory = None;
+            Self::add_parse_statistics(&mut result, parse_timing, old_tree.is_some(), memory);
         }
 
         // Validate result if enabled
@@ -165,12 +450,6 @@ impl NixParser {
             self.validate_result(&result)?;
         }
 
-        // Cache the result
-        #[cfg(feature = "cache")]
-        if let Some(ref cache) = self.cache {
-            cache.insert(source.to_string(), result.clone());
-        }
-
         Ok(result)
     }
 
@@ -202,9 +481,14 @@ impl NixParser {
     }
 
     /// Add a plugin to the parser
+    ///
+    /// Plugins run in order of decreasing `Plugin::priority`, and plugins
+    /// with the same priority in the order they were added.
     #[cfg(feature = "plugins")]
     pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) {
-        self.plugins.push(plugin);
+        let priority = plugin.priority();
+        let index = self.plugins.partition_point(|existing| existing.priority() >= priority);
+        self.plugins.insert(index, plugin);
     }
 
     /// Remove all plugins
@@ -214,48 +498,208 @@ impl NixParser {
     }
 
     // Private helper methods
+
+    /// Reject sources larger than `config.max_source_bytes`
+    fn check_source_size(&self, source: &str) -> Result<()> {
+        match self.config.max_source_bytes {
+            Some(limit) if source.len() > limit => {
+                Err(ParseError::resource_limit("source_size", format!("{limit} bytes")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the Nix files under `dir`, sorted so results are deterministic
+    fn nix_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = find_nix_files(dir)
+            .map_err(|e| ParseError::IoError(format!("{}: {}", dir.display(), e)))?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Run Tree-sitter over `source`, enforcing `config.timeout_ms` and
+    /// the cancellation token
+    fn parse_tree(
+        &mut self,
+        source: &str,
+        old_tree: Option<&Tree>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Tree> {
+        let timeout_ms = self.config.timeout_ms;
+        if timeout_ms.is_none() && cancellation.is_none() {
+            return self.inner.parse(source, old_tree)
+                .ok_or_else(|| ParseError::ParseFailed("Tree-sitter parse returned None".to_string()));
+        }
+        let is_cancelled = || cancellation.is_some_and(CancellationToken::is_cancelled);
+
+        let deadline = timeout_ms.map(|timeout_ms| Instant::now() + Duration::from_millis(timeout_ms));
+        let mut timed_out = false;
+        let mut progress = |_: &ParseState| {
+            timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            timed_out || is_cancelled()
+        };
+
+        let bytes = source.as_bytes();
+        let tree = self.inner.parse_with_options(
+            &mut |offset, _| &bytes[offset.min(bytes.len())..],
+            old_tree,
+            Some(ParseOptions::new().progress_callback(&mut progress)),
+        );
+
+        match tree {
+            Some(tree) => Ok(tree),
+            None if timed_out || is_cancelled() => {
+                // Tree-sitter would otherwise resume the abandoned parse on the next call
+                self.inner.reset();
+                match timeout_ms {
+                    Some(timeout_ms) if timed_out => Err(ParseError::timeout(timeout_ms)),
+                    _ => Err(ParseError::ParseFailed("cancelled".to_string())),
+                }
+            }
+            None => Err(ParseError::ParseFailed("Tree-sitter parse returned None".to_string())),
+        }
+    }
     
     #[cfg(feature = "plugins")]
-    fn apply_preprocessing_plugins(&self, source: &str) -> Result<String> {
+    fn apply_preprocessing_plugins(&mut self, source: &str) -> Result<String> {
         let mut processed = source.to_string();
         
-        for plugin in &self.plugins {
-            processed = plugin.preprocess(&processed)
-                .map_err(|e| ParseError::PluginError(format!("Preprocessing failed: {}", e)))?;
+        for plugin in &mut self.plugins {
+            processed = plugin.pre_process(processed)
+                .map_err(|e| ParseError::PluginError(format!("Preprocessing failed: {e}")))?;
         }
         
         Ok(processed)
     }
 
     #[cfg(feature = "plugins")]
-    fn apply_postprocessing_plugins(&self, result: &mut ParseResult) -> Result<()> {
-        for plugin in &self.plugins {
-            plugin.postprocess(result)
-                .map_err(|e| ParseError::PluginError(format!("Postprocessing failed: {}", e)))?;
+    fn apply_postprocessing_plugins(&mut self, mut tree: Tree) -> Result<Tree> {
+        for plugin in &mut self.plugins {
+            tree = plugin.post_process(tree)
+                .map_err(|e| ParseError::PluginError(format!("Postprocessing failed: {e}")))?;
+            plugin.validate(&tree)
+                .map_err(|e| ParseError::PluginError(format!("Validation failed: {e}")))?;
         }
         
+        Ok(tree)
+    }
+
+    /// Return the first syntax error in `result`, if it has any
+    fn check_no_syntax_errors(result: &ParseResult) -> Result<()> {
+        match result.diagnostics().iter().find(|d| d.severity == DiagnosticSeverity::Error) {
+            Some(error) => Err(ParseError::syntax_error_with_context(
+                error.location.line,
+                error.location.column,
+                error.message.clone(),
+                ErrorContext {
+                    suggestions: error.suggestions.clone(),
+                    ..ErrorContext::from_location(result.source(), error.location.line, error.location.column)
+                },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Report legacy `let { ... }` expressions
+    ///
+    /// The grammar reports their `let` as a syntax error. Language versions
+    /// that accept the form get a deprecation warning instead, suggesting a
+    /// migration to `let ... in`.
+    fn check_legacy_let(&self, result: &mut ParseResult) {
+        let version = self.config.language_version;
+        let mut keywords = Vec::new();
+        let mut nodes = result.tree().root_node().preorder();
+        while let Some(node) = nodes.next() {
+            keywords.extend(legacy_let_keyword(node).map(|keyword| keyword.start_byte()));
+            // The keyword is an error, so only subtrees with errors need to
+            // be visited
+            if !node.has_error() {
+                nodes.skip_children();
+            }
+        }
+        drop(nodes);
+
+        let migration = "rewrite it as `let ... in body`, moving the `body` attribute after `in`";
+        for start_byte in keywords {
+            let diagnostics = result.diagnostics_mut();
+            let Some(index) = diagnostics.iter().position(|d| {
+                d.code == Some(DiagnosticCode::SyntaxError) && d.location.start_byte == start_byte
+            }) else {
+                continue;
+            };
+            if version.supports_legacy_let() {
+                let location = diagnostics[index].location;
+                diagnostics[index] = ParseDiagnostic::warning(location, "legacy `let { ... }` is deprecated")
+                    .with_code(DiagnosticCode::LegacyLet)
+                    .with_suggestion(migration);
+            } else {
+                diagnostics[index].suggestions = vec![
+                    migration.to_string(),
+                    "target Nix 2.3 to keep the legacy `let { ... }` form".to_string(),
+                ];
+            }
+        }
+    }
+
+    /// Report problems with the structure of a flake
+    ///
+    /// The expression is built with locations even if the configuration
+    /// leaves them out, so the diagnostics point at the offending values.
+    /// Sources whose expression cannot be built are not checked.
+    fn check_flake(&self, result: &mut ParseResult) {
+        let Some(node) = result.tree().root_node().child_by_field_name("expression") else {
+            return;
+        };
+        let config = ParserConfig { include_locations: true, ..self.config.clone() };
+        if let Ok(expr) = Expression::from_tree_sitter_node_with_config(node, result.source(), &config) {
+            for diagnostic in validate_flake(&expr) {
+                result.add_diagnostic(diagnostic);
+            }
+        }
+    }
+
+    /// Reject syntax that the configured language version or grammar
+    /// layer does not accept, and report integer literals that do not fit
+    /// in an `i64` as errors
+    fn check_language_features(&self, result: &mut ParseResult) -> Result<()> {
+        let version = self.config.language_version;
+        let layer = self.config.grammar_layer;
+        let mut overflows = Vec::new();
+
+        for node in result.tree().root_node().preorder() {
+            layer.check_node(node, result.source())?;
+            if matches!(node.kind(), "float" | "uri" | "integer") {
+                let text = node.utf8_text(result.source().as_bytes())
+                    .map_err(|e| ParseError::ParseFailed(e.to_string()))?;
+                version.check_literal(node.kind(), text)?;
+                if node.kind() == "integer" && text.parse::<i64>().is_err() {
+                    let location = SourceLocation::from_tree_sitter_node(&node);
+                    overflows.push(
+                        ParseDiagnostic::error(location, integer_out_of_range(text))
+                            .with_code(DiagnosticCode::IntegerOverflow),
+                    );
+                }
+            }
+        }
+
+        for diagnostic in overflows {
+            result.add_diagnostic(diagnostic);
+        }
         Ok(())
     }
 
     /// Add parsing statistics to the parse result
-    fn add_parse_statistics(&self, result: &mut ParseResult, _source: &str) {
+    fn add_parse_statistics(
+        result: &mut ParseResult,
+        parse_timing: TimingResult,
+        incremental: bool,
+        memory: Option<MemoryStats>,
+    ) {
         use crate::parser::result::ParseStats;
-        use crate::utils::Timer;
         
-        // Start timing the statistics calculation
-        let timer = Timer::start("parse_statistics");
-        
-        // Create parse time statistics (using a simple metric for now)
-        let parse_time_ms = 1; // Placeholder - in real usage this would be actual parse time
-        
-        let stats = ParseStats::from_result(result, parse_time_ms, false);
+        let mut stats = ParseStats::from_result(result, parse_timing, incremental);
+        stats.memory = memory;
         result.set_statistics(Some(stats));
-        
-        // Complete timing measurement
-        let timing_result = timer.stop();
-        
-        // In a real implementation, this timing info could be logged or stored
-        let _ = timing_result.format(); // Use the label functionality
     }
 
     fn validate_result(&self, result: &ParseResult) -> Result<()> {
@@ -277,6 +721,63 @@ impl Default for NixParser {
     }
 }
 
+/// Parse Nix source code with a parser using the default configuration
+///
+/// This is a shorthand for creating a [`NixParser`] and calling
+/// [`NixParser::parse`]; reuse a parser when parsing many sources.
+///
+/// # Errors
+///
+/// Returns `ParseError` if the parser cannot be created or parsing fails.
+pub fn parse(source: &str) -> Result<ParseResult> {
+    NixParser::new()?.parse(source)
+}
+
+/// Parse Nix source code into an expression
+///
+/// Unlike [`parse`], syntax errors are not recovered from: the first error
+/// diagnostic is returned instead of a best-effort expression.
+///
+/// # Examples
+///
+/// ```rust
+/// use nix_parser::{Expression, parse_expr};
+///
+/// assert_eq!(parse_expr("42")?.into_unlocated(), Expression::Integer(42));
+/// assert!(parse_expr("{ x = 1 }").is_err());
+/// # Ok::<(), nix_parser::ParseError>(())
+/// ```
+///
+/// # Errors
+///
+/// Returns `ParseError::SyntaxError` for the first error in the source,
+/// and `ParseError::ParseFailed` if the source contains no expression.
+pub fn parse_expr(source: &str) -> Result<Expression> {
+    parse_expr_with_config(source, ParserConfig { allow_errors: false, ..ParserConfig::default() })
+}
+
+/// Parse Nix source code into an expression using a custom configuration
+///
+/// Error diagnostics are only returned as errors if `allow_errors` is
+/// false; otherwise a best-effort expression is built as configured.
+///
+/// # Errors
+///
+/// Returns `ParseError` if the parser cannot be created or parsing fails,
+/// `ParseError::SyntaxError` for the first error in the source unless
+/// `allow_errors` is set, and `ParseError::ParseFailed` if the source
+/// contains no expression.
+pub fn parse_expr_with_config(source: &str, config: ParserConfig) -> Result<Expression> {
+    let allow_errors = config.allow_errors;
+    let result = NixParser::with_config(config)?.parse(source)?;
+    if !allow_errors {
+        NixParser::check_no_syntax_errors(&result)?;
+    }
+    result
+        .expression()?
+        .ok_or_else(|| ParseError::ParseFailed("source contains no expression".to_string()))
+}
+
 // Ensure NixParser is Send and Sync when appropriate
 unsafe impl Send for NixParser {}
 unsafe impl Sync for NixParser {}
@@ -284,6 +785,8 @@ unsafe impl Sync for NixParser {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::grammar::GrammarLayer;
+    use std::io::Cursor;
 
     #[test]
     fn test_parser_creation() {
@@ -291,6 +794,21 @@ mod tests {
         assert!(parser.is_ok());
     }
 
+    #[test]
+    fn test_parse_functions() {
+        let result = parse("{ x = 1 }").unwrap();
+        assert!(result.has_errors());
+
+        assert_eq!(parse_expr("1 + 2").unwrap(), NixParser::new().unwrap().parse("1 + 2").unwrap().expression().unwrap().unwrap());
+        let err = parse_expr("{ x = 1 }").unwrap_err();
+        assert!(matches!(err, ParseError::SyntaxError { line: 1, column: 8, .. }), "{err:?}");
+        assert!(matches!(parse_expr("9223372036854775808"), Err(ParseError::SyntaxError { .. })));
+        assert!(matches!(parse_expr("# nothing"), Err(ParseError::SyntaxError { .. })));
+
+        let lenient = parse_expr_with_config("{ x = 1 }", ParserConfig::default()).unwrap();
+        assert!(matches!(lenient.unlocated(), Expression::AttributeSet { .. }));
+    }
+
     #[test]
     fn test_simple_parse() {
         let mut parser = NixParser::new().unwrap();
@@ -301,6 +819,37 @@ mod tests {
         assert!(!parse_result.has_errors());
     }
 
+    #[test]
+    fn test_parse_converts_expression() {
+        use crate::ast::{BinaryOperator, Binding, Expression, SourceLocation};
+
+        let mut parser = NixParser::new().unwrap();
+        let result = parser.parse("let a = 1; in a + 2").unwrap();
+        let expr = result.expression().unwrap().unwrap();
+        assert_eq!(expr, Expression::LetIn {
+            bindings: vec![Binding {
+                name: "a".to_string(),
+                value: Expression::Integer(1),
+                inherit: false,
+                from: None,
+            }],
+            body: Box::new(Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                left: Box::new(Expression::Identifier("a".to_string())),
+                right: Box::new(Expression::Integer(2)),
+            }),
+        });
+
+        // Expressions, diagnostics and the crate root share one location type
+        let location: SourceLocation = expr.location().unwrap();
+        let root_location: crate::SourceLocation = location;
+        let utils_location: crate::utils::SourceLocation = root_location;
+        assert_eq!(utils_location.end_byte, 19);
+        let result = parser.parse("[ 1 ) ]").unwrap();
+        let diagnostic_location: SourceLocation = result.diagnostics()[0].location;
+        assert_eq!(diagnostic_location.line, 1);
+    }
+
     #[test]
     fn test_error_handling() {
         let mut parser = NixParser::new().unwrap();
@@ -312,13 +861,485 @@ mod tests {
         assert!(parse_result.has_errors());
     }
 
+    fn parse_with_strategy(strategy: RecoveryStrategy, source: &str) -> Result<ParseResult> {
+        let config = ParserConfig::builder()
+            .recovery_strategy(strategy)
+            .include_locations(false)
+            .build();
+        NixParser::with_config(config).unwrap().parse(source)
+    }
+
+    #[test]
+    fn test_fail_fast_recovery() {
+        let err = parse_with_strategy(RecoveryStrategy::FailFast, "if true then").unwrap_err();
+        assert!(matches!(err, ParseError::SyntaxError { line: 1, column: 1, .. }), "{err:?}");
+
+        let err = parse_with_strategy(RecoveryStrategy::FailFast, "{ x = 1 }").unwrap_err();
+        assert_eq!(err.location(), Some((1, 8)));
+        assert_eq!(err.primary_message(), "Missing: ;");
+        let snippet = err.context().and_then(|context| context.source_snippet.as_deref());
+        assert_eq!(snippet, Some("{ x = 1 }\n       ^"));
+        assert_eq!(err.context().unwrap().suggestions, ["insert ';' after this binding"]);
+
+        assert!(parse_with_strategy(RecoveryStrategy::FailFast, "{ x = 1; }").is_ok());
+    }
+
+    #[test]
+    fn test_continue_recovery() {
+        let result = parse_with_strategy(RecoveryStrategy::Continue, "if true then").unwrap();
+        assert!(result.has_errors());
+        assert_eq!(result.expression().unwrap(), Some(crate::ast::Expression::Boolean(true)));
+
+        let result = parse_with_strategy(RecoveryStrategy::Continue, "{ x = 1 }").unwrap();
+        assert_eq!(result.diagnostics().len(), 1);
+        let expected = NixParser::new().unwrap().parse("{ x = 1; }").unwrap().expression().unwrap();
+        assert_eq!(result.expression().unwrap(), expected);
+
+        // Bindings and list elements that failed to parse are skipped
+        let result = parse_with_strategy(RecoveryStrategy::Continue, "{ a = [ 1 (2 + ) 3 ]; b = ; }").unwrap();
+        let expected = NixParser::new().unwrap().parse("{ a = [ 1 3 ]; }").unwrap().expression().unwrap();
+        assert_eq!(result.expression().unwrap(), expected);
+        assert!(result.fixes().is_empty());
+    }
+
+    #[test]
+    fn test_auto_fix_recovery() {
+        let result = parse_with_strategy(RecoveryStrategy::AutoFix, "{ x = 1 }").unwrap();
+        assert!(!result.has_errors());
+        assert_eq!(result.source(), "{ x = 1; }");
+        assert_eq!(result.fixes().len(), 1);
+        assert_eq!(result.fixes()[0].inserted, ";");
+        assert_eq!(result.fixes()[0].to_string(), "inserted missing `;` at 1:8");
+        assert_eq!(result.diagnostics()[0].severity, DiagnosticSeverity::Info);
+
+        let result = parse_with_strategy(RecoveryStrategy::AutoFix, "f (x").unwrap();
+        assert_eq!(result.source(), "f (x)");
+        assert!(!result.has_errors());
+
+        // Nothing to insert: errors are reported as under `Continue`
+        let result = parse_with_strategy(RecoveryStrategy::AutoFix, "if true then").unwrap();
+        assert!(result.has_errors());
+        assert!(result.fixes().is_empty());
+        assert_eq!(result.expression().unwrap(), Some(crate::ast::Expression::Boolean(true)));
+    }
+
+    #[test]
+    fn test_deeply_nested_input() {
+        // Far beyond the default nesting limit of 1000
+        let depth = 20_000;
+        let mut parser = NixParser::new().unwrap();
+        for source in [
+            format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+            format!("1{}", " + 1".repeat(depth)),
+            format!("{}1", "x: ".repeat(depth)),
+        ] {
+            let result = parser.parse(&source).unwrap();
+            assert!(!result.has_errors());
+            assert!(matches!(
+                result.expression(),
+                Err(ParseError::ResourceLimitExceeded { ref resource, .. }) if resource == "nesting_depth"
+            ));
+        }
+
+        // Unclosed delimiters produce deep error trees
+        let result = parser.parse(&"[ (".repeat(depth)).unwrap();
+        assert!(result.has_errors());
+        assert!(result.expression().is_err());
+
+        // Nesting just within the limit converts
+        let source = format!("{}1{}", "(".repeat(900), ")".repeat(900));
+        assert_eq!(parser.parse(&source).unwrap().expression().unwrap(), Some(crate::ast::Expression::Integer(1)));
+    }
+
     #[test]
     fn test_config_update() {
         let mut parser = NixParser::new().unwrap();
-        let mut config = ParserConfig::default();
-        config.allow_errors = false;
+        let config = ParserConfig { allow_errors: false, ..ParserConfig::default() };
         
         parser.set_config(config);
         assert!(!parser.config().allow_errors);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_language_version_gating() {
+        let parse_as = |version: LanguageVersion, source: &str| {
+            let config = ParserConfig::builder().language_version(version).build();
+            NixParser::with_config(config).unwrap().parse(source)
+        };
+
+        for source in ["[ 1.5e3 ]", "{ inputs.nixpkgs.url = github:NixOS/nixpkgs; }"] {
+            assert!(parse_as(LanguageVersion::Latest, source).is_ok(), "{}", source);
+
+            let err = parse_as(LanguageVersion::Nix23, source).unwrap_err();
+            assert!(
+                matches!(err, ParseError::FeatureNotSupported { suggestion: Some(_), .. }),
+                "{source}: {err:?}"
+            );
+        }
+
+        // Ordinary URLs predate flakes
+        assert!(parse_as(LanguageVersion::Nix23, "https://nixos.org").is_ok());
+    }
+
+    #[test]
+    fn test_legacy_let() {
+        let parse_as = |version: LanguageVersion, strategy: RecoveryStrategy, source: &str| {
+            let config = ParserConfig::builder()
+                .language_version(version)
+                .recovery_strategy(strategy)
+                .build();
+            NixParser::with_config(config).unwrap().parse(source).unwrap()
+        };
+        let expr = |result: &ParseResult| result.expression().unwrap().unwrap();
+
+        let source = "x: let { y = x; body = [ y ]; }";
+        let result = parse_as(LanguageVersion::Nix23, RecoveryStrategy::FailFast, source);
+        assert!(!result.has_errors());
+        assert_eq!(expr(&result), expr(&parse("x: rec { y = x; body = [ y ]; }.body").unwrap()));
+
+        let [warning] = result.diagnostics() else {
+            panic!("expected one diagnostic, got {:?}", result.diagnostics());
+        };
+        assert_eq!(warning.severity, DiagnosticSeverity::Warning);
+        assert_eq!(warning.code, Some(DiagnosticCode::LegacyLet));
+        assert_eq!((warning.location.line, warning.location.column), (1, 4));
+        assert_eq!(warning.suggestions.len(), 1);
+
+        // Later versions keep the syntax error, suggesting the migration
+        let result = parse_as(LanguageVersion::Latest, RecoveryStrategy::Continue, source);
+        assert!(result.has_errors());
+        assert_eq!(result.diagnostics()[0].code, Some(DiagnosticCode::SyntaxError));
+        assert_eq!(result.diagnostics()[0].suggestions.len(), 2);
+        assert_eq!(expr(&result), expr(&parse("x: { y = x; body = [ y ]; }").unwrap()));
+    }
+
+    #[test]
+    fn test_validate_flakes() {
+        let source = "{ description = \"A flake\"; outputs = { }; }";
+        assert!(!parse(source).unwrap().has_errors());
+
+        let config = ParserConfig::builder().validate_flakes(true).include_locations(false).build();
+        let result = NixParser::with_config(config).unwrap().parse(source).unwrap();
+        let [error] = result.diagnostics() else {
+            panic!("expected one diagnostic, got {:?}", result.diagnostics());
+        };
+        assert_eq!(error.code, Some(DiagnosticCode::InvalidFlake));
+        assert_eq!((error.location.start_byte, error.location.end_byte), (37, 40));
+    }
+
+    #[test]
+    fn test_grammar_layer_gating() {
+        let parse_as = |layer: GrammarLayer, source: &str| {
+            let config = ParserConfig::builder().grammar_layer(layer).build();
+            NixParser::with_config(config).unwrap().parse(source)
+        };
+
+        assert!(parse_as(GrammarLayer::Advanced, "import ./x").is_ok());
+        assert!(parse_as(GrammarLayer::Experimental, "import ./x").is_ok());
+        for layer in [GrammarLayer::Basic, GrammarLayer::Standard] {
+            let err = parse_as(layer, "import ./x").unwrap_err();
+            let ParseError::FeatureNotSupported { feature, suggestion } = err else {
+                panic!("expected an unsupported feature, got {err:?}");
+            };
+            assert!(feature.starts_with("import"), "{}", feature);
+            assert_eq!(suggestion.as_deref(), Some("use the advanced grammar layer"));
+        }
+
+        // Builtins are gated wherever they are referenced, not only in calls
+        let bypasses = [
+            (GrammarLayer::Standard, "builtins.import ./x"),
+            (GrammarLayer::Standard, "let i = import; in i ./x"),
+            (GrammarLayer::Standard, "(import) ./x"),
+            (GrammarLayer::Standard, "let inherit (builtins) import; in import ./x"),
+            (GrammarLayer::Standard, "__import ./x"),
+            (GrammarLayer::Standard, "scopedImport { } ./x"),
+            (GrammarLayer::Basic, "[ import ]"),
+            (GrammarLayer::Basic, "{ a = derivation; }"),
+        ];
+        for (layer, source) in bypasses {
+            assert!(
+                matches!(parse_as(layer, source), Err(ParseError::FeatureNotSupported { .. })),
+                "{} parsed at the {} layer", source, layer.name()
+            );
+            assert!(parse_as(GrammarLayer::Advanced, source).is_ok(), "{}", source);
+        }
+        // Attribute names and parameters are not references
+        assert!(parse_as(GrammarLayer::Basic, "{ import = 1; derivation.builtins = 2; }").is_ok());
+        assert!(parse_as(GrammarLayer::Standard, "{ import }: x: x.import").is_ok());
+        assert!(parse_as(GrammarLayer::Standard, "let inherit (lib) derivation; in 1").is_ok());
+
+        assert!(parse_as(GrammarLayer::Basic, "{ a = [ \"b\" ]; c = -2 * 3; }").is_ok());
+        assert!(parse_as(GrammarLayer::Basic, "let a = 1; in a").is_err());
+        assert!(parse_as(GrammarLayer::Standard, "let f = x: x.y or 1; in f { }").is_ok());
+        assert!(parse_as(GrammarLayer::Standard, "with pkgs; hello").is_err());
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let mut parser = NixParser::new().unwrap();
+        let max = parser.parse("9223372036854775807").unwrap();
+        assert!(!max.has_errors());
+        assert_eq!(max.expression().unwrap(), Some(crate::ast::Expression::Integer(i64::MAX)));
+
+        let result = parser.parse("[\n  9223372036854775808\n]").unwrap();
+        let diagnostics = result.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "integer literal '9223372036854775808' out of range for i64");
+        assert_eq!(diagnostics[0].code, Some(DiagnosticCode::IntegerOverflow));
+        assert_eq!((diagnostics[0].location.line, diagnostics[0].location.column), (2, 3));
+
+        let err = result.expression().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ParseError::SyntaxError { line: 2, column: 3, ref message, .. } if message.contains("out of range for i64")
+            ),
+            "{err:?}"
+        );
+
+        let config = ParserConfig::builder().recovery_strategy(RecoveryStrategy::FailFast).build();
+        let err = NixParser::with_config(config).unwrap().parse("9223372036854775808").unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ParseError::SyntaxError { line: 1, column: 1, ref message, .. } if message.contains("out of range for i64")
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut parser = NixParser::new().unwrap();
+
+        let valid = dir.path().join("default.nix");
+        std::fs::write(&valid, "{ x = 1; }").unwrap();
+        let result = parser.parse_file(&valid).unwrap();
+        assert!(!result.has_errors());
+        assert_eq!(result.file_path(), Some(valid.as_path()));
+
+        let broken = dir.path().join("broken.nix");
+        std::fs::write(&broken, "{ x = ; }").unwrap();
+        let result = parser.parse_file(&broken).unwrap();
+        assert!(result.has_errors());
+        assert_eq!(result.file_path(), Some(broken.as_path()));
+
+        let binary = dir.path().join("binary.nix");
+        std::fs::write(&binary, [0x7b, 0xff, 0x7d]).unwrap();
+        assert!(matches!(parser.parse_file(&binary), Err(ParseError::Utf8Error(_))));
+
+        let missing = dir.path().join("missing.nix");
+        let err = parser.parse_file(&missing).unwrap_err();
+        assert!(matches!(err, ParseError::IoError(ref msg) if msg.contains("missing.nix")), "{err:?}");
+
+        let other = dir.path().join("notes.txt");
+        std::fs::write(&other, "{ }").unwrap();
+        assert!(matches!(parser.parse_file(&other), Err(ParseError::ValidationError(_))));
+    }
+
+    fn mixed_nix_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        std::fs::write(dir.path().join("default.nix"), "{ x = 1; }").unwrap();
+        std::fs::write(dir.path().join("broken.nix"), "{ x = ; }").unwrap();
+        std::fs::write(dir.path().join("binary.nix"), [0x7b, 0xff, 0x7d]).unwrap();
+        std::fs::write(dir.path().join("lib/util.nix"), "x: x").unwrap();
+        std::fs::write(dir.path().join("README.md"), "not nix").unwrap();
+        dir
+    }
+
+    fn assert_mixed_results(dir: &Path, results: &[(PathBuf, Result<ParseResult>)]) {
+        let names: Vec<_> = results.iter()
+            .map(|(path, _)| path.strip_prefix(dir).unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["binary.nix", "broken.nix", "default.nix", "lib/util.nix"]);
+
+        assert!(matches!(results[0].1, Err(ParseError::Utf8Error(_))));
+        assert!(results[1].1.as_ref().unwrap().has_errors());
+        assert!(!results[2].1.as_ref().unwrap().has_errors());
+        assert!(!results[3].1.as_ref().unwrap().has_errors());
+    }
+
+    #[test]
+    fn test_parse_directory() {
+        let dir = mixed_nix_tree();
+        let mut parser = NixParser::new().unwrap();
+
+        let results = parser.parse_directory(dir.path()).unwrap();
+        assert_mixed_results(dir.path(), &results);
+
+        assert!(matches!(
+            parser.parse_directory(dir.path().join("missing")),
+            Err(ParseError::IoError(_))
+        ));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parse_directory_parallel() {
+        let dir = mixed_nix_tree();
+        let parser = NixParser::new().unwrap();
+
+        let results = parser.parse_directory_parallel(dir.path()).unwrap();
+        assert_mixed_results(dir.path(), &results);
+    }
+
+    #[test]
+    fn test_parse_statistics_timing() {
+        let source = format!("[ {} ]", "{ a = 1; b = [ \"x\" 2.5 ]; } ".repeat(2_000));
+        let config = ParserConfig::builder().collect_statistics(true).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+
+        let result = parser.parse(&source).unwrap();
+        let stats = result.statistics().expect("statistics were requested");
+
+        assert_eq!(stats.timing.label, "tree_sitter_parse");
+        assert!(stats.timing.duration > Duration::ZERO);
+        assert_eq!(stats.parse_time_ms, stats.timing.duration_ms());
+        assert_eq!(stats.source_size, source.len());
+        assert!(!stats.incremental);
+    }
+
+    #[cfg(feature = "memory-stats")]
+    #[test]
+    fn test_parse_statistics_memory() {
+        let source = format!("[ {} ]", "{ a = 1; b = [ \"x\" 2.5 ]; } ".repeat(2_000));
+        let config = ParserConfig::builder().collect_statistics(true).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+
+        let result = parser.parse(&source).unwrap();
+        let memory = result.statistics().unwrap().memory.clone().expect("memory is measured");
+        // At least the result's copy of the source
+        assert!(memory.peak_bytes >= source.len(), "{memory:?}");
+        assert!(memory.allocations > 0);
+
+        assert_eq!(NixParser::new().unwrap().parse(&source).unwrap().statistics(), None);
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        // With no time at all, the parse stops at Tree-sitter's first
+        // progress report whatever the speed of the machine
+        let source = format!("[ {} ]", "{ a = 1; b = \"x\"; } ".repeat(10_000));
+        let config = ParserConfig::builder().timeout_ms(Some(0)).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+
+        let err = parser.parse(&source).unwrap_err();
+        assert!(matches!(err, ParseError::Timeout { timeout_ms: 0 }), "{err:?}");
+
+        // The abandoned parse must not leak into the next call
+        parser.set_config(ParserConfig::default());
+        let result = parser.parse("{ x = 1; }").unwrap();
+        assert!(!result.has_errors());
+        assert_eq!(result.source(), "{ x = 1; }");
+    }
+
+    #[test]
+    fn test_max_source_bytes() {
+        let config = ParserConfig::builder().max_source_bytes(Some(10)).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+
+        let err = parser.parse("{ x = 1; y = 2; }").unwrap_err();
+        assert!(
+            matches!(err, ParseError::ResourceLimitExceeded { ref resource, ref limit } if resource == "source_size" && limit == "10 bytes"),
+            "{err:?}"
+        );
+        assert!(!parser.parse("{ x = 1; }").unwrap().has_errors());
+        assert_eq!(ParserConfig::default().max_source_bytes, None);
+
+        // Sources cached by a parser without the limit are rejected as well
+        #[cfg(feature = "cache")]
+        {
+            let cache = Arc::new(ParseCache::new(10));
+            let mut unlimited = NixParser::new().unwrap();
+            unlimited.enable_cache(Arc::clone(&cache));
+            assert!(unlimited.parse("{ x = 1; y = 2; }").is_ok());
+            parser.enable_cache(cache);
+            let err = parser.parse("{ x = 1; y = 2; }").unwrap_err();
+            assert!(matches!(err, ParseError::ResourceLimitExceeded { .. }), "{err:?}");
+            let err = parser.parse_view("{ x = 1; y = 2; }").unwrap_err();
+            assert!(matches!(err, ParseError::ResourceLimitExceeded { .. }), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let mut parser = NixParser::new().unwrap();
+        let result = parser.parse_reader(Cursor::new(&b"{ x = 1; }"[..])).unwrap();
+        assert!(!result.has_errors());
+        assert_eq!(result.source(), "{ x = 1; }");
+        assert!(parser.parse_reader(Cursor::new(&b"{ x = ; }"[..])).unwrap().has_errors());
+        assert!(matches!(
+            parser.parse_reader(Cursor::new(&[0x7b, 0xff, 0x7d][..])),
+            Err(ParseError::Utf8Error(_))
+        ));
+
+        let config = ParserConfig::builder().max_source_bytes(Some(10)).build();
+        let mut parser = NixParser::with_config(config).unwrap();
+        assert!(parser.parse_reader(Cursor::new(&b"{ x = 1; }"[..])).is_ok());
+        let err = parser.parse_reader(Cursor::new(&b"{ x = 1; y = 2; }"[..])).unwrap_err();
+        assert!(matches!(err, ParseError::ResourceLimitExceeded { .. }), "{err:?}");
+    }
+
+    #[test]
+    #[cfg(feature = "cache")]
+    fn test_parse_view_from_disk_cache() {
+        use crate::cache::CacheStats;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = "{ x = 1; y = ; }";
+        let mut parser = NixParser::new().unwrap();
+        let view = parser.parse_view(source).unwrap();
+        assert_eq!(view, parser.parse(source).unwrap().to_json_view().unwrap());
+
+        let cache = Arc::new(ParseCache::with_disk(dir.path(), 10).unwrap());
+        parser.enable_cache(Arc::clone(&cache));
+        assert_eq!(parser.parse_view(source).unwrap(), view);
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1, insertions: 1, evictions: 0 });
+
+        // A parser with a fresh cache over the same directory, as in a new process
+        let cache = Arc::new(ParseCache::with_disk(dir.path(), 10).unwrap());
+        let mut parser = NixParser::new().unwrap();
+        parser.enable_cache(Arc::clone(&cache));
+        assert_eq!(parser.parse_view(source).unwrap(), view);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 0, insertions: 0, evictions: 0 });
+    }
+
+    #[test]
+    fn test_parse_cancellation() {
+        let source = format!("[ {} ]", "{ a = 1; b = \"x\"; } ".repeat(100_000));
+        let token = CancellationToken::new();
+        let mut parser = NixParser::new().unwrap();
+
+        // Cancel as soon as the parse starts rather than after a delay, so
+        // the parse cannot finish first on a fast machine
+        let started = Arc::new(std::sync::Barrier::new(2));
+        let canceller = std::thread::spawn({
+            let (token, started) = (token.clone(), Arc::clone(&started));
+            move || {
+                started.wait();
+                token.cancel();
+            }
+        });
+        started.wait();
+        let err = parser.parse_with_cancellation(&source, None, &token).unwrap_err();
+        canceller.join().unwrap();
+        assert!(matches!(err, ParseError::ParseFailed(ref message) if message == "cancelled"), "{err:?}");
+
+        // A cancelled token aborts parses before they start, cached or not
+        let err = parser.parse_with_cancellation("{ x = 1; }", None, &token).unwrap_err();
+        assert!(matches!(err, ParseError::ParseFailed(ref message) if message == "cancelled"), "{err:?}");
+        #[cfg(feature = "cache")]
+        {
+            parser.enable_cache(Arc::new(ParseCache::new(10)));
+            parser.parse("{ x = 1; }").unwrap();
+            assert!(parser.parse_with_cancellation("{ x = 1; }", None, &token).is_err());
+        }
+
+        // The token does not outlive the call
+        let result = parser.parse("{ x = 1; }").unwrap();
+        assert!(!result.has_errors());
+        assert_eq!(result.source(), "{ x = 1; }");
+    }
+}
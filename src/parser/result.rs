@@ -1,10 +1,17 @@
 //! Parser result types and diagnostic information
 
-use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
 use tree_sitter::{Tree, Node};
 
 use crate::ast::{Expression, SourceLocation};
-use crate::error::Result;
+use crate::parser::ParserConfig;
+use crate::error::{AppliedFix, Diagnostic, ErrorContext, ParseError, Result, Severity};
+use crate::parser::suggestions;
+use crate::utils::{MemoryStats, NodeExt, TimingResult};
 
 /// Result of a parsing operation
 ///
@@ -16,6 +23,9 @@ pub struct ParseResult {
     source: String,
     diagnostics: Vec<ParseDiagnostic>,
     statistics: Option<ParseStats>,
+    config: ParserConfig,
+    file_path: Option<PathBuf>,
+    fixes: Vec<AppliedFix>,
 }
 
 impl ParseResult {
@@ -38,6 +48,9 @@ impl ParseResult {
             source,
             diagnostics,
             statistics: None,
+            config: ParserConfig::default(),
+            file_path: None,
+            fixes: Vec::new(),
         })
     }
     
@@ -56,9 +69,23 @@ impl ParseResult {
         &self.diagnostics
     }
     
+    /// Get the parse diagnostics for the parser to amend
+    pub(crate) fn diagnostics_mut(&mut self) -> &mut Vec<ParseDiagnostic> {
+        &mut self.diagnostics
+    }
+    
+    /// Get the diagnostics in a stable order, without duplicates
+    ///
+    /// Diagnostics are sorted by line and column, most severe first, then
+    /// by message. Of the diagnostics with the same message at the same
+    /// position, only the most severe is kept.
+    pub fn sorted_diagnostics(&self) -> Vec<ParseDiagnostic> {
+        sort_diagnostics(&self.diagnostics)
+    }
+    
     /// Check if parsing resulted in any errors
     pub fn has_errors(&self) -> bool {
-        self.diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+        has_errors(&self.diagnostics)
     }
     
     /// Check if parsing resulted in any warnings
@@ -68,6 +95,10 @@ impl ParseResult {
     
     /// Get the root expression from the parse tree
     ///
+    /// Unless the configured `RecoveryStrategy` is `FailFast`, subtrees
+    /// that failed to parse are skipped, so a source with syntax errors
+    /// still yields a best-effort expression.
+    ///
     /// # Errors
     ///
     /// Returns `ParseError` if the tree structure is invalid or the
@@ -77,13 +108,61 @@ impl ParseResult {
         
         // Look for the expression field in the source_file node
         if let Some(expr_node) = root.child_by_field_name("expression") {
-            Expression::from_tree_sitter_node(expr_node, &self.source)
+            Expression::from_tree_sitter_node_with_config(expr_node, &self.source, &self.config)
+                .map(Some)
+        } else if root.is_error() && self.config.recovery_strategy.recovers() {
+            // Nothing parsed as a whole file, but parts of it may have
+            Expression::from_tree_sitter_node_with_config(root, &self.source, &self.config)
                 .map(Some)
         } else {
             Ok(None)
         }
     }
     
+    /// Get the smallest expression containing a byte range
+    ///
+    /// A range spanning several sibling expressions yields their common
+    /// parent. The returned expression keeps its location wrapper, so its
+    /// span is available through [`Expression::location`]. Returns `None`
+    /// if no expression contains the range, or if source locations are
+    /// disabled in the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`ParseResult::expression`].
+    pub fn enclosing_expression(&self, start_byte: usize, end_byte: usize) -> Result<Option<Expression>> {
+        let Some(root) = self.expression()? else {
+            return Ok(None);
+        };
+        Ok(root.find_enclosing(start_byte, end_byte).last().map(|expr| (*expr).clone()))
+    }
+    
+    /// Get the expression an editor selection of `start_byte..end_byte`
+    /// grows to
+    ///
+    /// This is the smallest expression containing the range with a span
+    /// larger than the range itself, so when the selection already covers
+    /// an expression, its parent is returned. Repeated calls with the span
+    /// of the previous result walk up the ancestors to the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`ParseResult::expression`].
+    pub fn expand_selection(&self, start_byte: usize, end_byte: usize) -> Result<Option<Expression>> {
+        let Some(root) = self.expression()? else {
+            return Ok(None);
+        };
+        let chain = root.find_enclosing(start_byte, end_byte);
+        Ok(chain
+            .into_iter()
+            .rev()
+            .find(|expr| {
+                expr.location()
+                    .is_some_and(|location| (location.start_byte, location.end_byte) != (start_byte, end_byte))
+            })
+            .cloned())
+    }
+    
     /// Get detailed error information
     pub fn error_summary(&self) -> Option<String> {
         if !self.has_errors() {
@@ -98,14 +177,14 @@ impl ParseResult {
             return None;
         }
         
-        let mut summary = String::new();
-        summary.push_str(&format!("Found {} parse error(s):\n", errors.len()));
+        let mut summary = format!("Found {} parse error(s):\n", errors.len());
         
         for error in errors {
-            summary.push_str(&format!("  - Line {}, Column {}: {}\n", 
-                                     error.location.line, 
-                                     error.location.column, 
-                                     error.message));
+            // Writing to a `String` cannot fail
+            let _ = writeln!(summary, "  - Line {}, Column {}: {}",
+                             error.location.line,
+                             error.location.column,
+                             error.message);
         }
         
         Some(summary)
@@ -126,50 +205,217 @@ impl ParseResult {
         self.statistics = statistics;
     }
     
-    // Private helper methods
+    /// Get the configuration used when converting the tree to an AST
+    pub const fn config(&self) -> &ParserConfig {
+        &self.config
+    }
     
-    fn collect_errors(node: &Node, source: &str, diagnostics: &mut Vec<ParseDiagnostic>) {
-        if node.is_error() {
-            let location = SourceLocation::from_tree_sitter_node(node);
-            let text = node.utf8_text(source.as_bytes())
-                .unwrap_or("<invalid UTF-8>")
-                .to_string();
-                
-            diagnostics.push(ParseDiagnostic {
-                severity: DiagnosticSeverity::Error,
-                location,
-                message: format!("Syntax error near: '{}'", text),
-                code: Some("syntax_error".to_string()),
-                source: Some("nix-parser".to_string()),
-            });
-        }
-        
-        // Check for missing nodes (Tree-sitter represents these specially)
-        if node.is_missing() {
-            let location = SourceLocation::from_tree_sitter_node(node);
-            diagnostics.push(ParseDiagnostic {
-                severity: DiagnosticSeverity::Error,
-                location,
-                message: format!("Missing: {}", node.kind()),
-                code: Some("missing_node".to_string()),
-                source: Some("nix-parser".to_string()),
-            });
+    /// Set the configuration used when converting the tree to an AST
+    pub fn set_config(&mut self, config: ParserConfig) {
+        self.config = config;
+    }
+    
+    /// Get the path of the file this result was parsed from, if any
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+    
+    /// Set the path of the file this result was parsed from
+    pub fn set_file_path(&mut self, file_path: Option<PathBuf>) {
+        self.file_path = file_path;
+    }
+    
+    /// Serialize the result as JSON for IDEs and CI
+    ///
+    /// The schema is [`ParseResultJson`]: the AST (with source locations,
+    /// unless they were disabled), diagnostics, statistics and applied
+    /// fixes. Floats serialize with enough precision to read back exactly.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError` if the AST cannot be built from the tree, or
+    /// `ParseError::ParseFailed` if serialization fails.
+    pub fn to_json(&self) -> Result<String> {
+        self.to_json_view()?.to_json()
+    }
+    
+    /// Build the serializable view of the result used by [`ParseResult::to_json`]
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError` if the AST cannot be built from the tree.
+    pub fn to_json_view(&self) -> Result<ParseResultJson> {
+        Ok(ParseResultJson {
+            version: ParseResultJson::VERSION,
+            file_path: self.file_path.clone(),
+            expression: self.expression()?,
+            diagnostics: self.diagnostics.clone(),
+            statistics: self.statistics.clone(),
+            fixes: self.fixes.clone(),
+        })
+    }
+    
+    /// Get the fixes `RecoveryStrategy::AutoFix` applied to the source
+    pub fn fixes(&self) -> &[AppliedFix] {
+        &self.fixes
+    }
+    
+    /// Set the fixes applied to the source before it was parsed
+    pub fn set_fixes(&mut self, fixes: Vec<AppliedFix>) {
+        self.fixes = fixes;
+    }
+    
+    /// Count the nodes of each kind in the parse tree
+    ///
+    /// Anonymous nodes are counted under their token, such as `=` or `;`,
+    /// and nodes Tree-sitter inserted for missing tokens are counted too.
+    pub fn node_kind_histogram(&self) -> HashMap<String, usize> {
+        let mut histogram = HashMap::new();
+        for node in self.tree.root_node().preorder() {
+            *histogram.entry(node.kind().to_string()).or_insert(0) += 1;
         }
-        
-        // Recursively check children
-        for i in 0..node.child_count() {
-            if let Some(child) = node.child(i) {
-                Self::collect_errors(&child, source, diagnostics);
+        histogram
+    }
+    
+    // Private helper methods
+    
+    fn collect_errors(root: &Node, source: &str, diagnostics: &mut Vec<ParseDiagnostic>) {
+        // Walk with a cursor instead of recursing, as trees can be arbitrarily deep
+        let mut cursor = root.walk();
+        loop {
+            let node = cursor.node();
+            if node.is_error() {
+                let location = SourceLocation::from_tree_sitter_node(&node);
+                let text = node.utf8_text(source.as_bytes())
+                    .unwrap_or("<invalid UTF-8>")
+                    .to_string();
+                    
+                diagnostics.push(ParseDiagnostic {
+                    severity: DiagnosticSeverity::Error,
+                    location,
+                    message: format!("Syntax error near: '{text}'"),
+                    code: Some(DiagnosticCode::SyntaxError),
+                    source: Some("nix-parser".to_string()),
+                    suggestions: suggestions::for_error(source, node.start_byte(), node.end_byte()),
+                });
+            }
+            
+            // Check for missing nodes (Tree-sitter represents these specially)
+            if node.is_missing() {
+                let location = SourceLocation::from_tree_sitter_node(&node);
+                diagnostics.push(ParseDiagnostic {
+                    severity: DiagnosticSeverity::Error,
+                    location,
+                    message: format!("Missing: {}", node.kind()),
+                    code: Some(DiagnosticCode::MissingNode),
+                    source: Some("nix-parser".to_string()),
+                    suggestions: suggestions::for_missing(node, source).into_iter().collect(),
+                });
+            }
+
+            // The missing end of a string is hidden, so report it at the
+            // end of the string
+            if let Some(delimiter) = suggestions::unterminated_string(node) {
+                let (row, column) = (node.end_position().row, node.end_position().column);
+                let location = SourceLocation {
+                    start_byte: node.end_byte(),
+                    end_byte: node.end_byte(),
+                    start_position: (row, column),
+                    end_position: (row, column),
+                    line: row + 1,
+                    column: column + 1,
+                };
+                diagnostics.push(ParseDiagnostic {
+                    severity: DiagnosticSeverity::Error,
+                    location,
+                    message: format!("Missing: {delimiter}"),
+                    code: Some(DiagnosticCode::MissingNode),
+                    source: Some("nix-parser".to_string()),
+                    suggestions: vec![suggestions::for_unterminated_string(node, delimiter, source)],
+                });
+            }
+            
+            // Only subtrees containing errors need to be visited
+            if node.has_error() && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return;
+                }
             }
         }
     }
 }
 
+/// Serializable view of a `ParseResult`
+///
+/// Fields are only ever added, and `version` is bumped when an existing
+/// field changes meaning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseResultJson {
+    /// Schema version
+    pub version: u32,
+    /// Path of the parsed file, if it was parsed from disk
+    pub file_path: Option<PathBuf>,
+    /// Root expression, if the source contained one
+    pub expression: Option<Expression>,
+    /// Errors, warnings and informational messages
+    pub diagnostics: Vec<ParseDiagnostic>,
+    /// Parsing statistics, if they were collected
+    pub statistics: Option<ParseStats>,
+    /// Fixes applied by `RecoveryStrategy::AutoFix`
+    pub fixes: Vec<AppliedFix>,
+}
+
+impl ParseResultJson {
+    /// Current schema version
+    pub const VERSION: u32 = 1;
+
+    /// Serialize the view to JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ParseFailed` if serialization fails.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self)
+            .map_err(|e| ParseError::ParseFailed(format!("JSON serialization failed: {e}")))
+    }
+
+    /// Get the diagnostics in the order of [`ParseResult::sorted_diagnostics`]
+    pub fn sorted_diagnostics(&self) -> Vec<ParseDiagnostic> {
+        sort_diagnostics(&self.diagnostics)
+    }
+
+    /// Check if parsing resulted in any errors
+    pub fn has_errors(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+}
+
+/// Sort diagnostics by position, severity and message, keeping only the
+/// most severe of those with the same message at the same position
+fn sort_diagnostics(diagnostics: &[ParseDiagnostic]) -> Vec<ParseDiagnostic> {
+    let mut diagnostics = diagnostics.to_vec();
+    diagnostics.sort_by(|a, b| {
+        (a.location.line, a.location.column, Reverse(a.severity), &a.message)
+            .cmp(&(b.location.line, b.location.column, Reverse(b.severity), &b.message))
+    });
+    let mut seen = HashSet::new();
+    diagnostics.retain(|d| seen.insert((d.location.line, d.location.column, d.message.clone())));
+    diagnostics
+}
+
+/// Check if any of the diagnostics is an error
+fn has_errors(diagnostics: &[ParseDiagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+}
+
 /// A diagnostic message from parsing
 ///
 /// Represents errors, warnings, and informational messages
 /// generated during the parsing process.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ParseDiagnostic {
     /// Severity level of the diagnostic
     pub severity: DiagnosticSeverity,
@@ -181,10 +427,14 @@ pub struct ParseDiagnostic {
     pub message: String,
     
     /// Optional diagnostic code
-    pub code: Option<String>,
+    pub code: Option<DiagnosticCode>,
     
     /// Source of the diagnostic (e.g., "nix-parser", "plugin-name")
     pub source: Option<String>,
+
+    /// Suggested fixes, such as a token to insert
+    #[serde(default)]
+    pub suggestions: Vec<String>,
 }
 
 impl ParseDiagnostic {
@@ -196,6 +446,7 @@ impl ParseDiagnostic {
             message: message.into(),
             code: None,
             source: Some("nix-parser".to_string()),
+            suggestions: Vec::new(),
         }
     }
     
@@ -207,6 +458,7 @@ impl ParseDiagnostic {
             message: message.into(),
             code: None,
             source: Some("nix-parser".to_string()),
+            suggestions: Vec::new(),
         }
     }
     
@@ -218,20 +470,52 @@ impl ParseDiagnostic {
             message: message.into(),
             code: None,
             source: Some("nix-parser".to_string()),
+            suggestions: Vec::new(),
         }
     }
     
     /// Set the diagnostic code
-    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+    ///
+    /// Strings naming a built-in code are converted to it, and other
+    /// strings become [`DiagnosticCode::Other`].
+    pub fn with_code(mut self, code: impl Into<DiagnosticCode>) -> Self {
         self.code = Some(code.into());
         self
     }
     
+    /// Add a suggested fix
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestions.push(suggestion.into());
+        self
+    }
+    
     /// Set the diagnostic source
     pub fn with_source(mut self, source: impl Into<String>) -> Self {
         self.source = Some(source.into());
         self
     }
+
+    /// Convert to a [`Diagnostic`] spanning the same source range, for
+    /// rendering with a `DiagnosticBuilder`
+    ///
+    /// Suggested fixes are carried over as the suggestions of its context.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => Severity::Error,
+            DiagnosticSeverity::Warning => Severity::Warning,
+            DiagnosticSeverity::Info => Severity::Info,
+        };
+        let (end_row, end_column) = self.location.end_position;
+        let diagnostic = Diagnostic::new(severity, self.location.line, self.location.column, self.message.clone())
+            .with_end(end_row + 1, end_column + 1);
+        if self.suggestions.is_empty() {
+            return diagnostic;
+        }
+        diagnostic.with_context(ErrorContext {
+            suggestions: self.suggestions.clone(),
+            ..ErrorContext::default()
+        })
+    }
 }
 
 impl fmt::Display for ParseDiagnostic {
@@ -245,7 +529,8 @@ impl fmt::Display for ParseDiagnostic {
 }
 
 /// Severity level for diagnostics
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DiagnosticSeverity {
     /// Informational message
     Info,
@@ -265,8 +550,103 @@ impl fmt::Display for DiagnosticSeverity {
     }
 }
 
+/// Code identifying the kind of a diagnostic
+///
+/// Codes are serialized as their [`code_str`](Self::code_str), which is
+/// stable and can be used to filter or suppress diagnostics. Plugins may
+/// emit codes of their own, which are kept as [`DiagnosticCode::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum DiagnosticCode {
+    /// Source that does not match the grammar
+    SyntaxError,
+    /// Token that is missing and was inserted by error recovery
+    MissingNode,
+    /// Integer literal that does not fit in 64 bits
+    IntegerOverflow,
+    /// Syntax error that was fixed automatically before parsing
+    AutoFix,
+    /// Deprecated legacy `let { ... }` expression
+    LegacyLet,
+    /// Flake whose structure Nix would reject
+    InvalidFlake,
+    /// Code that is not built in, such as one emitted by a plugin
+    Other(String),
+}
+
+impl DiagnosticCode {
+    /// Get all built-in codes
+    pub fn all() -> &'static [DiagnosticCode] {
+        &[
+            DiagnosticCode::SyntaxError,
+            DiagnosticCode::MissingNode,
+            DiagnosticCode::IntegerOverflow,
+            DiagnosticCode::AutoFix,
+            DiagnosticCode::LegacyLet,
+            DiagnosticCode::InvalidFlake,
+        ]
+    }
+
+    /// Get the code as a string
+    pub fn code_str(&self) -> &str {
+        match self {
+            DiagnosticCode::SyntaxError => "syntax_error",
+            DiagnosticCode::MissingNode => "missing_node",
+            DiagnosticCode::IntegerOverflow => "integer_overflow",
+            DiagnosticCode::AutoFix => "auto_fix",
+            DiagnosticCode::LegacyLet => "legacy_let",
+            DiagnosticCode::InvalidFlake => "invalid_flake",
+            DiagnosticCode::Other(code) => code,
+        }
+    }
+
+    /// Get a description of what the code means
+    pub fn description(&self) -> &'static str {
+        match self {
+            DiagnosticCode::SyntaxError => "the source does not match the Nix grammar",
+            DiagnosticCode::MissingNode => "a token is missing",
+            DiagnosticCode::IntegerOverflow => "an integer literal is out of range for a 64-bit integer",
+            DiagnosticCode::AutoFix => "a syntax error was fixed automatically",
+            DiagnosticCode::LegacyLet => "the deprecated `let { ... }` form is used instead of `let ... in`",
+            DiagnosticCode::InvalidFlake => "the flake does not have the structure Nix expects",
+            DiagnosticCode::Other(_) => "a diagnostic that is not built in",
+        }
+    }
+}
+
+impl From<&str> for DiagnosticCode {
+    fn from(code: &str) -> Self {
+        Self::all()
+            .iter()
+            .find(|known| known.code_str() == code)
+            .cloned()
+            .unwrap_or_else(|| DiagnosticCode::Other(code.to_string()))
+    }
+}
+
+impl From<String> for DiagnosticCode {
+    fn from(code: String) -> Self {
+        Self::from(code.as_str())
+    }
+}
+
+impl From<DiagnosticCode> for String {
+    fn from(code: DiagnosticCode) -> Self {
+        match code {
+            DiagnosticCode::Other(code) => code,
+            known => known.code_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code_str())
+    }
+}
+
 /// Statistics about a parse result
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ParseStats {
     /// Number of nodes in the parse tree
     pub node_count: usize,
@@ -274,6 +654,9 @@ pub struct ParseStats {
     /// Parse time in milliseconds
     pub parse_time_ms: u64,
     
+    /// Timing measurement of the Tree-sitter parse
+    pub timing: TimingResult,
+    
     /// Number of errors found
     pub error_count: usize,
     
@@ -285,11 +668,16 @@ pub struct ParseStats {
     
     /// Whether incremental parsing was used
     pub incremental: bool,
+    
+    /// Memory allocated while parsing, measured with the `memory-stats`
+    /// feature
+    #[serde(default)]
+    pub memory: Option<MemoryStats>,
 }
 
 impl ParseStats {
-    /// Create statistics from a parse result
-    pub fn from_result(result: &ParseResult, parse_time_ms: u64, incremental: bool) -> Self {
+    /// Create statistics from a parse result and the timing of its parse
+    pub fn from_result(result: &ParseResult, timing: TimingResult, incremental: bool) -> Self {
         let node_count = Self::count_nodes(&result.tree.root_node());
         let error_count = result.diagnostics.iter()
             .filter(|d| d.severity == DiagnosticSeverity::Error)
@@ -300,22 +688,18 @@ impl ParseStats {
             
         Self {
             node_count,
-            parse_time_ms,
+            parse_time_ms: timing.duration_ms(),
+            timing,
             error_count,
             warning_count,
             source_size: result.source.len(),
             incremental,
+            memory: None,
         }
     }
     
     fn count_nodes(node: &Node) -> usize {
-        let mut count = 1;
-        for i in 0..node.child_count() {
-            if let Some(child) = node.child(i) {
-                count += Self::count_nodes(&child);
-            }
-        }
-        count
+        node.preorder().count()
     }
 }
 
@@ -324,7 +708,7 @@ mod tests {
     use super::*;
     use tree_sitter::{Parser, Language};
 
-    extern "C" {
+    unsafe extern "C" {
         fn tree_sitter_nix() -> Language;
     }
 
@@ -356,6 +740,80 @@ mod tests {
         assert!(result.error_summary().is_some());
     }
 
+    #[test]
+    fn test_sorted_diagnostics() {
+        let source = "let a = { b = ; in a";
+        let mut parser = create_test_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let mut result = ParseResult::from_tree(tree, source.to_string()).unwrap();
+        let missing = result.diagnostics()[1].clone();
+        assert_eq!(missing.message, "Missing: identifier");
+        result.add_diagnostic(ParseDiagnostic::warning(missing.location, "Missing: identifier"));
+        result.add_diagnostic(ParseDiagnostic::info(SourceLocation::new(1, 5, 4, 5), "binding `a`"));
+        result.add_diagnostic(missing);
+
+        let sorted: Vec<String> = result.sorted_diagnostics().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "error: Syntax error near: 'let a = { b = ; in a' at 1:1",
+                "info: binding `a` at 1:5",
+                "error: Missing: identifier at 1:14",
+            ]
+        );
+        assert_eq!(result.diagnostics().len(), 5);
+    }
+
+    fn suggestions(source: &str) -> Vec<String> {
+        let mut parser = create_test_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let result = ParseResult::from_tree(tree, source.to_string()).unwrap();
+        result.diagnostics().iter().flat_map(|d| d.suggestions.clone()).collect()
+    }
+
+    #[test]
+    fn test_syntax_error_suggestions() {
+        assert_eq!(suggestions("{ x = 1 }"), ["insert ';' after this binding"]);
+        assert_eq!(suggestions("(1 + 2"), ["insert ')' to close the '(' at 1:1"]);
+        assert_eq!(suggestions("f ({ a = 1; )"), ["insert '}' to close the '{' at 1:4"]);
+        assert_eq!(suggestions("1 + 2)"), ["remove the unmatched ')' at 1:6"]);
+        assert_eq!(suggestions("let a = 1; b = 2 in a"), ["insert ';' before the 'in' at 1:18"]);
+
+        // Unterminated strings, inside and at the end of an `ERROR`
+        assert_eq!(suggestions("{ a = \"b; }"), ["insert '\"' to close the string at 1:7"]);
+        assert_eq!(suggestions("x: ''\n  a"), ["insert \"''\" to close the string at 1:4"]);
+
+        // There is no single text to insert for a missing value
+        assert!(suggestions("{ y = ; }").is_empty());
+
+        let mut parser = create_test_parser();
+        let tree = parser.parse("x: \"abc", None).unwrap();
+        let result = ParseResult::from_tree(tree, "x: \"abc".to_string()).unwrap();
+        let diagnostic = &result.diagnostics()[0];
+        assert_eq!(diagnostic.to_string(), "error: Missing: \" at 1:8");
+        let context = diagnostic.to_diagnostic().context.unwrap();
+        assert_eq!(context.suggestions, ["insert '\"' to close the string at 1:4"]);
+    }
+
+    #[test]
+    fn test_diagnostic_codes() {
+        let mut parser = create_test_parser();
+        let source = "{ a = ; b = [ 1 ( ]; }";
+        let tree = parser.parse(source, None).unwrap();
+        let result = ParseResult::from_tree(tree, source.to_string()).unwrap();
+        let codes: Vec<_> = result.diagnostics().iter().map(|d| d.code.clone()).collect();
+        assert_eq!(codes, [Some(DiagnosticCode::MissingNode), Some(DiagnosticCode::SyntaxError)]);
+
+        for code in DiagnosticCode::all() {
+            assert_eq!(DiagnosticCode::from(code.code_str()), *code);
+            assert_eq!(serde_json::to_string(code).unwrap(), format!("\"{code}\""));
+        }
+        let other: DiagnosticCode = serde_json::from_str("\"store_path\"").unwrap();
+        assert_eq!(other, DiagnosticCode::Other("store_path".to_string()));
+        assert_eq!(String::from(other), "store_path");
+        assert_eq!(DiagnosticCode::IntegerOverflow.to_string(), "integer_overflow");
+    }
+
     #[test]
     fn test_diagnostic_creation() {
         let location = SourceLocation::new(1, 5, 0, 5);
@@ -365,20 +823,120 @@ mod tests {
             
         assert_eq!(diag.severity, DiagnosticSeverity::Error);
         assert_eq!(diag.message, "Test error");
-        assert_eq!(diag.code, Some("E001".to_string()));
+        assert_eq!(diag.code, Some(DiagnosticCode::Other("E001".to_string())));
         assert_eq!(diag.source, Some("test".to_string()));
     }
 
+    #[test]
+    fn test_json_round_trip() {
+        let source = "{ pi = 3.141592653589793; tiny = 1.0e-300; s = \"a${b}c\"; p = ./${n}.nix; bad = ; }";
+        let config = ParserConfig::builder().collect_statistics(true).build();
+        let result = crate::parser::NixParser::with_config(config).unwrap().parse(source).unwrap();
+
+        let json = result.to_json().unwrap();
+        assert!(json.contains("\"severity\":\"error\""), "{}", json);
+
+        let view: ParseResultJson = serde_json::from_str(&json).unwrap();
+        assert_eq!(view, result.to_json_view().unwrap());
+        assert_eq!(view.version, ParseResultJson::VERSION);
+        assert_eq!(view.diagnostics, result.diagnostics());
+        assert!(view.statistics.is_some());
+
+        // Locations, float bits and interpolated parts all survive
+        let expected = result.expression().unwrap().unwrap();
+        let actual = view.expression.unwrap();
+        assert_eq!(format!("{actual:?}"), format!("{:?}", expected));
+        let Expression::AttributeSet { attributes, .. } = actual.unlocated() else {
+            panic!("expected an attribute set, got {actual:?}");
+        };
+        assert_eq!(attributes[0].value, Expression::Float(std::f64::consts::PI));
+        assert_eq!(attributes[1].value, Expression::Float(1.0e-300));
+    }
+
+    #[test]
+    fn test_expand_selection() {
+        let source = "f (a + b * c) d";
+        let result = crate::parser::NixParser::new().unwrap().parse(source).unwrap();
+        let span = |expr: &Expression| {
+            let location = expr.location().unwrap();
+            &source[location.start_byte..location.end_byte]
+        };
+
+        let c = result.enclosing_expression(11, 12).unwrap().unwrap();
+        assert_eq!(c, Expression::ident("c"));
+
+        // Grow the selection from the cursor in `c` up to the root
+        let mut chain = Vec::new();
+        let (mut start, mut end) = (11, 11);
+        while let Some(expr) = result.expand_selection(start, end).unwrap() {
+            let location = expr.location().unwrap();
+            (start, end) = (location.start_byte, location.end_byte);
+            chain.push(span(&expr).to_string());
+        }
+        assert_eq!(chain, ["c", "b * c", "a + b * c", "f (a + b * c)", source]);
+
+        // Ranges spanning siblings resolve to their common parent
+        let parent = result.enclosing_expression(3, 8).unwrap().unwrap();
+        assert_eq!(span(&parent), "a + b * c");
+        let parent = result.enclosing_expression(0, 15).unwrap().unwrap();
+        assert_eq!(span(&parent), source);
+        assert_eq!(result.enclosing_expression(0, 100).unwrap(), None);
+    }
+
     #[test]
     fn test_parse_stats() {
         let mut parser = create_test_parser();
         let tree = parser.parse("{ x = 1; }", None).unwrap();
         let result = ParseResult::from_tree(tree, "{ x = 1; }".to_string()).unwrap();
         
-        let stats = ParseStats::from_result(&result, 100, false);
+        let timing = TimingResult {
+            label: "parse".to_string(),
+            duration: std::time::Duration::from_millis(100),
+        };
+        let stats = ParseStats::from_result(&result, timing, false);
         assert!(stats.node_count > 0);
         assert_eq!(stats.parse_time_ms, 100);
+        assert_eq!(stats.timing.label, "parse");
         assert_eq!(stats.source_size, 10);
         assert!(!stats.incremental);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_count_nodes_deep_tree() {
+        let depth = 100_000;
+        let source = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let mut parser = create_test_parser();
+        let tree = parser.parse(&source, None).unwrap();
+        let result = ParseResult::from_tree(tree, source).unwrap();
+
+        // Each level is a parenthesized node with its two parentheses
+        let timing = TimingResult {
+            label: "parse".to_string(),
+            duration: std::time::Duration::ZERO,
+        };
+        let stats = ParseStats::from_result(&result, timing, false);
+        assert_eq!(stats.node_count, 3 * depth + 2);
+        assert_eq!(stats.node_count, result.tree.root_node().descendant_count());
+        assert_eq!(ParseStats::count_nodes(&result.tree.root_node().child(0).unwrap()), 3 * depth + 1);
+    }
+
+    #[test]
+    fn test_node_kind_histogram() {
+        let source = "{ x = 1; y = 2; }";
+        let mut parser = create_test_parser();
+        let tree = parser.parse(source, None).unwrap();
+        let result = ParseResult::from_tree(tree, source.to_string()).unwrap();
+
+        let histogram = result.node_kind_histogram();
+        assert_eq!(histogram["source_file"], 1);
+        assert_eq!(histogram["attrset"], 1);
+        assert_eq!(histogram["binding"], 2);
+        assert_eq!(histogram["attrpath"], 2);
+        assert_eq!(histogram["identifier"], 2);
+        assert_eq!(histogram["integer"], 2);
+        assert_eq!(histogram["="], 2);
+        assert_eq!(histogram[";"], 2);
+        assert_eq!(histogram.get("string"), None);
+        assert_eq!(histogram.values().sum::<usize>(), result.tree.root_node().descendant_count());
+    }
+}
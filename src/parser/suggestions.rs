@@ -0,0 +1,254 @@
+//! Suggested fixes for common syntax errors
+//!
+//! Tree-sitter reports two kinds of errors: tokens it assumed to be
+//! missing, and `ERROR` nodes wrapping text it could not make sense of.
+//! Missing tokens are explained by the node they are missing from, while
+//! the text of `ERROR` nodes is scanned for unbalanced delimiters and
+//! unterminated strings.
+
+use tree_sitter::Node;
+
+use crate::utils::text::TextUtils;
+
+/// Suggest a fix for a token that Tree-sitter reported as missing
+///
+/// Returns `None` for missing named nodes, such as an identifier or a
+/// value, because there is no single text to insert for them.
+pub(crate) fn for_missing(node: Node, source: &str) -> Option<String> {
+    if node.is_named() {
+        return None;
+    }
+    let token = node.kind();
+    let parent = node.parent();
+    let suggestion = match token {
+        ";" => match parent.map(|parent| parent.kind()) {
+            Some("binding") => "insert ';' after this binding".to_string(),
+            Some("inherit") => "insert ';' after this inherit".to_string(),
+            _ => "insert ';'".to_string(),
+        },
+        ")" | "]" | "}" => {
+            let opener = parent.and_then(|parent| {
+                let mut cursor = parent.walk();
+                parent
+                    .children(&mut cursor)
+                    .take_while(|child| child.start_byte() < node.start_byte())
+                    .find(|child| !child.is_named() && opens(child.kind(), token))
+            });
+            match opener {
+                Some(opener) => format!(
+                    "insert '{}' to close the '{}' at {}",
+                    token,
+                    opener.kind(),
+                    position(&TextUtils::new(source), opener.start_byte())
+                ),
+                None => format!("insert '{token}'"),
+            }
+        }
+        _ => format!("insert '{token}'"),
+    };
+    Some(suggestion)
+}
+
+/// Get the closing delimiter of a string whose end Tree-sitter reported as
+/// missing
+///
+/// The end of a string is a hidden external token, so unlike other missing
+/// tokens it is not visible as a child. Such a string contains an error
+/// that none of its children do.
+pub(crate) fn unterminated_string(node: Node) -> Option<&'static str> {
+    let delimiter = match node.kind() {
+        "string" => "\"",
+        "indented_string" => "''",
+        _ => return None,
+    };
+    let mut cursor = node.walk();
+    let unterminated = node.has_error() && !node.children(&mut cursor).any(|child| child.has_error());
+    unterminated.then_some(delimiter)
+}
+
+/// Suggest a fix for a string reported by [`unterminated_string`]
+pub(crate) fn for_unterminated_string(node: Node, delimiter: &str, source: &str) -> String {
+    format!(
+        "insert {} to close the string at {}",
+        quote(delimiter),
+        position(&TextUtils::new(source), node.start_byte())
+    )
+}
+
+/// Suggest fixes for the text of an `ERROR` node starting at `start_byte`
+pub(crate) fn for_error(source: &str, start_byte: usize, end_byte: usize) -> Vec<String> {
+    let Some(text) = source.get(start_byte..end_byte) else {
+        return Vec::new();
+    };
+    let lines = TextUtils::new(source);
+    let at = |offset: usize| position(&lines, start_byte + offset);
+
+    match scan(text) {
+        Some(Problem::Unclosed(frame)) => vec![match frame {
+            Frame::Bracket(open, offset) => {
+                format!("insert '{}' to close the '{}' at {}", closer(open), open as char, at(offset))
+            }
+            Frame::Interpolation(offset) => format!("insert '}}' to close the '${{' at {}", at(offset)),
+            Frame::String(offset) => format!("insert {} to close the string at {}", quote("\""), at(offset)),
+            Frame::IndentedString(offset) => format!("insert {} to close the string at {}", quote("''"), at(offset)),
+        }],
+        Some(Problem::Unmatched(close, offset)) => {
+            vec![format!("remove the unmatched '{}' at {}", close as char, at(offset))]
+        }
+        Some(Problem::MissingSemicolon(offset)) => vec![format!("insert ';' before the 'in' at {}", at(offset))],
+        None => Vec::new(),
+    }
+}
+
+/// A delimited construct that is still open while scanning
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+    /// An opening `(`, `[` or `{`
+    Bracket(u8, usize),
+    /// An interpolation, `${`
+    Interpolation(usize),
+    /// A double-quoted string
+    String(usize),
+    /// An indented string, `''`
+    IndentedString(usize),
+}
+
+/// A problem found by [`scan`], with the offset it is reported at
+#[derive(Debug, Clone, Copy)]
+enum Problem {
+    /// A delimiter that is never closed
+    Unclosed(Frame),
+    /// A closing bracket without an opening one
+    Unmatched(u8, usize),
+    /// The `in` of a `let` not preceded by a `;`
+    MissingSemicolon(usize),
+}
+
+/// Scan the text of an `ERROR` node for the first problem with its
+/// delimiters
+///
+/// Only ASCII bytes are compared, so the scan can step over multi-byte
+/// characters a byte at a time.
+fn scan(text: &str) -> Option<Problem> {
+    let bytes = text.as_bytes();
+    let is_word = |byte: u8| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'\'' | b'-');
+    let mut stack: Vec<Frame> = Vec::new();
+    // Whether the text starts a `let`, and the last byte of code before
+    // the current one, to find an `in` without a `;` before it
+    let in_let = bytes.starts_with(b"let") && !bytes.get(3).is_some_and(|&byte| is_word(byte));
+    let mut previous = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        match stack.last() {
+            Some(Frame::String(_)) => {
+                i += match rest {
+                    [b'\\', ..] => 2,
+                    [b'$', b'{', ..] => {
+                        stack.push(Frame::Interpolation(i));
+                        2
+                    }
+                    [b'"', ..] => {
+                        stack.pop();
+                        1
+                    }
+                    _ => 1,
+                };
+                continue;
+            }
+            Some(Frame::IndentedString(_)) => {
+                i += match rest {
+                    [b'\'', b'\'', b'\\', ..] => 4,
+                    [b'\'', b'\'', b'$' | b'\'', ..] => 3,
+                    [b'\'', b'\'', ..] => {
+                        stack.pop();
+                        2
+                    }
+                    [b'$', b'{', ..] => {
+                        stack.push(Frame::Interpolation(i));
+                        2
+                    }
+                    _ => 1,
+                };
+                continue;
+            }
+            _ => {}
+        }
+
+        let byte = rest[0];
+        match rest {
+            [b'#', ..] => {
+                i += rest.iter().position(|&byte| byte == b'\n').unwrap_or(rest.len());
+                continue;
+            }
+            [b'/', b'*', ..] => {
+                i += rest.windows(2).position(|pair| pair == b"*/").map_or(rest.len(), |end| end + 2);
+                continue;
+            }
+            [b'"', ..] => stack.push(Frame::String(i)),
+            [b'\'', b'\'', ..] => {
+                stack.push(Frame::IndentedString(i));
+                i += 2;
+                continue;
+            }
+            [b'$', b'{', ..] => {
+                stack.push(Frame::Interpolation(i));
+                i += 2;
+                continue;
+            }
+            [b'(' | b'[' | b'{', ..] => stack.push(Frame::Bracket(byte, i)),
+            [b')' | b']' | b'}', ..] => match stack.pop() {
+                Some(Frame::Bracket(open, _)) if closer(open) == byte as char => {}
+                Some(Frame::Interpolation(_)) if byte == b'}' => {}
+                Some(frame) => return Some(Problem::Unclosed(frame)),
+                None => return Some(Problem::Unmatched(byte, i)),
+            },
+            _ if is_word(byte) && !byte.is_ascii_digit() => {
+                let length = rest.iter().position(|&byte| !is_word(byte)).unwrap_or(rest.len());
+                if in_let && stack.is_empty() && &rest[..length] == b"in" && previous.is_some_and(|byte| byte != b';') {
+                    return Some(Problem::MissingSemicolon(i));
+                }
+                previous = Some(rest[length - 1]);
+                i += length;
+                continue;
+            }
+            _ => {}
+        }
+        if !byte.is_ascii_whitespace() {
+            previous = Some(byte);
+        }
+        i += 1;
+    }
+    stack.pop().map(Problem::Unclosed)
+}
+
+/// Check if `open` is the token opening a construct closed by `close`
+fn opens(open: &str, close: &str) -> bool {
+    matches!((open, close), ("(", ")") | ("[", "]") | ("{" | "${", "}"))
+}
+
+/// Get the bracket closing `open`
+const fn closer(open: u8) -> char {
+    match open {
+        b'(' => ')',
+        b'[' => ']',
+        _ => '}',
+    }
+}
+
+/// Quote a token for a suggestion, using double quotes for tokens
+/// containing single quotes
+fn quote(token: &str) -> String {
+    if token.contains('\'') {
+        format!("\"{token}\"")
+    } else {
+        format!("'{token}'")
+    }
+}
+
+/// Format the line and column of a byte offset
+fn position(lines: &TextUtils, offset: usize) -> String {
+    let (line, column) = lines.byte_to_line_col(offset);
+    format!("{line}:{column}")
+}
@@ -1,25 +1,56 @@
+//! Plugins hooking into the parser
+//!
+//! A [`Plugin`] can rewrite the source before parsing, rewrite or validate
+//! the resulting tree, and report diagnostics of its own.
+
 use crate::error::Result;
+use crate::parser::{ParseDiagnostic, ParseResult};
 use tree_sitter::Tree;
 
 /// Plugin trait for extending parser functionality
 pub trait Plugin: Send + Sync {
     /// Name of the plugin
     fn name(&self) -> &str;
+
+    /// Priority of the plugin; plugins with a higher priority run first
+    fn priority(&self) -> i32 {
+        0
+    }
     
     /// Pre-process source code before parsing
+    ///
+    /// # Errors
+    ///
+    /// An error aborts the parse.
     fn pre_process(&mut self, source: String) -> Result<String> {
         Ok(source)
     }
     
     /// Post-process the parsed tree
+    ///
+    /// # Errors
+    ///
+    /// An error aborts the parse.
     fn post_process(&mut self, tree: Tree) -> Result<Tree> {
         Ok(tree)
     }
     
     /// Validate the parsed tree
+    ///
+    /// # Errors
+    ///
+    /// An error rejects the tree, failing the parse.
     fn validate(&self, _tree: &Tree) -> Result<()> {
         Ok(())
     }
+
+    /// Report problems found in a parse result
+    ///
+    /// The diagnostics are appended to the result's diagnostics. Use
+    /// `ParseDiagnostic::with_source` to attribute them to the plugin.
+    fn diagnostics(&self, _result: &ParseResult) -> Vec<ParseDiagnostic> {
+        Vec::new()
+    }
 }
 
 /// Example plugin that adds logging
@@ -27,7 +58,14 @@ pub struct LoggingPlugin {
     name: String,
 }
 
+impl Default for LoggingPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LoggingPlugin {
+    /// Create the plugin
     pub fn new() -> Self {
         Self {
             name: "logging".to_string(),
@@ -55,7 +93,7 @@ impl Plugin for LoggingPlugin {
 pub struct WhitespaceNormalizer;
 
 impl Plugin for WhitespaceNormalizer {
-    fn name(&self) -> &str {
+    fn name(&self) -> &'static str {
         "whitespace_normalizer"
     }
     
@@ -69,6 +107,8 @@ impl Plugin for WhitespaceNormalizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::SourceLocation;
+    use crate::parser::{DiagnosticSeverity, NixParser};
     
     #[test]
     fn test_logging_plugin() {
@@ -76,6 +116,92 @@ mod tests {
         assert_eq!(plugin.name(), "logging");
     }
     
+    /// Plugin that appends its tag to the source as a comment
+    struct Tagger {
+        tag: &'static str,
+        priority: i32,
+    }
+
+    impl Plugin for Tagger {
+        fn name(&self) -> &str {
+            self.tag
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn pre_process(&mut self, source: String) -> Result<String> {
+            Ok(format!("{} # {}", source, self.tag))
+        }
+    }
+
+    /// Plugin that warns about hardcoded store paths in strings
+    struct StorePathLint;
+
+    impl Plugin for StorePathLint {
+        fn name(&self) -> &'static str {
+            "store-path-lint"
+        }
+
+        fn diagnostics(&self, result: &ParseResult) -> Vec<ParseDiagnostic> {
+            let mut diagnostics = Vec::new();
+            let mut stack = vec![result.tree().root_node()];
+            while let Some(node) = stack.pop() {
+                let text = &result.source()[node.byte_range()];
+                if node.kind() == "string" && text.contains("/nix/store/") {
+                    let location = SourceLocation::from_tree_sitter_node(&node);
+                    diagnostics.push(
+                        ParseDiagnostic::warning(location, "hardcoded store path")
+                            .with_code("store_path")
+                            .with_source(self.name()),
+                    );
+                }
+                let mut cursor = node.walk();
+                stack.extend(node.children(&mut cursor));
+            }
+            diagnostics
+        }
+    }
+
+    #[test]
+    fn test_plugin_diagnostics() {
+        let mut parser = NixParser::new().unwrap();
+        parser.add_plugin(Box::new(StorePathLint));
+
+        let result = parser.parse("{ src = \"/nix/store/abc-foo\"; name = \"foo\"; }").unwrap();
+        assert_eq!(result.diagnostics().len(), 1);
+        let diagnostic = &result.diagnostics()[0];
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostic.message, "hardcoded store path");
+        assert_eq!(diagnostic.source.as_deref(), Some("store-path-lint"));
+        assert_eq!((diagnostic.location.line, diagnostic.location.column), (1, 9));
+        assert!(!result.has_errors());
+
+        assert!(parser.parse("{ name = \"foo\"; }").unwrap().diagnostics().is_empty());
+    }
+
+    fn tagged_source(plugins: Vec<Tagger>) -> String {
+        let mut parser = NixParser::new().unwrap();
+        for plugin in plugins {
+            parser.add_plugin(Box::new(plugin));
+        }
+        parser.parse("1").unwrap().source().to_string()
+    }
+
+    #[test]
+    fn test_plugin_priority() {
+        let low = || Tagger { tag: "low", priority: -1 };
+        let high = || Tagger { tag: "high", priority: 10 };
+        assert_eq!(tagged_source(vec![low(), high()]), "1 # high # low");
+        assert_eq!(tagged_source(vec![high(), low()]), "1 # high # low");
+
+        // Equal priorities keep insertion order
+        let first = Tagger { tag: "first", priority: 0 };
+        let second = Tagger { tag: "second", priority: 0 };
+        assert_eq!(tagged_source(vec![first, second]), "1 # first # second");
+    }
+
     #[test]
     fn test_whitespace_normalizer() {
         let mut plugin = WhitespaceNormalizer;
@@ -0,0 +1,72 @@
+//! Python bindings, built as the `nix_parser` extension module
+//!
+//! Build with `maturin develop --features python`. Results are returned as
+//! plain Python objects following the schema of `ParseResultJson`.
+
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use crate::parser::{NixParser, ParseResult};
+
+create_exception!(nix_parser, ParseError, PyException, "Raised when Nix source cannot be parsed");
+
+impl From<crate::error::ParseError> for PyErr {
+    fn from(err: crate::error::ParseError) -> Self {
+        ParseError::new_err(err.to_string())
+    }
+}
+
+/// Convert a parse result to a dict through its JSON view
+fn to_dict<'py>(py: Python<'py>, result: &ParseResult) -> PyResult<Bound<'py, PyAny>> {
+    let json = result.to_json()?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// Parse Nix source code into a dict
+///
+/// The dict holds the AST with source locations under `expression`, and a
+/// list of syntax errors and warnings under `diagnostics`.
+#[pyfunction]
+fn parse<'py>(py: Python<'py>, source: &str) -> PyResult<Bound<'py, PyAny>> {
+    let result = NixParser::new()?.parse(source)?;
+    to_dict(py, &result)
+}
+
+/// A reusable Nix parser
+#[pyclass(name = "NixParser", unsendable)]
+struct PyNixParser {
+    inner: NixParser,
+}
+
+#[pymethods]
+impl PyNixParser {
+    /// Create a parser with the default configuration
+    #[new]
+    fn new() -> PyResult<Self> {
+        Ok(Self { inner: NixParser::new()? })
+    }
+
+    /// Parse Nix source code into a dict, like the module's `parse`
+    fn parse<'py>(&mut self, py: Python<'py>, source: &str) -> PyResult<Bound<'py, PyAny>> {
+        let result = self.inner.parse(source)?;
+        to_dict(py, &result)
+    }
+
+    /// Parse a Nix file into a dict, recording its path under `file_path`
+    fn parse_file<'py>(&mut self, py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyAny>> {
+        let result = self.inner.parse_file(path)?;
+        to_dict(py, &result)
+    }
+}
+
+/// The `nix_parser` Python module
+#[pymodule]
+fn nix_parser(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_class::<PyNixParser>()?;
+    module.add("ParseError", module.py().get_type::<ParseError>())?;
+    Ok(())
+}
@@ -1,4 +1,17 @@
-use crate::ast::Expression;
+//! Pattern matching on Nix code
+//!
+//! `QueryEngine` matches `Pattern`s against the AST, while `TreeQuery` runs
+//! Tree-sitter queries against the concrete syntax tree.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tree_sitter::{Node, Query, QueryCursor, StreamingIterator};
+
+use crate::ast::{Expression, SourceLocation};
+use crate::error::{ParseError, Result};
+use crate::parser::ParseResult;
+use crate::visitor::{LocationTracker, VisitControl, Visitor};
 
 /// Query engine for pattern matching on AST
 pub struct QueryEngine {
@@ -6,6 +19,7 @@ pub struct QueryEngine {
 }
 
 impl QueryEngine {
+    /// Create an engine without patterns
     pub fn new() -> Self {
         Self {
             patterns: Vec::new(),
@@ -17,12 +31,45 @@ impl QueryEngine {
         self.patterns.push(pattern);
     }
     
-    /// Execute queries on an expression
+    /// Execute queries on an expression and all of its sub-expressions
+    ///
+    /// Returns one `Match` per pattern per matching sub-expression, in
+    /// pre-order.
     pub fn query(&self, expr: &Expression) -> Vec<Match> {
+        let mut collector = MatchCollector {
+            patterns: &self.patterns,
+            matches: Vec::new(),
+            locations: LocationTracker::default(),
+        };
+        collector.visit_expression(expr);
+        collector.matches
+    }
+    
+    /// Execute queries on the tree of a parse result, reporting where each match is
+    ///
+    /// The tree is converted with source locations enabled regardless of the
+    /// configuration it was parsed with, so every `Match` has a `location`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError` if the tree cannot be converted to an AST.
+    pub fn query_with_locations(&self, result: &ParseResult) -> Result<Vec<Match>> {
+        let Some(root) = result.tree().root_node().child_by_field_name("expression") else {
+            return Ok(Vec::new());
+        };
+        
+        let mut config = result.config().clone();
+        config.include_locations = true;
+        let expr = Expression::from_tree_sitter_node_with_config(root, result.source(), &config)?;
+        Ok(self.query(&expr))
+    }
+    
+    /// Execute queries on the top-level expression only
+    pub fn query_root(&self, expr: &Expression) -> Vec<Match> {
         let mut matches = Vec::new();
         
         for pattern in &self.patterns {
-            if let Some(m) = pattern.match_expression(expr) {
+            if let Some(m) = pattern.match_expression(expr, expr.location()) {
                 matches.push(m);
             }
         }
@@ -31,14 +78,112 @@ impl QueryEngine {
     }
 }
 
+impl Default for QueryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Visitor that tests every sub-expression against a set of patterns
+struct MatchCollector<'a> {
+    patterns: &'a [Pattern],
+    matches: Vec<Match>,
+    locations: LocationTracker,
+}
+
+impl Visitor for MatchCollector<'_> {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        // Location wrappers are tested through the expression they wrap
+        let Some(location) = self.locations.enter(expr) else {
+            return VisitControl::Continue;
+        };
+        for pattern in self.patterns {
+            if let Some(m) = pattern.match_expression(expr, location) {
+                self.matches.push(m);
+            }
+        }
+        VisitControl::Continue
+    }
+}
+
+/// A Tree-sitter query compiled against the Nix grammar
+///
+/// Queries use Tree-sitter's S-expression syntax and run directly on the
+/// concrete syntax tree, e.g. `(binary_expression left: (integer) @lhs)`.
+pub struct TreeQuery {
+    query: Query,
+}
+
+impl TreeQuery {
+    /// Compile a query
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidQuery` if the query is malformed or refers
+    /// to node kinds or fields that do not exist in the grammar.
+    pub fn new(source: &str) -> Result<Self> {
+        let query = Query::new(&crate::grammar::language(), source)
+            .map_err(|e| ParseError::InvalidQuery(e.to_string()))?;
+        Ok(Self { query })
+    }
+    
+    /// Get the names of the captures used in the query
+    pub fn capture_names(&self) -> &[&str] {
+        self.query.capture_names()
+    }
+    
+    /// Run the query over `node`, returning every capture in document order
+    ///
+    /// `source` must be the text `node` was parsed from.
+    pub fn captures(&self, node: Node<'_>, source: &str) -> Vec<QueryCapture> {
+        let names = self.query.capture_names();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.captures(&self.query, node, source.as_bytes());
+        
+        let mut captures = Vec::new();
+        while let Some((query_match, index)) = matches.next() {
+            let capture = query_match.captures[*index];
+            let byte_range = capture.node.byte_range();
+            captures.push(QueryCapture {
+                name: names[capture.index as usize].to_string(),
+                kind: capture.node.kind().to_string(),
+                text: source.get(byte_range.clone()).unwrap_or_default().to_string(),
+                byte_range,
+            });
+        }
+        captures
+    }
+    
+    /// Run the query over the whole tree of a parse result
+    pub fn captures_in(&self, result: &ParseResult) -> Vec<QueryCapture> {
+        self.captures(result.tree().root_node(), result.source())
+    }
+}
+
+/// A node captured by a `TreeQuery`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryCapture {
+    /// Name of the capture, without the leading `@`
+    pub name: String,
+    /// Node kind of the captured node
+    pub kind: String,
+    /// Byte range of the captured node in the source
+    pub byte_range: Range<usize>,
+    /// Source text of the captured node
+    pub text: String,
+}
+
 /// Pattern for matching AST nodes
 #[derive(Debug, Clone)]
 pub struct Pattern {
+    /// Name reported in the matches of the pattern
     pub name: String,
+    /// Matcher expressions are tested against
     pub matcher: Matcher,
 }
 
 impl Pattern {
+    /// Create a named pattern
     pub fn new(name: impl Into<String>, matcher: Matcher) -> Self {
         Self {
             name: name.into(),
@@ -46,15 +191,14 @@ impl Pattern {
         }
     }
     
-    fn match_expression(&self, expr: &Expression) -> Option<Match> {
-        if self.matcher.matches(expr) {
-            Some(Match {
-                pattern_name: self.name.clone(),
-                matched_expression: expr.clone(),
-            })
-        } else {
-            None
-        }
+    fn match_expression(&self, expr: &Expression, location: Option<SourceLocation>) -> Option<Match> {
+        let captures = self.matcher.captures(expr)?;
+        Some(Match {
+            pattern_name: self.name.clone(),
+            matched_expression: expr.clone(),
+            location,
+            captures,
+        })
     }
 }
 
@@ -73,26 +217,69 @@ pub enum Matcher {
     /// Match a binary operation
     BinaryOp(crate::ast::BinaryOperator),
     
-    /// Combine matchers
+    /// Match a binary operation whose operands match the given matchers
+    BinaryOperands(crate::ast::BinaryOperator, Box<Matcher>, Box<Matcher>),
+    
+    /// Match the inner matcher and bind the matched expression to a name
+    Capture(String, Box<Matcher>),
+    
+    /// Match both matchers, keeping the captures of both
     And(Box<Matcher>, Box<Matcher>),
+
+    /// Match either matcher, keeping the captures of the first one that
+    /// matches
     Or(Box<Matcher>, Box<Matcher>),
+
+    /// Match if the inner matcher does not; never captures
     Not(Box<Matcher>),
 }
 
 impl Matcher {
+    /// Check if an expression matches
     pub fn matches(&self, expr: &Expression) -> bool {
+        self.captures(expr).is_some()
+    }
+    
+    /// Match an expression, returning the captured sub-expressions on success
+    ///
+    /// When several captures share a name, the last one bound wins.
+    pub fn captures(&self, expr: &Expression) -> Option<HashMap<String, Expression>> {
+        let matched = || Some(HashMap::new());
+        let inner = expr.unlocated();
         match self {
-            Matcher::Any => true,
-            Matcher::Type(t) => t.matches(expr),
+            Matcher::Any => matched(),
+            Matcher::Type(t) => t.matches(inner).then(HashMap::new),
             Matcher::Identifier(name) => {
-                matches!(expr, Expression::Identifier(id) if id == name)
+                matches!(inner, Expression::Identifier(id) if id == name).then(HashMap::new)
             }
             Matcher::BinaryOp(op) => {
-                matches!(expr, Expression::BinaryOp { op: expr_op, .. } if expr_op == op)
+                matches!(inner, Expression::BinaryOp { op: expr_op, .. } if expr_op == op)
+                    .then(HashMap::new)
+            }
+            Matcher::BinaryOperands(op, left_matcher, right_matcher) => match inner {
+                Expression::BinaryOp { op: expr_op, left, right } if expr_op == op => {
+                    let mut captures = left_matcher.captures(left)?;
+                    captures.extend(right_matcher.captures(right)?);
+                    Some(captures)
+                }
+                _ => None,
+            },
+            // Captures keep the location of what they bind
+            Matcher::Capture(name, m) => {
+                let mut captures = m.captures(expr)?;
+                captures.insert(name.clone(), expr.clone());
+                Some(captures)
             }
-            Matcher::And(a, b) => a.matches(expr) && b.matches(expr),
-            Matcher::Or(a, b) => a.matches(expr) || b.matches(expr),
-            Matcher::Not(m) => !m.matches(expr),
+            Matcher::And(a, b) => {
+                let mut captures = a.captures(expr)?;
+                captures.extend(b.captures(expr)?);
+                Some(captures)
+            }
+            Matcher::Or(a, b) => a.captures(expr).or_else(|| b.captures(expr)),
+            Matcher::Not(m) => match m.captures(expr) {
+                Some(_) => None,
+                None => matched(),
+            },
         }
     }
 }
@@ -100,59 +287,85 @@ impl Matcher {
 /// Expression type for pattern matching
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExpressionType {
+    /// Integer literal
     Integer,
+    /// Float literal
     Float,
+    /// String literal without interpolation
     String,
+    /// `true` or `false`
     Boolean,
+    /// `null`
     Null,
+    /// Variable reference
     Identifier,
+    /// List
     List,
+    /// Attribute set, recursive or not
     AttributeSet,
+    /// Function
     Function,
+    /// Function application
     Application,
+    /// `let ... in` expression
     LetIn,
+    /// `with` expression
     With,
+    /// `if` expression
     If,
+    /// `assert` expression
     Assert,
+    /// Binary operation
     BinaryOp,
+    /// Unary operation
     UnaryOp,
+    /// Attribute selection
     Select,
+    /// `?` attribute test
     HasAttr,
+    /// String with interpolations
     Interpolation,
 }
 
 impl ExpressionType {
+    /// Check if an expression, once unlocated, is of this type
     pub fn matches(&self, expr: &Expression) -> bool {
-        match (self, expr) {
-            (ExpressionType::Integer, Expression::Integer(_)) => true,
-            (ExpressionType::Float, Expression::Float(_)) => true,
-            (ExpressionType::String, Expression::String(_)) => true,
-            (ExpressionType::Boolean, Expression::Boolean(_)) => true,
-            (ExpressionType::Null, Expression::Null) => true,
-            (ExpressionType::Identifier, Expression::Identifier(_)) => true,
-            (ExpressionType::List, Expression::List(_)) => true,
-            (ExpressionType::AttributeSet, Expression::AttributeSet { .. }) => true,
-            (ExpressionType::Function, Expression::Function { .. }) => true,
-            (ExpressionType::Application, Expression::Application { .. }) => true,
-            (ExpressionType::LetIn, Expression::LetIn { .. }) => true,
-            (ExpressionType::With, Expression::With { .. }) => true,
-            (ExpressionType::If, Expression::If { .. }) => true,
-            (ExpressionType::Assert, Expression::Assert { .. }) => true,
-            (ExpressionType::BinaryOp, Expression::BinaryOp { .. }) => true,
-            (ExpressionType::UnaryOp, Expression::UnaryOp { .. }) => true,
-            (ExpressionType::Select, Expression::Select { .. }) => true,
-            (ExpressionType::HasAttr, Expression::HasAttr { .. }) => true,
-            (ExpressionType::Interpolation, Expression::StringInterpolation { .. }) => true,
-            _ => false,
-        }
+        matches!(
+            (self, expr.unlocated()),
+            (ExpressionType::Integer, Expression::Integer(_))
+                | (ExpressionType::Float, Expression::Float(_))
+                | (ExpressionType::String, Expression::String(_))
+                | (ExpressionType::Boolean, Expression::Boolean(_))
+                | (ExpressionType::Null, Expression::Null)
+                | (ExpressionType::Identifier, Expression::Identifier(_))
+                | (ExpressionType::List, Expression::List(_))
+                | (ExpressionType::AttributeSet, Expression::AttributeSet { .. })
+                | (ExpressionType::Function, Expression::Function { .. })
+                | (ExpressionType::Application, Expression::Application { .. })
+                | (ExpressionType::LetIn, Expression::LetIn { .. })
+                | (ExpressionType::With, Expression::With { .. })
+                | (ExpressionType::If, Expression::If { .. })
+                | (ExpressionType::Assert, Expression::Assert { .. })
+                | (ExpressionType::BinaryOp, Expression::BinaryOp { .. })
+                | (ExpressionType::UnaryOp, Expression::UnaryOp { .. })
+                | (ExpressionType::Select, Expression::Select { .. })
+                | (ExpressionType::HasAttr, Expression::HasAttr { .. })
+                | (ExpressionType::Interpolation, Expression::StringInterpolation { .. })
+        )
     }
 }
 
 /// A match result
 #[derive(Debug, Clone)]
 pub struct Match {
+    /// Name of the pattern that matched
     pub pattern_name: String,
+    /// Expression the pattern matched
     pub matched_expression: Expression,
+    /// Source span of the matched expression, if it was parsed with locations
+    pub location: Option<SourceLocation>,
+    /// Sub-expressions bound by `Matcher::Capture`, by name
+    pub captures: HashMap<String, Expression>,
 }
 
 #[cfg(test)]
@@ -162,12 +375,15 @@ mod tests {
     
     #[test]
     fn test_pattern_matching() {
-        let engine = QueryEngine::new();
+        let mut engine = QueryEngine::new();
         
         let expr = Expression::Integer(42);
         let pattern = Pattern::new("integer", Matcher::Type(ExpressionType::Integer));
         
         assert!(pattern.matcher.matches(&expr));
+        
+        engine.add_pattern(pattern);
+        assert_eq!(engine.query(&expr).len(), 1);
     }
     
     #[test]
@@ -196,4 +412,157 @@ mod tests {
         
         assert!(matcher.matches(&expr));
     }
-}
\ No newline at end of file
+    
+    fn parse(source: &str) -> Expression {
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        parser.parse(source).unwrap().expression().unwrap().unwrap()
+    }
+    
+    #[test]
+    fn test_query_finds_nested_matches() {
+        let mut engine = QueryEngine::new();
+        engine.add_pattern(Pattern::new("integer", Matcher::Type(ExpressionType::Integer)));
+        
+        let matches = engine.query(&parse("[1 2 3]"));
+        let values: Vec<_> = matches.iter()
+            .map(|m| m.matched_expression.unlocated().clone())
+            .collect();
+        assert_eq!(values, vec![
+            Expression::Integer(1),
+            Expression::Integer(2),
+            Expression::Integer(3),
+        ]);
+        assert!(matches.iter().all(|m| m.pattern_name == "integer"));
+        
+        // The root alone is a list
+        assert!(engine.query_root(&parse("[1 2 3]")).is_empty());
+    }
+    
+    fn capture(name: &str, matcher: Matcher) -> Matcher {
+        Matcher::Capture(name.to_string(), Box::new(matcher))
+    }
+    
+    #[test]
+    fn test_capture_binary_operands() {
+        let mut engine = QueryEngine::new();
+        engine.add_pattern(Pattern::new("add", Matcher::BinaryOperands(
+            BinaryOperator::Add,
+            Box::new(capture("lhs", Matcher::Any)),
+            Box::new(capture("rhs", Matcher::Type(ExpressionType::Integer))),
+        )));
+        
+        let matches = engine.query(&parse("[ (x + 1) (x + y) ]"));
+        assert_eq!(matches.len(), 1);
+        
+        let captures = &matches[0].captures;
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures["lhs"].unlocated(), &Expression::Identifier("x".to_string()));
+        assert_eq!(captures["rhs"].unlocated(), &Expression::Integer(1));
+        // Captured expressions keep their source location
+        assert!(captures["lhs"].location().is_some());
+    }
+    
+    #[test]
+    fn test_capture_composition() {
+        let expr = Expression::Integer(42);
+        
+        let both = Matcher::And(
+            Box::new(capture("a", Matcher::Any)),
+            Box::new(capture("b", Matcher::Type(ExpressionType::Integer))),
+        );
+        let captures = both.captures(&expr).unwrap();
+        assert_eq!(captures.keys().count(), 2);
+        
+        // A failing side discards the captures of the other
+        let failing = Matcher::And(
+            Box::new(capture("a", Matcher::Any)),
+            Box::new(capture("b", Matcher::Type(ExpressionType::Float))),
+        );
+        assert!(failing.captures(&expr).is_none());
+        
+        // Only the branch that matched contributes
+        let either = Matcher::Or(
+            Box::new(capture("float", Matcher::Type(ExpressionType::Float))),
+            Box::new(capture("int", Matcher::Type(ExpressionType::Integer))),
+        );
+        let captures = either.captures(&expr).unwrap();
+        assert_eq!(captures.keys().collect::<Vec<_>>(), vec!["int"]);
+        
+        let negated = Matcher::Not(Box::new(capture("float", Matcher::Type(ExpressionType::Float))));
+        assert!(negated.captures(&expr).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_query_with_locations() {
+        let source = "[ 1 (2 + x) ]";
+        let config = crate::parser::ParserConfig::builder().include_locations(false).build();
+        let mut parser = crate::parser::NixParser::with_config(config).unwrap();
+        let result = parser.parse(source).unwrap();
+        
+        let mut engine = QueryEngine::new();
+        engine.add_pattern(Pattern::new("add", Matcher::BinaryOp(BinaryOperator::Add)));
+        engine.add_pattern(Pattern::new("x", Matcher::Identifier("x".to_string())));
+        
+        // Without locations in the AST there is nothing to report
+        let expr = result.expression().unwrap().unwrap();
+        assert!(engine.query(&expr).iter().all(|m| m.location.is_none()));
+        
+        let matches = engine.query_with_locations(&result).unwrap();
+        let spans: Vec<_> = matches.iter()
+            .map(|m| {
+                let location = m.location.expect("match should be located");
+                (m.pattern_name.as_str(), &source[location.start_byte..location.end_byte])
+            })
+            .collect();
+        assert_eq!(spans, vec![("add", "2 + x"), ("x", "x")]);
+    }
+    
+    #[test]
+    fn test_tree_query_captures() {
+        let query = TreeQuery::new("(binary_expression left: (integer) @lhs right: (_) @rhs)").unwrap();
+        assert_eq!(query.capture_names(), &["lhs", "rhs"]);
+        
+        let source = "[ (1 + x) (y * 2) (3 == 4) ]";
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        let result = parser.parse(source).unwrap();
+        
+        let captures: Vec<_> = query.captures_in(&result).into_iter()
+            .map(|c| (c.name, c.text))
+            .collect();
+        assert_eq!(captures, vec![
+            ("lhs".to_string(), "1".to_string()),
+            ("rhs".to_string(), "x".to_string()),
+            ("lhs".to_string(), "3".to_string()),
+            ("rhs".to_string(), "4".to_string()),
+        ]);
+        
+        let first = &query.captures_in(&result)[0];
+        assert_eq!(first.kind, "integer");
+        assert_eq!(&source[first.byte_range.clone()], "1");
+    }
+    
+    #[test]
+    fn test_tree_query_errors() {
+        for invalid in ["(binary_expression", "(no_such_node) @n", "(binary_expression nope: (_))"] {
+            assert!(
+                matches!(TreeQuery::new(invalid), Err(ParseError::InvalidQuery(_))),
+                "{}", invalid
+            );
+        }
+    }
+    
+    #[test]
+    fn test_query_finds_identifiers_in_let() {
+        let mut engine = QueryEngine::new();
+        engine.add_pattern(Pattern::new("identifier", Matcher::Type(ExpressionType::Identifier)));
+        
+        let matches = engine.query(&parse("let a = b; c = a; in a + c"));
+        let names: Vec<_> = matches.iter()
+            .map(|m| match m.matched_expression.unlocated() {
+                Expression::Identifier(id) => id.as_str(),
+                other => panic!("Expected identifier, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(names, vec!["b", "a", "a", "c"]);
+    }
+}
@@ -1,6 +1,7 @@
 #include <tree_sitter/parser.h>
 #include <string.h>
 #include <stdbool.h>
+#include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 
@@ -17,12 +18,22 @@ enum TokenType {
     COMMENT,
 };
 
+// Lexical contexts the scanner can be in. Code outside of any string is
+// the empty stack, and an interpolation switches back to code until its
+// closing brace.
+enum Context {
+    CONTEXT_CODE,
+    CONTEXT_STRING,
+    CONTEXT_INDENTED_STRING,
+    CONTEXT_PATH,
+    CONTEXT_INTERPOLATION,
+};
+
+#define MAX_CONTEXT_DEPTH TREE_SITTER_SERIALIZATION_BUFFER_SIZE
+
 typedef struct {
-    bool in_string;
-    bool in_indented_string;
-    int interpolation_depth;
-    int paren_depth;
-    int brace_depth;
+    uint8_t contexts[MAX_CONTEXT_DEPTH];
+    unsigned depth;
 } Scanner;
 
 static inline void advance(TSLexer *lexer) {
@@ -33,6 +44,30 @@ static inline void skip(TSLexer *lexer) {
     lexer->advance(lexer, true);
 }
 
+static inline uint8_t current_context(Scanner *scanner) {
+    return scanner->depth > 0 ? scanner->contexts[scanner->depth - 1] : CONTEXT_CODE;
+}
+
+static inline bool push_context(Scanner *scanner, uint8_t context) {
+    if (scanner->depth == MAX_CONTEXT_DEPTH) {
+        return false;
+    }
+    scanner->contexts[scanner->depth++] = context;
+    return true;
+}
+
+static inline void pop_context(Scanner *scanner) {
+    if (scanner->depth > 0) {
+        scanner->depth--;
+    }
+}
+
+static inline bool is_path_char(int32_t c) {
+    return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') ||
+           (c >= '0' && c <= '9') || c == '.' || c == '_' || c == '-' ||
+           c == '+' || c == '/';
+}
+
 static bool scan_string_content(TSLexer *lexer) {
     bool has_content = false;
     lexer->mark_end(lexer);
@@ -61,39 +96,82 @@ static bool scan_string_content(TSLexer *lexer) {
     return has_content;
 }
 
-static bool scan_indented_string_content(TSLexer *lexer) {
+// Scan inside an indented string, producing content, the closing '' or an
+// interpolation start. The escapes ''' (literal ''), ''$ (literal $) and
+// ''\x are kept inside content tokens.
+static bool scan_indented_string(Scanner *scanner, TSLexer *lexer,
+                                 const bool *valid_symbols) {
     bool has_content = false;
-    int quote_count = 0;
-    lexer->mark_end(lexer);
-    
-    while (lexer->lookahead != 0) {
+
+    for (;;) {
+        lexer->mark_end(lexer);
+
+        if (lexer->lookahead == 0) {
+            break;
+        }
+
         if (lexer->lookahead == '\'') {
-            quote_count++;
             advance(lexer);
-            if (quote_count == 2) {
-                // Back up - might be end of string
-                return has_content;
+            if (lexer->lookahead != '\'') {
+                has_content = true;
+                continue;
             }
-            has_content = true;
-            lexer->mark_end(lexer);
-        } else {
-            quote_count = 0;
-            if (lexer->lookahead == '$') {
+            advance(lexer);
+            if (lexer->lookahead == '\'' || lexer->lookahead == '$') {
                 advance(lexer);
-                if (lexer->lookahead == '{') {
-                    // Back up - this is interpolation start
-                    return has_content;
-                }
                 has_content = true;
-                lexer->mark_end(lexer);
-            } else {
+                continue;
+            }
+            if (lexer->lookahead == '\\') {
                 advance(lexer);
+                if (lexer->lookahead != 0) {
+                    advance(lexer);
+                }
                 has_content = true;
+                continue;
+            }
+
+            // Closing '': emit pending content first
+            if (has_content) {
+                break;
+            }
+            if (!valid_symbols[INDENTED_STRING_END]) {
+                return false;
+            }
+            lexer->mark_end(lexer);
+            pop_context(scanner);
+            lexer->result_symbol = INDENTED_STRING_END;
+            return true;
+        }
+
+        if (lexer->lookahead == '$') {
+            advance(lexer);
+            if (lexer->lookahead == '{') {
+                if (has_content) {
+                    break;
+                }
+                if (!valid_symbols[INTERPOLATION_START] ||
+                    !push_context(scanner, CONTEXT_INTERPOLATION)) {
+                    return false;
+                }
+                advance(lexer);
                 lexer->mark_end(lexer);
+                lexer->result_symbol = INTERPOLATION_START;
+                return true;
             }
+            has_content = true;
+            continue;
         }
+
+        advance(lexer);
+        has_content = true;
     }
-    return has_content;
+
+    if (has_content && valid_symbols[INDENTED_STRING_CONTENT]) {
+        lexer->result_symbol = INDENTED_STRING_CONTENT;
+        return true;
+    }
+    return false;
 }
 
 static bool scan_escape_sequence(TSLexer *lexer) {
@@ -129,201 +207,253 @@ static bool scan_escape_sequence(TSLexer *lexer) {
     }
 }
 
-static bool scan_comment(TSLexer *lexer) {
-    if (lexer->lookahead == '#') {
-        advance(lexer);
-        while (lexer->lookahead != 0 && lexer->lookahead != '\n') {
+// Scan the rest of a /* */ comment, after its opening /*
+static bool scan_block_comment(TSLexer *lexer) {
+    int depth = 1;
+    while (depth > 0 && lexer->lookahead != 0) {
+        if (lexer->lookahead == '/') {
             advance(lexer);
-        }
-        return true;
-    }
-    
-    if (lexer->lookahead == '/') {
-        advance(lexer);
-        if (lexer->lookahead == '*') {
+            if (lexer->lookahead == '*') {
+                advance(lexer);
+                depth++;
+            }
+        } else if (lexer->lookahead == '*') {
             advance(lexer);
-            
-            int depth = 1;
-            while (depth > 0 && lexer->lookahead != 0) {
-                if (lexer->lookahead == '/') {
-                    advance(lexer);
-                    if (lexer->lookahead == '*') {
-                        advance(lexer);
-                        depth++;
-                    }
-                } else if (lexer->lookahead == '*') {
-                    advance(lexer);
-                    if (lexer->lookahead == '/') {
-                        advance(lexer);
-                        depth--;
-                    }
-                } else {
-                    advance(lexer);
-                }
+            if (lexer->lookahead == '/') {
+                advance(lexer);
+                depth--;
             }
-            return depth == 0;
+        } else {
+            advance(lexer);
         }
     }
-    
-    return false;
+    return depth == 0;
 }
 
-void *tree_sitter_nix_external_scanner_create() {
-    Scanner *scanner = calloc(1, sizeof(Scanner));
-    return scanner;
-}
-
-void tree_sitter_nix_external_scanner_destroy(void *payload) {
-    free(payload);
-}
-
-unsigned tree_sitter_nix_external_scanner_serialize(void *payload, char *buffer) {
-    Scanner *scanner = (Scanner *)payload;
-    buffer[0] = scanner->in_string;
-    buffer[1] = scanner->in_indented_string;
-    buffer[2] = scanner->interpolation_depth;
-    buffer[3] = scanner->paren_depth;
-    buffer[4] = scanner->brace_depth;
-    return 5;
+static bool scan_line_comment(TSLexer *lexer) {
+    advance(lexer);
+    while (lexer->lookahead != 0 && lexer->lookahead != '\n') {
+        advance(lexer);
+    }
+    return true;
 }
 
-void tree_sitter_nix_external_scanner_deserialize(void *payload, const char *buffer, unsigned length) {
-    Scanner *scanner = (Scanner *)payload;
-    if (length >= 5) {
-        scanner->in_string = buffer[0];
-        scanner->in_indented_string = buffer[1];
-        scanner->interpolation_depth = buffer[2];
-        scanner->paren_depth = buffer[3];
-        scanner->brace_depth = buffer[4];
+// Scan the rest of a path up to its first interpolation, after its
+// leading `/`, `./`, `../` or `~/`. The token ends before the `${`.
+// Paths without interpolations are left to the grammar's `path` token.
+static bool scan_interpolated_path_start(TSLexer *lexer) {
+    while (is_path_char(lexer->lookahead)) {
+        advance(lexer);
+    }
+    lexer->mark_end(lexer);
+    if (lexer->lookahead != '$') {
+        return false;
     }
+    advance(lexer);
+    return lexer->lookahead == '{';
 }
 
-bool tree_sitter_nix_external_scanner_scan(void *payload, TSLexer *lexer,
-                                            const bool *valid_symbols) {
-    Scanner *scanner = (Scanner *)payload;
-    
-    // Skip whitespace except when in strings
-    if (!scanner->in_string && !scanner->in_indented_string) {
-        while (lexer->lookahead == ' ' || lexer->lookahead == '\t' || 
-               lexer->lookahead == '\n' || lexer->lookahead == '\r') {
-            skip(lexer);
-        }
+// Scan code, where strings and interpolated paths start and interpolations
+// end. Whitespace and comments are only skipped here, never inside strings.
+static bool scan_code(Scanner *scanner, TSLexer *lexer, const bool *valid_symbols) {
+    while (lexer->lookahead == ' ' || lexer->lookahead == '\t' || 
+           lexer->lookahead == '\n' || lexer->lookahead == '\r') {
+        skip(lexer);
     }
-    
-    // Handle comments
-    if (valid_symbols[COMMENT] && scan_comment(lexer)) {
-        lexer->result_symbol = COMMENT;
-        return true;
-    }
-    
-    // Handle string scanning
-    if (!scanner->in_string && !scanner->in_indented_string) {
-        // Check for string start
-        if (valid_symbols[STRING_START] && lexer->lookahead == '"') {
-            advance(lexer);
-            scanner->in_string = true;
-            lexer->result_symbol = STRING_START;
-            return true;
-        }
-        
-        // Check for indented string start
-        if (valid_symbols[INDENTED_STRING_START] && lexer->lookahead == '\'') {
+
+    switch (lexer->lookahead) {
+        case '#':
+            if (valid_symbols[COMMENT] && scan_line_comment(lexer)) {
+                lexer->result_symbol = COMMENT;
+                return true;
+            }
+            return false;
+
+        case '"':
+            if (valid_symbols[STRING_START] && push_context(scanner, CONTEXT_STRING)) {
+                advance(lexer);
+                lexer->result_symbol = STRING_START;
+                return true;
+            }
+            return false;
+
+        case '\'':
             advance(lexer);
-            if (lexer->lookahead == '\'') {
+            if (lexer->lookahead == '\'' && valid_symbols[INDENTED_STRING_START] &&
+                push_context(scanner, CONTEXT_INDENTED_STRING)) {
                 advance(lexer);
-                scanner->in_indented_string = true;
                 lexer->result_symbol = INDENTED_STRING_START;
                 return true;
             }
-        }
-    }
-    
-    // Inside regular string
-    if (scanner->in_string) {
-        // Check for string end
-        if (valid_symbols[STRING_END] && lexer->lookahead == '"') {
+            return false;
+
+        case '}':
+            if (current_context(scanner) == CONTEXT_INTERPOLATION &&
+                valid_symbols[INTERPOLATION_END]) {
+                advance(lexer);
+                pop_context(scanner);
+                lexer->result_symbol = INTERPOLATION_END;
+                return true;
+            }
+            return false;
+
+        case '/':
             advance(lexer);
-            scanner->in_string = false;
-            lexer->result_symbol = STRING_END;
-            return true;
-        }
-        
-        // Check for interpolation start
-        if (valid_symbols[INTERPOLATION_START] && lexer->lookahead == '$') {
+            if (lexer->lookahead == '*') {
+                advance(lexer);
+                if (valid_symbols[COMMENT] && scan_block_comment(lexer)) {
+                    lexer->result_symbol = COMMENT;
+                    return true;
+                }
+                return false;
+            }
+            break;
+
+        case '.':
             advance(lexer);
-            if (lexer->lookahead == '{') {
+            if (lexer->lookahead == '.') {
                 advance(lexer);
-                scanner->interpolation_depth++;
-                scanner->brace_depth = 1;
-                lexer->result_symbol = INTERPOLATION_START;
-                return true;
             }
-        }
-        
-        // Check for escape sequence
-        if (valid_symbols[ESCAPE_SEQUENCE] && scan_escape_sequence(lexer)) {
-            lexer->result_symbol = ESCAPE_SEQUENCE;
+            if (lexer->lookahead != '/') {
+                return false;
+            }
+            advance(lexer);
+            break;
+
+        case '~':
+            advance(lexer);
+            if (lexer->lookahead != '/') {
+                return false;
+            }
+            advance(lexer);
+            break;
+
+        default:
+            return false;
+    }
+
+    // An interpolated path is lexed like a string whose delimiters are
+    // empty, so its start token holds the text before the first `${`
+    if (valid_symbols[STRING_START] && scan_interpolated_path_start(lexer) &&
+        push_context(scanner, CONTEXT_PATH)) {
+        lexer->result_symbol = STRING_START;
+        return true;
+    }
+    return false;
+}
+
+static bool scan_string(Scanner *scanner, TSLexer *lexer, const bool *valid_symbols) {
+    if (valid_symbols[STRING_END] && lexer->lookahead == '"') {
+        advance(lexer);
+        pop_context(scanner);
+        lexer->result_symbol = STRING_END;
+        return true;
+    }
+
+    if (lexer->lookahead == '$') {
+        // A `$` not followed by `{` is content
+        lexer->mark_end(lexer);
+        advance(lexer);
+        if (lexer->lookahead == '{') {
+            if (!valid_symbols[INTERPOLATION_START] ||
+                !push_context(scanner, CONTEXT_INTERPOLATION)) {
+                return false;
+            }
+            advance(lexer);
+            lexer->mark_end(lexer);
+            lexer->result_symbol = INTERPOLATION_START;
             return true;
         }
-        
-        // String content
-        if (valid_symbols[STRING_CONTENT] && scan_string_content(lexer)) {
-            lexer->result_symbol = STRING_CONTENT;
-            return true;
+        if (!valid_symbols[STRING_CONTENT]) {
+            return false;
         }
+        scan_string_content(lexer);
+        lexer->result_symbol = STRING_CONTENT;
+        return true;
     }
-    
-    // Inside indented string
-    if (scanner->in_indented_string) {
-        // Check for indented string end
-        if (valid_symbols[INDENTED_STRING_END] && lexer->lookahead == '\'') {
+
+    if (valid_symbols[ESCAPE_SEQUENCE] && scan_escape_sequence(lexer)) {
+        lexer->result_symbol = ESCAPE_SEQUENCE;
+        return true;
+    }
+
+    if (valid_symbols[STRING_CONTENT] && scan_string_content(lexer)) {
+        lexer->result_symbol = STRING_CONTENT;
+        return true;
+    }
+    return false;
+}
+
+// Scan the rest of an interpolated path: literal segments, further
+// interpolations, and an empty end token once a character that cannot be
+// part of a path is reached.
+static bool scan_path(Scanner *scanner, TSLexer *lexer, const bool *valid_symbols) {
+    lexer->mark_end(lexer);
+
+    if (lexer->lookahead == '$') {
+        advance(lexer);
+        if (lexer->lookahead == '{' && valid_symbols[INTERPOLATION_START] &&
+            push_context(scanner, CONTEXT_INTERPOLATION)) {
             advance(lexer);
-            if (lexer->lookahead == '\'') {
-                advance(lexer);
-                scanner->in_indented_string = false;
-                lexer->result_symbol = INDENTED_STRING_END;
-                return true;
-            }
+            lexer->mark_end(lexer);
+            lexer->result_symbol = INTERPOLATION_START;
+            return true;
         }
-        
-        // Check for interpolation start
-        if (valid_symbols[INTERPOLATION_START] && lexer->lookahead == '$') {
+    } else if (is_path_char(lexer->lookahead)) {
+        while (is_path_char(lexer->lookahead)) {
             advance(lexer);
-            if (lexer->lookahead == '{') {
-                advance(lexer);
-                scanner->interpolation_depth++;
-                scanner->brace_depth = 1;
-                lexer->result_symbol = INTERPOLATION_START;
-                return true;
-            }
         }
-        
-        // Indented string content
-        if (valid_symbols[INDENTED_STRING_CONTENT] && scan_indented_string_content(lexer)) {
-            lexer->result_symbol = INDENTED_STRING_CONTENT;
+        if (valid_symbols[STRING_CONTENT]) {
+            lexer->mark_end(lexer);
+            lexer->result_symbol = STRING_CONTENT;
             return true;
         }
     }
-    
-    // Handle interpolation end
-    if (scanner->interpolation_depth > 0 && valid_symbols[INTERPOLATION_END]) {
-        // Track brace depth to find matching closing brace
-        if (lexer->lookahead == '{') {
-            scanner->brace_depth++;
-            advance(lexer);
-            return false;
-        } else if (lexer->lookahead == '}') {
-            scanner->brace_depth--;
-            if (scanner->brace_depth == 0) {
-                advance(lexer);
-                scanner->interpolation_depth--;
-                lexer->result_symbol = INTERPOLATION_END;
-                return true;
+
+    if (valid_symbols[STRING_END]) {
+        pop_context(scanner);
+        lexer->result_symbol = STRING_END;
+        return true;
+    }
+    return false;
+}
+
+void *tree_sitter_nix_external_scanner_create() {
+    Scanner *scanner = calloc(1, sizeof(Scanner));
+    return scanner;
+}
+
+void tree_sitter_nix_external_scanner_destroy(void *payload) {
+    free(payload);
+}
+
+unsigned tree_sitter_nix_external_scanner_serialize(void *payload, char *buffer) {
+    Scanner *scanner = (Scanner *)payload;
+    memcpy(buffer, scanner->contexts, scanner->depth);
+    return scanner->depth;
+}
+
+void tree_sitter_nix_external_scanner_deserialize(void *payload, const char *buffer, unsigned length) {
+    Scanner *scanner = (Scanner *)payload;
+    scanner->depth = length < MAX_CONTEXT_DEPTH ? length : MAX_CONTEXT_DEPTH;
+    memcpy(scanner->contexts, buffer, scanner->depth);
+}
+
+bool tree_sitter_nix_external_scanner_scan(void *payload, TSLexer *lexer,
+                                            const bool *valid_symbols) {
+    Scanner *scanner = (Scanner *)payload;
+
+    switch (current_context(scanner)) {
+        case CONTEXT_STRING:
+            return scan_string(scanner, lexer, valid_symbols);
+        case CONTEXT_INDENTED_STRING:
+            if (valid_symbols[INDENTED_STRING_CONTENT] || valid_symbols[INDENTED_STRING_END]) {
+                return scan_indented_string(scanner, lexer, valid_symbols);
             }
-            advance(lexer);
             return false;
-        }
+        case CONTEXT_PATH:
+            return scan_path(scanner, lexer, valid_symbols);
+        default:
+            return scan_code(scanner, lexer, valid_symbols);
     }
-    
-    return false;
-}
\ No newline at end of file
+}
@@ -7,7 +7,7 @@ use std::os::raw::{c_char, c_uint};
 use std::ptr;
 
 // External scanner functions from C
-extern "C" {
+unsafe extern "C" {
     fn tree_sitter_nix_external_scanner_create() -> *mut std::os::raw::c_void;
     fn tree_sitter_nix_external_scanner_destroy(scanner: *mut std::os::raw::c_void);
     fn tree_sitter_nix_external_scanner_serialize(
@@ -43,6 +43,10 @@ impl ExternalScanner {
     /// - The C function is designed to be called from Rust
     /// - We properly manage the returned pointer
     /// - Memory cleanup is handled in Drop
+    ///
+    /// # Panics
+    ///
+    /// Panics if the C scanner fails to allocate its state.
     pub fn new() -> Self {
         let scanner = unsafe { tree_sitter_nix_external_scanner_create() };
         assert!(!scanner.is_null(), "Failed to create external scanner");
@@ -66,7 +70,7 @@ impl ExternalScanner {
         let length = unsafe {
             tree_sitter_nix_external_scanner_serialize(
                 self.scanner,
-                buffer.as_mut_ptr() as *mut c_char,
+                buffer.as_mut_ptr().cast::<c_char>(),
             )
         };
         
@@ -79,6 +83,9 @@ impl ExternalScanner {
     ///
     /// * `data` - Serialized state data
     ///
+    /// Empty states, and states too long to have come from
+    /// [`serialize`](Self::serialize), are ignored.
+    ///
     /// # Safety
     ///
     /// This function is safe because:
@@ -86,20 +93,29 @@ impl ExternalScanner {
     /// - We provide valid pointers to the C function
     /// - The C function is designed for this interface
     pub fn deserialize(&mut self, data: &[u8]) {
-        if data.is_empty() {
+        let Ok(length) = c_uint::try_from(data.len()) else {
+            return;
+        };
+        if length == 0 {
             return;
         }
         
         unsafe {
             tree_sitter_nix_external_scanner_deserialize(
                 self.scanner,
-                data.as_ptr() as *const c_char,
-                data.len() as c_uint,
+                data.as_ptr().cast::<c_char>(),
+                length,
             );
         }
     }
 }
 
+impl Default for ExternalScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for ExternalScanner {
     /// Clean up the external scanner
     ///
@@ -185,6 +201,16 @@ impl TokenType {
         }
     }
     
+    /// Get the token type with a numeric tag, the inverse of `as u32`
+    pub fn from_u32(value: u32) -> Option<TokenType> {
+        Self::all().iter().copied().find(|token| *token as u32 == value)
+    }
+    
+    /// Get the token type with a name, the inverse of [`TokenType::name`]
+    pub fn from_name(name: &str) -> Option<TokenType> {
+        Self::all().iter().copied().find(|token| token.name() == name)
+    }
+    
     /// Check if this token type is related to strings
     pub const fn is_string_token(self) -> bool {
         matches!(self, 
@@ -206,8 +232,8 @@ impl TokenType {
 
 /// Scan for tokens using the external scanner
 /// 
-/// This function provides a safe wrapper around the raw C scanner function
-/// for use by Tree-sitter and testing.
+/// This function wraps the raw C scanner function for use by Tree-sitter
+/// and testing.
 /// 
 /// # Arguments
 /// 
@@ -221,14 +247,18 @@ impl TokenType {
 /// 
 /// # Safety
 /// 
-/// This function is safe when called with valid pointers from Tree-sitter's
-/// lexer infrastructure. The lexer and valid_symbols pointers must be valid
-/// for the duration of the call.
-pub fn scan_token(
+/// `lexer` must be null or point to a lexer from Tree-sitter's lexer
+/// infrastructure that stays valid for the duration of the call.
+pub unsafe fn scan_token(
     scanner: &mut ExternalScanner, 
     lexer: *mut std::os::raw::c_void,
     valid_symbols: &[bool; 10] // 10 token types
 ) -> bool {
+    // The C scanner dereferences the lexer unconditionally
+    if lexer.is_null() {
+        return false;
+    }
+
     unsafe {
         tree_sitter_nix_external_scanner_scan(
             scanner.scanner,
@@ -282,6 +312,19 @@ mod tests {
         assert_eq!(TokenType::all().len(), 10);
     }
 
+    #[test]
+    fn test_token_type_conversion() {
+        for &token in TokenType::all() {
+            assert_eq!(TokenType::from_u32(token as u32), Some(token));
+            assert_eq!(TokenType::from_name(token.name()), Some(token));
+        }
+        assert_eq!(TokenType::from_u32(9), Some(TokenType::Comment));
+        assert_eq!(TokenType::from_u32(10), None);
+        assert_eq!(TokenType::from_name("string_end"), Some(TokenType::StringEnd));
+        assert_eq!(TokenType::from_name("StringEnd"), None);
+        assert_eq!(TokenType::from_name(""), None);
+    }
+
     #[test]
     fn test_token_type_display() {
         assert_eq!(format!("{}", TokenType::StringStart), "string_start");
@@ -294,7 +337,7 @@ mod tests {
         
         // Test with null lexer (should be safe and just return false)
         let valid_symbols = [false; 10];
-        let result = scan_token(&mut scanner, std::ptr::null_mut(), &valid_symbols);
+        let result = unsafe { scan_token(&mut scanner, std::ptr::null_mut(), &valid_symbols) };
         
         // With null lexer, should return false (no token found)
         assert!(!result);
@@ -0,0 +1,8 @@
+//! Helpers shared by the unit tests
+
+use crate::ast::Expression;
+
+/// Parse `source` into an expression, panicking on syntax errors
+pub(crate) fn parse_expr(source: &str) -> Expression {
+    crate::parse_expr(source).unwrap()
+}
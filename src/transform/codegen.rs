@@ -1,13 +1,523 @@
 //! Code generation utilities
 
+use std::borrow::Cow;
+
+use crate::ast::{
+    precedence, AttrKey, Associativity, Attribute, Binding, BinaryOperator, Expression,
+    Parameter, UnaryOperator,
+};
+use crate::utils::string::{escape_nix_string, needs_quoting};
+
 /// Code generator for converting AST back to Nix source code
-/// 
+///
 /// Provides functionality to serialize Nix expressions back into
 /// properly formatted source code with configurable styling options.
-pub struct CodeGenerator {}
+///
+/// Expressions that fit within the line width are printed on one line, the
+/// same way `Expression`'s `Display` implementation does; larger attribute
+/// sets, lists, `let` and `if` expressions are broken over several lines.
+#[derive(Debug, Clone, Default)]
+pub struct CodeGenerator {
+    context: GenerationContext,
+}
+
+impl CodeGenerator {
+    /// Create a code generator with the default formatting options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use custom formatting options
+    pub fn with_context(mut self, context: GenerationContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Get the formatting options
+    pub fn context(&self) -> &GenerationContext {
+        &self.context
+    }
+
+    /// Generate Nix source code for an expression
+    ///
+    /// With `use_inherit` enabled, the default, the output parses back to an
+    /// expression equal to `expr`. Disabling it spells out inherits as
+    /// bindings, which mean the same but parse back as bindings.
+    pub fn generate(&self, expr: &Expression) -> String {
+        let mut printer = Printer::new(&self.context, false);
+        printer.expr(expr, 0);
+        printer.out
+    }
+}
 
 /// Context information for code generation
-/// 
+///
 /// Contains formatting preferences, indentation settings,
 /// and other options that control code generation output.
-pub struct GenerationContext {}
\ No newline at end of file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationContext {
+    /// Number of spaces per indentation level
+    pub indent_width: usize,
+    /// Group consecutive `inherit`s; when disabled, inherits are spelled
+    /// out as bindings wherever that does not change their meaning
+    pub use_inherit: bool,
+    /// Line width beyond which expressions are broken over several lines
+    pub max_line_width: usize,
+}
+
+impl Default for GenerationContext {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            use_inherit: true,
+            max_line_width: 80,
+        }
+    }
+}
+
+/// One line of an attribute set or `let` block
+enum Entry<'a> {
+    /// `key = value;`
+    Assign { key: String, value: Cow<'a, Expression> },
+    /// `inherit (source) names;`
+    Inherit { source: Option<&'a Expression>, names: Vec<&'a str> },
+}
+
+/// Output buffer for a single `generate` call
+struct Printer<'a> {
+    context: &'a GenerationContext,
+    out: String,
+    /// Print everything on one line, ignoring the line width
+    flat: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(context: &'a GenerationContext, flat: bool) -> Self {
+        Self {
+            context,
+            out: String::new(),
+            flat,
+        }
+    }
+
+    /// Width of the line printed so far
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |index| index + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    /// Start a new line at `level`, or write a space when printing flat
+    fn line(&mut self, level: usize) {
+        if self.flat {
+            self.out.push(' ');
+        } else {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(level * self.context.indent_width));
+        }
+    }
+
+    /// Render `expr` on a single line
+    fn flat_text(&self, expr: &Expression) -> String {
+        let mut printer = Printer::new(self.context, true);
+        printer.layout(expr, 0);
+        printer.out
+    }
+
+    /// Write `expr`, breaking it over several lines if it does not fit
+    fn expr(&mut self, expr: &Expression, level: usize) {
+        if self.flat {
+            self.layout(expr, level);
+            return;
+        }
+
+        let text = self.flat_text(expr);
+        if self.column() + text.chars().count() <= self.context.max_line_width {
+            self.out.push_str(&text);
+        } else {
+            self.layout(expr, level);
+        }
+    }
+
+    /// Write `expr`, parenthesized if it binds more loosely than `min_precedence`
+    fn operand(&mut self, expr: &Expression, min_precedence: u8, level: usize) {
+        if expr.precedence() < min_precedence {
+            self.out.push('(');
+            self.expr(expr, level);
+            self.out.push(')');
+        } else {
+            self.expr(expr, level);
+        }
+    }
+
+    fn layout(&mut self, expr: &Expression, level: usize) {
+        match expr {
+            Expression::Located { expr, .. } => self.layout(expr, level),
+            Expression::List(elements) => {
+                if elements.is_empty() {
+                    self.out.push_str("[ ]");
+                    return;
+                }
+                self.out.push('[');
+                for element in elements {
+                    self.line(level + 1);
+                    // Juxtaposed list elements would otherwise parse as an application
+                    self.operand(element, precedence::SELECT, level + 1);
+                }
+                self.line(level);
+                self.out.push(']');
+            }
+            Expression::AttributeSet { recursive, attributes } => {
+                if *recursive {
+                    self.out.push_str("rec ");
+                }
+                let entries = self.attribute_entries(*recursive, attributes);
+                self.block("{", &entries, "}", level);
+            }
+            Expression::LetIn { bindings, body } => {
+                let entries = self.binding_entries(bindings);
+                self.block("let", &entries, "in", level);
+                self.line(level);
+                self.expr(body, level);
+            }
+            Expression::Function { parameter, body } => {
+                self.parameter(parameter, level);
+                self.out.push_str(": ");
+                self.expr(body, level);
+            }
+            Expression::Application { function, argument } => {
+                self.operand(function, precedence::APPLICATION, level);
+                self.out.push(' ');
+                self.operand(argument, precedence::SELECT, level);
+            }
+            Expression::With { scope, body } => {
+                self.out.push_str("with ");
+                self.expr(scope, level);
+                self.out.push(';');
+                self.line(level);
+                self.expr(body, level);
+            }
+            Expression::Assert { condition, body } => {
+                self.out.push_str("assert ");
+                self.expr(condition, level);
+                self.out.push(';');
+                self.line(level);
+                self.expr(body, level);
+            }
+            Expression::If { condition, then_branch, else_branch } => {
+                self.out.push_str("if ");
+                self.expr(condition, level);
+                self.out.push_str(" then");
+                self.line(level + 1);
+                self.expr(then_branch, level + 1);
+                self.line(level);
+                self.out.push_str("else");
+                self.line(level + 1);
+                self.expr(else_branch, level + 1);
+            }
+            Expression::BinaryOp { op, left, right } => self.binary_op(*op, left, right, level),
+            Expression::UnaryOp { op, operand } => {
+                self.out.push_str(op.as_str());
+                match op {
+                    UnaryOperator::Negate => self.operand(operand, precedence::ATOM, level),
+                    UnaryOperator::Not => {
+                        self.operand(operand, BinaryOperator::Add.precedence(), level);
+                    }
+                }
+            }
+            Expression::Select { expr, path, default } => {
+                self.operand(expr, precedence::ATOM, level);
+                self.out.push('.');
+                self.attr_path(path);
+                if let Some(default) = default {
+                    self.out.push_str(" or ");
+                    self.operand(default, precedence::SELECT, level);
+                }
+            }
+            Expression::HasAttr { expr, path } => {
+                self.operand(expr, precedence::ATOM, level);
+                self.out.push_str(" ? ");
+                self.attr_path(path);
+            }
+            Expression::Import { path } => {
+                self.out.push_str("import ");
+                self.operand(path, precedence::SELECT, level);
+            }
+            // Literals, identifiers and inherits are always printed on one line
+            _ => self.out.push_str(&expr.to_string()),
+        }
+    }
+
+    fn binary_op(&mut self, op: BinaryOperator, left: &Expression, right: &Expression, level: usize) {
+        let op_level = op.precedence();
+        let (left_min, right_min) = match op.associativity() {
+            Associativity::Left => (op_level, op_level + 1),
+            Associativity::Right => (op_level + 1, op_level),
+            Associativity::None => (op_level + 1, op_level + 1),
+        };
+        self.operand(left, left_min, level);
+        self.out.push(' ');
+        self.out.push_str(op.as_str());
+        self.out.push(' ');
+        self.operand(right, right_min, level);
+    }
+
+    /// Write `open`, one entry per line, then `close`
+    fn block(&mut self, open: &str, entries: &[Entry<'_>], close: &str, level: usize) {
+        self.out.push_str(open);
+        if entries.is_empty() {
+            self.out.push(' ');
+            self.out.push_str(close);
+            return;
+        }
+        for entry in entries {
+            self.line(level + 1);
+            self.entry(entry, level + 1);
+        }
+        self.line(level);
+        self.out.push_str(close);
+    }
+
+    fn entry(&mut self, entry: &Entry<'_>, level: usize) {
+        match entry {
+            Entry::Assign { key, value } => {
+                self.out.push_str(key);
+                self.out.push_str(" = ");
+                self.expr(value, level);
+            }
+            Entry::Inherit { source, names } => {
+                self.out.push_str("inherit");
+                if let Some(source) = source {
+                    self.out.push_str(" (");
+                    self.expr(source, level);
+                    self.out.push(')');
+                }
+                for name in names {
+                    self.out.push(' ');
+                    self.attr_name(name);
+                }
+            }
+        }
+        self.out.push(';');
+    }
+
+    fn attribute_entries(&self, recursive: bool, attributes: &'a [Attribute]) -> Vec<Entry<'a>> {
+        let mut entries = Vec::new();
+        for attr in attributes {
+            let inherited = match attr.value.unlocated() {
+                Expression::Inherit { source, attributes } if attributes.len() == 1 => {
+                    Some((source.as_deref(), attributes[0].as_str()))
+                }
+                _ => None,
+            };
+
+            match inherited {
+                // Only a non-recursive set can spell out a plain `inherit x;`
+                Some((source, name)) if !self.context.use_inherit && (source.is_some() || !recursive) => {
+                    entries.push(Entry::Assign {
+                        key: Self::attr_name_text(name),
+                        value: Cow::Owned(Self::inherited_value(source, name)),
+                    });
+                }
+                Some((source, name)) => self.push_inherit(&mut entries, source, name),
+                None => entries.push(Entry::Assign {
+                    key: self.attr_key_text(&attr.key),
+                    value: Cow::Borrowed(&attr.value),
+                }),
+            }
+        }
+        entries
+    }
+
+    fn binding_entries(&self, bindings: &'a [Binding]) -> Vec<Entry<'a>> {
+        let mut entries = Vec::new();
+        for binding in bindings {
+            if !binding.inherit {
+                entries.push(Entry::Assign {
+                    key: Self::attr_name_text(&binding.name),
+                    value: Cow::Borrowed(&binding.value),
+                });
+            } else if !self.context.use_inherit && binding.from.is_some() {
+                // `let` is recursive, so only inherits with a source can be spelled out
+                entries.push(Entry::Assign {
+                    key: Self::attr_name_text(&binding.name),
+                    value: Cow::Owned(Self::inherited_value(binding.from.as_ref(), &binding.name)),
+                });
+            } else {
+                self.push_inherit(&mut entries, binding.from.as_ref(), &binding.name);
+            }
+        }
+        entries
+    }
+
+    /// Add an inherited name, joining it to the previous `inherit` if allowed
+    fn push_inherit(&self, entries: &mut Vec<Entry<'a>>, source: Option<&'a Expression>, name: &'a str) {
+        if self.context.use_inherit
+            && let Some(Entry::Inherit { source: previous, names }) = entries.last_mut()
+            && *previous == source
+        {
+            names.push(name);
+            return;
+        }
+        entries.push(Entry::Inherit { source, names: vec![name] });
+    }
+
+    /// The value an `inherit` entry stands for
+    fn inherited_value(source: Option<&Expression>, name: &str) -> Expression {
+        match source {
+            Some(source) => Expression::Select {
+                expr: Box::new(source.clone()),
+                path: vec![AttrKey::Static(vec![name.to_string()])],
+                default: None,
+            },
+            None => Expression::Identifier(name.to_string()),
+        }
+    }
+
+    fn parameter(&mut self, parameter: &Parameter, level: usize) {
+        match parameter {
+            Parameter::Identifier(name) => self.out.push_str(name),
+            Parameter::Pattern { fields, ellipsis, bind } => {
+                self.out.push('{');
+                for (i, field) in fields.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+                    self.out.push_str(&field.name);
+                    if let Some(default) = &field.default {
+                        self.out.push_str(" ? ");
+                        self.expr(default, level);
+                    }
+                }
+                if *ellipsis {
+                    self.out.push_str(if fields.is_empty() { " ..." } else { ", ..." });
+                }
+                self.out.push_str(" }");
+                if let Some(bind) = bind {
+                    self.out.push('@');
+                    self.out.push_str(bind);
+                }
+            }
+        }
+    }
+
+    fn attr_name_text(name: &str) -> String {
+        if needs_quoting(name) {
+            format!("\"{}\"", escape_nix_string(name))
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn attr_name(&mut self, name: &str) {
+        let text = Self::attr_name_text(name);
+        self.out.push_str(&text);
+    }
+
+    fn attr_path(&mut self, path: &[AttrKey]) {
+        let text = path.iter().map(|key| self.attr_key_text(key)).collect::<Vec<_>>().join(".");
+        self.out.push_str(&text);
+    }
+
+    fn attr_key_text(&self, key: &AttrKey) -> String {
+        match key {
+            AttrKey::Static(path) => path
+                .iter()
+                .map(|segment| Self::attr_name_text(segment))
+                .collect::<Vec<_>>()
+                .join("."),
+            // Strings are valid keys as written; anything else needs `${}`
+            AttrKey::Dynamic(expr) => match expr.unlocated() {
+                Expression::String(_) | Expression::StringInterpolation { .. } => {
+                    self.flat_text(expr)
+                }
+                _ => format!("${{{}}}", self.flat_text(expr)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn generator(max_line_width: usize, use_inherit: bool) -> CodeGenerator {
+        CodeGenerator::new().with_context(GenerationContext {
+            use_inherit,
+            max_line_width,
+            ..GenerationContext::default()
+        })
+    }
+
+    #[test]
+    fn test_generate_round_trip() {
+        let source = r#"
+            let
+              lib = import <nixpkgs>;
+              inherit (lib) optional concatMap;
+              double = x: x * 2;
+            in
+            { pkgs, enable ? false, ... }@args:
+            rec {
+              name = "demo-${args.version or version}";
+              deps = [ pkgs.hello (double 2) (-1) { } [ ] ] ++ optional enable pkgs.git;
+              nested = (a.b).c;
+              check = !(x ? y) && (1 + (2 + 3)) * 4 == 4 || -x.y < 0;
+              merged = { a = 1; inherit name; } // { b = if enable then 1.5 else null; };
+              body = with lib; assert enable || true; f (g x) (y: y);
+              inherit (pkgs) stdenv;
+              "quoted key" = { ${name} = 1; };
+            }
+        "#;
+        let expr = parse_expr(source);
+
+        for width in [0, 20, 80, usize::MAX] {
+            let printed = generator(width, true).generate(&expr);
+            assert_eq!(parse_expr(&printed), expr, "printed as:\n{printed}");
+        }
+    }
+
+    #[test]
+    fn test_generate_flat() {
+        let expr = parse_expr("{ a = 1; b = [ 1 2 ]; }");
+        assert_eq!(CodeGenerator::new().generate(&expr), expr.to_string());
+    }
+
+    #[test]
+    fn test_generate_formatted_attribute_set() {
+        let expr = parse_expr(
+            r#"{ pkgs, lib, ... }: { name = "hello"; version = "2.12"; src = pkgs.fetchurl { url = "mirror://gnu/hello/hello-2.12.tar.gz"; hash = "sha256-jZkUKv2SV28wsM18tCqNxoCZmLxdYH2Idh9RLibH2yA="; }; inherit lib; meta = { license = lib.licenses.gpl3Plus; platforms = lib.platforms.all; }; buildInputs = [ pkgs.gettext pkgs.libiconv ]; doCheck = if pkgs.stdenv.isDarwin then false else true; }"#,
+        );
+        let golden = include_str!("testdata/formatted_attrset.nix");
+        assert_eq!(CodeGenerator::new().generate(&expr), golden.trim_end());
+    }
+
+    #[test]
+    fn test_generate_inherit_options() {
+        let expr = parse_expr("{ inherit a; inherit b; inherit (s) c; }");
+        assert_eq!(generator(80, true).generate(&expr), "{ inherit a b; inherit (s) c; }");
+        assert_eq!(generator(80, false).generate(&expr), "{ a = a; b = b; c = s.c; }");
+
+        // Bindings are not turned into inherits, so the output parses back to the same set
+        let expr = parse_expr("{ a = a; inherit b; }");
+        let generated = generator(80, true).generate(&expr);
+        assert_eq!(generated, "{ a = a; inherit b; }");
+        assert_eq!(parse_expr(&generated), expr);
+
+        // Spelling out `inherit x;` in a recursive scope would make it refer to itself
+        let expr = parse_expr("let inherit x; y = 1; in rec { inherit z; }");
+        assert_eq!(
+            generator(80, false).generate(&expr),
+            "let inherit x; y = 1; in rec { inherit z; }"
+        );
+    }
+
+    #[test]
+    fn test_generate_indent_width() {
+        let expr = parse_expr("let a = [ 1 2 ]; in a");
+        let generator = CodeGenerator::new().with_context(GenerationContext {
+            indent_width: 4,
+            max_line_width: 0,
+            ..GenerationContext::default()
+        });
+        assert_eq!(generator.generate(&expr), "let\n    a = [\n        1\n        2\n    ];\nin\na");
+    }
+}
@@ -0,0 +1,519 @@
+//! Partial evaluation of the pure, constant subset of Nix
+
+use std::collections::HashMap;
+
+use crate::ast::{AttrKey, Attribute, BinaryOperator, Binding, Expression, Parameter, PathType, StringPart};
+use crate::transform::optimize::ConstantFolder;
+use crate::visitor::{VisitorMut, walk_expression_mut};
+
+/// Partial evaluator for Nix expressions
+///
+/// Reduces the parts of an expression that can be evaluated without
+/// builtins, imports or function calls: arithmetic, comparisons, string
+/// and list concatenation, `//` on attribute sets, `if` and `assert` with
+/// constant conditions, and `let` bindings, `rec` attributes and
+/// selections whose values are constant. Everything else is left in
+/// place, with its constant parts reduced.
+///
+/// Like the optimizer's constant folding, a reduction is only made if Nix
+/// would perform it without error.
+#[derive(Debug, Clone, Default)]
+pub struct Evaluator;
+
+impl Evaluator {
+    /// Create a new evaluator
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Evaluate an expression as far as possible
+    ///
+    /// Returns [`Evaluation::Value`] if the expression reduces to a
+    /// constant, and [`Evaluation::Stuck`] with the partially reduced
+    /// expression otherwise.
+    pub fn evaluate(&self, expr: &Expression) -> Evaluation {
+        let mut reduced = expr.clone();
+        Reducer::default().visit_expression_mut(&mut reduced);
+        if is_constant(&reduced) {
+            Evaluation::Value(reduced.into_unlocated())
+        } else {
+            Evaluation::Stuck(reduced)
+        }
+    }
+}
+
+/// Outcome of evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Evaluation {
+    /// The expression always evaluates to this constant
+    Value(Expression),
+    /// Evaluation needs values that are not known statically, such as
+    /// function arguments or the results of builtins; holds the expression
+    /// with its constant parts reduced
+    Stuck(Expression),
+}
+
+impl Evaluation {
+    /// Get the constant the expression evaluates to, if any
+    pub fn value(&self) -> Option<&Expression> {
+        match self {
+            Evaluation::Value(value) => Some(value),
+            Evaluation::Stuck(_) => None,
+        }
+    }
+
+    /// Get the reduced expression, whether or not it is constant
+    pub fn expression(&self) -> &Expression {
+        match self {
+            Evaluation::Value(expr) | Evaluation::Stuck(expr) => expr,
+        }
+    }
+
+    /// Check if evaluation got stuck
+    pub fn is_stuck(&self) -> bool {
+        matches!(self, Evaluation::Stuck(_))
+    }
+}
+
+/// Check if an expression is a constant: a literal, or a list or
+/// non-recursive attribute set of constants
+///
+/// Attribute sets are only constant if their keys are distinct single
+/// names, so that they can be looked up and merged directly.
+fn is_constant(expr: &Expression) -> bool {
+    match expr.unlocated() {
+        Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Uri(_)
+        | Expression::Boolean(_)
+        | Expression::Null => true,
+        Expression::Path(path) => !matches!(path, PathType::Interpolated(_)),
+        Expression::List(items) => items.iter().all(is_constant),
+        Expression::AttributeSet { recursive: false, attributes } => {
+            attributes.iter().enumerate().all(|(index, attr)| {
+                matches!(attr_name(attr), Some(name) if !attributes[..index]
+                    .iter()
+                    .any(|other| attr_name(other) == Some(name)))
+                    && is_constant(&attr.value)
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Get the name of an attribute whose key is a single static name
+fn attr_name(attr: &Attribute) -> Option<&str> {
+    match &attr.key {
+        AttrKey::Static(path) if path.len() == 1 => Some(&path[0]),
+        _ => None,
+    }
+}
+
+/// Get the attributes of a constant attribute set
+fn constant_attributes(expr: &Expression) -> Option<&[Attribute]> {
+    match expr.unlocated() {
+        Expression::AttributeSet { recursive: false, attributes } if is_constant(expr) => Some(attributes),
+        _ => None,
+    }
+}
+
+/// Result of looking up an attribute path in a constant
+enum Lookup<'e> {
+    Found(&'e Expression),
+    Missing,
+    /// A value along the path is not an attribute set
+    NotAttributeSet,
+}
+
+/// Look up an attribute path in a constant
+fn lookup<'e>(expr: &'e Expression, path: &[String]) -> Lookup<'e> {
+    let Some((first, rest)) = path.split_first() else {
+        return Lookup::Found(expr);
+    };
+    let Some(attributes) = constant_attributes(expr) else {
+        return Lookup::NotAttributeSet;
+    };
+    match attributes.iter().find(|attr| attr_name(attr) == Some(first.as_str())) {
+        Some(attr) => lookup(&attr.value, rest),
+        None => Lookup::Missing,
+    }
+}
+
+/// Static path of an attribute and its value
+type StaticEntry<'a> = (&'a [String], &'a Expression);
+
+/// Build the attributes of a set from static paths and their values,
+/// turning `a.b = 1; a.c = 2;` into `a = { b = 1; c = 2; };`
+///
+/// Returns `None` if a path is defined twice, or is both defined and
+/// extended.
+fn nest(entries: Vec<StaticEntry<'_>>) -> Option<Vec<Attribute>> {
+    let mut groups: Vec<(&String, Vec<StaticEntry<'_>>)> = Vec::new();
+    for (path, value) in entries {
+        let (first, rest) = path.split_first()?;
+        match groups.iter_mut().find(|(name, _)| *name == first) {
+            Some((_, members)) => members.push((rest, value)),
+            None => groups.push((first, vec![(rest, value)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, members)| {
+            let value = match members.as_slice() {
+                [([], value)] => (*value).clone(),
+                _ if members.iter().any(|(rest, _)| rest.is_empty()) => return None,
+                _ => Expression::AttributeSet { recursive: false, attributes: nest(members)? },
+            };
+            Some(Attribute { key: AttrKey::Static(vec![name.clone()]), value, location: None })
+        })
+        .collect()
+}
+
+/// Mutable visitor that reduces constant expressions bottom-up
+///
+/// `env` maps the variables in scope whose values are constant to those
+/// values. Variables that are shadowed by a non-constant binding are
+/// removed from it, so every identifier found in it can be replaced.
+#[derive(Default)]
+struct Reducer {
+    env: HashMap<String, Expression>,
+}
+
+impl Reducer {
+    /// Reduce a copy of an expression in the given environment
+    fn reduced(expr: &Expression, env: &HashMap<String, Expression>) -> Expression {
+        let mut reducer = Reducer { env: env.clone() };
+        let mut expr = expr.clone();
+        reducer.visit_expression_mut(&mut expr);
+        expr
+    }
+
+    /// Reduce an expression whose operands have been reduced already
+    fn reduce(expr: &Expression) -> Option<Expression> {
+        use Expression as E;
+
+        match expr {
+            E::BinaryOp { op: BinaryOperator::Update, left, right } => {
+                let mut attributes = constant_attributes(left)?.to_vec();
+                for attr in constant_attributes(right)? {
+                    match attributes.iter_mut().find(|other| attr_name(other) == attr_name(attr)) {
+                        Some(other) => other.value = attr.value.clone(),
+                        None => attributes.push(attr.clone()),
+                    }
+                }
+                Some(E::AttributeSet { recursive: false, attributes })
+            }
+            E::Select { expr, path, default } => match lookup(expr, AttrKey::static_names(path)?) {
+                Lookup::Found(value) => Some(value.clone().into_unlocated()),
+                Lookup::Missing => default.as_ref().map(|default| default.as_ref().clone().into_unlocated()),
+                Lookup::NotAttributeSet => None,
+            },
+            E::HasAttr { expr, path } if is_constant(expr) => {
+                let path = AttrKey::static_names(path)?;
+                Some(E::Boolean(matches!(lookup(expr, path), Lookup::Found(_))))
+            }
+            E::StringInterpolation { parts } => {
+                let mut string = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Literal(literal) => string.push_str(literal),
+                        StringPart::Interpolation(expr) => match expr.unlocated() {
+                            E::String(value) => string.push_str(value),
+                            _ => return None,
+                        },
+                    }
+                }
+                Some(E::String(string))
+            }
+            E::Assert { condition, body } if matches!(condition.unlocated(), E::Boolean(true)) => {
+                Some(body.as_ref().clone().into_unlocated())
+            }
+            E::AttributeSet { recursive: false, attributes } => {
+                let mut entries = Vec::new();
+                for attr in attributes {
+                    match &attr.key {
+                        AttrKey::Static(path) if is_constant(&attr.value) => {
+                            entries.push((path.as_slice(), &attr.value));
+                        }
+                        _ => return None,
+                    }
+                }
+                if entries.iter().all(|(path, _)| path.len() == 1) {
+                    return None;
+                }
+                Some(E::AttributeSet { recursive: false, attributes: nest(entries)? })
+            }
+            E::LetIn { body, .. } if is_constant(body) => Some(body.as_ref().clone().into_unlocated()),
+            _ => ConstantFolder::fold(expr),
+        }
+    }
+
+    /// Bind the constant values of a recursive scope
+    ///
+    /// `entries` holds the names defined by the scope with the expression
+    /// defining each, or `None` for names inherited from the enclosing
+    /// scope. Other names listed in `shadowed` are defined by the scope
+    /// without a value that can be looked up. Returns the environment of
+    /// the scope.
+    fn bind_scope<'a>(
+        &self,
+        entries: &[(&'a str, Option<Expression>)],
+        shadowed: impl IntoIterator<Item = &'a str>,
+    ) -> HashMap<String, Expression> {
+        let mut env = self.env.clone();
+        for name in entries.iter().map(|(name, _)| *name).chain(shadowed) {
+            env.remove(name);
+        }
+        for (name, _) in entries.iter().filter(|(_, value)| value.is_none()) {
+            if let Some(value) = self.env.get(*name) {
+                env.insert(name.to_string(), value.clone());
+            }
+        }
+
+        // Bindings may refer to each other in any order, so bind values
+        // until no more become constant
+        let mut pending: Vec<_> = entries
+            .iter()
+            .filter_map(|(name, value)| Some((*name, value.as_ref()?)))
+            .collect();
+        loop {
+            let before = pending.len();
+            pending.retain(|(name, value)| {
+                let value = Self::reduced(value, &env);
+                if !is_constant(&value) {
+                    return true;
+                }
+                env.insert(name.to_string(), value.into_unlocated());
+                false
+            });
+            if pending.len() == before {
+                return env;
+            }
+        }
+    }
+
+    /// The expression defining a name in the scope defining it, or `None`
+    /// if it is inherited from the enclosing scope
+    fn definition(value: &Expression, name: &str) -> Option<Expression> {
+        match value.unlocated() {
+            Expression::Inherit { source: Some(source), .. } => Some(Expression::Select {
+                expr: source.clone(),
+                path: vec![AttrKey::Static(vec![name.to_string()])],
+                default: None,
+            }),
+            Expression::Inherit { source: None, .. } => None,
+            _ => Some(value.clone()),
+        }
+    }
+
+    /// Replace the inherited values of a scope with their constant values
+    fn resolve_inherits<'e>(
+        env: &HashMap<String, Expression>,
+        values: impl IntoIterator<Item = (&'e str, &'e mut Expression)>,
+    ) {
+        for (name, value) in values {
+            if matches!(value.unlocated(), Expression::Inherit { .. })
+                && let Some(constant) = env.get(name)
+            {
+                *value = constant.clone();
+            }
+        }
+    }
+}
+
+impl VisitorMut for Reducer {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        if let Expression::Identifier(name) = expr
+            && let Some(value) = self.env.get(name)
+        {
+            *expr = value.clone();
+            return;
+        }
+        walk_expression_mut(self, expr);
+
+        if let Some(reduced) = Self::reduce(expr) {
+            *expr = reduced;
+        }
+    }
+
+    fn visit_function_mut(&mut self, parameter: &mut Parameter, body: &mut Expression) {
+        let outer = self.env.clone();
+        match parameter {
+            Parameter::Identifier(name) => {
+                self.env.remove(name);
+            }
+            Parameter::Pattern { fields, bind, .. } => {
+                for name in fields.iter().map(|field| &field.name).chain(bind.iter()) {
+                    self.env.remove(name);
+                }
+                for default in fields.iter_mut().filter_map(|field| field.default.as_mut()) {
+                    self.visit_expression_mut(default);
+                }
+            }
+        }
+        self.visit_expression_mut(body);
+        self.env = outer;
+    }
+
+    fn visit_let_in_mut(&mut self, bindings: &mut Vec<Binding>, body: &mut Expression) {
+        let entries: Vec<_> = bindings
+            .iter()
+            .map(|binding| (binding.name.as_str(), Self::definition(&binding.value, &binding.name)))
+            .collect();
+        let env = self.bind_scope(&entries, []);
+
+        for binding in bindings.iter_mut() {
+            let inherited = matches!(binding.value.unlocated(), Expression::Inherit { .. });
+            if inherited && env.contains_key(&binding.name) {
+                binding.inherit = false;
+                binding.from = None;
+            }
+        }
+        Self::resolve_inherits(
+            &env,
+            bindings.iter_mut().map(|binding| (binding.name.as_str(), &mut binding.value)),
+        );
+
+        let outer = std::mem::replace(&mut self.env, env);
+        for binding in bindings.iter_mut() {
+            self.visit_expression_mut(&mut binding.value);
+        }
+        self.visit_expression_mut(body);
+        self.env = outer;
+    }
+
+    fn visit_attribute_set_mut(&mut self, recursive: &mut bool, attributes: &mut Vec<Attribute>) {
+        // Computed keys are evaluated outside of `rec` sets
+        for attr in attributes.iter_mut() {
+            self.visit_attr_key_mut(&mut attr.key);
+        }
+
+        let env = if *recursive {
+            let entries: Vec<_> = attributes
+                .iter()
+                .filter_map(|attr| {
+                    let name = attr_name(attr)?;
+                    Some((name, Self::definition(&attr.value, name)))
+                })
+                .collect();
+            let nested = attributes.iter().filter_map(|attr| match &attr.key {
+                AttrKey::Static(path) if path.len() > 1 => Some(path[0].as_str()),
+                _ => None,
+            });
+            self.bind_scope(&entries, nested)
+        } else {
+            // Only inherited values are looked up by name
+            let mut env = HashMap::new();
+            for attr in attributes.iter() {
+                let Some(name) = attr_name(attr) else {
+                    continue;
+                };
+                if !matches!(attr.value.unlocated(), Expression::Inherit { .. }) {
+                    continue;
+                }
+                let value = match Self::definition(&attr.value, name) {
+                    Some(definition) => Self::reduced(&definition, &self.env),
+                    None => match self.env.get(name) {
+                        Some(value) => value.clone(),
+                        None => continue,
+                    },
+                };
+                if is_constant(&value) {
+                    env.insert(name.to_string(), value.into_unlocated());
+                }
+            }
+            env
+        };
+        let resolve = attributes.iter_mut().filter_map(|attr| match &attr.key {
+            AttrKey::Static(path) if path.len() == 1 => Some((path[0].as_str(), &mut attr.value)),
+            _ => None,
+        });
+        Self::resolve_inherits(&env, resolve);
+
+        if *recursive {
+            let outer = std::mem::replace(&mut self.env, env);
+            for attr in attributes.iter_mut() {
+                self.visit_expression_mut(&mut attr.value);
+            }
+            self.env = outer;
+
+            // Constant values do not refer to the set, so it need not be
+            // recursive anymore
+            *recursive = !attributes
+                .iter()
+                .all(|attr| matches!(attr.key, AttrKey::Static(_)) && is_constant(&attr.value));
+        } else {
+            for attr in attributes.iter_mut() {
+                self.visit_expression_mut(&mut attr.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn evaluate(source: &str) -> Evaluation {
+        Evaluator::new().evaluate(&parse_expr(source))
+    }
+
+    #[test]
+    fn test_evaluate_constants() {
+        let cases = [
+            ("let x = 1 + 1; in x * 3", "6"),
+            ("let a = b + 1; b = 2; in [ a b ]", "[ 3 2 ]"),
+            ("let c = \"c\"; in \"a\" + \"b${c}\"", "\"abc\""),
+            ("[ 1 ] ++ [ 2 ]", "[ 1 2 ]"),
+            ("{ a = 1; b = 2; } // { b = 3; }", "{ a = 1; b = 3; }"),
+            ("if 1 < 2 then \"yes\" else x", "\"yes\""),
+            ("{ a.b = 1; }.a.b", "1"),
+            ("{ a.b = 1; a.c = 2; }", "{ a = { b = 1; c = 2; }; }"),
+            ("{ a = 1; }.b or 2", "2"),
+            ("{ a = { b = 1; }; } ? a.b", "true"),
+            ("rec { a = 1; b = a + 1; }", "{ a = 1; b = 2; }"),
+            ("let x = 1; in { inherit x; y = x; }", "{ x = 1; y = 1; }"),
+            ("let s = { x = 1; }; inherit (s) x; in x", "1"),
+            ("assert true; 1", "1"),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(evaluate(source), Evaluation::Value(parse_expr(expected)), "evaluating {source}");
+        }
+    }
+
+    #[test]
+    fn test_evaluate_stuck() {
+        let sources = [
+            "f x",
+            "x: x + 1",
+            "{ a = 1; }.b",
+            "1 / 0",
+            "assert false; 1",
+            "let x = x; in x",
+            "import ./a.nix",
+        ];
+        for source in sources {
+            let evaluation = evaluate(source);
+            assert!(evaluation.is_stuck(), "evaluating {source}");
+            assert_eq!(evaluation.expression(), &parse_expr(source), "evaluating {source}");
+        }
+
+        // Constant parts are still reduced
+        let evaluation = evaluate("let n = 2 * 3; in f n");
+        assert_eq!(evaluation, Evaluation::Stuck(parse_expr("let n = 6; in f 6")));
+        assert_eq!(evaluation.value(), None);
+        assert_eq!(
+            evaluate("let x = 1; in y: x + y").expression(),
+            &parse_expr("let x = 1; in y: 1 + y")
+        );
+        // Function parameters shadow constant bindings
+        assert_eq!(
+            evaluate("let x = 1; in x: x + 1").expression(),
+            &parse_expr("let x = 1; in x: x + 1")
+        );
+    }
+}
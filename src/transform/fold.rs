@@ -0,0 +1,162 @@
+//! Bottom-up rebuilding of expressions
+
+use std::mem;
+
+use crate::ast::{Expression, Parameter};
+use crate::error::{ParseError, Result};
+use crate::transform::TransformPass;
+use crate::visitor::{VisitorMut, walk_expression_mut};
+
+/// Rebuild `expr` bottom-up, replacing every node with the result of `f`
+///
+/// `f` is applied post-order, so it receives each node with its children
+/// already folded. Every nested expression is visited, including
+/// attribute keys, pattern defaults and interpolations, and so are the
+/// location wrappers around parsed expressions.
+///
+/// # Errors
+///
+/// The first error returned by `f` stops the fold and is returned.
+pub fn fold_expression(
+    expr: Expression,
+    f: &mut impl FnMut(Expression) -> Result<Expression>,
+) -> Result<Expression> {
+    let mut folder = Folder { f, error: None };
+    let mut result = expr;
+    folder.visit_expression_mut(&mut result);
+    match folder.error {
+        Some(error) => Err(error),
+        None => Ok(result),
+    }
+}
+
+/// Transformation pass applying a fold to the whole expression
+///
+/// # Examples
+///
+/// ```rust
+/// use nix_parser::ast::Expression;
+/// use nix_parser::transform::{FoldPass, utils::apply_pass};
+///
+/// let pass = FoldPass::new("double", "Double integer literals", |expr| {
+///     Ok(match expr {
+///         Expression::Integer(n) => Expression::Integer(n * 2),
+///         expr => expr,
+///     })
+/// });
+/// let step = apply_pass(pass, Expression::Integer(21))?.unwrap();
+/// assert_eq!(step.after, Expression::Integer(42));
+/// # Ok::<(), nix_parser::ParseError>(())
+/// ```
+pub struct FoldPass<F> {
+    name: String,
+    description: String,
+    f: F,
+}
+
+impl<F: FnMut(Expression) -> Result<Expression>> FoldPass<F> {
+    /// Create a pass folding with `f`
+    pub fn new(name: impl Into<String>, description: impl Into<String>, f: F) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            f,
+        }
+    }
+}
+
+impl<F: FnMut(Expression) -> Result<Expression>> TransformPass for FoldPass<F> {
+    fn apply(&mut self, expression: Expression) -> Result<Expression> {
+        fold_expression(expression, &mut self.f)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Mutable visitor that replaces each node with the result of a fold
+/// after visiting its children
+struct Folder<'f, F> {
+    f: &'f mut F,
+    /// The first error returned by the fold, after which nothing else is
+    /// visited
+    error: Option<ParseError>,
+}
+
+impl<F: FnMut(Expression) -> Result<Expression>> VisitorMut for Folder<'_, F> {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        if self.error.is_some() {
+            return;
+        }
+        walk_expression_mut(self, expr);
+        if self.error.is_some() {
+            return;
+        }
+        match (self.f)(mem::replace(expr, Expression::Null)) {
+            Ok(folded) => *expr = folded,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    fn visit_function_mut(&mut self, parameter: &mut Parameter, body: &mut Expression) {
+        if let Parameter::Pattern { fields, .. } = parameter {
+            for default in fields.iter_mut().filter_map(|field| field.default.as_mut()) {
+                self.visit_expression_mut(default);
+            }
+        }
+        self.visit_expression_mut(body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    #[test]
+    fn test_fold_expression() {
+        let mut increment = |expr| -> Result<Expression> {
+            Ok(match expr {
+                Expression::Integer(n) => Expression::Integer(n + 1),
+                expr => expr,
+            })
+        };
+        let source = r#"let a = [ 1 { b = 2; ${"c"} = x: 3; } ]; in { y ? 4 }: "${toString 5}" + a"#;
+        let expected = r#"let a = [ 2 { b = 3; ${"c"} = x: 4; } ]; in { y ? 5 }: "${toString 6}" + a"#;
+        assert_eq!(fold_expression(parse_expr(source), &mut increment).unwrap(), parse_expr(expected));
+
+        // Children are folded before their parents
+        let mut visited = Vec::new();
+        fold_expression(parse_expr("(1 + 2) * 3"), &mut |expr| {
+            if let Expression::Integer(n) = expr {
+                visited.push(n.to_string());
+            } else if let Expression::BinaryOp { op, .. } = expr {
+                visited.push(op.to_string());
+            }
+            Ok(expr)
+        })
+        .unwrap();
+        assert_eq!(visited, ["1", "2", "+", "3", "*"]);
+    }
+
+    #[test]
+    fn test_fold_expression_error() {
+        let mut folded = 0;
+        let err = fold_expression(parse_expr("[ 1 x 2 ]"), &mut |expr| {
+            folded += 1;
+            match expr.unlocated() {
+                Expression::Identifier(id) => Err(ParseError::semantic_error(format!("unknown `{id}`"))),
+                _ => Ok(expr),
+            }
+        })
+        .unwrap_err();
+        assert_eq!(err.primary_message(), "unknown `x`");
+        // `1`, its location and `x`
+        assert_eq!(folded, 3);
+    }
+}
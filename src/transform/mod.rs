@@ -7,11 +7,17 @@ pub mod refactor;
 pub mod optimize;
 pub mod codegen;
 pub mod normalize;
+pub mod substitute;
+pub mod evaluate;
+pub mod fold;
 
 pub use self::refactor::{Refactorer, RefactorRule, RefactorResult};
 pub use self::optimize::{Optimizer, OptimizationPass, OptimizationResult};
 pub use self::codegen::{CodeGenerator, GenerationContext};
 pub use self::normalize::{Normalizer, NormalizationRule};
+pub use self::substitute::{beta_reduce, substitute};
+pub use self::evaluate::{Evaluation, Evaluator};
+pub use self::fold::{fold_expression, FoldPass};
 
 use crate::ast::Expression;
 use crate::error::Result;
@@ -24,6 +30,7 @@ pub struct Transformer {
     refactorer: Refactorer,
     optimizer: Optimizer,
     normalizer: Normalizer,
+    passes: Vec<Box<dyn TransformPass>>,
 }
 
 impl Transformer {
@@ -33,38 +40,51 @@ impl Transformer {
             refactorer: Refactorer::new(),
             optimizer: Optimizer::new(),
             normalizer: Normalizer::new(),
+            passes: Vec::new(),
         }
     }
+
+    /// Add a custom pass, such as a [`FoldPass`]
+    ///
+    /// Custom passes run after the built-in transformations, in the order
+    /// they were added.
+    pub fn add_pass(&mut self, pass: Box<dyn TransformPass>) {
+        self.passes.push(pass);
+    }
     
     /// Apply all transformations to an expression
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of a pass; the remaining passes are not run.
     pub fn transform(&mut self, expression: Expression) -> Result<TransformResult> {
         let mut current = expression.clone();
         let mut steps = Vec::new();
         
         // Normalization (first pass)
-        if let Ok(normalized) = self.normalizer.normalize(current.clone()) {
-            if normalized != current {
-                steps.push(TransformStep {
-                    name: "normalize".to_string(),
-                    description: "Normalize expression structure".to_string(),
-                    before: current.clone(),
-                    after: normalized.clone(),
-                });
-                current = normalized;
-            }
+        if let Ok(normalized) = self.normalizer.normalize(current.clone())
+            && normalized != current
+        {
+            steps.push(TransformStep {
+                name: "normalize".to_string(),
+                description: "Normalize expression structure".to_string(),
+                before: current.clone(),
+                after: normalized.clone(),
+            });
+            current = normalized;
         }
         
         // Optimization
-        if let Ok(optimized) = self.optimizer.optimize(current.clone()) {
-            if optimized.expression != current {
-                steps.push(TransformStep {
-                    name: "optimize".to_string(),
-                    description: optimized.description,
-                    before: current.clone(),
-                    after: optimized.expression.clone(),
-                });
-                current = optimized.expression;
-            }
+        if let Ok(optimized) = self.optimizer.optimize(current.clone())
+            && optimized.expression != current
+        {
+            steps.push(TransformStep {
+                name: "optimize".to_string(),
+                description: optimized.description,
+                before: current.clone(),
+                after: optimized.expression.clone(),
+            });
+            current = optimized.expression;
         }
         
         // Refactoring (if any rules are enabled)
@@ -82,6 +102,23 @@ impl Transformer {
             }
         }
         
+        // Custom passes, whose errors are not recoverable
+        for pass in &mut self.passes {
+            if !pass.should_apply(&current) {
+                continue;
+            }
+            let after = pass.apply(current.clone())?;
+            if after != current {
+                steps.push(TransformStep {
+                    name: pass.name().to_string(),
+                    description: pass.description().to_string(),
+                    before: current,
+                    after: after.clone(),
+                });
+                current = after;
+            }
+        }
+        
         Ok(TransformResult {
             original: expression,
             transformed: current,
@@ -179,6 +216,10 @@ pub struct TransformStep {
 /// that can be applied to Nix ASTs.
 pub trait TransformPass {
     /// Apply the transformation to an expression
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the expression cannot be transformed.
     fn apply(&mut self, expression: Expression) -> Result<Expression>;
     
     /// Get the name of this transformation pass
@@ -197,9 +238,15 @@ pub trait TransformPass {
 
 /// Utility functions for working with transformations
 pub mod utils {
-    use super::*;
+    use super::{TransformPass, Expression, Result, TransformStep};
     
     /// Apply a transformation pass to an expression
+    ///
+    /// Returns `None` if the pass does not apply to the expression.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pass, if it fails.
     pub fn apply_pass<P: TransformPass>(
         mut pass: P, 
         expression: Expression
@@ -227,10 +274,12 @@ pub mod utils {
     }
     
     /// Check if two expressions are structurally equivalent
+    ///
+    /// This is `Expression`'s `==`: source locations are ignored, so an
+    /// expression built by hand equals the same expression parsed from
+    /// source.
     pub fn expressions_equal(a: &Expression, b: &Expression) -> bool {
-        // This would need to implement deep structural comparison
-        // For now, use a simple comparison
-        std::ptr::eq(a, b)
+        a == b
     }
 }
 
@@ -258,4 +307,42 @@ mod tests {
         assert!(!result.was_transformed());
         assert_eq!(result.summary(), "No transformations applied");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_transformer_fold_pass() {
+        use crate::ast::Expression;
+        use crate::parser::NixParser;
+
+        let mut parser = NixParser::new().unwrap();
+        let mut parse = |source: &str| parser.parse(source).unwrap().expression().unwrap().unwrap();
+
+        let mut transformer = Transformer::new();
+        transformer.add_pass(Box::new(FoldPass::new("increment", "Increment integer literals", |expr| {
+            Ok(match expr {
+                Expression::Integer(n) => Expression::Integer(n + 1),
+                expr => expr,
+            })
+        })));
+        let result = transformer.transform(parse("[ (1 + 2) x ]")).unwrap();
+        assert_eq!(result.transformed, parse("[ 4 x ]"));
+        assert_eq!(result.summary(), "Applied 2 transformation(s): optimize, increment");
+    }
+
+    #[test]
+    fn test_expressions_equal() {
+        use crate::ast::{BinaryOperator, Expression};
+        use crate::parser::NixParser;
+
+        let built = |right: i64| Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(Expression::Identifier("x".to_string())),
+            right: Box::new(Expression::Integer(right)),
+        };
+        let mut parser = NixParser::new().unwrap();
+        let parsed = parser.parse("x + 1").unwrap().expression().unwrap().unwrap();
+
+        assert!(utils::expressions_equal(&built(1), &built(1)));
+        assert!(utils::expressions_equal(&parsed, &built(1)));
+        assert!(!utils::expressions_equal(&parsed, &built(2)));
+    }
+}
@@ -1,38 +1,176 @@
 //! AST normalization
 
-use crate::ast::Expression;
+use std::fmt;
+
+use crate::analysis::scope::references;
+use crate::ast::{AttrKey, Attribute, Expression};
 use crate::error::Result;
+use crate::visitor::{VisitorMut, walk_expression_mut};
 
 /// AST normalizer for standardizing expression structure
-/// 
+///
 /// Applies normalization rules to convert expressions into
 /// a canonical form for consistent analysis and optimization.
-pub struct Normalizer {}
+pub struct Normalizer {
+    rules: Vec<NormalizationRule>,
+}
 impl Normalizer {
     /// Create a new normalizer with default rules
-    pub fn new() -> Self { Self {} }
+    ///
+    /// Every rule is opt-in, so the default normalizer leaves expressions
+    /// unchanged.
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
     /// Normalize an expression to canonical form
-    /// 
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `expr` - The expression to normalize
-    /// 
+    ///
     /// # Returns
-    /// 
+    ///
     /// The normalized expression
-    pub fn normalize(&mut self, expr: Expression) -> Result<Expression> { Ok(expr) }
+    ///
+    /// # Errors
+    ///
+    /// Never fails; every rule applies to any expression.
+    pub fn normalize(&mut self, mut expr: Expression) -> Result<Expression> {
+        for rule in &self.rules {
+            match rule {
+                NormalizationRule::RemoveRedundantRec => {
+                    RedundantRecRemover.visit_expression_mut(&mut expr);
+                }
+            }
+        }
+        Ok(expr)
+    }
     /// Configure the normalizer with custom rules
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_config` - Normalization configuration options
-    pub fn with_config(self, _config: Config) -> Self { self }
+    ///
+    /// * `config` - Normalization configuration options
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.rules = config.rules;
+        self
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// A single normalization rule for transforming expressions
-#[derive(Debug, Clone)]
-pub struct NormalizationRule {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NormalizationRule {
+    /// Turn `rec { ... }` into `{ ... }` when no attribute refers to another
+    RemoveRedundantRec,
+}
+
+impl NormalizationRule {
+    /// Get all normalization rules
+    pub const fn all() -> &'static [NormalizationRule] {
+        &[NormalizationRule::RemoveRedundantRec]
+    }
+
+    /// Get the rule name
+    pub const fn name(self) -> &'static str {
+        match self {
+            NormalizationRule::RemoveRedundantRec => "remove-redundant-rec",
+        }
+    }
+}
+
+impl fmt::Display for NormalizationRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// Configuration options for normalization
-#[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Rules to apply, in order; none are enabled by default
+    pub rules: Vec<NormalizationRule>,
+}
+
+/// Mutable visitor that drops `rec` from sets that never use it
+struct RedundantRecRemover;
+
+impl RedundantRecRemover {
+    /// Check if any attribute of a `rec` set refers to a name the set binds
+    fn is_self_referential(attributes: &[Attribute]) -> bool {
+        let names: Vec<&String> = attributes
+            .iter()
+            .filter_map(|attr| attr.key.static_path()?.first())
+            .collect();
+
+        attributes.iter().any(|attr| {
+            // `inherit x;` takes `x` from the enclosing scope, even in a `rec` set
+            let inherited = matches!(attr.value.unlocated(), Expression::Inherit { source: None, .. });
+            names.iter().any(|name| {
+                let in_key = match &attr.key {
+                    AttrKey::Dynamic(key) => references(key, name),
+                    AttrKey::Static(_) => false,
+                };
+                in_key || (!inherited && references(&attr.value, name))
+            })
+        })
+    }
+}
+
+impl VisitorMut for RedundantRecRemover {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+
+        if let Expression::AttributeSet { recursive, attributes } = expr
+            && *recursive
+            && !Self::is_self_referential(attributes)
+        {
+            *recursive = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn normalize(source: &str) -> Expression {
+        let config = Config {
+            rules: vec![NormalizationRule::RemoveRedundantRec],
+        };
+        Normalizer::new().with_config(config).normalize(parse_expr(source)).unwrap()
+    }
+
+    #[test]
+    fn test_remove_redundant_rec() {
+        assert_eq!(normalize("rec { a = 1; b = 2; }"), parse_expr("{ a = 1; b = 2; }"));
+        assert_eq!(normalize("rec { a = x; inherit b; }"), parse_expr("{ a = x; inherit b; }"));
+        assert_eq!(
+            normalize("rec { a = 1; b = (a: a) 2; c = rec { d = 3; }; }"),
+            parse_expr("{ a = 1; b = (a: a) 2; c = { d = 3; }; }")
+        );
+    }
+
+    #[test]
+    fn test_keep_recursive_rec() {
+        for source in [
+            "rec { a = 1; b = a + 1; }",
+            "rec { a.b = 1; c = a.b; }",
+            "rec { a = 1; inherit (a) b; }",
+            "rec { f = x: g x; g = y: y; }",
+        ] {
+            assert_eq!(normalize(source), parse_expr(source), "normalizing {source}");
+        }
+    }
+
+    #[test]
+    fn test_rules_are_opt_in() {
+        let expr = parse_expr("rec { a = 1; }");
+        assert_eq!(Normalizer::new().normalize(expr.clone()).unwrap(), expr);
+    }
+}
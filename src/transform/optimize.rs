@@ -1,42 +1,111 @@
 //! Optimization transformations
 
-use crate::ast::Expression;
+use std::fmt;
+
+use crate::ast::{BinaryOperator, Expression, UnaryOperator};
 use crate::error::Result;
+use crate::transform::fold::fold_expression;
 
 /// Code optimization engine for Nix expressions
-/// 
+///
 /// Applies various optimization techniques to improve performance
 /// and reduce redundancy while preserving semantic equivalence.
-pub struct Optimizer {}
+pub struct Optimizer {
+    passes: Vec<OptimizationPass>,
+}
 impl Optimizer {
     /// Create a new optimizer with default optimization passes
-    pub fn new() -> Self { Self {} }
+    pub fn new() -> Self {
+        Self {
+            passes: OptimizationPass::all().to_vec(),
+        }
+    }
     /// Apply optimization transformations to an expression
-    /// 
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `expr` - The expression to optimize
-    /// 
+    ///
     /// # Returns
-    /// 
-    /// An optimization result containing the optimized expression
-    pub fn optimize(&mut self, expr: Expression) -> Result<OptimizationResult> {
+    ///
+    /// An optimization result containing the optimized expression and a
+    /// description listing every rewrite that was made
+    ///
+    /// # Errors
+    ///
+    /// Never fails: expressions that cannot be folded are kept as they are.
+    pub fn optimize(&mut self, mut expr: Expression) -> Result<OptimizationResult> {
+        let mut rewrites = Vec::new();
+
+        for pass in &self.passes {
+            match pass {
+                OptimizationPass::ConstantFolding => {
+                    expr = fold_expression(expr, &mut |expr| {
+                        Ok(match ConstantFolder::fold(&expr) {
+                            Some(folded) => {
+                                rewrites.push(format!("{expr} -> {folded}"));
+                                folded
+                            }
+                            None => expr,
+                        })
+                    })?;
+                }
+            }
+        }
+
+        let description = if rewrites.is_empty() {
+            "No optimization applied".to_string()
+        } else {
+            format!("Folded {} constant expression(s): {}", rewrites.len(), rewrites.join("; "))
+        };
         Ok(OptimizationResult {
             expression: expr,
-            description: "No optimization applied".to_string(),
+            description,
         })
     }
     /// Configure the optimizer with custom optimization passes
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_config` - Optimization configuration options
-    pub fn with_config(self, _config: Config) -> Self { self }
+    ///
+    /// * `config` - Optimization configuration options
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.passes = config.passes;
+        self
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// A single optimization pass that can transform expressions
-#[derive(Debug, Clone)]
-pub struct OptimizationPass {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptimizationPass {
+    /// Evaluate operators, negations and `if`s whose operands are literals
+    ConstantFolding,
+}
+
+impl OptimizationPass {
+    /// Get all optimization passes, in the order they run
+    pub const fn all() -> &'static [OptimizationPass] {
+        &[OptimizationPass::ConstantFolding]
+    }
+
+    /// Get the pass name
+    pub const fn name(self) -> &'static str {
+        match self {
+            OptimizationPass::ConstantFolding => "constant-folding",
+        }
+    }
+}
+
+impl fmt::Display for OptimizationPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// Result of applying optimization to an expression
 #[derive(Debug, Clone)]
@@ -49,4 +118,180 @@ pub struct OptimizationResult {
 
 /// Configuration options for optimization
 #[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+pub struct Config {
+    /// Passes to run, in order
+    pub passes: Vec<OptimizationPass>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            passes: OptimizationPass::all().to_vec(),
+        }
+    }
+}
+
+/// Folds constant expressions, one node at a time
+///
+/// Only rewrites that Nix itself would perform without error are made:
+/// integer overflow, division by zero and type mismatches are left for
+/// the evaluator to report.
+pub(crate) struct ConstantFolder;
+
+impl ConstantFolder {
+    /// Fold an expression whose operands are literals, without recursing
+    pub(crate) fn fold(expr: &Expression) -> Option<Expression> {
+        match expr {
+            Expression::BinaryOp { op, left, right } => {
+                Self::fold_binary(*op, left.unlocated(), right.unlocated())
+            }
+            Expression::UnaryOp { op, operand } => match (op, operand.unlocated()) {
+                (UnaryOperator::Not, Expression::Boolean(b)) => Some(Expression::Boolean(!b)),
+                (UnaryOperator::Negate, Expression::Integer(n)) => {
+                    n.checked_neg().map(Expression::Integer)
+                }
+                (UnaryOperator::Negate, Expression::Float(n)) => Some(Expression::Float(-n)),
+                _ => None,
+            },
+            Expression::If { condition, then_branch, else_branch } => {
+                match condition.unlocated() {
+                    Expression::Boolean(true) => Some(then_branch.as_ref().clone().into_unlocated()),
+                    Expression::Boolean(false) => Some(else_branch.as_ref().clone().into_unlocated()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn fold_binary(op: BinaryOperator, left: &Expression, right: &Expression) -> Option<Expression> {
+        use BinaryOperator as Op;
+        use Expression as E;
+
+        match (op, left, right) {
+            // `&&`, `||` and `->` short-circuit, so the right operand may be anything
+            (Op::And, E::Boolean(false), _) => Some(E::Boolean(false)),
+            (Op::Or, E::Boolean(true), _) | (Op::Implies, E::Boolean(false), _) => {
+                Some(E::Boolean(true))
+            }
+            (Op::And | Op::Implies, E::Boolean(true), E::Boolean(r))
+            | (Op::Or, E::Boolean(false), E::Boolean(r)) => Some(E::Boolean(*r)),
+
+            (Op::Add, E::String(l), E::String(r)) => Some(E::String(format!("{l}{r}"))),
+            (Op::Concat, E::List(l), E::List(r)) => {
+                Some(E::List(l.iter().chain(r).cloned().collect()))
+            }
+
+            (_, E::Integer(l), E::Integer(r)) => match op {
+                Op::Add => l.checked_add(*r).map(E::Integer),
+                Op::Subtract => l.checked_sub(*r).map(E::Integer),
+                Op::Multiply => l.checked_mul(*r).map(E::Integer),
+                Op::Divide => l.checked_div(*r).map(E::Integer),
+                _ => Self::compare(op, l.cmp(r)),
+            },
+            // Mixed arithmetic promotes the integer operand to a float
+            (_, E::Float(_) | E::Integer(_), E::Float(_) | E::Integer(_)) => {
+                let (l, r) = (Self::as_float(left)?, Self::as_float(right)?);
+                match op {
+                    Op::Add => Some(E::Float(l + r)),
+                    Op::Subtract => Some(E::Float(l - r)),
+                    Op::Multiply => Some(E::Float(l * r)),
+                    Op::Divide if r != 0.0 => Some(E::Float(l / r)),
+                    _ => Self::compare(op, l.partial_cmp(&r)?),
+                }
+            }
+
+            (Op::Equal | Op::NotEqual, E::Boolean(l), E::Boolean(r)) => {
+                Self::compare(op, l.cmp(r))
+            }
+            (Op::Equal | Op::NotEqual, E::String(l), E::String(r)) => Self::compare(op, l.cmp(r)),
+            (Op::Equal | Op::NotEqual, E::Null, E::Null) => {
+                Self::compare(op, std::cmp::Ordering::Equal)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_float(expr: &Expression) -> Option<f64> {
+        match expr {
+            Expression::Float(n) => Some(*n),
+            // Nix converts integers the same way, rounding large ones
+            #[allow(clippy::cast_precision_loss)]
+            Expression::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    /// Evaluate a comparison operator given how its operands are ordered
+    fn compare(op: BinaryOperator, ordering: std::cmp::Ordering) -> Option<Expression> {
+        use std::cmp::Ordering;
+
+        let result = match op {
+            BinaryOperator::Equal => ordering == Ordering::Equal,
+            BinaryOperator::NotEqual => ordering != Ordering::Equal,
+            BinaryOperator::Less => ordering == Ordering::Less,
+            BinaryOperator::LessEqual => ordering != Ordering::Greater,
+            BinaryOperator::Greater => ordering == Ordering::Greater,
+            BinaryOperator::GreaterEqual => ordering != Ordering::Less,
+            _ => return None,
+        };
+        Some(Expression::Boolean(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn optimize(source: &str) -> OptimizationResult {
+        Optimizer::new().optimize(parse_expr(source)).unwrap()
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let cases = [
+            ("1 + 2", "3"),
+            ("(1 + 2) * 3", "9"),
+            ("7 / 2", "3"),
+            ("1 + 0.5", "1.5"),
+            ("true && false", "false"),
+            ("false && x", "false"),
+            ("!(1 < 2) || true", "true"),
+            ("\"a\" + \"b\"", "\"ab\""),
+            ("[ 1 ] ++ [ 2 ]", "[ 1 2 ]"),
+            ("if true then a else b", "a"),
+            ("if 1 == 2 then a else { x = 2 * 2; }", "{ x = 4; }"),
+        ];
+
+        for (source, expected) in cases {
+            let result = optimize(source);
+            assert_eq!(result.expression, parse_expr(expected), "folding {source}");
+        }
+    }
+
+    #[test]
+    fn test_fold_description() {
+        let result = optimize("(1 + 2) * 3");
+        assert_eq!(result.description, "Folded 2 constant expression(s): 1 + 2 -> 3; 3 * 3 -> 9");
+    }
+
+    #[test]
+    fn test_non_constant_expressions_left_alone() {
+        for source in ["x + 1", "true && x", "if c then 1 else 2", "1 / 0", "\"a\" + 1", "[ x ] ++ y"] {
+            let result = optimize(source);
+            assert_eq!(result.expression, parse_expr(source), "folding {source}");
+            assert_eq!(result.description, "No optimization applied");
+        }
+
+        let overflow = format!("{} + 1", i64::MAX);
+        assert_eq!(optimize(&overflow).expression, parse_expr(&overflow));
+    }
+
+    #[test]
+    fn test_disabled_constant_folding() {
+        let mut optimizer = Optimizer::new().with_config(Config { passes: Vec::new() });
+        let result = optimizer.optimize(parse_expr("1 + 2")).unwrap();
+        assert_eq!(result.expression, parse_expr("1 + 2"));
+    }
+}
@@ -1,37 +1,177 @@
 //! Refactoring transformations
 
-use crate::ast::Expression;
-use crate::error::Result;
+use std::fmt;
+use std::ops::Range;
+
+use crate::analysis::scope::{ScopeAnalyzer, ScopeType, references};
+use crate::ast::{AttrKey, Binding, Expression, Parameter};
+use crate::error::{ParseError, Result};
+use crate::transform::substitute::{substitute_counting, substitute_without_renaming};
+use crate::utils::string::needs_quoting;
+use crate::visitor::{VisitControl, Visitor, VisitorMut, walk_expression_mut};
 
 /// Automated refactoring engine for Nix code
-/// 
+///
 /// Applies configurable refactoring rules to transform code
 /// while preserving semantic meaning and improving structure.
-pub struct Refactorer {}
+pub struct Refactorer {
+    rules: Vec<RefactorRule>,
+}
 impl Refactorer {
     /// Create a new refactorer with default rules
-    pub fn new() -> Self { Self {} }
+    ///
+    /// Every rule is opt-in, so the default refactorer makes no changes.
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
     /// Apply refactoring transformations to an expression
-    /// 
+    ///
+    /// Rules run in order, each on the output of the previous one.
+    ///
     /// # Arguments
-    /// 
-    /// * `_expr` - The expression to refactor
-    /// 
+    ///
+    /// * `expr` - The expression to refactor
+    ///
     /// # Returns
-    /// 
-    /// A vector of refactoring results showing what was changed
-    pub fn refactor(&mut self, _expr: Expression) -> Result<Vec<RefactorResult>> { Ok(Vec::new()) }
+    ///
+    /// A vector of refactoring results showing what was changed, with one
+    /// entry per rule that changed the expression
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of a rule, such as a substitution that
+    /// would capture a variable.
+    pub fn refactor(&mut self, expr: Expression) -> Result<Vec<RefactorResult>> {
+        let mut results = Vec::new();
+        let mut current = expr;
+
+        for rule in &self.rules {
+            let mut after = current.clone();
+            let changes = match rule {
+                RefactorRule::InlineLet => {
+                    let mut inliner = LetInliner::default();
+                    inliner.visit_expression_mut(&mut after);
+                    inliner.inlined
+                }
+            };
+            if changes.is_empty() {
+                continue;
+            }
+
+            results.push(RefactorResult {
+                rule_name: rule.name().to_string(),
+                description: changes.join("; "),
+                before: current,
+                after: after.clone(),
+            });
+            current = after;
+        }
+        Ok(results)
+    }
     /// Configure the refactorer with custom rules
-    /// 
+    ///
     /// # Arguments
-    /// 
-    /// * `_config` - Refactoring configuration options
-    pub fn with_config(self, _config: Config) -> Self { self }
+    ///
+    /// * `config` - Refactoring configuration options
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.rules = config.rules;
+        self
+    }
+
+    /// Rename the variable used at a byte offset
+    ///
+    /// The binding the use refers to is resolved with [`ScopeAnalyzer`],
+    /// and it is renamed to `new_name` along with every reference to it.
+    /// Variables of the same name bound elsewhere are left alone. The
+    /// expression must carry source locations.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::ValidationError` if there is no variable at
+    /// `offset`, if it is not bound by a `let` or a function, if
+    /// `new_name` is not an identifier, or if the rename would make a
+    /// reference refer to a different binding. Pattern fields and the
+    /// attributes of `rec` sets are part of a value's interface, so they
+    /// are not renamed either.
+    pub fn rename(&self, expr: &Expression, offset: usize, new_name: &str) -> Result<RefactorResult> {
+        let name = match expr.find_at_offset(offset).last().map(|expr| expr.unlocated()) {
+            Some(Expression::Identifier(name)) => name.as_str(),
+            _ => return Err(ParseError::ValidationError(format!("No variable at offset {offset}"))),
+        };
+        if needs_quoting(new_name) {
+            return Err(ParseError::ValidationError(format!("`{new_name}` is not a valid identifier")));
+        }
+
+        // Scopes open in source order, so among nested scopes binding the
+        // name, the innermost one is the last to start
+        let scopes = ScopeAnalyzer::new().analyze(expr)?;
+        let scope = scopes
+            .iter()
+            .filter(|scope| scope.binds(name) && scope.scope_type != ScopeType::With)
+            .filter(|scope| scope.range.as_ref().is_some_and(|range| range.start <= offset && offset <= range.end))
+            .max_by_key(|scope| scope.range.as_ref().map_or(0, |range| range.start))
+            .ok_or_else(|| ParseError::ValidationError(format!("`{name}` is not bound by a let or a function")))?;
+        if scope.scope_type == ScopeType::RecAttrSet {
+            return Err(ParseError::ValidationError(format!(
+                "`{name}` is an attribute of a rec set and cannot be renamed"
+            )));
+        }
+
+        let mut after = expr.clone();
+        if name != new_name {
+            let mut renamer = BinderRenamer {
+                range: scope.range.clone().unwrap_or_default(),
+                name,
+                new_name,
+                outcome: None,
+            };
+            renamer.visit_expression_mut(&mut after);
+            renamer.outcome.unwrap_or_else(|| {
+                Err(ParseError::InvalidNode(format!("no binding of `{name}` at the scope's location")))
+            })?;
+        }
+
+        Ok(RefactorResult {
+            rule_name: "rename".to_string(),
+            description: format!("renamed `{name}` to `{new_name}`"),
+            before: expr.clone(),
+            after,
+        })
+    }
+}
+
+impl Default for Refactorer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// A single refactoring rule that can transform code
-#[derive(Debug, Clone)]
-pub struct RefactorRule {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RefactorRule {
+    /// Replace a `let` binding used exactly once with its value
+    InlineLet,
+}
+
+impl RefactorRule {
+    /// Get all refactoring rules
+    pub const fn all() -> &'static [RefactorRule] {
+        &[RefactorRule::InlineLet]
+    }
+
+    /// Get the rule name, as reported in `RefactorResult::rule_name`
+    pub const fn name(self) -> &'static str {
+        match self {
+            RefactorRule::InlineLet => "inline-let",
+        }
+    }
+}
+
+impl fmt::Display for RefactorRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// Result of applying a refactoring rule
 #[derive(Debug, Clone)]
@@ -47,5 +187,397 @@ pub struct RefactorResult {
 }
 
 /// Configuration options for refactoring
-#[derive(Debug, Clone)]
-pub struct Config {}
\ No newline at end of file
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Rules to apply, in order; none are enabled by default
+    pub rules: Vec<RefactorRule>,
+}
+
+/// Mutable visitor that inlines single-use `let` bindings, innermost first
+///
+/// A binding is inlined when its name occurs exactly once in the body and
+/// the other bindings, it does not refer to itself, its value has no
+/// effects beyond producing a value (no `import`, `assert`, `throw`,
+/// `abort` or `builtins.trace`), and none of the names in the value would
+/// be captured by a scope between the `let` and the use. A `with` may
+/// provide any name, so values with free variables are not inlined into
+/// one. A use as `inherit name;` becomes `name = value;`, but `inherit`
+/// bindings are never inlined.
+#[derive(Default)]
+struct LetInliner {
+    inlined: Vec<String>,
+}
+
+impl LetInliner {
+    /// Inline one binding of `bindings`, returning whether anything changed
+    fn inline_one(&mut self, bindings: &mut Vec<Binding>, body: &mut Expression) -> bool {
+        for index in 0..bindings.len() {
+            let binding = &bindings[index];
+            if binding.inherit
+                || references(&binding.value, &binding.name)
+                || has_side_effects(&binding.value)
+            {
+                continue;
+            }
+
+            let Some((new_body, new_siblings)) = Self::substitute_binding(bindings, index, body) else {
+                continue;
+            };
+            self.inlined.push(format!("inlined let binding `{}`", binding.name));
+            *bindings = new_siblings;
+            *body = new_body;
+            return true;
+        }
+        false
+    }
+
+    /// Substitute the binding at `index` into the body and the other
+    /// bindings, if it is used exactly once and can be inlined there
+    fn substitute_binding(
+        bindings: &[Binding],
+        index: usize,
+        body: &Expression,
+    ) -> Option<(Expression, Vec<Binding>)> {
+        let Binding { name, value, .. } = &bindings[index];
+        if !value.free_variables().is_empty()
+            && (uses_under_with(body, name)
+                || bindings.iter().any(|binding| uses_under_with(&binding.value, name)))
+        {
+            return None;
+        }
+
+        let (new_body, mut uses) = substitute_counting(body, name, value)?;
+        let mut new_siblings = Vec::with_capacity(bindings.len() - 1);
+        for (other, sibling) in bindings.iter().enumerate() {
+            if other == index {
+                continue;
+            }
+            let (value, sibling_uses) = substitute_counting(&sibling.value, name, value)?;
+            uses += sibling_uses;
+            new_siblings.push(Binding { value, ..sibling.clone() });
+        }
+        (uses == 1).then_some((new_body, new_siblings))
+    }
+}
+
+impl VisitorMut for LetInliner {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+
+        let Expression::LetIn { bindings, body } = expr else {
+            return;
+        };
+        while self.inline_one(bindings, body) {}
+        if bindings.is_empty() {
+            let body = std::mem::replace(body.as_mut(), Expression::Null);
+            *expr = body.into_unlocated();
+        }
+    }
+}
+
+/// Check if evaluating `expr` may do more than produce a value
+fn has_side_effects(expr: &Expression) -> bool {
+    let mut finder = SideEffectFinder { found: false };
+    finder.visit_expression(expr);
+    finder.found
+}
+
+/// Visitor that looks for imports, assertions and effectful builtins
+struct SideEffectFinder {
+    found: bool,
+}
+
+impl Visitor for SideEffectFinder {
+    fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+        let effectful = match expr {
+            Expression::Import { .. } | Expression::Assert { .. } => true,
+            Expression::Identifier(name) => matches!(name.as_str(), "import" | "throw" | "abort"),
+            Expression::Select { expr, path, .. } => {
+                matches!(expr.unlocated(), Expression::Identifier(base) if base == "builtins")
+                    && matches!(
+                        path.first().and_then(AttrKey::static_path).and_then(<[String]>::first).map(String::as_str),
+                        Some("import" | "throw" | "abort" | "trace" | "seq" | "deepSeq")
+                    )
+            }
+            _ => false,
+        };
+        if effectful {
+            self.found = true;
+            return VisitControl::Stop;
+        }
+        VisitControl::Continue
+    }
+}
+
+/// Check if `name` occurs free in the body of a `with` inside `expr`
+fn uses_under_with(expr: &Expression, name: &str) -> bool {
+    struct WithFinder<'a> {
+        name: &'a str,
+        found: bool,
+    }
+
+    impl Visitor for WithFinder<'_> {
+        fn visit_with(&mut self, scope: &Expression, body: &Expression) -> VisitControl {
+            if references(body, self.name) {
+                self.found = true;
+                return VisitControl::Stop;
+            }
+            self.visit_expression(scope)
+        }
+    }
+
+    let mut finder = WithFinder { name, found: false };
+    finder.visit_expression(expr);
+    finder.found
+}
+
+/// Mutable visitor that renames a variable in the `let` or function
+/// spanning `range`, along with the references to it
+struct BinderRenamer<'a> {
+    range: Range<usize>,
+    name: &'a str,
+    new_name: &'a str,
+    /// Result of the rename, once the binder has been found
+    outcome: Option<Result<()>>,
+}
+
+impl BinderRenamer<'_> {
+    /// Rename the binding of the name in a `let` or a function
+    fn rename(&self, expr: &mut Expression) -> Result<()> {
+        let (name, new_name) = (self.name, self.new_name);
+        let replacement = Expression::Identifier(new_name.to_string());
+        let captured = || {
+            ParseError::ValidationError(format!(
+                "Renaming `{name}` to `{new_name}` would change what a reference to `{new_name}` refers to"
+            ))
+        };
+        let already_bound = || ParseError::ValidationError(format!("`{new_name}` is already bound here"));
+        let substitute = |expr: &Expression| substitute_without_renaming(expr, name, &replacement).ok_or_else(captured);
+
+        match expr {
+            Expression::LetIn { bindings, body } => {
+                if bindings.iter().any(|binding| binding.name == new_name) {
+                    return Err(already_bound());
+                }
+                // `inherit` entries without source refer to the enclosing scope
+                let in_scope = |binding: &&Binding| {
+                    !matches!(binding.value.unlocated(), Expression::Inherit { source: None, .. })
+                };
+                let values = bindings.iter().filter(in_scope).map(|binding| &binding.value);
+                if values.chain([&**body]).any(|expr| references(expr, new_name)) {
+                    return Err(captured());
+                }
+
+                for binding in bindings.iter_mut() {
+                    if in_scope(&&*binding) {
+                        binding.value = substitute(&binding.value)?;
+                        if let Expression::Inherit { source: Some(source), .. } = binding.value.unlocated() {
+                            binding.from = Some((**source).clone());
+                        }
+                    }
+                    if binding.name != name {
+                        continue;
+                    }
+                    // `inherit name;` becomes `new_name = name;`
+                    if let Expression::Inherit { source, .. } = binding.value.unlocated() {
+                        binding.value = match source {
+                            Some(source) => Expression::Select {
+                                expr: source.clone(),
+                                path: vec![AttrKey::Static(vec![name.to_string()])],
+                                default: None,
+                            },
+                            None => Expression::Identifier(name.to_string()),
+                        };
+                        binding.inherit = false;
+                        binding.from = None;
+                    }
+                    binding.name = new_name.to_string();
+                }
+                **body = substitute(body)?;
+            }
+            Expression::Function { parameter, body } => {
+                match parameter {
+                    Parameter::Identifier(param) => *param = new_name.to_string(),
+                    Parameter::Pattern { fields, bind, .. } => {
+                        if fields.iter().any(|field| field.name == name) {
+                            return Err(ParseError::ValidationError(format!(
+                                "`{name}` is a pattern field, which callers pass by name, and cannot be renamed"
+                            )));
+                        }
+                        if fields.iter().any(|field| field.name == new_name) {
+                            return Err(already_bound());
+                        }
+                        for default in fields.iter_mut().filter_map(|field| field.default.as_mut()) {
+                            if references(default, new_name) {
+                                return Err(captured());
+                            }
+                            *default = substitute(default)?;
+                        }
+                        *bind = Some(new_name.to_string());
+                    }
+                }
+                if references(body, new_name) {
+                    return Err(captured());
+                }
+                **body = substitute(body)?;
+            }
+            _ => {
+                return Err(ParseError::InvalidNode(format!(
+                    "expected a let or a function binding `{name}`"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VisitorMut for BinderRenamer<'_> {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        if self.outcome.is_some() {
+            return;
+        }
+        if let Expression::Located { location, expr: inner } = expr
+            && location.start_byte == self.range.start
+            && location.end_byte == self.range.end
+            && matches!(inner.unlocated(), Expression::LetIn { .. } | Expression::Function { .. })
+        {
+            let mut target = &mut **inner;
+            while let Expression::Located { expr, .. } = target {
+                target = expr;
+            }
+            self.outcome = Some(self.rename(target));
+            return;
+        }
+        walk_expression_mut(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn inline(source: &str) -> Expression {
+        let config = Config {
+            rules: vec![RefactorRule::InlineLet],
+        };
+        let expr = parse_expr(source);
+        let results = Refactorer::new().with_config(config).refactor(expr.clone()).unwrap();
+        results.last().map_or(expr, |result| result.after.clone())
+    }
+
+    #[test]
+    fn test_inline_single_use_binding() {
+        let config = Config {
+            rules: vec![RefactorRule::InlineLet],
+        };
+        let results = Refactorer::new()
+            .with_config(config)
+            .refactor(parse_expr("let x = 1; in x + 1"))
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].rule_name, "inline-let");
+        assert_eq!(results[0].description, "inlined let binding `x`");
+        assert_eq!(results[0].before, parse_expr("let x = 1; in x + 1"));
+        assert_eq!(results[0].after, parse_expr("1 + 1"));
+    }
+
+    #[test]
+    fn test_inline_chains_and_keeps_other_bindings() {
+        assert_eq!(inline("let a = b; b = 1 + 2; in a * 3"), parse_expr("(1 + 2) * 3"));
+        assert_eq!(inline("let x = f 1; y = 2; in [ x y y ]"), parse_expr("let y = 2; in [ (f 1) y y ]"));
+        // An inherit of the name is a use, spelled out as a binding
+        assert_eq!(inline("let x = 1; in { inherit x; }"), parse_expr("{ x = 1; }"));
+    }
+
+    #[test]
+    fn test_bindings_not_inlined() {
+        for source in [
+            // used more than once, or not at all
+            "let x = 1; in x + x",
+            "let x = 1; in 2",
+            // recursive
+            "let xs = [ 1 ] ++ xs; in xs",
+            // effectful
+            "let x = import ./a.nix; in x",
+            "let x = throw \"no\"; in x",
+            "let x = builtins.trace \"hi\" 1; in x",
+            // the use refers to a different `x`
+            "let x = 1; in (x: x)",
+            // `y` would be captured by the function or the `with`
+            "let x = y; in (y: x)",
+            "let x = y; in with z; x",
+            // inherit bindings
+            "let inherit (pkgs) x; in x",
+        ] {
+            assert_eq!(inline(source), parse_expr(source), "refactoring {source}");
+        }
+    }
+
+    /// Rename the variable at the `|` marking the cursor in `source`
+    fn rename(source: &str, new_name: &str) -> Result<Expression> {
+        let offset = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        let result = Refactorer::new().rename(&parse_expr(&source), offset, new_name)?;
+        assert_eq!(result.before, parse_expr(&source));
+        Ok(result.after)
+    }
+
+    #[test]
+    fn test_rename() {
+        let expected = parse_expr("let z = 1; in z + (y: y)");
+        assert_eq!(rename("let x = 1; in |x + (y: y)", "z").unwrap(), expected);
+        assert_eq!(rename("let x = 1; in x| + (y: y)", "z").unwrap(), expected);
+
+        // Only the references to the renamed binding change
+        assert_eq!(
+            rename("let x = 1; in [ |x (x: x) (let x = 2; in x) ]", "z").unwrap(),
+            parse_expr("let z = 1; in [ z (x: x) (let x = 2; in x) ]")
+        );
+        assert_eq!(
+            rename("let x = 1; in [ x (x: |x) (let x = 2; in x) ]", "z").unwrap(),
+            parse_expr("let x = 1; in [ x (z: z) (let x = 2; in x) ]")
+        );
+        assert_eq!(
+            rename("{ a }@args: |args.a", "self").unwrap(),
+            parse_expr("{ a }@self: self.a")
+        );
+        assert_eq!(
+            rename("let x = 1; in with s; { inherit x; y = |x; }", "z").unwrap(),
+            parse_expr("let z = 1; in with s; { x = z; y = z; }")
+        );
+        assert_eq!(
+            rename("let inherit (s) x; in |x + 1", "z").unwrap(),
+            parse_expr("let z = s.x; in z + 1")
+        );
+    }
+
+    #[test]
+    fn test_rename_rejected() {
+        let rejected = |source: &str, new_name: &str| {
+            let err = rename(source, new_name).unwrap_err();
+            let ParseError::ValidationError(message) = err else {
+                panic!("expected a validation error, got {err:?}");
+            };
+            message
+        };
+
+        // The new name would capture a free variable, or be captured
+        assert!(rejected("let x = 1; in |x + y", "y").contains("would change"));
+        assert!(rejected("let x = 1; in y: |x + y", "y").contains("would change"));
+        assert!(rejected("let x = 1; y = 2; in |x", "y").contains("already bound"));
+        // Interfaces, free variables and invalid names
+        assert!(rejected("{ x }: |x", "z").contains("pattern field"));
+        assert!(rejected("rec { x = 1; y = |x; }", "z").contains("rec set"));
+        assert!(rejected("f |x", "z").contains("not bound"));
+        assert!(rejected("let x = 1; |in x", "z").contains("No variable"));
+        assert!(rejected("let x = 1; in |x", "in").contains("not a valid identifier"));
+    }
+
+    #[test]
+    fn test_rules_are_opt_in() {
+        let results = Refactorer::new().refactor(parse_expr("let x = 1; in x")).unwrap();
+        assert!(results.is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! Capture-avoiding substitution and beta reduction
+
+use std::collections::HashSet;
+
+use crate::analysis::scope::references;
+use crate::ast::{AttrKey, Attribute, Binding, Expression, Parameter};
+use crate::visitor::{VisitorMut, walk_expression_mut};
+
+/// Replace the free occurrences of `name` in `expr` with `replacement`
+///
+/// Bound variables that would capture a free variable of `replacement` are
+/// renamed first, by appending `'` until the name is unused. Pattern fields
+/// and the attributes of `rec` sets are visible from outside and cannot be
+/// renamed, so `None` is returned if one of them would capture. `with`
+/// never captures, because lexically bound names (builtins included) take
+/// precedence over the names it provides.
+///
+/// `inherit name;` entries are rewritten to `name = replacement;`.
+pub fn substitute(expr: &Expression, name: &str, replacement: &Expression) -> Option<Expression> {
+    let mut substitution = Substitution::new(name, replacement);
+    let mut result = expr.clone();
+    substitution.visit_expression_mut(&mut result);
+    (!substitution.failed).then_some(result)
+}
+
+/// Like [`substitute`], but returns `None` instead of renaming a bound
+/// variable that would capture a free variable of `replacement`
+pub(crate) fn substitute_without_renaming(
+    expr: &Expression,
+    name: &str,
+    replacement: &Expression,
+) -> Option<Expression> {
+    substitute_counting(expr, name, replacement).map(|(result, _)| result)
+}
+
+/// Like [`substitute_without_renaming`], but also returns the number of
+/// occurrences replaced, `inherit name;` entries included
+pub(crate) fn substitute_counting(
+    expr: &Expression,
+    name: &str,
+    replacement: &Expression,
+) -> Option<(Expression, usize)> {
+    let mut substitution = Substitution::new(name, replacement);
+    substitution.strict = true;
+    let mut result = expr.clone();
+    substitution.visit_expression_mut(&mut result);
+    (!substitution.failed).then_some((result, substitution.replaced))
+}
+
+/// Reduce an application of a function literal, `(x: body) arg`
+///
+/// Returns `body` with `arg` substituted for `x`, or `None` if `expr` is
+/// not such an application or the substitution is not possible. Functions
+/// taking an attribute set pattern are not reduced.
+pub fn beta_reduce(expr: &Expression) -> Option<Expression> {
+    let Expression::Application { function, argument } = expr.unlocated() else {
+        return None;
+    };
+    let Expression::Function { parameter: Parameter::Identifier(name), body } = function.unlocated() else {
+        return None;
+    };
+    substitute(body, name, argument)
+}
+
+/// Mutable visitor that substitutes a value for the free occurrences of a
+/// name, renaming bound variables that would capture the value's names
+struct Substitution<'a> {
+    name: &'a str,
+    replacement: &'a Expression,
+    /// Free variables of the replacement, which must stay free
+    free: HashSet<String>,
+    /// Whether a binder that cannot be renamed would capture
+    failed: bool,
+    /// Whether capturing binders fail the substitution instead of being
+    /// renamed
+    strict: bool,
+    /// Number of occurrences replaced so far
+    replaced: usize,
+}
+
+impl<'a> Substitution<'a> {
+    fn new(name: &'a str, replacement: &'a Expression) -> Self {
+        Self {
+            name,
+            replacement,
+            free: replacement.free_variables(),
+            failed: false,
+            strict: false,
+            replaced: 0,
+        }
+    }
+
+    /// Pick a name derived from `base` that is neither free in the
+    /// replacement nor in `taken`
+    ///
+    /// Every capturing binder is renamed through this, so in strict mode it
+    /// fails the substitution.
+    fn fresh_name(&mut self, base: &str, taken: &HashSet<String>) -> String {
+        self.failed |= self.strict;
+        let mut name = format!("{base}'");
+        while name == self.name || self.free.contains(&name) || taken.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    /// Rename the free occurrences of `from` in `exprs` to `to`
+    fn rename<'e>(&mut self, exprs: impl IntoIterator<Item = &'e mut Expression>, from: &str, to: &str) {
+        let to = Expression::Identifier(to.to_string());
+        let mut renaming = Substitution::new(from, &to);
+        for expr in exprs {
+            renaming.visit_expression_mut(expr);
+        }
+        self.failed |= renaming.failed;
+    }
+
+    /// Rename a `let` binding that would capture a name of the replacement
+    fn rename_binding(&mut self, bindings: &mut [Binding], body: &mut Expression, index: usize) {
+        let mut taken = body.free_variables();
+        for binding in bindings.iter() {
+            taken.extend(binding.value.free_variables());
+            taken.insert(binding.name.clone());
+        }
+        let old = bindings[index].name.clone();
+        let fresh = self.fresh_name(&old, &taken);
+
+        // `inherit` entries without source refer to the enclosing scope
+        let scope = bindings
+            .iter_mut()
+            .filter(|binding| !matches!(binding.value.unlocated(), Expression::Inherit { source: None, .. }))
+            .map(|binding| &mut binding.value);
+        self.rename(scope.chain([body]), &old, &fresh);
+
+        let binding = &mut bindings[index];
+        if let Expression::Inherit { source, .. } = binding.value.unlocated() {
+            binding.value = match source {
+                Some(source) => Expression::Select {
+                    expr: source.clone(),
+                    path: vec![AttrKey::Static(vec![old.clone()])],
+                    default: None,
+                },
+                None => Expression::Identifier(old.clone()),
+            };
+            binding.inherit = false;
+            binding.from = None;
+        }
+        binding.name = fresh;
+    }
+}
+
+impl VisitorMut for Substitution<'_> {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        if self.failed || !references(expr, self.name) {
+            return;
+        }
+        if matches!(expr, Expression::Identifier(id) if id == self.name) {
+            *expr = self.replacement.clone();
+            self.replaced += 1;
+            return;
+        }
+        walk_expression_mut(self, expr);
+    }
+
+    // Scopes are only entered if they refer to the name, so they do not
+    // bind it unless they also inherit it from the enclosing scope
+
+    fn visit_function_mut(&mut self, parameter: &mut Parameter, body: &mut Expression) {
+        match parameter {
+            Parameter::Identifier(param) => {
+                if self.free.contains(param) {
+                    let fresh = self.fresh_name(param, &body.free_variables());
+                    self.rename([&mut *body], param, &fresh);
+                    *param = fresh;
+                }
+            }
+            Parameter::Pattern { fields, bind, .. } => {
+                if fields.iter().any(|field| self.free.contains(&field.name)) {
+                    self.failed = true;
+                    return;
+                }
+                if let Some(bind) = bind
+                    && self.free.contains(bind)
+                {
+                    let mut taken = body.free_variables();
+                    for field in fields.iter() {
+                        taken.insert(field.name.clone());
+                        taken.extend(field.default.iter().flat_map(Expression::free_variables));
+                    }
+                    let fresh = self.fresh_name(bind, &taken);
+                    let defaults = fields.iter_mut().filter_map(|field| field.default.as_mut());
+                    self.rename(defaults.chain([&mut *body]), bind, &fresh);
+                    *bind = fresh;
+                }
+                for default in fields.iter_mut().filter_map(|field| field.default.as_mut()) {
+                    self.visit_expression_mut(default);
+                }
+            }
+        }
+        self.visit_expression_mut(body);
+    }
+
+    fn visit_let_in_mut(&mut self, bindings: &mut Vec<Binding>, body: &mut Expression) {
+        for index in 0..bindings.len() {
+            if self.free.contains(&bindings[index].name) {
+                self.rename_binding(bindings, body, index);
+            }
+        }
+
+        let shadowed = bindings.iter().any(|binding| binding.name == self.name);
+        for binding in bindings.iter_mut() {
+            if binding.value.inherits(self.name) {
+                binding.value = self.replacement.clone();
+                binding.inherit = false;
+                self.replaced += 1;
+            } else if !shadowed {
+                self.visit_expression_mut(&mut binding.value);
+                if let Expression::Inherit { source: Some(source), .. } = binding.value.unlocated() {
+                    binding.from = Some((**source).clone());
+                }
+            }
+        }
+        if !shadowed {
+            self.visit_expression_mut(body);
+        }
+    }
+
+    fn visit_attribute_set_mut(&mut self, recursive: &mut bool, attributes: &mut Vec<Attribute>) {
+        // Computed keys are evaluated outside of `rec` sets
+        for attr in attributes.iter_mut() {
+            self.visit_attr_key_mut(&mut attr.key);
+        }
+
+        let names: Vec<&String> = attributes
+            .iter()
+            .filter(|_| *recursive)
+            .filter_map(|attr| match &attr.key {
+                AttrKey::Static(path) => path.first(),
+                AttrKey::Dynamic(_) => None,
+            })
+            .collect();
+        if names.iter().any(|name| self.free.contains(*name)) {
+            self.failed = true;
+            return;
+        }
+        let shadowed = names.iter().any(|name| *name == self.name);
+
+        for attr in attributes.iter_mut() {
+            if attr.value.inherits(self.name) {
+                attr.value = self.replacement.clone();
+                self.replaced += 1;
+            } else if !shadowed {
+                self.visit_expression_mut(&mut attr.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_expr;
+
+    fn substituted(source: &str, name: &str, replacement: &str) -> Option<Expression> {
+        substitute(&parse_expr(source), name, &parse_expr(replacement))
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(substituted("x + y", "x", "1"), Some(parse_expr("1 + y")));
+        assert_eq!(substituted("x: x", "x", "1"), Some(parse_expr("x: x")));
+        assert_eq!(substituted("let x = 2; in x", "x", "1"), Some(parse_expr("let x = 2; in x")));
+        assert_eq!(
+            substituted("let a = x; in { inherit x; b = a; }", "x", "f 1"),
+            Some(parse_expr("let a = f 1; in { x = f 1; b = a; }"))
+        );
+        assert_eq!(
+            substituted("rec { inherit x; b = x; }", "x", "1"),
+            Some(parse_expr("rec { x = 1; b = x; }"))
+        );
+        assert_eq!(substituted("with s; x", "x", "y"), Some(parse_expr("with s; y")));
+    }
+
+    #[test]
+    fn test_substitute_avoids_capture() {
+        // Naive substitution would turn `y: x` into the identity function
+        assert_eq!(substituted("y: x", "x", "y"), Some(parse_expr("y': y")));
+        assert_eq!(substituted("y: y' + x", "x", "y"), Some(parse_expr("y'': y' + y")));
+        assert_eq!(
+            substituted("let y = 1; inherit z; in x + y + z", "x", "y + z"),
+            Some(parse_expr("let y' = 1; z' = z; in y + z + y' + z'"))
+        );
+        assert_eq!(
+            substituted("{ a }@y: x + y.a", "x", "y"),
+            Some(parse_expr("{ a }@y': y + y'.a"))
+        );
+
+        // Pattern fields and `rec` attributes cannot be renamed
+        assert_eq!(substituted("{ y }: x", "x", "y"), None);
+        assert_eq!(substituted("rec { y = x; }", "x", "y"), None);
+        // Binders that do not enclose the name are left alone
+        assert_eq!(substituted("[ (y: y) x ]", "x", "y"), Some(parse_expr("[ (y: y) y ]")));
+    }
+
+    #[test]
+    fn test_beta_reduce() {
+        assert_eq!(beta_reduce(&parse_expr("(x: x + 1) 2")), Some(parse_expr("2 + 1")));
+        assert_eq!(beta_reduce(&parse_expr("(x: y: x) y")), Some(parse_expr("y': y")));
+        assert_eq!(beta_reduce(&parse_expr("({ a }: a) { a = 1; }")), None);
+        assert_eq!(beta_reduce(&parse_expr("f 1")), None);
+    }
+}
@@ -0,0 +1,12 @@
+{ pkgs, lib, ... }: {
+  name = "hello";
+  version = "2.12";
+  src = pkgs.fetchurl {
+    url = "mirror://gnu/hello/hello-2.12.tar.gz";
+    hash = "sha256-jZkUKv2SV28wsM18tCqNxoCZmLxdYH2Idh9RLibH2yA=";
+  };
+  inherit lib;
+  meta = { license = lib.licenses.gpl3Plus; platforms = lib.platforms.all; };
+  buildInputs = [ pkgs.gettext pkgs.libiconv ];
+  doCheck = if pkgs.stdenv.isDarwin then false else true;
+}
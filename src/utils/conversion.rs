@@ -1,9 +1,13 @@
 //! Conversion utilities for Tree-sitter integration
 
-use tree_sitter::Node;
+use tree_sitter::{Node, TreeCursor};
+
+use crate::ast::Expression;
+use crate::error::{ParseError, Result};
+use crate::grammar::FieldName;
 
 /// Extension trait for Tree-sitter Tree types
-/// 
+///
 /// Provides additional functionality for working with Tree-sitter
 /// parse trees in the context of Nix parsing.
 pub trait TreeSitterExt {
@@ -11,13 +15,174 @@ pub trait TreeSitterExt {
 }
 
 /// Extension trait for Tree-sitter Node types
-/// 
+///
 /// Provides convenient methods for extracting information
 /// from Tree-sitter nodes and converting to AST types.
-pub trait NodeExt {
-    // Extensions for Node types  
+pub trait NodeExt<'tree> {
+    /// Get the source text of the node
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::Utf8Error` if the node does not span valid
+    /// UTF-8 in `source`.
+    fn text<'s>(&self, source: &'s str) -> Result<&'s str>;
+
+    /// Get the named children of the node, in source order
+    ///
+    /// `Node` has an inherent `named_children` taking a cursor, which
+    /// method calls resolve to first, so call this one as
+    /// `NodeExt::named_children(&node)`.
+    fn named_children(&self) -> Vec<Node<'tree>>;
+
+    /// Get the child stored in a grammar field
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::InvalidNode` if the node has no such child.
+    fn field(&self, name: FieldName) -> Result<Node<'tree>>;
+
+    /// Iterate over the node and its descendants in pre-order
+    fn preorder(&self) -> Preorder<'tree>;
+
+    /// Convert the node to an AST expression
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Expression::from_tree_sitter_node`].
+    fn to_expression(&self, source: &str) -> Result<Expression>;
 }
 
 impl TreeSitterExt for tree_sitter::Tree {}
 
-impl NodeExt for Node<'_> {}
\ No newline at end of file
+impl<'tree> NodeExt<'tree> for Node<'tree> {
+    fn text<'s>(&self, source: &'s str) -> Result<&'s str> {
+        Ok(self.utf8_text(source.as_bytes())?)
+    }
+
+    fn named_children(&self) -> Vec<Node<'tree>> {
+        let mut cursor = self.walk();
+        Node::named_children(self, &mut cursor).collect()
+    }
+
+    fn field(&self, name: FieldName) -> Result<Node<'tree>> {
+        self.child_by_field_name(name.as_str()).ok_or_else(|| ParseError::InvalidNode(
+            format!("{} missing field '{}'", self.kind(), name)
+        ))
+    }
+
+    fn preorder(&self) -> Preorder<'tree> {
+        Preorder { cursor: self.walk(), state: PreorderState::Start }
+    }
+
+    fn to_expression(&self, source: &str) -> Result<Expression> {
+        Expression::from_tree_sitter_node(*self, source)
+    }
+}
+
+/// Iterator over the nodes of a subtree in pre-order
+///
+/// Walks with a cursor instead of recursing, as trees can be arbitrarily
+/// deep. Created by [`NodeExt::preorder`].
+pub struct Preorder<'tree> {
+    cursor: TreeCursor<'tree>,
+    state: PreorderState,
+}
+
+/// Where a [`Preorder`] walk goes from the cursor's node
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PreorderState {
+    /// The node has not been returned yet
+    Start,
+    /// Into the children of the node, then on to its next sibling
+    Descend,
+    /// Past the children of the node, to its next sibling
+    SkipChildren,
+    /// Nowhere, as the walk is over
+    Done,
+}
+
+impl Preorder<'_> {
+    /// Skip the descendants of the node last returned
+    pub fn skip_children(&mut self) {
+        if self.state == PreorderState::Descend {
+            self.state = PreorderState::SkipChildren;
+        }
+    }
+}
+
+impl<'tree> Iterator for Preorder<'tree> {
+    type Item = Node<'tree>;
+
+    fn next(&mut self) -> Option<Node<'tree>> {
+        match self.state {
+            PreorderState::Start => {}
+            PreorderState::Descend if self.cursor.goto_first_child() => {}
+            PreorderState::Descend | PreorderState::SkipChildren => {
+                while !self.cursor.goto_next_sibling() {
+                    if !self.cursor.goto_parent() {
+                        self.state = PreorderState::Done;
+                        return None;
+                    }
+                }
+            }
+            PreorderState::Done => return None,
+        }
+        self.state = PreorderState::Descend;
+        Some(self.cursor.node())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::NixParser;
+
+    #[test]
+    fn test_node_ext() {
+        let source = "let a = 1; in a + 2";
+        let mut parser = NixParser::new().unwrap();
+        let result = parser.parse(source).unwrap();
+        let root = result.tree().root_node();
+
+        let let_node = root.field(FieldName::Expression).unwrap();
+        assert_eq!(let_node.kind(), "let_expression");
+        assert_eq!(let_node.text(source).unwrap(), source);
+
+        let body = let_node.field(FieldName::Body).unwrap();
+        assert_eq!(body.text(source).unwrap(), "a + 2");
+        assert_eq!(body.field(FieldName::Left).unwrap().text(source).unwrap(), "a");
+        let children: Vec<&str> = NodeExt::named_children(&body)
+            .iter()
+            .map(|child| child.text(source).unwrap())
+            .collect();
+        assert_eq!(children, ["a", "2"]);
+
+        let err = body.field(FieldName::Condition).unwrap_err();
+        assert!(matches!(err, ParseError::InvalidNode(ref message) if message.contains("'condition'")));
+
+        let sum = NixParser::new().unwrap().parse("a + 2").unwrap().expression().unwrap().unwrap();
+        assert_eq!(body.to_expression(source).unwrap(), sum);
+    }
+
+    #[test]
+    fn test_preorder() {
+        let source = "[ a ] ++ [ 2 ]";
+        let mut parser = NixParser::new().unwrap();
+        let result = parser.parse(source).unwrap();
+        let concat = result.tree().root_node().field(FieldName::Expression).unwrap();
+
+        let texts: Vec<&str> = concat.preorder().map(|node| node.text(source).unwrap()).collect();
+        assert_eq!(texts, ["[ a ] ++ [ 2 ]", "[ a ]", "[", "a", "]", "++", "[ 2 ]", "[", "2", "]"]);
+
+        // Skipping the children of the left list
+        let mut nodes = concat.preorder();
+        let mut texts = Vec::new();
+        while let Some(node) = nodes.next() {
+            if node.kind() == "list" && node.start_byte() == 0 {
+                nodes.skip_children();
+            }
+            texts.push(node.text(source).unwrap());
+        }
+        assert_eq!(texts, ["[ a ] ++ [ 2 ]", "[ a ]", "++", "[ 2 ]", "[", "2", "]"]);
+    }
+}
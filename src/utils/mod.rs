@@ -8,8 +8,10 @@ pub mod conversion;
 pub use self::text::{TextUtils, LineInfo};
 pub use self::position::{Position, Range, SourceLocation};
 pub use self::validation::{Validator, ValidationRule};
-pub use self::conversion::{TreeSitterExt, NodeExt};
+pub use self::conversion::{TreeSitterExt, NodeExt, Preorder};
 pub use self::perf::{Timer, TimingResult, MemoryStats};
+#[cfg(feature = "memory-stats")]
+pub use self::perf::MemoryTracker;
 
 /// Common constants used throughout the parser
 pub mod constants {
@@ -83,7 +85,7 @@ pub mod perf {
     }
     
     /// Result of a timing measurement
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     pub struct TimingResult {
         /// Label identifying what was timed
         pub label: String,
@@ -99,7 +101,7 @@ pub mod perf {
         
         /// Get duration in milliseconds
         pub fn duration_ms(&self) -> u64 {
-            self.duration.as_millis() as u64
+            u64::try_from(self.duration.as_millis()).unwrap_or(u64::MAX)
         }
     }
     
@@ -110,13 +112,18 @@ pub mod perf {
     }
     
     /// Memory usage statistics
-    #[derive(Debug, Clone, PartialEq, Eq)]
+    ///
+    /// With the `memory-stats` feature, these are measured by an allocator
+    /// wrapping the system allocator, which the feature installs as the
+    /// global allocator. Only allocations made through Rust's allocator are
+    /// seen, so the memory Tree-sitter allocates in C is not counted.
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     pub struct MemoryStats {
         /// Peak memory usage in bytes
         pub peak_bytes: usize,
         /// Current memory usage in bytes
         pub current_bytes: usize,
-        /// Number of allocations
+        /// Number of allocations, reallocations included
         pub allocations: usize,
     }
     
@@ -129,6 +136,172 @@ pub mod perf {
                 allocations: 0,
             }
         }
+
+        /// Get the memory usage of the whole process
+        #[cfg(feature = "memory-stats")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "memory-stats")))]
+        pub fn snapshot() -> Self {
+            use std::sync::atomic::Ordering;
+
+            Self {
+                peak_bytes: tracking::PEAK.load(Ordering::Relaxed),
+                current_bytes: tracking::CURRENT.load(Ordering::Relaxed),
+                allocations: tracking::ALLOCATIONS.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Measures the memory allocated by the current thread between
+    /// `start` and `stop`, like [`Timer`] measures time
+    ///
+    /// Memory freed by another thread than the one that allocated it is
+    /// not credited back, so it counts towards `current_bytes`.
+    #[cfg(feature = "memory-stats")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "memory-stats")))]
+    #[derive(Debug)]
+    pub struct MemoryTracker {
+        start: tracking::ThreadUsage,
+        /// Peak of an enclosing measurement, restored when this one stops
+        outer_peak: usize,
+    }
+
+    #[cfg(feature = "memory-stats")]
+    impl MemoryTracker {
+        /// Start measuring
+        pub fn start() -> Self {
+            let start = tracking::ThreadUsage::get();
+            let outer_peak = tracking::ThreadUsage::reset_peak();
+            Self { start, outer_peak }
+        }
+
+        /// Stop measuring and return the memory allocated since `start`
+        ///
+        /// `peak_bytes` and `current_bytes` are relative to the usage at
+        /// `start`.
+        pub fn stop(self) -> MemoryStats {
+            let end = tracking::ThreadUsage::get();
+            tracking::ThreadUsage::restore_peak(self.outer_peak);
+            MemoryStats {
+                peak_bytes: end.peak.saturating_sub(self.start.current),
+                current_bytes: end.current.saturating_sub(self.start.current),
+                allocations: end.allocations - self.start.allocations,
+            }
+        }
+    }
+
+    /// Global allocator counting the memory of the process and of each
+    /// thread
+    #[cfg(feature = "memory-stats")]
+    mod tracking {
+        use std::alloc::{GlobalAlloc, Layout, System};
+        use std::cell::Cell;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        pub(super) static CURRENT: AtomicUsize = AtomicUsize::new(0);
+        pub(super) static PEAK: AtomicUsize = AtomicUsize::new(0);
+        pub(super) static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        thread_local! {
+            // Const-initialized without destructors, so the allocator can
+            // use them without allocating, even while the thread exits
+            static THREAD_CURRENT: Cell<usize> = const { Cell::new(0) };
+            static THREAD_PEAK: Cell<usize> = const { Cell::new(0) };
+            static THREAD_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        #[global_allocator]
+        static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+        /// Memory usage of the current thread
+        #[derive(Debug, Clone, Copy)]
+        pub(super) struct ThreadUsage {
+            pub(super) current: usize,
+            pub(super) peak: usize,
+            pub(super) allocations: usize,
+        }
+
+        impl ThreadUsage {
+            pub(super) fn get() -> Self {
+                Self {
+                    current: THREAD_CURRENT.get(),
+                    peak: THREAD_PEAK.get(),
+                    allocations: THREAD_ALLOCATIONS.get(),
+                }
+            }
+
+            /// Restart the peak from the current usage, returning the old peak
+            pub(super) fn reset_peak() -> usize {
+                THREAD_PEAK.replace(THREAD_CURRENT.get())
+            }
+
+            /// Fold the peak of a finished measurement into an older one
+            pub(super) fn restore_peak(peak: usize) {
+                THREAD_PEAK.set(THREAD_PEAK.get().max(peak));
+            }
+        }
+
+        fn grow(size: usize) {
+            let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+            PEAK.fetch_max(current, Ordering::Relaxed);
+            let _ = THREAD_CURRENT.try_with(|thread_current| {
+                let current = thread_current.get() + size;
+                thread_current.set(current);
+                THREAD_PEAK.set(THREAD_PEAK.get().max(current));
+            });
+        }
+
+        fn shrink(size: usize) {
+            CURRENT.fetch_sub(size, Ordering::Relaxed);
+            let _ = THREAD_CURRENT.try_with(|current| current.set(current.get().saturating_sub(size)));
+        }
+
+        fn count_allocation() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            let _ = THREAD_ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        }
+
+        /// The system allocator, counting what passes through it
+        struct TrackingAllocator;
+
+        // SAFETY: every call is forwarded to the system allocator, and the
+        // counters neither allocate nor touch the allocated memory
+        unsafe impl GlobalAlloc for TrackingAllocator {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                let ptr = unsafe { System.alloc(layout) };
+                if !ptr.is_null() {
+                    count_allocation();
+                    grow(layout.size());
+                }
+                ptr
+            }
+
+            unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+                let ptr = unsafe { System.alloc_zeroed(layout) };
+                if !ptr.is_null() {
+                    count_allocation();
+                    grow(layout.size());
+                }
+                ptr
+            }
+
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                unsafe { System.dealloc(ptr, layout) };
+                shrink(layout.size());
+            }
+
+            unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+                let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+                if !new_ptr.is_null() {
+                    count_allocation();
+                    if new_size > layout.size() {
+                        grow(new_size - layout.size());
+                    } else {
+                        shrink(layout.size() - new_size);
+                    }
+                }
+                new_ptr
+            }
+        }
     }
     
     impl Default for MemoryStats {
@@ -140,14 +313,17 @@ pub mod perf {
 
 /// String manipulation utilities specific to Nix
 pub mod string {
-    /// Check if a string is a valid Nix identifier
+    /// Check if a string is lexically a Nix identifier
+    ///
+    /// This is Nix's `[a-zA-Z_][a-zA-Z0-9_'-]*`, which places no further
+    /// restriction on how an identifier ends. Keywords such as `let` match
+    /// too, although they cannot be used as variables: see [`is_keyword`],
+    /// and [`needs_quoting`] for attribute names.
     pub fn is_valid_identifier(s: &str) -> bool {
-        if s.is_empty() {
-            return false;
-        }
-        
         let mut chars = s.chars();
-        let first = chars.next().unwrap();
+        let Some(first) = chars.next() else {
+            return false;
+        };
         
         // First character must be letter or underscore
         if !first.is_ascii_alphabetic() && first != '_' {
@@ -159,17 +335,22 @@ pub mod string {
     }
     
     /// Escape a string for use in Nix code
+    ///
+    /// `$` is only escaped where it starts `${`, as elsewhere it stands
+    /// for itself. A `$` at the end of `s` is left alone, so a string
+    /// continuing with an interpolation must escape it separately.
     pub fn escape_nix_string(s: &str) -> String {
         let mut result = String::with_capacity(s.len() + 20);
+        let mut chars = s.chars().peekable();
         
-        for c in s.chars() {
+        while let Some(c) = chars.next() {
             match c {
                 '"' => result.push_str("\\\""),
                 '\\' => result.push_str("\\\\"),
                 '\n' => result.push_str("\\n"),
                 '\r' => result.push_str("\\r"),
                 '\t' => result.push_str("\\t"),
-                '$' => result.push_str("\\$"),
+                '$' if chars.peek() == Some(&'{') => result.push_str("\\$"),
                 c => result.push(c),
             }
         }
@@ -178,6 +359,14 @@ pub mod string {
     }
     
     /// Unescape a Nix string literal
+    ///
+    /// Both `\$` and a bare `$` stand for `$`; the caller is responsible
+    /// for splitting off `${` interpolations first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the string contains an unknown escape such as
+    /// `\q`, or ends with a lone `\`.
     pub fn unescape_nix_string(s: &str) -> Result<String, String> {
         let mut result = String::with_capacity(s.len());
         let mut chars = s.chars();
@@ -191,7 +380,7 @@ pub mod string {
                     Some('\\') => result.push('\\'),
                     Some('"') => result.push('"'),
                     Some('$') => result.push('$'),
-                    Some(other) => return Err(format!("Invalid escape sequence: \\{}", other)),
+                    Some(other) => return Err(format!("Invalid escape sequence: \\{other}")),
                     None => return Err("Unterminated escape sequence".to_string()),
                 }
             } else {
@@ -202,9 +391,82 @@ pub mod string {
         Ok(result)
     }
     
+    /// Escape a string for use as the content of a Nix indented string
+    ///
+    /// `''` becomes `'''` and `${` becomes `''${`. A `'` at the end, or
+    /// before `${`, would run into the following `''`, so it is written as
+    /// `''\'`. Indentation and a leading newline are not escaped, as Nix
+    /// strips them: callers choose the layout of the string.
+    pub fn escape_indented_string(s: &str) -> String {
+        let mut result = String::with_capacity(s.len() + 8);
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix("''") {
+                result.push_str("'''");
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                result.push_str("''${");
+                rest = after;
+            } else if rest == "'" || rest.starts_with("'${") {
+                result.push_str("''\\'");
+                rest = &rest[1..];
+            } else {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        result
+    }
+
+    /// Unescape the content of a Nix indented string (`''...''`)
+    ///
+    /// Handles `'''` (a literal `''`), `''$` (a literal `$`) and `''\`
+    /// followed by `n`, `r`, `t` or any other character, which stands for
+    /// itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if `''` is followed by anything but `'`, `$` or
+    /// `\`, or if the string ends in the middle of an escape.
+    pub fn unescape_indented_string(s: &str) -> Result<String, String> {
+        let mut result = String::with_capacity(s.len());
+        let mut rest = s;
+
+        while let Some(index) = rest.find("''") {
+            result.push_str(&rest[..index]);
+            let mut chars = rest[index + 2..].chars();
+            match chars.next() {
+                Some('\'') => result.push_str("''"),
+                Some('$') => result.push('$'),
+                Some('\\') => match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some(other) => result.push(other),
+                    None => return Err("Unterminated escape sequence".to_string()),
+                },
+                Some(other) => return Err(format!("Invalid escape sequence: ''{other}")),
+                None => return Err("Unterminated indented string".to_string()),
+            }
+            rest = chars.as_str();
+        }
+
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Check if a string is a Nix keyword
+    pub fn is_keyword(s: &str) -> bool {
+        crate::utils::constants::NIX_KEYWORDS.contains(&s)
+    }
+    
     /// Check if a string needs to be quoted as a Nix string
+    ///
+    /// Attribute names and variables must be identifiers that are not
+    /// keywords.
     pub fn needs_quoting(s: &str) -> bool {
-        !is_valid_identifier(s) || crate::utils::constants::NIX_KEYWORDS.contains(&s)
+        !is_valid_identifier(s) || is_keyword(s)
     }
 }
 
@@ -217,11 +479,14 @@ pub mod fs {
     pub fn is_nix_file(path: &Path) -> bool {
         path.extension()
             .and_then(|ext| ext.to_str())
-            .map(|ext| NIX_EXTENSIONS.iter().any(|nix_ext| nix_ext == &format!(".{}", ext)))
-            .unwrap_or(false)
+            .is_some_and(|ext| NIX_EXTENSIONS.iter().any(|nix_ext| nix_ext == &format!(".{ext}")))
     }
     
     /// Find all Nix files in a directory recursively
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error of the first directory that cannot be read.
     pub fn find_nix_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
         let mut nix_files = Vec::new();
         find_nix_files_recursive(dir, &mut nix_files)?;
@@ -260,6 +525,26 @@ mod tests {
         assert!(!string::is_valid_identifier(""));
         assert!(!string::is_valid_identifier("foo.bar"));
         assert!(!string::is_valid_identifier("foo@bar"));
+        assert!(!string::is_valid_identifier("-foo"));
+        assert!(!string::is_valid_identifier("'foo"));
+        assert!(string::is_valid_identifier("foo-"));
+        assert!(string::is_valid_identifier("foo''"));
+    }
+    
+    #[test]
+    fn test_keywords() {
+        // Keywords are lexically identifiers, but cannot be used as names
+        for keyword in ["let", "in", "if", "then", "else", "with", "inherit", "rec", "assert", "or"] {
+            assert!(string::is_valid_identifier(keyword), "{}", keyword);
+            assert!(string::is_keyword(keyword), "{}", keyword);
+            assert!(string::needs_quoting(keyword), "{}", keyword);
+        }
+        // Builtin values are ordinary identifiers
+        for name in ["true", "null", "import", "letter", "in'"] {
+            assert!(!string::is_keyword(name), "{}", name);
+            assert!(!string::needs_quoting(name), "{}", name);
+        }
+        assert!(!string::is_keyword("1in"));
     }
     
     #[test]
@@ -268,6 +553,10 @@ mod tests {
         assert_eq!(string::escape_nix_string("hello \"world\""), "hello \\\"world\\\"");
         assert_eq!(string::escape_nix_string("line1\nline2"), "line1\\nline2");
         assert_eq!(string::escape_nix_string("${var}"), "\\${var}");
+        assert_eq!(string::escape_nix_string("$5"), "$5");
+        assert_eq!(string::escape_nix_string("cost: $5"), "cost: $5");
+        assert_eq!(string::escape_nix_string("$$ {} $"), "$$ {} $");
+        assert_eq!(string::escape_nix_string("$${a}"), "$\\${a}");
     }
     
     #[test]
@@ -276,11 +565,47 @@ mod tests {
         assert_eq!(string::unescape_nix_string("hello \\\"world\\\"").unwrap(), "hello \"world\"");
         assert_eq!(string::unescape_nix_string("line1\\nline2").unwrap(), "line1\nline2");
         assert_eq!(string::unescape_nix_string("\\${var}").unwrap(), "${var}");
+        assert_eq!(string::unescape_nix_string("$5").unwrap(), "$5");
+        assert_eq!(string::unescape_nix_string("\\$5").unwrap(), "$5");
+        for s in ["cost: $5", "${a} $${b} $", "\\$\"\n"] {
+            assert_eq!(string::unescape_nix_string(&string::escape_nix_string(s)).unwrap(), s);
+        }
         
         assert!(string::unescape_nix_string("invalid\\x").is_err());
         assert!(string::unescape_nix_string("incomplete\\").is_err());
     }
-    
+
+    #[test]
+    fn test_indented_string_escaping() {
+        assert_eq!(string::escape_indented_string("hello"), "hello");
+        assert_eq!(string::escape_indented_string("a''b"), "a'''b");
+        assert_eq!(string::escape_indented_string("${var} $5 $"), "''${var} $5 $");
+        assert_eq!(string::escape_indented_string("it's"), "it's");
+        assert_eq!(string::escape_indented_string("'a'"), "'a''\\'");
+        assert_eq!(string::escape_indented_string("\\n\"\n"), "\\n\"\n");
+
+        let mut parser = crate::parser::NixParser::new().unwrap();
+        for s in ["a''b", "'''", "x ${y} '", "a'''${b}"] {
+            let escaped = string::escape_indented_string(s);
+            assert_eq!(string::unescape_indented_string(&escaped).unwrap(), s);
+            let source = format!("''{escaped}''");
+            let expr = parser.parse(&source).unwrap().expression().unwrap().unwrap();
+            assert_eq!(expr, crate::ast::Expression::string(s), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_indented_string_unescaping() {
+        assert_eq!(string::unescape_indented_string("hello").unwrap(), "hello");
+        assert_eq!(string::unescape_indented_string("a'''b").unwrap(), "a''b");
+        assert_eq!(string::unescape_indented_string("''${var}").unwrap(), "${var}");
+        assert_eq!(string::unescape_indented_string("''\\t''\\n''\\x").unwrap(), "\t\nx");
+        assert_eq!(string::unescape_indented_string("it's \\n").unwrap(), "it's \\n");
+
+        assert!(string::unescape_indented_string("''x").is_err());
+        assert!(string::unescape_indented_string("''\\").is_err());
+    }
+
     #[test]
     fn test_needs_quoting() {
         assert!(!string::needs_quoting("foo"));
@@ -319,4 +644,28 @@ mod tests {
         assert!(formatted.contains("test timer"));
         assert!(formatted.contains("ms") || formatted.contains("µs"));
     }
+
+    #[cfg(feature = "memory-stats")]
+    #[test]
+    fn test_memory_tracker() {
+        let outer = perf::MemoryTracker::start();
+        let kept = vec![0u8; 1024];
+
+        let inner = perf::MemoryTracker::start();
+        drop(vec![0u8; 1 << 20]);
+        let stats = inner.stop();
+        assert!(stats.peak_bytes >= 1 << 20, "{stats:?}");
+        assert_eq!(stats.current_bytes, 0);
+        assert_eq!(stats.allocations, 1);
+
+        // The inner measurement's peak counts towards the outer one
+        let stats = outer.stop();
+        assert!(stats.peak_bytes >= (1 << 20) + 1024, "{stats:?}");
+        assert!(stats.current_bytes >= 1024, "{stats:?}");
+        drop(kept);
+
+        let process = MemoryStats::snapshot();
+        assert!(process.peak_bytes >= 1 << 20);
+        assert!(process.allocations >= 2);
+    }
 }
\ No newline at end of file
@@ -1,7 +1,9 @@
 //! Position and location utilities
 
 /// A position in source code with line and column information
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Positions are ordered by line, then by column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     /// Line number (1-based)
     pub line: usize,
@@ -10,6 +12,9 @@ pub struct Position {
 }
 
 /// A range representing a span in source code
+///
+/// Like tree-sitter ranges, the end is exclusive: the range covers the
+/// positions from `start` up to, but not including, `end`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Range {
     /// Starting position of the range
@@ -18,5 +23,76 @@ pub struct Range {
     pub end: Position,
 }
 
+impl Range {
+    /// Check if `position` is inside the range
+    ///
+    /// Empty ranges contain no position.
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position < self.end
+    }
+
+    /// Check if the range shares at least one position with `other`
+    ///
+    /// Adjacent ranges, where one ends where the other starts, do not
+    /// overlap, and neither do empty ranges, which contain no position.
+    pub fn overlaps(&self, other: &Range) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+
+    /// Check if the range contains no position, as its end is not after
+    /// its start
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+}
+
 // Re-export from ast module to avoid duplication
-pub use crate::ast::SourceLocation;
\ No newline at end of file
+pub use crate::ast::SourceLocation;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (usize, usize), end: (usize, usize)) -> Range {
+        Range {
+            start: Position { line: start.0, column: start.1 },
+            end: Position { line: end.0, column: end.1 },
+        }
+    }
+
+    #[test]
+    fn test_range_contains() {
+        let range = range((1, 5), (2, 3));
+        assert!(range.contains(Position { line: 1, column: 5 }));
+        assert!(range.contains(Position { line: 1, column: 80 }));
+        assert!(range.contains(Position { line: 2, column: 2 }));
+        assert!(!range.contains(Position { line: 2, column: 3 }));
+        assert!(!range.contains(Position { line: 1, column: 4 }));
+        assert!(!range.contains(Position { line: 3, column: 1 }));
+
+        let empty = Range { start: range.start, end: range.start };
+        assert!(!empty.contains(range.start));
+    }
+
+    #[test]
+    fn test_range_overlaps() {
+        let a = range((1, 1), (2, 5));
+        // Overlapping and nested ranges
+        assert!(a.overlaps(&range((2, 4), (3, 1))));
+        assert!(range((2, 4), (3, 1)).overlaps(&a));
+        assert!(a.overlaps(&range((1, 3), (1, 4))));
+        assert!(a.overlaps(&a));
+        // Adjacent ranges
+        assert!(!a.overlaps(&range((2, 5), (3, 1))));
+        assert!(!range((2, 5), (3, 1)).overlaps(&a));
+        // Disjoint ranges
+        assert!(!a.overlaps(&range((4, 1), (4, 2))));
+        assert!(!range((4, 1), (4, 2)).overlaps(&a));
+        // Empty ranges, even inside another range
+        let empty = range((1, 3), (1, 3));
+        assert!(empty.is_empty() && !a.is_empty());
+        assert!(!a.overlaps(&empty));
+        assert!(!empty.overlaps(&a));
+        assert!(!empty.overlaps(&empty));
+    }
+}
@@ -1,15 +1,142 @@
 //! Text processing utilities
 
 /// Utilities for text processing and manipulation
-/// 
-/// Provides common text operations needed for parsing and analysis,
-/// including line/column tracking, string escaping, and formatting.
+///
+/// Precomputes the start offset of every line of a source, so that
+/// conversions between byte offsets and line/column positions take
+/// logarithmic time. Lines and columns are 1-based, and columns count
+/// bytes like Tree-sitter does. A source ending in a newline has a final,
+/// empty line after it.
 #[derive(Debug, Clone)]
-pub struct TextUtils {}
+pub struct TextUtils<'a> {
+    source: &'a str,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl<'a> TextUtils<'a> {
+    /// Index the lines of `source`
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { source, line_starts }
+    }
+
+    /// Get the number of lines
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Convert a byte offset to a 1-based line and column
+    ///
+    /// Offsets past the end of the source are clamped to the end.
+    pub fn byte_to_line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let index = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        (index + 1, offset - self.line_starts[index] + 1)
+    }
+
+    /// Convert a 1-based line and column to a byte offset
+    ///
+    /// The column may point just past the end of the line's content.
+    /// Returns `None` if the line does not exist or the column is outside
+    /// of it.
+    pub fn line_col_to_byte(&self, line: usize, column: usize) -> Option<usize> {
+        let info = self.line(line)?;
+        let offset = info.start_byte + column.checked_sub(1)?;
+        (offset <= info.end_byte).then_some(offset)
+    }
+
+    /// Get information about a 1-based line
+    pub fn line(&self, number: usize) -> Option<LineInfo<'a>> {
+        let start_byte = *self.line_starts.get(number.checked_sub(1)?)?;
+        Some(self.line_from(number, start_byte))
+    }
+
+    /// Get information about the line containing a byte offset
+    pub fn line_at(&self, offset: usize) -> LineInfo<'a> {
+        let (number, column) = self.byte_to_line_col(offset);
+        self.line_from(number, offset.min(self.source.len()) + 1 - column)
+    }
+
+    /// Build the info of the line starting at `start_byte`
+    fn line_from(&self, number: usize, start_byte: usize) -> LineInfo<'a> {
+        let mut end_byte = self.line_starts.get(number).map_or(self.source.len(), |next| next - 1);
+        if self.source[start_byte..end_byte].ends_with('\r') {
+            end_byte -= 1;
+        }
+        LineInfo {
+            number,
+            start_byte,
+            end_byte,
+            content: &self.source[start_byte..end_byte],
+        }
+    }
+}
 
 /// Information about a line in source text
-/// 
-/// Contains metadata about individual lines including position,
-/// content, and formatting information.
-#[derive(Debug, Clone)]
-pub struct LineInfo {}
\ No newline at end of file
+///
+/// The byte range excludes the line terminator (`\n` or `\r\n`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfo<'a> {
+    /// Line number (1-based)
+    pub number: usize,
+    /// Byte offset of the first character of the line
+    pub start_byte: usize,
+    /// Byte offset just past the line's content
+    pub end_byte: usize,
+    /// Text of the line, without the line terminator
+    pub content: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_to_line_col() {
+        let text = TextUtils::new("let\n  a = 1;\nin a\n");
+        assert_eq!(text.line_count(), 4);
+        assert_eq!(text.byte_to_line_col(0), (1, 1));
+        assert_eq!(text.byte_to_line_col(3), (1, 4));
+        assert_eq!(text.byte_to_line_col(4), (2, 1));
+        assert_eq!(text.byte_to_line_col(6), (2, 3));
+        assert_eq!(text.byte_to_line_col(17), (3, 5));
+        // The empty line after the trailing newline, and past the end
+        assert_eq!(text.byte_to_line_col(18), (4, 1));
+        assert_eq!(text.byte_to_line_col(100), (4, 1));
+
+        let empty = TextUtils::new("");
+        assert_eq!(empty.line_count(), 1);
+        assert_eq!(empty.byte_to_line_col(0), (1, 1));
+    }
+
+    #[test]
+    fn test_line_col_to_byte() {
+        let source = "let\n  a = 1;\nin a\n";
+        let text = TextUtils::new(source);
+        for offset in 0..=source.len() {
+            let (line, column) = text.byte_to_line_col(offset);
+            assert_eq!(text.line_col_to_byte(line, column), Some(offset));
+        }
+        assert_eq!(text.line_col_to_byte(1, 5), None);
+        assert_eq!(text.line_col_to_byte(4, 2), None);
+        assert_eq!(text.line_col_to_byte(5, 1), None);
+        assert_eq!(text.line_col_to_byte(0, 1), None);
+        assert_eq!(text.line_col_to_byte(1, 0), None);
+    }
+
+    #[test]
+    fn test_line_info() {
+        let text = TextUtils::new("a = 1;\r\nb\n");
+        assert_eq!(
+            text.line(1),
+            Some(LineInfo { number: 1, start_byte: 0, end_byte: 6, content: "a = 1;" })
+        );
+        assert_eq!(text.line_at(9).content, "b");
+        assert_eq!(text.line(3), Some(LineInfo { number: 3, start_byte: 10, end_byte: 10, content: "" }));
+        assert_eq!(text.line(4), None);
+        assert_eq!(text.line_at(7).number, 1);
+    }
+}
@@ -1,179 +1,692 @@
-use crate::ast::*;
+//! Traversal of the AST
+//!
+//! [`Visitor`] walks an expression by reference, with a hook per kind of
+//! expression, and [`VisitorMut`] rewrites one in place.
+
+use crate::ast::{
+    AttrKey, Attribute, BinaryOperator, Binding, Expression, Parameter, PathType, SourceLocation, StringPart,
+    UnaryOperator,
+};
+
+/// Controls how a [`Visitor`] continues after visiting an expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisitControl {
+    /// Visit the expression's children, then its siblings
+    #[default]
+    Continue,
+    /// Do not visit the expression's children, but carry on with its siblings
+    SkipChildren,
+    /// Stop the traversal entirely
+    Stop,
+}
+
+impl VisitControl {
+    /// Check if the traversal should stop
+    pub fn is_stop(self) -> bool {
+        self == VisitControl::Stop
+    }
+}
 
 /// Visitor trait for traversing the AST
+///
+/// Traversal can be cut short by returning [`VisitControl::SkipChildren`] or
+/// [`VisitControl::Stop`] from `enter_expression`, which is called before
+/// each expression is visited.
 pub trait Visitor {
-    fn visit_expression(&mut self, expr: &Expression) {
-        match expr {
-            Expression::Integer(n) => self.visit_integer(*n),
-            Expression::Float(f) => self.visit_float(*f),
-            Expression::String(s) => self.visit_string(s),
-            Expression::Path(p) => self.visit_path(p),
-            Expression::Boolean(b) => self.visit_boolean(*b),
-            Expression::Null => self.visit_null(),
-            Expression::Identifier(id) => self.visit_identifier(id),
-            Expression::List(items) => self.visit_list(items),
-            Expression::AttributeSet { recursive, attributes } => {
-                self.visit_attribute_set(*recursive, attributes)
-            }
-            Expression::Function { parameter, body } => {
-                self.visit_function(parameter, body)
-            }
-            Expression::Application { function, argument } => {
-                self.visit_application(function, argument)
-            }
-            Expression::LetIn { bindings, body } => {
-                self.visit_let_in(bindings, body)
-            }
-            Expression::With { scope, body } => {
-                self.visit_with(scope, body)
-            }
-            Expression::If { condition, then_branch, else_branch } => {
-                self.visit_if(condition, then_branch, else_branch)
-            }
-            Expression::Assert { condition, body } => {
-                self.visit_assert(condition, body)
-            }
-            Expression::BinaryOp { op, left, right } => {
-                self.visit_binary_op(*op, left, right)
-            }
-            Expression::UnaryOp { op, operand } => {
-                self.visit_unary_op(*op, operand)
-            }
-            Expression::Select { expr, path, default } => {
-                self.visit_select(expr, path, default.as_deref())
-            }
-            Expression::HasAttr { expr, path } => {
-                self.visit_has_attr(expr, path)
-            }
-            Expression::StringInterpolation { parts } => {
-                self.visit_string_interpolation(parts)
-            }
-            Expression::Import { path } => {
-                self.visit_import(path)
-            }
-            Expression::Inherit { source, attributes } => {
-                self.visit_inherit(source.as_deref(), attributes)
-            }
+    /// Visit an expression, then its children unless `enter_expression`
+    /// skips them
+    fn visit_expression(&mut self, expr: &Expression) -> VisitControl {
+        match self.enter_expression(expr) {
+            VisitControl::Continue => walk_expression(self, expr),
+            VisitControl::SkipChildren => VisitControl::Continue,
+            VisitControl::Stop => VisitControl::Stop,
         }
     }
     
+    /// Called before each expression is visited, to decide how to continue
+    fn enter_expression(&mut self, _expr: &Expression) -> VisitControl {
+        VisitControl::Continue
+    }
+    
+    /// Visit an integer literal
     fn visit_integer(&mut self, _n: i64) {}
+    /// Visit a float literal
     fn visit_float(&mut self, _f: f64) {}
+    /// Visit a string without interpolations
     fn visit_string(&mut self, _s: &str) {}
+    /// Visit a path literal
     fn visit_path(&mut self, _p: &PathType) {}
+    /// Visit an unquoted URI literal, given as written
+    fn visit_uri(&mut self, _uri: &str) {}
+    /// Visit a boolean literal
     fn visit_boolean(&mut self, _b: bool) {}
+    /// Visit `null`
     fn visit_null(&mut self) {}
+    /// Visit a variable reference
     fn visit_identifier(&mut self, _id: &str) {}
     
-    fn visit_list(&mut self, items: &[Expression]) {
+    /// Visit a list and its elements
+    fn visit_list(&mut self, items: &[Expression]) -> VisitControl {
         for item in items {
-            self.visit_expression(item);
+            if self.visit_expression(item).is_stop() {
+                return VisitControl::Stop;
+            }
         }
+        VisitControl::Continue
     }
     
-    fn visit_attribute_set(&mut self, _recursive: bool, attributes: &[Attribute]) {
+    /// Visit an attribute set, its dynamic keys and its values
+    fn visit_attribute_set(&mut self, _recursive: bool, attributes: &[Attribute]) -> VisitControl {
         for attr in attributes {
-            self.visit_expression(&attr.value);
+            if self.visit_attr_key(&attr.key).is_stop() || self.visit_expression(&attr.value).is_stop() {
+                return VisitControl::Stop;
+            }
         }
+        VisitControl::Continue
     }
     
-    fn visit_function(&mut self, _parameter: &Parameter, body: &Expression) {
-        self.visit_expression(body);
+    /// Visit the key of an attribute, and its expression if it is dynamic
+    fn visit_attr_key(&mut self, key: &AttrKey) -> VisitControl {
+        match key {
+            AttrKey::Dynamic(expr) => self.visit_expression(expr),
+            AttrKey::Static(_) => VisitControl::Continue,
+        }
+    }
+    
+    /// Visit a function and its body
+    ///
+    /// Default values of the parameter's pattern are not visited.
+    fn visit_function(&mut self, _parameter: &Parameter, body: &Expression) -> VisitControl {
+        self.visit_expression(body)
     }
     
-    fn visit_application(&mut self, function: &Expression, argument: &Expression) {
-        self.visit_expression(function);
-        self.visit_expression(argument);
+    /// Visit a function call, its function and its argument
+    fn visit_application(&mut self, function: &Expression, argument: &Expression) -> VisitControl {
+        if self.visit_expression(function).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.visit_expression(argument)
     }
     
-    fn visit_let_in(&mut self, bindings: &[Binding], body: &Expression) {
+    /// Visit a `let`, the values of its bindings and its body
+    fn visit_let_in(&mut self, bindings: &[Binding], body: &Expression) -> VisitControl {
         for binding in bindings {
-            self.visit_expression(&binding.value);
+            if self.visit_expression(&binding.value).is_stop() {
+                return VisitControl::Stop;
+            }
         }
-        self.visit_expression(body);
+        self.visit_expression(body)
     }
     
-    fn visit_with(&mut self, scope: &Expression, body: &Expression) {
-        self.visit_expression(scope);
-        self.visit_expression(body);
+    /// Visit a `with`, its scope and its body
+    fn visit_with(&mut self, scope: &Expression, body: &Expression) -> VisitControl {
+        if self.visit_expression(scope).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.visit_expression(body)
     }
     
+    /// Visit an `if`, its condition and both branches
     fn visit_if(
         &mut self,
         condition: &Expression,
         then_branch: &Expression,
         else_branch: &Expression,
-    ) {
-        self.visit_expression(condition);
-        self.visit_expression(then_branch);
-        self.visit_expression(else_branch);
+    ) -> VisitControl {
+        if self.visit_expression(condition).is_stop() || self.visit_expression(then_branch).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.visit_expression(else_branch)
     }
     
-    fn visit_assert(&mut self, condition: &Expression, body: &Expression) {
-        self.visit_expression(condition);
-        self.visit_expression(body);
+    /// Visit an `assert`, its condition and its body
+    fn visit_assert(&mut self, condition: &Expression, body: &Expression) -> VisitControl {
+        if self.visit_expression(condition).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.visit_expression(body)
     }
     
+    /// Visit a binary operation and its operands
     fn visit_binary_op(
         &mut self,
         _op: BinaryOperator,
         left: &Expression,
         right: &Expression,
-    ) {
-        self.visit_expression(left);
-        self.visit_expression(right);
+    ) -> VisitControl {
+        if self.visit_expression(left).is_stop() {
+            return VisitControl::Stop;
+        }
+        self.visit_expression(right)
     }
     
-    fn visit_unary_op(&mut self, _op: UnaryOperator, operand: &Expression) {
-        self.visit_expression(operand);
+    /// Visit a unary operation and its operand
+    fn visit_unary_op(&mut self, _op: UnaryOperator, operand: &Expression) -> VisitControl {
+        self.visit_expression(operand)
     }
     
+    /// Visit an attribute selection, its base, its dynamic keys and its default
     fn visit_select(
         &mut self,
         expr: &Expression,
-        _path: &[String],
+        path: &[AttrKey],
         default: Option<&Expression>,
-    ) {
-        self.visit_expression(expr);
-        if let Some(def) = default {
-            self.visit_expression(def);
+    ) -> VisitControl {
+        if self.visit_expression(expr).is_stop() {
+            return VisitControl::Stop;
+        }
+        for key in path {
+            if self.visit_attr_key(key).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        match default {
+            Some(def) => self.visit_expression(def),
+            None => VisitControl::Continue,
         }
     }
     
-    fn visit_has_attr(&mut self, expr: &Expression, _path: &[String]) {
-        self.visit_expression(expr);
+    /// Visit an attribute test, the expression tested and its dynamic keys
+    fn visit_has_attr(&mut self, expr: &Expression, path: &[AttrKey]) -> VisitControl {
+        if self.visit_expression(expr).is_stop() {
+            return VisitControl::Stop;
+        }
+        for key in path {
+            if self.visit_attr_key(key).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        VisitControl::Continue
     }
     
-    fn visit_string_interpolation(&mut self, parts: &[StringPart]) {
+    /// Visit an interpolated string and its interpolations
+    fn visit_string_interpolation(&mut self, parts: &[StringPart]) -> VisitControl {
         for part in parts {
             match part {
                 StringPart::Literal(_) => {
                     // Nothing to visit for literals
                 }
                 StringPart::Interpolation(expr) => {
-                    self.visit_expression(expr);
+                    if self.visit_expression(expr).is_stop() {
+                        return VisitControl::Stop;
+                    }
                 }
             }
         }
+        VisitControl::Continue
+    }
+    
+    /// Visit an `import` and its path
+    fn visit_import(&mut self, path: &Expression) -> VisitControl {
+        self.visit_expression(path)
+    }
+    
+    /// Visit an `inherit` and its source, if any
+    fn visit_inherit(&mut self, source: Option<&Expression>, _attributes: &[String]) -> VisitControl {
+        match source {
+            Some(source_expr) => self.visit_expression(source_expr),
+            None => VisitControl::Continue,
+        }
+    }
+    
+    /// Visit a location wrapper and the expression it wraps
+    fn visit_located(&mut self, _location: &SourceLocation, expr: &Expression) -> VisitControl {
+        self.visit_expression(expr)
+    }
+}
+
+/// Default traversal for [`Visitor::visit_expression`]
+///
+/// Dispatches to the visitor's per-variant hook for `expr`, returning
+/// [`VisitControl::Stop`] if the traversal was stopped beneath it.
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) -> VisitControl {
+    match expr {
+        Expression::Integer(n) => {
+            visitor.visit_integer(*n);
+            VisitControl::Continue
+        }
+        Expression::Float(f) => {
+            visitor.visit_float(*f);
+            VisitControl::Continue
+        }
+        Expression::String(s) => {
+            visitor.visit_string(s);
+            VisitControl::Continue
+        }
+        Expression::Path(p) => {
+            visitor.visit_path(p);
+            VisitControl::Continue
+        }
+        Expression::Uri(uri) => {
+            visitor.visit_uri(uri);
+            VisitControl::Continue
+        }
+        Expression::Boolean(b) => {
+            visitor.visit_boolean(*b);
+            VisitControl::Continue
+        }
+        Expression::Null => {
+            visitor.visit_null();
+            VisitControl::Continue
+        }
+        Expression::Identifier(id) => {
+            visitor.visit_identifier(id);
+            VisitControl::Continue
+        }
+        Expression::List(items) => visitor.visit_list(items),
+        Expression::AttributeSet { recursive, attributes } => {
+            visitor.visit_attribute_set(*recursive, attributes)
+        }
+        Expression::Function { parameter, body } => {
+            visitor.visit_function(parameter, body)
+        }
+        Expression::Application { function, argument } => {
+            visitor.visit_application(function, argument)
+        }
+        Expression::LetIn { bindings, body } => {
+            visitor.visit_let_in(bindings, body)
+        }
+        Expression::With { scope, body } => {
+            visitor.visit_with(scope, body)
+        }
+        Expression::If { condition, then_branch, else_branch } => {
+            visitor.visit_if(condition, then_branch, else_branch)
+        }
+        Expression::Assert { condition, body } => {
+            visitor.visit_assert(condition, body)
+        }
+        Expression::BinaryOp { op, left, right } => {
+            visitor.visit_binary_op(*op, left, right)
+        }
+        Expression::UnaryOp { op, operand } => {
+            visitor.visit_unary_op(*op, operand)
+        }
+        Expression::Select { expr, path, default } => {
+            visitor.visit_select(expr, path, default.as_deref())
+        }
+        Expression::HasAttr { expr, path } => {
+            visitor.visit_has_attr(expr, path)
+        }
+        Expression::StringInterpolation { parts } => {
+            visitor.visit_string_interpolation(parts)
+        }
+        Expression::Import { path } => {
+            visitor.visit_import(path)
+        }
+        Expression::Inherit { source, attributes } => {
+            visitor.visit_inherit(source.as_deref(), attributes)
+        }
+        Expression::Located { location, expr } => {
+            visitor.visit_located(location, expr)
+        }
+    }
+}
+
+/// Location of the expression a [`Visitor`] is entering
+///
+/// `enter_expression` sees a location wrapper before the expression it
+/// wraps. Passing every entered expression to [`LocationTracker::enter`]
+/// carries the wrapper's location over to the wrapped expression.
+#[derive(Debug, Clone, Default)]
+pub struct LocationTracker {
+    pending: Option<SourceLocation>,
+}
+
+impl LocationTracker {
+    /// Record an entered expression
+    ///
+    /// Returns `None` for a location wrapper, which visitors usually skip,
+    /// and otherwise the location of the expression, if it was wrapped.
+    pub fn enter(&mut self, expr: &Expression) -> Option<Option<SourceLocation>> {
+        if let Expression::Located { location, .. } = expr {
+            self.pending = Some(*location);
+            return None;
+        }
+        Some(self.pending.take())
+    }
+}
+
+/// Visitor trait for rewriting the AST in place
+///
+/// Mirrors [`Visitor`], but every hook receives mutable references. To
+/// replace a whole node, override `visit_expression_mut` and call
+/// [`walk_expression_mut`] for the default traversal.
+pub trait VisitorMut {
+    /// Visit any expression; the default dispatches to the hook for its variant
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+    }
+    
+    /// Visit an integer literal
+    fn visit_integer_mut(&mut self, _n: &mut i64) {}
+    /// Visit a float literal
+    fn visit_float_mut(&mut self, _f: &mut f64) {}
+    /// Visit a string literal without interpolations
+    fn visit_string_mut(&mut self, _s: &mut String) {}
+    /// Visit a path literal
+    fn visit_path_mut(&mut self, _p: &mut PathType) {}
+    /// Visit an unquoted URI literal
+    fn visit_uri_mut(&mut self, _uri: &mut String) {}
+    /// Visit a boolean literal
+    fn visit_boolean_mut(&mut self, _b: &mut bool) {}
+    /// Visit `null`
+    fn visit_null_mut(&mut self) {}
+    /// Visit a variable reference
+    fn visit_identifier_mut(&mut self, _id: &mut String) {}
+    
+    /// Visit a list, then its items
+    fn visit_list_mut(&mut self, items: &mut Vec<Expression>) {
+        for item in items {
+            self.visit_expression_mut(item);
+        }
+    }
+    
+    /// Visit an attribute set, then the key and value of each attribute
+    fn visit_attribute_set_mut(&mut self, _recursive: &mut bool, attributes: &mut Vec<Attribute>) {
+        for attr in attributes {
+            self.visit_attr_key_mut(&mut attr.key);
+            self.visit_expression_mut(&mut attr.value);
+        }
+    }
+    
+    /// Visit an attribute key, then the expression of a dynamic key
+    fn visit_attr_key_mut(&mut self, key: &mut AttrKey) {
+        if let AttrKey::Dynamic(expr) = key {
+            self.visit_expression_mut(expr);
+        }
+    }
+    
+    /// Visit a function, then its body
+    fn visit_function_mut(&mut self, _parameter: &mut Parameter, body: &mut Expression) {
+        self.visit_expression_mut(body);
+    }
+    
+    /// Visit a function application, then the function and its argument
+    fn visit_application_mut(&mut self, function: &mut Expression, argument: &mut Expression) {
+        self.visit_expression_mut(function);
+        self.visit_expression_mut(argument);
+    }
+    
+    /// Visit a `let` expression, then the value of each binding and the body
+    fn visit_let_in_mut(&mut self, bindings: &mut Vec<Binding>, body: &mut Expression) {
+        for binding in bindings {
+            self.visit_expression_mut(&mut binding.value);
+        }
+        self.visit_expression_mut(body);
+    }
+    
+    /// Visit a `with` expression, then its scope and body
+    fn visit_with_mut(&mut self, scope: &mut Expression, body: &mut Expression) {
+        self.visit_expression_mut(scope);
+        self.visit_expression_mut(body);
+    }
+    
+    /// Visit an `if` expression, then its condition and branches
+    fn visit_if_mut(
+        &mut self,
+        condition: &mut Expression,
+        then_branch: &mut Expression,
+        else_branch: &mut Expression,
+    ) {
+        self.visit_expression_mut(condition);
+        self.visit_expression_mut(then_branch);
+        self.visit_expression_mut(else_branch);
+    }
+    
+    /// Visit an `assert` expression, then its condition and body
+    fn visit_assert_mut(&mut self, condition: &mut Expression, body: &mut Expression) {
+        self.visit_expression_mut(condition);
+        self.visit_expression_mut(body);
+    }
+    
+    /// Visit a binary operation, then its operands
+    fn visit_binary_op_mut(
+        &mut self,
+        _op: &mut BinaryOperator,
+        left: &mut Expression,
+        right: &mut Expression,
+    ) {
+        self.visit_expression_mut(left);
+        self.visit_expression_mut(right);
+    }
+    
+    /// Visit a unary operation, then its operand
+    fn visit_unary_op_mut(&mut self, _op: &mut UnaryOperator, operand: &mut Expression) {
+        self.visit_expression_mut(operand);
+    }
+    
+    /// Visit an attribute selection, then the selected expression, dynamic
+    /// keys and default
+    fn visit_select_mut(
+        &mut self,
+        expr: &mut Expression,
+        path: &mut Vec<AttrKey>,
+        default: Option<&mut Expression>,
+    ) {
+        self.visit_expression_mut(expr);
+        for key in path {
+            self.visit_attr_key_mut(key);
+        }
+        if let Some(def) = default {
+            self.visit_expression_mut(def);
+        }
+    }
+    
+    /// Visit a `?` test, then the tested expression and dynamic keys
+    fn visit_has_attr_mut(&mut self, expr: &mut Expression, path: &mut Vec<AttrKey>) {
+        self.visit_expression_mut(expr);
+        for key in path {
+            self.visit_attr_key_mut(key);
+        }
+    }
+    
+    /// Visit an interpolated string, then its interpolated expressions
+    fn visit_string_interpolation_mut(&mut self, parts: &mut Vec<StringPart>) {
+        for part in parts {
+            if let StringPart::Interpolation(expr) = part {
+                self.visit_expression_mut(expr);
+            }
+        }
     }
     
-    fn visit_import(&mut self, path: &Expression) {
-        self.visit_expression(path);
+    /// Visit an import, then the imported path
+    fn visit_import_mut(&mut self, path: &mut Expression) {
+        self.visit_expression_mut(path);
     }
     
-    fn visit_inherit(&mut self, source: Option<&Expression>, _attributes: &[String]) {
+    /// Visit an inherited binding, then the expression it inherits from
+    fn visit_inherit_mut(&mut self, source: Option<&mut Expression>, _attributes: &mut Vec<String>) {
         if let Some(source_expr) = source {
-            self.visit_expression(source_expr);
+            self.visit_expression_mut(source_expr);
+        }
+    }
+    
+    /// Visit a location wrapper, then the expression it wraps
+    fn visit_located_mut(&mut self, _location: &mut SourceLocation, expr: &mut Expression) {
+        self.visit_expression_mut(expr);
+    }
+}
+
+/// Default traversal for [`VisitorMut::visit_expression_mut`]
+///
+/// Dispatches to the visitor's per-variant hook for `expr`.
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Integer(n) => visitor.visit_integer_mut(n),
+        Expression::Float(f) => visitor.visit_float_mut(f),
+        Expression::String(s) => visitor.visit_string_mut(s),
+        Expression::Path(p) => visitor.visit_path_mut(p),
+        Expression::Uri(uri) => visitor.visit_uri_mut(uri),
+        Expression::Boolean(b) => visitor.visit_boolean_mut(b),
+        Expression::Null => visitor.visit_null_mut(),
+        Expression::Identifier(id) => visitor.visit_identifier_mut(id),
+        Expression::List(items) => visitor.visit_list_mut(items),
+        Expression::AttributeSet { recursive, attributes } => {
+            visitor.visit_attribute_set_mut(recursive, attributes);
+        }
+        Expression::Function { parameter, body } => {
+            visitor.visit_function_mut(parameter, body);
+        }
+        Expression::Application { function, argument } => {
+            visitor.visit_application_mut(function, argument);
+        }
+        Expression::LetIn { bindings, body } => {
+            visitor.visit_let_in_mut(bindings, body);
+        }
+        Expression::With { scope, body } => {
+            visitor.visit_with_mut(scope, body);
+        }
+        Expression::If { condition, then_branch, else_branch } => {
+            visitor.visit_if_mut(condition, then_branch, else_branch);
+        }
+        Expression::Assert { condition, body } => {
+            visitor.visit_assert_mut(condition, body);
+        }
+        Expression::BinaryOp { op, left, right } => {
+            visitor.visit_binary_op_mut(op, left, right);
+        }
+        Expression::UnaryOp { op, operand } => {
+            visitor.visit_unary_op_mut(op, operand);
+        }
+        Expression::Select { expr, path, default } => {
+            visitor.visit_select_mut(expr, path, default.as_deref_mut());
+        }
+        Expression::HasAttr { expr, path } => {
+            visitor.visit_has_attr_mut(expr, path);
+        }
+        Expression::StringInterpolation { parts } => {
+            visitor.visit_string_interpolation_mut(parts);
+        }
+        Expression::Import { path } => {
+            visitor.visit_import_mut(path);
+        }
+        Expression::Inherit { source, attributes } => {
+            visitor.visit_inherit_mut(source.as_deref_mut(), attributes);
+        }
+        Expression::Located { location, expr } => {
+            visitor.visit_located_mut(location, expr);
+        }
+    }
+}
+
+/// Ancestors of the expression a [`PathVisitor`] is visiting
+///
+/// A location wrapper and the expression it wraps count as one node, so the
+/// path holds the wrappers where there are any. Use
+/// [`Expression::unlocated`] to match on the ancestors.
+#[derive(Debug, Clone, Default)]
+pub struct AncestorPath<'ast> {
+    ancestors: Vec<&'ast Expression>,
+}
+
+impl<'ast> AncestorPath<'ast> {
+    /// Get the ancestors, from the root down to the parent of the
+    /// expression being visited
+    pub fn current_path(&self) -> &[&'ast Expression] {
+        &self.ancestors
+    }
+
+    /// Get the parent of the expression being visited, unless it is the
+    /// root
+    pub fn parent(&self) -> Option<&'ast Expression> {
+        self.ancestors.last().copied()
+    }
+
+    /// Get the number of ancestors
+    pub fn depth(&self) -> usize {
+        self.ancestors.len()
+    }
+}
+
+/// Visitor that is told the ancestors of each expression it visits
+///
+/// Unlike [`Visitor`], this borrows the tree for the whole traversal, which
+/// is what lets the ancestors be kept. Every expression returned by
+/// [`Expression::subexpressions`] is visited, in source order, by
+/// [`walk_with_path`].
+pub trait PathVisitor<'ast> {
+    /// Visit an expression before its children, controlling whether they
+    /// are visited like [`Visitor::enter_expression`] does
+    fn enter_expression(&mut self, _expr: &'ast Expression, _path: &AncestorPath<'ast>) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Visit an expression after its children
+    fn exit_expression(&mut self, _expr: &'ast Expression, _path: &AncestorPath<'ast>) {}
+}
+
+/// Traverse `root` depth-first, keeping track of the ancestors of each
+/// expression
+///
+/// Returns [`VisitControl::Stop`] if the visitor stopped the traversal.
+pub fn walk_with_path<'ast, V: PathVisitor<'ast> + ?Sized>(visitor: &mut V, root: &'ast Expression) -> VisitControl {
+    fn walk<'ast, V: PathVisitor<'ast> + ?Sized>(
+        visitor: &mut V,
+        expr: &'ast Expression,
+        path: &mut AncestorPath<'ast>,
+    ) -> VisitControl {
+        match visitor.enter_expression(expr, path) {
+            VisitControl::Continue => {}
+            VisitControl::SkipChildren => return VisitControl::Continue,
+            VisitControl::Stop => return VisitControl::Stop,
+        }
+        path.ancestors.push(expr);
+        for child in expr.unlocated().subexpressions() {
+            if walk(visitor, child, path).is_stop() {
+                return VisitControl::Stop;
+            }
+        }
+        path.ancestors.pop();
+        visitor.exit_expression(expr, path);
+        VisitControl::Continue
+    }
+
+    walk(visitor, root, &mut AncestorPath::default())
+}
+
+/// Example mutable visitor that folds constant expressions in place
+///
+/// Operands are folded bottom-up, so `(1 + 2) * 3` becomes `9`. Each node
+/// is folded like `Optimizer` does, so operations that would overflow or
+/// divide by zero are left untouched.
+#[derive(Debug, Default)]
+pub struct ConstantFolderMut {
+    /// Number of expressions folded so far
+    pub folded: usize,
+}
+
+impl ConstantFolderMut {
+    /// Create a folder that has not folded anything yet
+    pub fn new() -> Self {
+        Self { folded: 0 }
+    }
+}
+
+impl VisitorMut for ConstantFolderMut {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+
+        if let Some(folded) = crate::transform::optimize::ConstantFolder::fold(expr) {
+            *expr = folded;
+            self.folded += 1;
         }
     }
 }
 
 /// Example visitor that collects all identifiers
 pub struct IdentifierCollector {
+    /// Names of the variables referenced, in visiting order
     pub identifiers: Vec<String>,
 }
 
+impl Default for IdentifierCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IdentifierCollector {
+    /// Create a collector without identifiers
     pub fn new() -> Self {
         Self {
             identifiers: Vec::new(),
@@ -214,4 +727,206 @@ mod tests {
         collector.visit_expression(&expr);
         assert_eq!(collector.identifiers, vec!["x", "y"]);
     }
+    
+    #[test]
+    fn test_visitor_walks_dynamic_keys() {
+        let mut collector = IdentifierCollector::new();
+        
+        let expr = Expression::AttributeSet {
+            recursive: false,
+            attributes: vec![Attribute {
+                key: AttrKey::Dynamic(Box::new(Expression::Identifier("k".to_string()))),
+                value: Expression::Identifier("v".to_string()),
+                location: None,
+            }],
+        };
+        
+        collector.visit_expression(&expr);
+        assert_eq!(collector.identifiers, vec!["k", "v"]);
+    }
+
+    /// Finds the first identifier, counting every expression it enters
+    struct FirstIdentifier {
+        found: Option<String>,
+        entered: usize,
+    }
+
+    impl Visitor for FirstIdentifier {
+        fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+            self.entered += 1;
+            match expr {
+                Expression::Identifier(id) => {
+                    self.found = Some(id.clone());
+                    VisitControl::Stop
+                }
+                _ => VisitControl::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_stops_early() {
+        let ids = (0..1000).map(|i| Expression::Identifier(format!("x{i}")));
+        let expr = Expression::List(
+            std::iter::once(Expression::Integer(0)).chain(ids).collect()
+        );
+        
+        let mut visitor = FirstIdentifier { found: None, entered: 0 };
+        assert_eq!(visitor.visit_expression(&expr), VisitControl::Stop);
+        assert_eq!(visitor.found.as_deref(), Some("x0"));
+        // The list, the integer and the first identifier
+        assert_eq!(visitor.entered, 3);
+    }
+    
+    #[test]
+    fn test_visitor_skips_children() {
+        struct SkipFunctions(IdentifierCollector);
+        
+        impl Visitor for SkipFunctions {
+            fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+                match expr {
+                    Expression::Function { .. } => VisitControl::SkipChildren,
+                    _ => VisitControl::Continue,
+                }
+            }
+            
+            fn visit_identifier(&mut self, id: &str) {
+                self.0.visit_identifier(id);
+            }
+        }
+        
+        // (x: y) z
+        let expr = Expression::Application {
+            function: Box::new(Expression::Function {
+                parameter: Parameter::Identifier("x".to_string()),
+                body: Box::new(Expression::Identifier("y".to_string())),
+            }),
+            argument: Box::new(Expression::Identifier("z".to_string())),
+        };
+        
+        let mut visitor = SkipFunctions(IdentifierCollector::new());
+        assert_eq!(visitor.visit_expression(&expr), VisitControl::Continue);
+        assert_eq!(visitor.0.identifiers, vec!["z"]);
+    }
+
+    #[test]
+    fn test_walk_with_path() {
+        /// Records the ancestors of `y`, and whether it is the body of a `with`
+        #[derive(Default)]
+        struct PathToY<'ast> {
+            path: Vec<&'ast Expression>,
+            in_with_body: bool,
+        }
+
+        impl<'ast> PathVisitor<'ast> for PathToY<'ast> {
+            fn enter_expression(&mut self, expr: &'ast Expression, path: &AncestorPath<'ast>) -> VisitControl {
+                if !matches!(expr.unlocated(), Expression::Identifier(id) if id == "y") {
+                    return VisitControl::Continue;
+                }
+                self.path = path.current_path().to_vec();
+                self.in_with_body = matches!(
+                    path.parent().map(Expression::unlocated),
+                    Some(Expression::With { body, .. }) if std::ptr::eq(body.as_ref(), expr)
+                );
+                VisitControl::Stop
+            }
+        }
+
+        let source = "let a = { b = [ (x: with s; y) ]; }; in a";
+        let expr = crate::parser::NixParser::new().unwrap().parse(source).unwrap().expression().unwrap().unwrap();
+        let mut visitor = PathToY::default();
+        assert_eq!(walk_with_path(&mut visitor, &expr), VisitControl::Stop);
+
+        let path: Vec<&Expression> = visitor.path.iter().map(|expr| expr.unlocated()).collect();
+        assert!(matches!(
+            path[..],
+            [
+                Expression::LetIn { .. },
+                Expression::AttributeSet { .. },
+                Expression::List(_),
+                Expression::Function { .. },
+                Expression::With { .. },
+            ]
+        ), "{path:?}");
+        assert!(visitor.in_with_body);
+    }
+
+    #[test]
+    fn test_location_tracker() {
+        /// Records the source text of every identifier
+        struct Identifiers<'s> {
+            source: &'s str,
+            locations: LocationTracker,
+            found: Vec<&'s str>,
+        }
+
+        impl Visitor for Identifiers<'_> {
+            fn enter_expression(&mut self, expr: &Expression) -> VisitControl {
+                let Some(location) = self.locations.enter(expr) else {
+                    return VisitControl::Continue;
+                };
+                if let (Expression::Identifier(_), Some(location)) = (expr, location) {
+                    self.found.push(&self.source[location.start_byte..location.end_byte]);
+                }
+                VisitControl::Continue
+            }
+        }
+
+        let source = "f (a + b) c";
+        let expr = crate::parser::NixParser::new().unwrap().parse(source).unwrap().expression().unwrap().unwrap();
+        let mut visitor = Identifiers { source, locations: LocationTracker::default(), found: Vec::new() };
+        visitor.visit_expression(&expr);
+        assert_eq!(visitor.found, ["f", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_constant_folder_mut() {
+        let mut folder = ConstantFolderMut::new();
+        
+        let mut expr = Expression::BinaryOp {
+            op: BinaryOperator::Add,
+            left: Box::new(Expression::Integer(1)),
+            right: Box::new(Expression::Integer(2)),
+        };
+        
+        folder.visit_expression_mut(&mut expr);
+        assert_eq!(expr, Expression::Integer(3));
+        assert_eq!(folder.folded, 1);
+    }
+    
+    #[test]
+    fn test_constant_folder_mut_nested() {
+        let mut folder = ConstantFolderMut::new();
+        
+        // [ ((1 + 2) * 3) x (1 / 0) ]
+        let mut expr = Expression::List(vec![
+            Expression::BinaryOp {
+                op: BinaryOperator::Multiply,
+                left: Box::new(Expression::BinaryOp {
+                    op: BinaryOperator::Add,
+                    left: Box::new(Expression::Integer(1)),
+                    right: Box::new(Expression::Integer(2)),
+                }),
+                right: Box::new(Expression::Integer(3)),
+            },
+            Expression::Identifier("x".to_string()),
+            Expression::BinaryOp {
+                op: BinaryOperator::Divide,
+                left: Box::new(Expression::Integer(1)),
+                right: Box::new(Expression::Integer(0)),
+            },
+        ]);
+        let division = match &expr {
+            Expression::List(items) => items[2].clone(),
+            _ => unreachable!(),
+        };
+        
+        folder.visit_expression_mut(&mut expr);
+        assert_eq!(expr, Expression::List(vec![
+            Expression::Integer(9),
+            Expression::Identifier("x".to_string()),
+            division,
+        ]));
+        assert_eq!(folder.folded, 2);
+    }
 }
\ No newline at end of file
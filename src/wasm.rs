@@ -0,0 +1,54 @@
+//! WebAssembly bindings for running the parser in the browser
+//!
+//! Build with `wasm-pack build --features wasm` and call `parse` from
+//! JavaScript.
+
+use wasm_bindgen::prelude::*;
+
+use crate::parser::NixParser;
+
+/// Parse Nix source code into a JavaScript object
+///
+/// The object follows the schema of `ParseResultJson`: `expression` holds
+/// the AST with source locations, and `diagnostics` the syntax errors and
+/// warnings, so sources with syntax errors still produce a result.
+///
+/// # Errors
+///
+/// Throws a JavaScript `Error` carrying the `ParseError` message if the
+/// parser cannot be created or no AST can be built from the source.
+#[wasm_bindgen]
+pub fn parse(source: &str) -> Result<JsValue, JsError> {
+    console_error_panic_hook::set_once();
+
+    let json = NixParser::new()?.parse(source)?.to_json()?;
+    js_sys::JSON::parse(&json).map_err(|_| JsError::new("the parse result is not valid JSON"))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    /// Convert a JavaScript value back to JSON
+    fn to_json(value: &JsValue) -> serde_json::Value {
+        let text: String = js_sys::JSON::stringify(value).unwrap().into();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse() {
+        let result = to_json(&parse("{ x = 1; }").unwrap());
+        assert_eq!(result["version"], 1);
+        assert_eq!(result["diagnostics"], serde_json::json!([]));
+
+        let root = &result["expression"]["Located"];
+        assert_eq!(root["location"]["end_byte"], 10);
+        let attribute = &root["expr"]["AttributeSet"]["attributes"][0];
+        assert_eq!(attribute["key"]["Static"], serde_json::json!(["x"]));
+        assert_eq!(attribute["value"]["Located"]["expr"]["Integer"], 1);
+
+        let result = to_json(&parse("{ x = ; }").unwrap());
+        assert_eq!(result["diagnostics"][0]["severity"], "error");
+    }
+}
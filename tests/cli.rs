@@ -0,0 +1,116 @@
+//! Integration tests for the `nix-parser` binary
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cli").join(name)
+}
+
+fn nix_parser(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_nix-parser")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_parse_formats() {
+    let valid = fixture("valid.nix");
+    let valid = valid.to_str().unwrap();
+
+    let output = nix_parser(&["parse", valid, "--format", "json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["version"], 1);
+    assert!(json["file_path"].as_str().unwrap().ends_with("valid.nix"));
+    assert!(json["expression"].is_object());
+
+    let output = nix_parser(&["parse", valid, "--format", "sexpr"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("(lambda (pattern (pkgs (apply (import (path search"), "{}", stdout(&output));
+
+    let output = nix_parser(&["parse", valid]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Function {"));
+
+    let output = nix_parser(&["parse", valid, "--format", "debug"]);
+    assert_eq!(stdout(&output).lines().count(), 1);
+}
+
+#[test]
+fn test_parse_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_nix-parser"))
+        .args(["parse", "-", "--format", "sexpr"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"[ 1 ]").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "(list (integer 1))");
+}
+
+#[test]
+fn test_check_exit_code() {
+    let output = nix_parser(&["check", fixture("valid.nix").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let invalid = fixture("invalid.nix");
+    let output = nix_parser(&["check", invalid.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let report = stdout(&output);
+    assert!(report.starts_with("error: "), "{}", report);
+    assert!(report.contains(&format!(" --> {}:3:", invalid.display())), "{}", report);
+    assert!(report.contains("3 |   y = ;\n"), "{}", report);
+
+    let output = nix_parser(&["check", invalid.to_str().unwrap(), "--format", "short"]);
+    let report = stdout(&output);
+    assert!(report.starts_with(&format!("{}:3:", invalid.display())), "{}", report);
+    assert!(report.contains(": error: "));
+
+    // Directories are searched for Nix files
+    let output = nix_parser(&["check", fixture("").to_str().unwrap(), "--format", "json"]);
+    assert_eq!(output.status.code(), Some(1));
+    let reports: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0]["file"].as_str().unwrap().ends_with("invalid.nix"));
+    assert!(!reports[0]["diagnostics"].as_array().unwrap().is_empty());
+    assert!(reports[1]["diagnostics"].as_array().unwrap().is_empty());
+
+    let output = nix_parser(&["check", fixture("missing.nix").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.nix"));
+}
+
+#[test]
+fn test_fmt() {
+    let output = nix_parser(&["fmt", fixture("valid.nix").to_str().unwrap()]);
+    assert!(output.status.success());
+    let formatted = stdout(&output);
+    assert!(formatted.contains("name = \"hello-${version}\";"), "{}", formatted);
+
+    let output = nix_parser(&["fmt", fixture("invalid.nix").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_stats() {
+    let output = nix_parser(&["stats", fixture("valid.nix").to_str().unwrap(), "--format", "json"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert!(json["statistics"]["node_count"].as_u64().unwrap() > 10);
+    assert_eq!(json["statistics"]["error_count"], 0);
+
+    let output = nix_parser(&["stats", fixture("valid.nix").to_str().unwrap()]);
+    assert!(stdout(&output).contains(" nodes, 0 errors, 0 warnings, "));
+}
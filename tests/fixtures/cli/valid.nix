@@ -0,0 +1,8 @@
+{ pkgs ? import <nixpkgs> {} }:
+
+let
+  version = "1.0";
+in {
+  name = "hello-${version}";
+  buildInputs = [ pkgs.gcc pkgs.make ];
+}
@@ -0,0 +1,35 @@
+"""Tests of the Python bindings; run `maturin develop` first."""
+
+import pytest
+
+import nix_parser
+
+
+def test_parse_returns_dict():
+    result = nix_parser.parse("{ x = 1; }")
+
+    assert set(result) >= {"version", "expression", "diagnostics"}
+    assert result["diagnostics"] == []
+
+    root = result["expression"]["Located"]
+    assert root["location"]["start_byte"] == 0
+    assert root["location"]["end_byte"] == 10
+    attribute = root["expr"]["AttributeSet"]["attributes"][0]
+    assert attribute["key"] == {"Static": ["x"]}
+    assert attribute["value"]["Located"]["expr"] == {"Integer": 1}
+
+
+def test_parser_class(tmp_path):
+    parser = nix_parser.NixParser()
+
+    result = parser.parse("{ x = ; }")
+    assert result["diagnostics"][0]["severity"] == "error"
+
+    path = tmp_path / "default.nix"
+    path.write_text("[ ]")
+    result = parser.parse_file(str(path))
+    assert result["file_path"] == str(path)
+    assert result["expression"]["Located"]["expr"] == {"List": []}
+
+    with pytest.raises(nix_parser.ParseError):
+        parser.parse_file(str(tmp_path / "missing.nix"))